@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use crate::{
+    error::{AppError, AppResult},
+    output::OutputFormat,
+};
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub profile: Option<String>,
+    /// Directory of canned responses to use instead of the network.
+    pub offline: Option<PathBuf>,
+    /// File to log requests to.
+    pub log: Option<PathBuf>,
+    pub exec: Option<ExecArgs>,
+}
+
+/// `oxat exec <method> [name=value ...] [--output <file>] [--format <format>] [--yes]`
+#[derive(Debug, Default)]
+pub struct ExecArgs {
+    pub method: String,
+    pub params: Vec<(String, String)>,
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+    /// Confirms a destructive command up front, since there's no prompt.
+    pub yes: bool,
+}
+
+impl Args {
+    pub fn parse() -> AppResult<Self> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--profile" | "-p" => {
+                    args.profile = Some(iter.next().ok_or_else(|| missing_value(&arg))?);
+                }
+                "--offline" => {
+                    let dir = iter.next().ok_or_else(|| missing_value(&arg))?;
+                    args.offline = Some(dir.into());
+                }
+                "--log" => {
+                    let path = iter.next().ok_or_else(|| missing_value(&arg))?;
+                    args.log = Some(path.into());
+                }
+                "exec" if args.exec.is_none() => {
+                    let method = iter.next().ok_or_else(|| missing_value(&arg))?;
+                    args.exec = Some(ExecArgs {
+                        method,
+                        ..Default::default()
+                    });
+                }
+                "--output" | "-o" if args.exec.is_some() => {
+                    let path = iter.next().ok_or_else(|| missing_value(&arg))?;
+                    if let Some(exec) = &mut args.exec {
+                        exec.output = Some(path.into());
+                    }
+                }
+                "--format" | "-f" if args.exec.is_some() => {
+                    let name = iter.next().ok_or_else(|| missing_value(&arg))?;
+                    let format = OutputFormat::parse(&name).ok_or_else(|| AppError::Config {
+                        src: name.clone(),
+                        err_span: (0, name.len()),
+                        msg: format!(
+                            "Unknown format '{}' (expected pretty, compact, ndjson, yaml or csv)",
+                            name
+                        ),
+                    })?;
+                    if let Some(exec) = &mut args.exec {
+                        exec.format = format;
+                    }
+                }
+                "--yes" | "-y" if args.exec.is_some() => {
+                    if let Some(exec) = &mut args.exec {
+                        exec.yes = true;
+                    }
+                }
+                _ => match (&mut args.exec, arg.split_once('=')) {
+                    (Some(exec), Some((name, value))) if !arg.starts_with('-') => {
+                        exec.params.push((name.to_string(), value.to_string()));
+                    }
+                    _ => return Err(unexpected(&arg).into()),
+                },
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn missing_value(flag: &str) -> AppError {
+    AppError::Config {
+        src: flag.to_string(),
+        err_span: (0, flag.len()),
+        msg: format!("{} requires a value", flag),
+    }
+}
+
+fn unexpected(arg: &str) -> AppError {
+    AppError::Config {
+        src: arg.to_string(),
+        err_span: (0, arg.len()),
+        msg: format!("Unexpected argument '{}'", arg),
+    }
+}
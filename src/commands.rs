@@ -1,93 +1,1197 @@
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    /// Free text, never rejected.
+    Text,
+    Integer,
+    /// `true` or `false`.
+    Boolean,
+    /// An `at://` URI.
+    AtUri,
+    /// A DID; handles are resolved to DIDs before sending when enabled.
+    Did,
+    /// Either a handle or a DID.
+    AtIdentifier,
+}
+
+impl ParamKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ParamKind::Text => "text",
+            ParamKind::Integer => "integer",
+            ParamKind::Boolean => "boolean",
+            ParamKind::AtUri => "at:// URI",
+            ParamKind::Did => "DID",
+            ParamKind::AtIdentifier => "handle or DID",
+        }
+    }
+
+    /// Checks a value against the kind, returning a short hint when it
+    /// doesn't fit.
+    pub fn validate(self, value: &str) -> Result<(), &'static str> {
+        let (valid, hint) = match self {
+            ParamKind::Text => return Ok(()),
+            ParamKind::Integer => (value.parse::<i64>().is_ok(), "must be a whole number"),
+            ParamKind::Boolean => (matches!(value, "true" | "false"), "must be true or false"),
+            ParamKind::AtUri => (
+                value
+                    .strip_prefix("at://")
+                    .is_some_and(|rest| !rest.is_empty()),
+                "must be an at:// URI",
+            ),
+            ParamKind::Did => (is_did(value), "must be a DID like did:plc:…"),
+            ParamKind::AtIdentifier => (
+                is_did(value) || is_handle(value),
+                "must be a handle or a DID",
+            ),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(hint)
+        }
+    }
+}
+
+/// `did:<method>:<identifier>` with a lowercase method.
+pub fn is_did(value: &str) -> bool {
+    let mut parts = value.splitn(3, ':');
+    matches!(
+        (parts.next(), parts.next(), parts.next()),
+        (Some("did"), Some(method), Some(id))
+            if !method.is_empty()
+                && method.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                && !id.is_empty()
+    )
+}
+
+/// A namespaced method name like `app.bsky.feed.getTimeline`: a reversed
+/// domain authority followed by a name that starts with a letter.
+pub fn is_nsid(value: &str) -> bool {
+    let segments: Vec<&str> = value.split('.').collect();
+    let Some((name, authority)) = segments.split_last() else {
+        return false;
+    };
+
+    authority.len() >= 2
+        && authority.iter().all(|segment| {
+            !segment.is_empty()
+                && !segment.starts_with('-')
+                && !segment.ends_with('-')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Splits `at://<repo>/<collection>/<rkey>` into its parts. The repo must
+/// be a DID or handle and the collection an NSID; URIs naming a whole repo
+/// or collection don't point at a single record.
+pub fn parse_at_uri(value: &str) -> Option<(&str, &str, &str)> {
+    let rest = value.strip_prefix("at://")?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let mut parts = rest.split('/');
+    let (Some(repo), Some(collection), Some(rkey), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    ((is_did(repo) || is_handle(repo))
+        && is_nsid(collection)
+        && !rkey.is_empty()
+        && rkey
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '~')))
+    .then_some((repo, collection, rkey))
+}
+
+/// The NSID's authority, which groups commands in the list, e.g.
+/// `app.bsky.feed` for `app.bsky.feed.getTimeline`.
+pub fn namespace(method: &str) -> &str {
+    method
+        .rsplit_once('.')
+        .map_or(method, |(namespace, _)| namespace)
+}
+
+/// A domain name with at least two labels, optionally prefixed with `@`.
+pub fn is_handle(value: &str) -> bool {
+    let value = value.trim_start_matches('@');
+    let labels: Vec<&str> = value.split('.').collect();
+
+    value.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: &'static str,
     pub description: &'static str,
     pub optional: bool,
     pub default: Option<&'static str>,
+    /// What each value must look like.
+    pub kind: ParamKind,
+    /// Takes a list, entered comma-separated and sent as `name=a&name=b`.
+    pub is_array: bool,
+}
+
+impl Parameter {
+    /// Whether it names an account, so it can be filled in with yours.
+    pub fn takes_account(&self) -> bool {
+        matches!(self.kind, ParamKind::Did | ParamKind::AtIdentifier)
+    }
+
+    /// The values entered for this parameter: each non-empty comma-separated
+    /// item for a list, otherwise the whole input.
+    pub fn values<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        if self.is_array {
+            input
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect()
+        } else {
+            vec![input]
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct XrpcCommand {
     pub method: &'static str,
+    /// A short name that can be typed instead of the method, e.g. `timeline`.
+    pub alias: Option<&'static str>,
     pub description: &'static str,
+    /// MIME type of the response. What's done with a body depends on the
+    /// type the server actually sends, falling back to this; see
+    /// `content::Handling`.
+    pub encoding: &'static str,
     pub parameters: &'static [Parameter],
+    /// Deletes data that can't be got back, so it's only sent once "yes" is
+    /// typed to confirm.
+    pub destructive: bool,
+}
+
+impl XrpcCommand {
+    pub fn is_binary(&self) -> bool {
+        self.encoding != JSON
+    }
+
+    pub fn param_index(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|param| param.name == name)
+    }
 }
 
-pub const AVAILABLE_COMMANDS: &[XrpcCommand] = &[
+pub const JSON: &str = "application/json";
+/// A repo or set of blocks in CAR format.
+pub const CAR: &str = "application/vnd.ipld.car";
+/// Whatever the server has, like a blob's original type.
+pub const ANY: &str = "*/*";
+
+/// Also fetched after login to show the count in the status bar.
+pub const GET_UNREAD_COUNT: &str = "app.bsky.notification.getUnreadCount";
+
+/// Its responses get a summary above the JSON.
+pub const GET_PROFILE: &str = "app.bsky.actor.getProfile";
+
+/// Methods whose responses are a `feed` of posts, shown as a list of posts
+/// instead of JSON.
+pub const FEED_METHODS: &[&str] = &[
+    "app.bsky.feed.getTimeline",
+    "app.bsky.feed.getAuthorFeed",
+    "app.bsky.feed.getActorLikes",
+    "app.bsky.feed.getFeed",
+];
+
+/// Used to follow `at://` URIs from the response view.
+pub const GET_RECORD: &str = "com.atproto.repo.getRecord";
+
+/// Sent as a procedure from the record's `at://` URI rather than as a query.
+pub const DELETE_RECORD: &str = "com.atproto.repo.deleteRecord";
+
+/// Not an XRPC method: builds a post record and sends it with
+/// `com.atproto.repo.createRecord`.
+pub const POST: &str = "oxat.post";
+
+/// Not an XRPC method: fetches both follower lists and shows the difference.
+pub const DIFF_FOLLOWERS: &str = "oxat.diffFollowers";
+
+static COMMANDS: OnceLock<Vec<XrpcCommand>> = OnceLock::new();
+
+/// Sets the command list to the built-ins plus `extra` (e.g. loaded from
+/// lexicons). Built-ins win over loaded commands with the same method.
+/// Only the first call has an effect.
+pub fn init(extra: Vec<XrpcCommand>) {
+    let mut commands = BUILTIN_COMMANDS.to_vec();
+    for command in extra {
+        if !commands.iter().any(|c| c.method == command.method) {
+            commands.push(command);
+        }
+    }
+    let _ = COMMANDS.set(commands);
+}
+
+/// The command whose method or alias is `name`.
+pub fn find(name: &str) -> Option<&'static XrpcCommand> {
+    all()
+        .iter()
+        .find(|cmd| cmd.method == name || cmd.alias == Some(name))
+}
+
+/// Whether `method` is a command that has to be confirmed before it's sent.
+pub fn is_destructive(method: &str) -> bool {
+    all()
+        .iter()
+        .any(|cmd| cmd.method == method && cmd.destructive)
+}
+
+/// Every available command, built-in or loaded.
+pub fn all() -> &'static [XrpcCommand] {
+    COMMANDS.get_or_init(|| BUILTIN_COMMANDS.to_vec())
+}
+
+const BUILTIN_COMMANDS: &[XrpcCommand] = &[
     XrpcCommand {
-        method: "app.bsky.actor.getProfile",
+        method: GET_PROFILE,
+        alias: Some("profile"),
         description: "Get an actor's profile details",
+        encoding: JSON,
+        destructive: false,
         parameters: &[Parameter {
             name: "actor",
             description: "The handle or DID of the actor",
             optional: false,
             default: None,
+            kind: ParamKind::AtIdentifier,
+            is_array: false,
+        }],
+    },
+    XrpcCommand {
+        method: "app.bsky.actor.getProfiles",
+        alias: Some("profiles"),
+        description: "Get several actors' profiles at once (up to 25)",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[Parameter {
+            name: "actors",
+            description: "Handles or DIDs, comma-separated",
+            optional: false,
+            default: None,
+            kind: ParamKind::AtIdentifier,
+            is_array: true,
         }],
     },
     XrpcCommand {
         method: "app.bsky.feed.getTimeline",
+        alias: Some("timeline"),
         description: "Get the user's home timeline",
+        encoding: JSON,
+        destructive: false,
         parameters: &[
             Parameter {
                 name: "limit",
                 description: "Number of results to return",
                 optional: true,
                 default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
             },
             Parameter {
                 name: "cursor",
                 description: "Pagination cursor from previous response",
                 optional: true,
                 default: None,
+                kind: ParamKind::Text,
+                is_array: false,
             },
         ],
     },
     XrpcCommand {
         method: "app.bsky.feed.getAuthorFeed",
+        alias: Some("posts"),
         description: "Get a feed of posts by an actor",
+        encoding: JSON,
+        destructive: false,
         parameters: &[
             Parameter {
                 name: "actor",
                 description: "The handle or DID of the author",
                 optional: false,
                 default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getPostThread",
+        alias: Some("thread"),
+        description: "Get a post and its replies",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "uri",
+                description: "The at:// URI of the post",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtUri,
+                is_array: false,
+            },
+            Parameter {
+                name: "depth",
+                description: "How many levels of replies to include",
+                optional: true,
+                default: Some("6"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getLikes",
+        alias: Some("likes"),
+        description: "Get the accounts that liked a post",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "uri",
+                description: "The at:// URI of the post",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtUri,
+                is_array: false,
+            },
+            Parameter {
+                name: "cid",
+                description: "Only count this version of the post",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getRepostedBy",
+        alias: Some("reposts"),
+        description: "Get the accounts that reposted a post",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "uri",
+                description: "The at:// URI of the post",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtUri,
+                is_array: false,
+            },
+            Parameter {
+                name: "cid",
+                description: "Only count this version of the post",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getActorLikes",
+        alias: Some("liked"),
+        description: "Get posts liked by an actor (only your own account)",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "actor",
+                description: "The handle or DID of the actor",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getFeed",
+        alias: Some("feed"),
+        description: "Get posts from a custom feed",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "feed",
+                description: "The at:// URI of the feed generator",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtUri,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
             },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getFeedGenerator",
+        alias: Some("generator"),
+        description: "Get a custom feed's details and whether it's online",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[Parameter {
+            name: "feed",
+            description: "The at:// URI of the feed generator",
+            optional: false,
+            default: None,
+            kind: ParamKind::AtUri,
+            is_array: false,
+        }],
+    },
+    XrpcCommand {
+        method: "app.bsky.feed.getFeedGenerators",
+        alias: Some("generators"),
+        description: "Get details of several custom feeds",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[Parameter {
+            name: "feeds",
+            description: "The at:// URIs of the feed generators, comma-separated",
+            optional: false,
+            default: None,
+            kind: ParamKind::AtUri,
+            is_array: true,
+        }],
+    },
+    XrpcCommand {
+        method: "app.bsky.notification.listNotifications",
+        alias: Some("notifications"),
+        description: "List your notifications",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
             Parameter {
                 name: "limit",
                 description: "Number of results",
                 optional: true,
                 default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "priority",
+                description: "Only notifications from priority accounts",
+                optional: true,
+                default: None,
+                kind: ParamKind::Boolean,
+                is_array: false,
+            },
+            Parameter {
+                name: "seenAt",
+                description: "Treat notifications before this datetime as seen",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
             },
             Parameter {
                 name: "cursor",
                 description: "Pagination cursor",
                 optional: true,
                 default: None,
+                kind: ParamKind::Text,
+                is_array: false,
             },
         ],
     },
+    XrpcCommand {
+        method: GET_UNREAD_COUNT,
+        alias: Some("unread"),
+        description: "Count your unread notifications",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[],
+    },
     XrpcCommand {
         method: "app.bsky.graph.getFollowers",
+        alias: Some("followers"),
         description: "Get a list of an actor's followers",
+        encoding: JSON,
+        destructive: false,
         parameters: &[
             Parameter {
                 name: "actor",
                 description: "The handle or DID of the actor",
                 optional: false,
                 default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
             },
             Parameter {
                 name: "limit",
                 description: "Number of results",
                 optional: true,
                 default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
             },
             Parameter {
                 name: "cursor",
                 description: "Pagination cursor",
                 optional: true,
                 default: None,
+                kind: ParamKind::Text,
+                is_array: false,
             },
         ],
     },
+    XrpcCommand {
+        method: "app.bsky.graph.getFollows",
+        alias: Some("follows"),
+        description: "Get a list of accounts an actor follows",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "actor",
+                description: "The handle or DID of the actor",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.graph.getBlocks",
+        alias: Some("blocks"),
+        description: "Get the accounts you block",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.graph.getMutes",
+        alias: Some("mutes"),
+        description: "Get the accounts you mute",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "app.bsky.graph.getLists",
+        alias: Some("lists"),
+        description: "Get the lists created by an actor",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "actor",
+                description: "The handle or DID of the actor",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: POST,
+        alias: Some("post"),
+        description: "Publish a post from your account",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "text",
+                description: "The post's text, up to 300 characters",
+                optional: false,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "reply",
+                description: "The at:// URI of a post to reply to",
+                optional: true,
+                default: None,
+                kind: ParamKind::AtUri,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: DELETE_RECORD,
+        alias: Some("delete"),
+        description: "Delete a record from your repo, like a post, like or follow",
+        encoding: JSON,
+        destructive: true,
+        parameters: &[Parameter {
+            name: "uri",
+            description: "The at:// URI of the record to delete",
+            optional: false,
+            default: None,
+            kind: ParamKind::AtUri,
+            is_array: false,
+        }],
+    },
+    XrpcCommand {
+        method: DIFF_FOLLOWERS,
+        alias: Some("diff"),
+        description: "Accounts that follow A but not B (fetches every page)",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "a",
+                description: "The handle or DID whose followers are listed",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "b",
+                description: "The handle or DID whose followers are excluded",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "com.atproto.sync.listBlobs",
+        alias: Some("blobs"),
+        description: "List blob CIDs for an account",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "did",
+                description: "The DID of the repo (handles are resolved)",
+                optional: false,
+                default: None,
+                kind: ParamKind::Did,
+                is_array: false,
+            },
+            Parameter {
+                name: "since",
+                description: "Only list blobs after this repo revision",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("500"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "com.atproto.sync.getRepo",
+        alias: Some("backup"),
+        description: "Download a full repo as a CAR file",
+        encoding: CAR,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "did",
+                description: "The DID of the repo (handles are resolved)",
+                optional: false,
+                default: None,
+                kind: ParamKind::Did,
+                is_array: false,
+            },
+            Parameter {
+                name: "since",
+                description: "Only include changes after this repo revision",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "com.atproto.sync.getBlob",
+        alias: Some("blob"),
+        description: "Download a blob, like an image or video, by its CID",
+        encoding: ANY,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "did",
+                description: "The DID of the repo holding the blob (handles are resolved)",
+                optional: false,
+                default: None,
+                kind: ParamKind::Did,
+                is_array: false,
+            },
+            Parameter {
+                name: "cid",
+                description: "The CID of the blob",
+                optional: false,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "com.atproto.repo.listRecords",
+        alias: Some("records"),
+        description: "List records in one collection of a repo",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "repo",
+                description: "Handle or DID of the repo",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "collection",
+                description: "NSID of the record collection, e.g. app.bsky.feed.post",
+                optional: false,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "limit",
+                description: "Number of results",
+                optional: true,
+                default: Some("50"),
+                kind: ParamKind::Integer,
+                is_array: false,
+            },
+            Parameter {
+                name: "reverse",
+                description: "List oldest records first",
+                optional: true,
+                default: None,
+                kind: ParamKind::Boolean,
+                is_array: false,
+            },
+            Parameter {
+                name: "cursor",
+                description: "Pagination cursor",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: GET_RECORD,
+        alias: Some("record"),
+        description: "Get a single record from a repo",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[
+            Parameter {
+                name: "repo",
+                description: "Handle or DID of the repo",
+                optional: false,
+                default: None,
+                kind: ParamKind::AtIdentifier,
+                is_array: false,
+            },
+            Parameter {
+                name: "collection",
+                description: "NSID of the record collection, e.g. app.bsky.feed.post",
+                optional: false,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "rkey",
+                description: "Record key",
+                optional: false,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+            Parameter {
+                name: "cid",
+                description: "CID of a specific version of the record",
+                optional: true,
+                default: None,
+                kind: ParamKind::Text,
+                is_array: false,
+            },
+        ],
+    },
+    XrpcCommand {
+        method: "com.atproto.server.describeServer",
+        alias: Some("server"),
+        description: "Describe the PDS: its DID, account domains and sign-up rules",
+        encoding: JSON,
+        destructive: false,
+        parameters: &[],
+    },
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_accepts_anything() {
+        for value in ["", "hello world", "at://not checked", "🦋"] {
+            assert_eq!(ParamKind::Text.validate(value), Ok(()));
+        }
+    }
+
+    #[test]
+    fn integers_must_be_whole_numbers() {
+        for value in ["0", "50", "-3"] {
+            assert_eq!(ParamKind::Integer.validate(value), Ok(()));
+        }
+        for value in ["", "1.5", "ten", "5 "] {
+            assert_eq!(
+                ParamKind::Integer.validate(value),
+                Err("must be a whole number")
+            );
+        }
+    }
+
+    #[test]
+    fn booleans_must_be_spelled_out() {
+        assert_eq!(ParamKind::Boolean.validate("true"), Ok(()));
+        assert_eq!(ParamKind::Boolean.validate("false"), Ok(()));
+        for value in ["", "1", "yes", "True"] {
+            assert_eq!(
+                ParamKind::Boolean.validate(value),
+                Err("must be true or false")
+            );
+        }
+    }
+
+    #[test]
+    fn at_uris_need_the_scheme_and_something_after_it() {
+        assert_eq!(ParamKind::AtUri.validate("at://did:plc:abc"), Ok(()));
+        assert_eq!(
+            ParamKind::AtUri.validate("at://bsky.app/app.bsky.feed.post/3k2a"),
+            Ok(())
+        );
+        for value in ["", "at://", "https://bsky.app", "did:plc:abc"] {
+            assert_eq!(
+                ParamKind::AtUri.validate(value),
+                Err("must be an at:// URI")
+            );
+        }
+    }
+
+    #[test]
+    fn dids_need_a_lowercase_method_and_an_identifier() {
+        for value in ["did:plc:z72i7hdynmk6r22z27h6tvur", "did:web:example.com"] {
+            assert_eq!(ParamKind::Did.validate(value), Ok(()));
+        }
+        for value in [
+            "",
+            "bsky.app",
+            "did:plc",
+            "did:plc:",
+            "did:PLC:abc",
+            "did::abc",
+        ] {
+            assert_eq!(
+                ParamKind::Did.validate(value),
+                Err("must be a DID like did:plc:…")
+            );
+        }
+    }
+
+    #[test]
+    fn at_identifiers_take_handles_or_dids() {
+        for value in ["bsky.app", "@alice.bsky.social", "did:plc:abc"] {
+            assert_eq!(ParamKind::AtIdentifier.validate(value), Ok(()));
+        }
+        for value in ["", "alice", "-bad.example", "bad..example", "did:plc:"] {
+            assert_eq!(
+                ParamKind::AtIdentifier.validate(value),
+                Err("must be a handle or a DID")
+            );
+        }
+    }
+
+    #[test]
+    fn at_uris_split_into_repo_collection_and_rkey() {
+        assert_eq!(
+            parse_at_uri("at://did:plc:z72i7hdynmk6r22z27h6tvur/app.bsky.feed.post/3k44dtxgaw22a"),
+            Some((
+                "did:plc:z72i7hdynmk6r22z27h6tvur",
+                "app.bsky.feed.post",
+                "3k44dtxgaw22a"
+            ))
+        );
+        assert_eq!(
+            parse_at_uri("at://bsky.app/app.bsky.actor.profile/self"),
+            Some(("bsky.app", "app.bsky.actor.profile", "self"))
+        );
+        assert_eq!(
+            parse_at_uri("at://did:web:example.com/app.bsky.feed.generator/whats-hot"),
+            Some((
+                "did:web:example.com",
+                "app.bsky.feed.generator",
+                "whats-hot"
+            ))
+        );
+    }
+
+    #[test]
+    fn at_uri_query_and_fragment_are_dropped() {
+        assert_eq!(
+            parse_at_uri("at://bsky.app/app.bsky.feed.post/3k44?cid=bafy#reply"),
+            Some(("bsky.app", "app.bsky.feed.post", "3k44"))
+        );
+    }
+
+    #[test]
+    fn at_uris_without_a_record_key_dont_parse() {
+        for uri in [
+            "at://bsky.app",
+            "at://bsky.app/app.bsky.feed.post",
+            "at://bsky.app/app.bsky.feed.post/",
+            "at://bsky.app/app.bsky.feed.post/3k44/extra",
+        ] {
+            assert_eq!(parse_at_uri(uri), None, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn at_uris_need_a_valid_authority_and_collection() {
+        for uri in [
+            "at://localhost/app.bsky.feed.post/3k44",
+            "at://did:plc/app.bsky.feed.post/3k44",
+            "at://bsky.app/post/3k44",
+            "at://bsky.app/app.bsky.feed.post/has space",
+        ] {
+            assert_eq!(parse_at_uri(uri), None, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn only_at_uris_parse() {
+        for value in [
+            "",
+            "bsky.app/app.bsky.feed.post/3k44",
+            "https://bsky.app/profile/bsky.app/post/3k44",
+            "AT://bsky.app/app.bsky.feed.post/3k44",
+            " at://bsky.app/app.bsky.feed.post/3k44",
+        ] {
+            assert_eq!(parse_at_uri(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn aliases_find_their_command() {
+        assert_eq!(
+            find("timeline").unwrap().method,
+            "app.bsky.feed.getTimeline"
+        );
+        assert_eq!(find("profile").unwrap().method, "app.bsky.actor.getProfile");
+        assert_eq!(find("delete").unwrap().method, DELETE_RECORD);
+        for cmd in BUILTIN_COMMANDS {
+            assert_eq!(find(cmd.method).unwrap().method, cmd.method);
+            if let Some(alias) = cmd.alias {
+                assert_eq!(find(alias).unwrap().method, cmd.method, "{}", alias);
+            }
+        }
+        assert!(find("Timeline").is_none());
+        assert!(find("").is_none());
+    }
+
+    #[test]
+    fn aliases_dont_collide() {
+        let mut names = std::collections::HashSet::new();
+        for cmd in BUILTIN_COMMANDS {
+            assert!(names.insert(cmd.method), "{} is listed twice", cmd.method);
+        }
+        for alias in BUILTIN_COMMANDS.iter().filter_map(|cmd| cmd.alias) {
+            assert!(names.insert(alias), "{} is taken", alias);
+            // Typed on its own, so it can't look like an NSID
+            assert!(
+                !alias.is_empty() && alias.chars().all(|c| c.is_ascii_lowercase()),
+                "{}",
+                alias
+            );
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{AppError, AppResult},
+    output::DEFAULT_EXPORT_FILENAME,
+    state::{DEFAULT_HISTORY_SIZE, DEFAULT_RETRIES, DEFAULT_SELF_PARAMS},
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// PDS used when the selected profile doesn't set one.
+    pub pds_host: Option<String>,
+    /// Most history entries to keep.
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    /// Color theme: `dark`, `light` or `high-contrast`.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    /// Set to `false` to skip clipboard setup entirely.
+    #[serde(default = "default_true")]
+    pub clipboard: bool,
+    /// Resolve handles to DIDs for parameters that require a DID.
+    #[serde(default = "default_true")]
+    pub resolve_handles: bool,
+    /// Parameters that mean your own account when left empty, by name or
+    /// as `method.param` for one command's.
+    #[serde(default = "default_self_params")]
+    pub self_params: Vec<String>,
+    /// Put the session's access token in copied curl commands instead of a
+    /// `$TOKEN` placeholder.
+    #[serde(default)]
+    pub curl_include_token: bool,
+    /// Show the composed request and wait for confirmation before sending
+    /// a built command.
+    #[serde(default)]
+    pub preview_requests: bool,
+    /// Seconds before a status message is cleared; longer messages stay up
+    /// a little longer.
+    #[serde(default = "default_error_timeout")]
+    pub error_timeout: u64,
+    /// Seconds to wait for a response before giving up.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Times to retry a request after a network error, timeout, 429 or
+    /// 502–504 before giving up.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Per-method `request_timeout` overrides, e.g. for slow sync calls.
+    #[serde(default)]
+    pub timeouts: BTreeMap<String, u64>,
+    /// Headers sent with every command, e.g. `atproto-accept-labelers`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Per-method headers, added to `headers`, e.g. an `atproto-proxy` for
+    /// chat methods.
+    #[serde(default)]
+    pub method_headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// Filename for `e` exports; `{method}`, `{timestamp}` and `{handle}` are
+    /// filled in.
+    #[serde(default = "default_export_filename")]
+    pub export_filename: String,
+    /// Export the request (method, URL, params, status, time and latency)
+    /// around the response body, instead of just the body.
+    #[serde(default)]
+    pub export_envelope: bool,
+    /// Outbound proxy, e.g. `http://proxy:8080` or `socks5h://127.0.0.1:9050`.
+    /// Defaults to `HTTPS_PROXY`, `ALL_PROXY` or `HTTP_PROXY`; set it to ""
+    /// to connect directly.
+    pub proxy: Option<String>,
+    /// PEM file of CA certificates to trust instead of the system's, e.g.
+    /// for a self-hosted PDS with an internal CA.
+    pub ca_cert: Option<PathBuf>,
+    /// Skip TLS certificate and hostname checks entirely. Only for testing
+    /// against a PDS you control.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+    /// File to log every request to, for debugging. `--log` overrides it.
+    pub log_file: Option<PathBuf>,
+    /// Directory of lexicon `.json` files to load as extra commands.
+    /// Defaults to `lexicons` in the config directory.
+    pub lexicon_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub profile: BTreeMap<String, Profile>,
+    /// The file as written, for laying a profile's settings over.
+    #[serde(skip)]
+    pub(crate) table: toml::Table,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pds_host: None,
+            history_size: default_history_size(),
+            theme: default_theme(),
+            clipboard: true,
+            resolve_handles: true,
+            self_params: default_self_params(),
+            curl_include_token: false,
+            preview_requests: false,
+            error_timeout: default_error_timeout(),
+            request_timeout: default_request_timeout(),
+            retries: DEFAULT_RETRIES,
+            timeouts: BTreeMap::new(),
+            headers: BTreeMap::new(),
+            method_headers: BTreeMap::new(),
+            export_filename: default_export_filename(),
+            export_envelope: false,
+            proxy: None,
+            ca_cert: None,
+            danger_accept_invalid_certs: false,
+            log_file: None,
+            lexicon_dir: None,
+            profile: BTreeMap::new(),
+            table: toml::Table::new(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_size() -> usize {
+    DEFAULT_HISTORY_SIZE
+}
+
+fn default_self_params() -> Vec<String> {
+    DEFAULT_SELF_PARAMS
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn default_theme() -> String {
+    "dark".to_string()
+}
+
+fn default_error_timeout() -> u64 {
+    5
+}
+
+fn default_request_timeout() -> u64 {
+    10
+}
+
+fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+fn default_export_filename() -> String {
+    DEFAULT_EXPORT_FILENAME.to_string()
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Profile {
+    /// Account to log in as, so launching goes straight to the password.
+    pub identifier: Option<String>,
+    /// Top-level settings to use instead, e.g. `pds_host` or `theme`.
+    #[serde(flatten)]
+    pub settings: toml::Table,
+}
+
+/// Directory holding oxat's config and persisted state, e.g.
+/// `~/.config/oxat` on Linux.
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_CONFIG_DIR.with(|dir| dir.borrow().clone()) {
+        return Some(dir);
+    }
+    dirs::config_dir().map(|dir| dir.join("oxat"))
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Replaces `config_dir` on one test's thread, so it can check what was
+    /// saved without other tests writing to the same files.
+    pub static TEST_CONFIG_DIR: std::cell::RefCell<Option<PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+impl Config {
+    pub fn lexicon_dir(&self) -> Option<PathBuf> {
+        self.lexicon_dir
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join("lexicons")))
+    }
+
+    /// Loads the config file, falling back to defaults if it doesn't exist.
+    pub fn load() -> AppResult<Self> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join(CONFIG_FILE)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the config at `path`, falling back to defaults if it doesn't
+    /// exist.
+    pub fn load_from(path: &Path) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents, path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(AppError::Config {
+                src: path.display().to_string(),
+                err_span: (0, 0),
+                msg: format!("Failed to read config: {}", e),
+            }
+            .into()),
+        }
+    }
+
+    /// Parses a config file's `contents`; `path` is only for the error.
+    pub fn parse(contents: &str, path: &Path) -> AppResult<Self> {
+        let mut config: Self = toml::from_str(contents).map_err(|e| AppError::Config {
+            err_span: e.span().map(|s| (s.start, s.len())).unwrap_or((0, 0)),
+            src: contents.to_string(),
+            msg: format!("Failed to parse {}: {}", path.display(), e.message()),
+        })?;
+        // Already known to parse, since the config did
+        config.table = toml::from_str(contents).unwrap_or_default();
+        Ok(config)
+    }
+
+    /// The config as profile `name` has it: the profile's settings in place
+    /// of the top-level ones they name, and the rest as they are. A table
+    /// like `headers` replaces the top-level one rather than adding to it.
+    pub fn with_profile(&self, name: &str) -> AppResult<Self> {
+        let profile = self.profile(name)?;
+        let mut table = self.table.clone();
+        table.extend(profile.settings.clone());
+
+        let mut config: Self =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| AppError::Config {
+                    src: name.to_string(),
+                    err_span: (0, name.len()),
+                    msg: format!("Bad settings in profile '{}': {}", name, e.message()),
+                })?;
+        config.table = self.table.clone();
+        Ok(config)
+    }
+
+    /// Sets top-level `values` in the config file, creating it if needed.
+    /// Everything else in the file is kept, though comments are lost since
+    /// it's rewritten from the parsed table. Returns the file's path.
+    pub fn save_values(values: &[(&str, toml::Value)]) -> AppResult<PathBuf> {
+        let Some(dir) = config_dir() else {
+            return Err(AppError::Config {
+                src: "saving settings".into(),
+                err_span: (0, 0),
+                msg: "No config directory on this system".into(),
+            }
+            .into());
+        };
+        let path = dir.join(CONFIG_FILE);
+        let config_error = |msg: String| AppError::Config {
+            src: path.display().to_string(),
+            err_span: (0, 0),
+            msg,
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(config_error(format!("Failed to read config: {}", e)).into()),
+        };
+        // A file that doesn't parse is left alone rather than overwritten
+        let mut table = contents
+            .parse::<toml::Table>()
+            .map_err(|e| config_error(format!("Failed to parse config: {}", e.message())))?;
+        for (key, value) in values {
+            table.insert(key.to_string(), value.clone());
+        }
+
+        let contents = toml::to_string(&table)
+            .map_err(|e| config_error(format!("Failed to write config: {}", e)))?;
+        fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&path, contents))
+            .map_err(|e| config_error(format!("Failed to write config: {}", e)))?;
+        Ok(path)
+    }
+
+    /// Looks up a named profile, erroring with the known names if it's missing.
+    pub fn profile(&self, name: &str) -> AppResult<&Profile> {
+        self.profile.get(name).ok_or_else(|| {
+            let known = if self.profile.is_empty() {
+                "no profiles are defined".to_string()
+            } else {
+                format!(
+                    "available profiles: {}",
+                    self.profile.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            };
+
+            AppError::Config {
+                src: name.to_string(),
+                err_span: (0, name.len()),
+                msg: format!("Unknown profile '{}' ({})", name, known),
+            }
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+pds_host = "https://pds.example.com"
+theme = "light"
+retries = 5
+export_filename = "{method}.json"
+
+[timeouts]
+"com.atproto.sync.getRepo" = 120
+
+[headers]
+atproto-accept-labelers = "did:plc:ar7c4by46qjdydhdevvrndac"
+
+[method_headers."chat.bsky.convo.listConvos"]
+atproto-proxy = "did:web:api.bsky.chat#bsky_chat"
+
+[profile.alt]
+identifier = "alt.example.com"
+
+[profile.work]
+identifier = "me.work.example"
+pds_host = "https://pds.work.example"
+theme = "high-contrast"
+
+[profile.work.headers]
+atproto-proxy = "did:web:work.example#service"
+"#;
+
+    #[test]
+    fn parses_a_sample_config() {
+        let config = Config::parse(SAMPLE, Path::new("config.toml")).unwrap();
+
+        assert_eq!(config.pds_host.as_deref(), Some("https://pds.example.com"));
+        assert_eq!(config.theme, "light");
+        assert_eq!(config.retries, 5);
+        assert_eq!(config.export_filename, "{method}.json");
+        assert_eq!(config.timeouts["com.atproto.sync.getRepo"], 120);
+        assert_eq!(
+            config.headers["atproto-accept-labelers"],
+            "did:plc:ar7c4by46qjdydhdevvrndac"
+        );
+        assert_eq!(
+            config.method_headers["chat.bsky.convo.listConvos"]["atproto-proxy"],
+            "did:web:api.bsky.chat#bsky_chat"
+        );
+        let alt = config.profile("alt").unwrap();
+        assert_eq!(alt.identifier.as_deref(), Some("alt.example.com"));
+        assert!(alt.settings.is_empty());
+
+        // Everything left out keeps its default
+        let defaults = Config::default();
+        assert_eq!(config.history_size, defaults.history_size);
+        assert_eq!(config.self_params, defaults.self_params);
+        assert!(config.clipboard);
+        assert!(!config.export_envelope);
+    }
+
+    #[test]
+    fn empty_config_is_the_defaults() {
+        let config = Config::parse("", Path::new("config.toml")).unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.theme, defaults.theme);
+        assert_eq!(config.history_size, defaults.history_size);
+        assert_eq!(config.retries, defaults.retries);
+        assert_eq!(config.export_filename, defaults.export_filename);
+    }
+
+    #[test]
+    fn missing_file_is_the_defaults() {
+        let path = std::env::temp_dir().join("oxat-no-such-dir/config.toml");
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.pds_host, None);
+        assert_eq!(config.theme, Config::default().theme);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_bad_value() {
+        let contents = "theme = \"dark\"\nretries = \"lots\"\n";
+        let err = Config::parse(contents, Path::new("config.toml")).unwrap_err();
+        let err = err.downcast_ref::<AppError>().expect("a config error");
+        let AppError::Config { src, err_span, msg } = err else {
+            panic!("expected a config error, got {:?}", err);
+        };
+        assert_eq!(src, contents);
+        assert_eq!(&contents[err_span.0..err_span.0 + err_span.1], "\"lots\"");
+        assert!(msg.contains("config.toml"), "{}", msg);
+    }
+
+    #[test]
+    fn unknown_profile_lists_the_known_ones() {
+        let config = Config::parse(SAMPLE, Path::new("config.toml")).unwrap();
+        let err = config.profile("main").unwrap_err();
+        assert!(
+            err.to_string().contains("available profiles: alt, work"),
+            "{}",
+            err
+        );
+        assert!(config.with_profile("main").is_err());
+    }
+
+    #[test]
+    fn a_profile_replaces_the_settings_it_names() {
+        let config = Config::parse(SAMPLE, Path::new("config.toml")).unwrap();
+        let work = config.with_profile("work").unwrap();
+
+        assert_eq!(work.pds_host.as_deref(), Some("https://pds.work.example"));
+        assert_eq!(work.theme, "high-contrast");
+        // A table is replaced whole, not merged
+        assert_eq!(work.headers.keys().collect::<Vec<_>>(), ["atproto-proxy"]);
+        // What it doesn't name comes from the top level
+        assert_eq!(work.retries, 5);
+        assert_eq!(work.timeouts["com.atproto.sync.getRepo"], 120);
+        assert_eq!(work.export_filename, "{method}.json");
+        assert_eq!(
+            work.profile("work").unwrap().identifier.as_deref(),
+            Some("me.work.example")
+        );
+
+        // A profile without settings leaves everything as it was
+        let alt = config.with_profile("alt").unwrap();
+        assert_eq!(alt.pds_host, config.pds_host);
+        assert_eq!(alt.theme, "light");
+    }
+
+    #[test]
+    fn bad_profile_settings_name_the_profile() {
+        let contents = "[profile.work]\nretries = \"lots\"\n";
+        let config = Config::parse(contents, Path::new("config.toml")).unwrap();
+        let err = config.with_profile("work").unwrap_err();
+        assert!(
+            err.to_string().contains("Bad settings in profile 'work'"),
+            "{}",
+            err
+        );
+    }
+}
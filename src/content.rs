@@ -0,0 +1,354 @@
+use serde_json::{json, Value};
+use std::{fs, path::PathBuf};
+
+use crate::{commands::JSON, App};
+
+/// What to do with a successful response body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handling {
+    /// Parse it and show it.
+    Json,
+    /// Keep it in memory and describe it, so it can be saved on request.
+    Image,
+    /// Stream it to a file.
+    Save,
+}
+
+impl Handling {
+    /// Decides from the response's `Content-Type`, falling back to the
+    /// command's declared `encoding` when the server doesn't send one.
+    pub fn for_response(declared: &str, content_type: Option<&str>) -> Self {
+        match content_type.map(essence) {
+            Some(actual) if is_json(&actual) => Handling::Json,
+            Some(actual) if actual.starts_with("image/") => Handling::Image,
+            // Left to the parser, whose error shows what came back instead
+            Some(actual) if declared == JSON && actual.starts_with("text/") => Handling::Json,
+            Some(_) => Handling::Save,
+            None if declared == JSON => Handling::Json,
+            None if declared.starts_with("image/") => Handling::Image,
+            None => Handling::Save,
+        }
+    }
+}
+
+/// Where a command's body goes if it turns out not to be JSON: `path`
+/// without an extension, which is picked from the type that came back.
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub path: PathBuf,
+    /// The command's declared output type, used when the response has none.
+    pub encoding: &'static str,
+}
+
+/// An image response, kept until it's saved or replaced.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+    /// Where `s` saves it, without an extension.
+    pub path: PathBuf,
+}
+
+/// The MIME type without parameters like `charset`, lowercased.
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn is_json(essence: &str) -> bool {
+    essence == JSON || essence.ends_with("+json")
+}
+
+/// The file extension for a saved body of `content_type`.
+pub fn extension(content_type: &str) -> &'static str {
+    match essence(content_type).as_str() {
+        "application/vnd.ipld.car" => "car",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/svg+xml" => "svg",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "video/quicktime" => "mov",
+        "application/vnd.apple.mpegurl" => "m3u8",
+        "text/plain" => "txt",
+        "text/html" => "html",
+        _ => "bin",
+    }
+}
+
+/// The format and pixel size of a PNG, JPEG, GIF or WebP image, read
+/// from its header.
+fn dimensions(bytes: &[u8]) -> Option<(&'static str, u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let le16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let b = bytes.get(at..at + 3)?;
+        Some(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16)
+    };
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(("PNG", be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some(("GIF", le16(6)?.into(), le16(8)?.into()));
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((
+                "WebP",
+                (le16(26)? & 0x3fff).into(),
+                (le16(28)? & 0x3fff).into(),
+            )),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(("WebP", (bits & 0x3fff) + 1, (bits >> 14 & 0x3fff) + 1))
+            }
+            b"VP8X" => Some(("WebP", le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments to the start of frame, which holds the size
+        let mut at = 2;
+        while at + 4 <= bytes.len() {
+            if bytes[at] != 0xff {
+                return None;
+            }
+            let marker = bytes[at + 1];
+            let is_frame = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_frame {
+                return Some(("JPEG", be16(at + 7)?.into(), be16(at + 5)?.into()));
+            }
+            at += 2 + usize::from(be16(at + 2)?);
+        }
+    }
+    None
+}
+
+/// What's shown in place of an image: its type, size and, when the header
+/// can be read, its format and dimensions.
+pub fn describe_image(image: &Image) -> Value {
+    let mut info = json!({
+        "contentType": image.content_type,
+        "bytes": image.bytes.len(),
+    });
+    if let Some((format, width, height)) = dimensions(&image.bytes) {
+        info["format"] = json!(format);
+        info["width"] = json!(width);
+        info["height"] = json!(height);
+    }
+    info
+}
+
+impl App {
+    /// Writes the image from the last response next to the exports, and
+    /// adds where it went to the description shown. Errors are messages
+    /// for the status bar.
+    pub(crate) fn save_image(&mut self) -> Result<PathBuf, String> {
+        let image = self
+            .state
+            .image
+            .as_ref()
+            .ok_or("The response isn't an image")?;
+
+        let path = image.path.with_extension(extension(&image.content_type));
+        fs::write(&path, &image.bytes)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        self.state.last_export = std::path::absolute(&path).ok();
+        if let Some(output) = &mut self.state.output {
+            output["savedTo"] = json!(path.display().to_string());
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, MockServer, Reply};
+    use std::path::Path;
+
+    /// A PDS answering every request with `body` as `content_type`.
+    fn serving(content_type: &'static str, body: Vec<u8>) -> MockServer {
+        MockServer::start(move |_| Reply {
+            status: 200,
+            headers: vec![("Content-Type".into(), content_type.into())],
+            body: body.clone(),
+        })
+    }
+
+    /// An app whose downloads go to a fresh directory of their own.
+    fn app_saving_to(server: &MockServer, name: &str) -> (App, PathBuf) {
+        let mut app = test_support::app(&server.url);
+        let dir =
+            std::env::temp_dir().join(format!("oxat-content-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        app.state.export_filename = dir.join("{method}.json").display().to_string();
+        (app, dir)
+    }
+
+    /// The header of a `width`×`height` PNG, up to the end of its size.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn json_is_parsed_and_shown() {
+        let server = serving(
+            "application/json; charset=utf-8",
+            br#"{"ok":true}"#.to_vec(),
+        );
+        let (mut app, dir) = app_saving_to(&server, "json");
+
+        smol::block_on(app.execute_raw("com.example.ping", "")).unwrap();
+
+        assert_eq!(app.state.output, Some(json!({ "ok": true })));
+        assert!(app.state.image.is_none());
+        assert!(files_in(&dir).is_empty());
+    }
+
+    #[test]
+    fn images_are_kept_in_memory_with_their_size() {
+        let server = serving("image/png", png(640, 480));
+        let (mut app, dir) = app_saving_to(&server, "image");
+
+        let params = ["did:plc:test".to_string(), "bafkrei".to_string()];
+        smol::block_on(app.execute_command("com.atproto.sync.getBlob", &params)).unwrap();
+
+        let image = app.state.image.as_ref().unwrap();
+        assert_eq!(image.content_type, "image/png");
+        assert_eq!(image.bytes, png(640, 480));
+        assert_eq!(
+            app.state.output,
+            Some(json!({
+                "contentType": "image/png",
+                "bytes": 24,
+                "format": "PNG",
+                "width": 640,
+                "height": 480,
+            }))
+        );
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("image/png image, 640×480; s saves it")
+        );
+        assert!(files_in(&dir).is_empty());
+    }
+
+    #[test]
+    fn other_bodies_are_streamed_to_a_file_named_by_type() {
+        let car = b"\x0a\xa1\x65roots".to_vec();
+        let server = serving("application/vnd.ipld.car", car.clone());
+        let (mut app, dir) = app_saving_to(&server, "car");
+
+        let params = ["did:plc:test".to_string()];
+        smol::block_on(app.execute_command("com.atproto.sync.getRepo", &params)).unwrap();
+
+        assert_eq!(files_in(&dir), ["com_atproto_sync_getRepo.car"]);
+        let path = dir.join("com_atproto_sync_getRepo.car");
+        assert_eq!(fs::read(&path).unwrap(), car);
+        assert!(!dir.join("com_atproto_sync_getRepo.part").exists());
+        assert_eq!(
+            app.state.output.as_ref().unwrap()["bytes"],
+            json!(car.len())
+        );
+        assert!(app.state.image.is_none());
+    }
+
+    #[test]
+    fn any_type_is_saved_with_the_extension_that_came_back() {
+        let server = serving("video/mp4", b"\0\0\0\x18ftypmp42".to_vec());
+        let (mut app, dir) = app_saving_to(&server, "any");
+
+        let params = ["did:plc:test".to_string(), "bafkrei".to_string()];
+        smol::block_on(app.execute_command("com.atproto.sync.getBlob", &params)).unwrap();
+
+        assert_eq!(files_in(&dir), ["com_atproto_sync_getBlob.mp4"]);
+    }
+
+    #[test]
+    fn json_that_comes_back_as_something_else() {
+        // Text is still read, so the parse error shows what came back
+        let server = serving("text/html", b"<html>Bad gateway</html>".to_vec());
+        let (mut app, dir) = app_saving_to(&server, "html");
+        assert!(smol::block_on(app.execute_raw("com.example.ping", "")).is_err());
+        let error = app.state.last_error.as_ref().unwrap();
+        assert!(error.message.starts_with("Failed to parse response"));
+        assert_eq!(error.body.as_deref(), Some("<html>Bad gateway</html>"));
+        assert!(files_in(&dir).is_empty());
+
+        // Anything else is saved rather than misread
+        let server = serving("application/octet-stream", vec![0, 1, 2]);
+        let (mut app, dir) = app_saving_to(&server, "octets");
+        smol::block_on(app.execute_raw("com.example.ping", "")).unwrap();
+        assert_eq!(files_in(&dir), ["com_example_ping.bin"]);
+    }
+
+    #[test]
+    fn the_declared_encoding_is_used_without_a_content_type() {
+        assert_eq!(Handling::for_response(JSON, None), Handling::Json);
+        assert_eq!(Handling::for_response("image/*", None), Handling::Image);
+        assert_eq!(Handling::for_response("*/*", None), Handling::Save);
+        assert_eq!(
+            Handling::for_response("*/*", Some("application/problem+json")),
+            Handling::Json
+        );
+    }
+
+    #[test]
+    fn extensions_follow_the_type_and_ignore_parameters() {
+        assert_eq!(extension("application/vnd.ipld.car"), "car");
+        assert_eq!(extension("IMAGE/JPEG"), "jpg");
+        assert_eq!(extension("text/plain; charset=utf-8"), "txt");
+        assert_eq!(extension("application/x-unknown"), "bin");
+        assert_eq!(extension(""), "bin");
+    }
+
+    #[test]
+    fn dimensions_are_read_from_each_format() {
+        assert_eq!(dimensions(&png(640, 480)), Some(("PNG", 640, 480)));
+        assert_eq!(
+            dimensions(b"GIF89a\x20\x03\x58\x02"),
+            Some(("GIF", 800, 600))
+        );
+        let jpeg = [
+            0xff, 0xd8, // start of image
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // an APP0 segment to skip
+            0xff, 0xc0, 0x00, 0x11, 0x08, 0x01, 0xe0, 0x02, 0x80, // start of frame
+        ];
+        assert_eq!(dimensions(&jpeg), Some(("JPEG", 640, 480)));
+    }
+
+    #[test]
+    fn truncated_headers_have_no_dimensions() {
+        assert_eq!(dimensions(&png(640, 480)[..20]), None);
+        assert_eq!(dimensions(b"GIF89a\x20\x03\x58"), None);
+        assert_eq!(dimensions(b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0"), None);
+        assert_eq!(
+            dimensions(&[0xff, 0xd8, 0xff, 0xc0, 0x00, 0x11, 0x08]),
+            None
+        );
+        assert_eq!(dimensions(b""), None);
+    }
+}
@@ -0,0 +1,114 @@
+use serde_json::Value;
+use std::time::SystemTime;
+
+use crate::{lexicon::Lexicon, state::InputMode, xrpc, App, FetchError};
+
+/// Asks the server for the schema published for an NSID.
+const RESOLVE_LEXICON: &str = "com.atproto.lexicon.resolveLexicon";
+
+/// Where Bluesky publishes the lexicons of `KNOWN_NAMESPACES`, for servers
+/// that can't resolve them.
+const KNOWN_LEXICONS: &str =
+    "https://raw.githubusercontent.com/bluesky-social/atproto/main/lexicons";
+const KNOWN_NAMESPACES: &[&str] = &["com.atproto.", "app.bsky.", "chat.bsky.", "tools.ozone."];
+
+impl App {
+    /// Shows the lexicon of `method` over the current screen. It's fetched
+    /// the first time and kept for the rest of the session.
+    pub(crate) async fn describe_command(&mut self, method: &str) {
+        if !self.state.lexicons.contains_key(method) {
+            match self.fetch_lexicon(method).await {
+                Ok(lexicon) => {
+                    self.state.lexicons.insert(method.to_string(), lexicon);
+                }
+                Err(msg) => {
+                    self.state.error = Some(msg);
+                    self.state.error_time = Some(SystemTime::now());
+                    return;
+                }
+            }
+        }
+
+        self.state.input.mode = InputMode::Lexicon {
+            method: method.to_string(),
+            previous: Box::new(self.state.input.mode.clone()),
+            previous_scroll: self.state.scroll_offset,
+        };
+        self.state.scroll_offset = 0;
+    }
+
+    /// Asks the server to resolve `method`'s lexicon, falling back to the
+    /// published copy for Bluesky's own namespaces. Errors are messages for
+    /// the status bar.
+    async fn fetch_lexicon(&mut self, method: &str) -> Result<Lexicon, String> {
+        if method.starts_with("oxat.") {
+            return Err(format!(
+                "{} is built into oxat, so it has no lexicon",
+                method
+            ));
+        }
+        if self.state.offline.is_some() {
+            return Err("Describing a command needs the network".into());
+        }
+
+        let url = format!(
+            "{}{}",
+            self.xrpc.url(RESOLVE_LEXICON),
+            xrpc::query_string(&[("nsid", method)])
+        );
+        let timeout = self.state.request_timeout;
+        let unresolved = match self.fetch(self.xrpc.get(&url), timeout).await {
+            Ok(res) if res.status.is_success() => match serde_json::from_str::<Value>(&res.body) {
+                Ok(mut body) if body["schema"].is_object() => {
+                    return Ok(Lexicon {
+                        source: body["uri"].as_str().unwrap_or(&url).to_string(),
+                        doc: body["schema"].take(),
+                    });
+                }
+                _ => "the server's answer had no schema".to_string(),
+            },
+            Ok(res) => {
+                // Servers without lexicon resolution say so with a 404 or 501
+                let error = serde_json::from_str::<Value>(&res.body)
+                    .ok()
+                    .and_then(|body| body["error"].as_str().map(str::to_string));
+                match error {
+                    Some(error) => format!("the server answered {} ({})", res.status, error),
+                    None => format!("the server answered {}", res.status),
+                }
+            }
+            Err(FetchError::Http(e)) => format!("the server couldn't be reached ({})", e),
+            Err(cancelled) => return Err(cancelled.message()),
+        };
+
+        let Some(known) = known_source(method) else {
+            return Err(format!(
+                "Couldn't find the lexicon for {}: {}",
+                method, unresolved
+            ));
+        };
+        match self.fetch(self.xrpc.http().get(&known), timeout).await {
+            Ok(res) if res.status.is_success() => serde_json::from_str(&res.body)
+                .map(|doc| Lexicon { doc, source: known })
+                .map_err(|e| format!("The lexicon for {} isn't valid JSON: {}", method, e)),
+            Ok(res) => Err(format!(
+                "Couldn't find the lexicon for {}: {}, and the published copy answered {}",
+                method, unresolved, res.status
+            )),
+            Err(FetchError::Http(e)) => Err(format!(
+                "Couldn't find the lexicon for {}: {}, and the published copy couldn't be fetched ({})",
+                method, unresolved, e
+            )),
+            Err(cancelled) => Err(cancelled.message()),
+        }
+    }
+}
+
+/// The published copy of `method`'s lexicon, if it's in a namespace whose
+/// lexicons Bluesky publishes.
+fn known_source(method: &str) -> Option<String> {
+    KNOWN_NAMESPACES
+        .iter()
+        .any(|namespace| method.starts_with(namespace))
+        .then(|| format!("{}/{}.json", KNOWN_LEXICONS, method.replace('.', "/")))
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use serde_json::json;
+
+use crate::{
+    error::{AppError, AppResult},
+    state::ErrorDetail,
+    xrpc, App,
+};
+
+/// Page size for `getFollowers`; the server caps it at 100.
+const PAGE_SIZE: u32 = 100;
+
+const FOLLOWERS_METHOD: &str = "app.bsky.graph.getFollowers";
+
+impl App {
+    /// Fetches every follower of `a` and `b` and replaces the output with the
+    /// accounts that follow `a` but not `b`, sorted by handle.
+    pub(crate) async fn diff_followers(
+        &mut self,
+        method: &str,
+        params: &[String],
+    ) -> AppResult<()> {
+        let (Some(a), Some(b)) = (params.first(), params.get(1)) else {
+            return Err(AppError::Request {
+                src: "diffing followers".into(),
+                err_span: (0, 0),
+                msg: "Two accounts are required".into(),
+            }
+            .into());
+        };
+
+        self.state.response_info = None;
+        // Many requests are sent, so there's no one URL to record
+        self.add_to_history(method, String::new(), params.to_vec());
+
+        let result = async {
+            let followers_a = self.fetch_followers(a).await?;
+            let followers_b = self.fetch_followers(b).await?;
+            AppResult::Ok((followers_a, followers_b))
+        }
+        .await;
+
+        let (followers_a, followers_b) = match result {
+            Ok(followers) => followers,
+            Err(e) => {
+                self.update_history_success(method, false);
+                return Err(e);
+            }
+        };
+
+        let mut only_a: Vec<_> = followers_a
+            .iter()
+            .filter(|(did, _)| !followers_b.contains_key(*did))
+            .map(|(did, handle)| json!({ "handle": handle, "did": did }))
+            .collect();
+        only_a.sort_by(|x, y| x["handle"].as_str().cmp(&y["handle"].as_str()));
+
+        self.state.collapsed.clear();
+        self.state.output = Some(json!({
+            "a": a,
+            "b": b,
+            "followersOfA": followers_a.len(),
+            "followersOfB": followers_b.len(),
+            "onlyFollowA": only_a.len(),
+            "followers": only_a,
+        }));
+        self.state.output_method = Some(method.to_string());
+        self.state.error = None;
+        self.update_history_success(method, true);
+        Ok(())
+    }
+
+    /// Pages through `app.bsky.graph.getFollowers` for `actor`, keeping only
+    /// DID → handle from each page so large lists don't hold every response.
+    async fn fetch_followers(&mut self, actor: &str) -> AppResult<BTreeMap<String, String>> {
+        let actor = actor.trim_start_matches('@');
+        let mut followers = BTreeMap::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let limit = PAGE_SIZE.to_string();
+            let mut params = vec![("actor", actor), ("limit", limit.as_str())];
+            if let Some(cursor) = &cursor {
+                params.push(("cursor", cursor));
+            }
+            let url = format!(
+                "{}{}",
+                self.xrpc.url(FOLLOWERS_METHOD),
+                xrpc::query_string(&params)
+            );
+
+            let req = self.xrpc.get(&url);
+
+            let timeout = self.state.timeout_for(FOLLOWERS_METHOD);
+            let page = match self.fetch(req, timeout).await {
+                Ok(res) if res.status.is_success() => {
+                    serde_json::from_str::<serde_json::Value>(&res.body).map_err(|e| {
+                        let msg = format!("Failed to parse response: {}", e);
+                        self.follower_error(&url, msg, AppError::deserialize(&url, e.to_string()))
+                    })?
+                }
+                Ok(res) => {
+                    let msg = format!("Fetching followers of {} failed ({})", actor, res.status);
+                    let error = AppError::http_status(&url, res.status.into(), res.body);
+                    return Err(self.follower_error(&url, msg, error));
+                }
+                Err(e) => {
+                    let msg = e.message();
+                    return Err(self.follower_error(&url, msg, e.into_error(&url)));
+                }
+            };
+
+            for follower in page["followers"].as_array().into_iter().flatten() {
+                if let Some(did) = follower["did"].as_str() {
+                    let handle = follower["handle"].as_str().unwrap_or_default();
+                    followers.insert(did.to_string(), handle.to_string());
+                }
+            }
+
+            cursor = page["cursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(followers)
+    }
+
+    /// Shows `msg` in the status bar and keeps the details of `error` for
+    /// the error view.
+    fn follower_error(&mut self, url: &str, msg: String, error: AppError) -> miette::Report {
+        let (status, body) = match &error {
+            AppError::HttpStatus { code, body, .. } => (Some(*code), Some(body.clone())),
+            _ => (None, None),
+        };
+        self.state.error = Some(msg.clone());
+        self.state.last_error = Some(ErrorDetail {
+            message: msg,
+            url: Some(url.to_string()),
+            status,
+            body,
+        });
+        error.into()
+    }
+}
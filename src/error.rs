@@ -1,5 +1,7 @@
 use miette::Diagnostic;
 use std::{error::Error, fmt::Display};
+use surf::StatusCode;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug, Diagnostic)]
 pub enum AppError {
@@ -21,6 +23,55 @@ pub enum AppError {
         msg: String,
     },
 
+    /// The server couldn't be reached or didn't answer in time.
+    #[diagnostic(code(bsky::network))]
+    Network {
+        #[source_code]
+        src: String,
+        #[label("no response")]
+        err_span: (usize, usize),
+        msg: String,
+    },
+
+    /// The server answered with a non-success status.
+    #[diagnostic(code(bsky::http_status))]
+    HttpStatus {
+        #[source_code]
+        src: String,
+        #[label("server returned {code}")]
+        err_span: (usize, usize),
+        code: u16,
+        body: String,
+    },
+
+    /// The response arrived but wasn't what we expected.
+    #[diagnostic(code(bsky::deserialize))]
+    Deserialize {
+        #[source_code]
+        src: String,
+        #[label("unexpected response")]
+        err_span: (usize, usize),
+        msg: String,
+    },
+
+    #[diagnostic(code(bsky::config))]
+    Config {
+        #[source_code]
+        src: String,
+        #[label("invalid config")]
+        err_span: (usize, usize),
+        msg: String,
+    },
+
+    #[diagnostic(code(bsky::startup))]
+    Startup {
+        #[source_code]
+        src: String,
+        #[label("component failed to start")]
+        err_span: (usize, usize),
+        msg: String,
+    },
+
     #[diagnostic(code(bsky::terminal))]
     Terminal {
         #[source_code]
@@ -33,14 +84,189 @@ pub enum AppError {
 
 pub type AppResult<T> = miette::Result<T>;
 
+impl AppError {
+    /// A failed request, with its URL as the source so the diagnostic shows
+    /// which call went wrong. The label goes on `focus` (e.g. a parameter
+    /// value) if it's in the URL, otherwise on the XRPC method.
+    pub fn request_at(url: &str, focus: Option<&str>, msg: String) -> Self {
+        AppError::Request {
+            src: url.to_string(),
+            err_span: url_span(url, focus),
+            msg,
+        }
+    }
+
+    /// A request to `url` that got no response.
+    pub fn network(url: &str, msg: String) -> Self {
+        AppError::Network {
+            src: url.to_string(),
+            err_span: url_span(url, None),
+            msg,
+        }
+    }
+
+    /// A request to `url` that the server answered with `code`.
+    pub fn http_status(url: &str, code: u16, body: String) -> Self {
+        AppError::HttpStatus {
+            src: url.to_string(),
+            err_span: url_span(url, None),
+            code,
+            body,
+        }
+    }
+
+    /// The XRPC error name in a failed response's body, e.g.
+    /// `AuthFactorTokenRequired`.
+    pub fn xrpc_error(&self) -> Option<String> {
+        let AppError::HttpStatus { body, .. } = self else {
+            return None;
+        };
+        let body = serde_json::from_str::<serde_json::Value>(body).ok()?;
+        Some(body.get("error")?.as_str()?.to_string())
+    }
+
+    /// A response from `url` that couldn't be parsed.
+    pub fn deserialize(url: &str, msg: String) -> Self {
+        AppError::Deserialize {
+            src: url.to_string(),
+            err_span: url_span(url, None),
+            msg,
+        }
+    }
+}
+
+/// The report's message, followed by its source with the labelled span
+/// underlined when it fits on one line. miette's graphical handler needs
+/// its `fancy` feature, so this stands in for it in headless output.
+pub fn render(report: &miette::Report) -> String {
+    let mut out = report.to_string();
+
+    let src = match report.downcast_ref::<AppError>() {
+        Some(
+            AppError::Auth { src, .. }
+            | AppError::Request { src, .. }
+            | AppError::Network { src, .. }
+            | AppError::HttpStatus { src, .. }
+            | AppError::Deserialize { src, .. }
+            | AppError::Config { src, .. }
+            | AppError::Startup { src, .. }
+            | AppError::Terminal { src, .. },
+        ) => src,
+        None => return out,
+    };
+    let Some(label) = report.labels().and_then(|mut labels| labels.next()) else {
+        return out;
+    };
+
+    let (start, end) = (label.offset(), label.offset() + label.len());
+    if label.is_empty() || src.contains('\n') || src.get(start..end).is_none() {
+        return out;
+    }
+    out.push_str(&format!(
+        "\n\n  {}\n  {}{} {}",
+        src,
+        " ".repeat(src[..start].width()),
+        "^".repeat(src[start..end].width()),
+        label.label().unwrap_or_default()
+    ));
+    out
+}
+
+/// Where to label `url`: on `focus` if it's in the URL, otherwise on the
+/// XRPC method.
+fn url_span(url: &str, focus: Option<&str>) -> (usize, usize) {
+    focus
+        .filter(|focus| !focus.is_empty())
+        .and_then(|focus| Some((url.find(focus)?, focus.len())))
+        .unwrap_or_else(|| method_span(url))
+}
+
+/// Offset and length of the NSID in an XRPC URL, or the whole URL if it
+/// doesn't look like one.
+fn method_span(url: &str) -> (usize, usize) {
+    const XRPC: &str = "/xrpc/";
+    match url.find(XRPC) {
+        Some(idx) => {
+            let start = idx + XRPC.len();
+            let len = url[start..].find('?').unwrap_or(url.len() - start);
+            (start, len)
+        }
+        None => (0, url.len()),
+    }
+}
+
 impl Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AppError::Auth { msg, .. } => write!(f, "Auth error: {}", msg),
             AppError::Request { msg, .. } => write!(f, "Request error: {}", msg),
+            AppError::Network { msg, .. } => write!(f, "Network error: {}", msg),
+            AppError::HttpStatus { code, body, .. } => {
+                let reason = StatusCode::try_from(*code)
+                    .map(|status| status.canonical_reason())
+                    .unwrap_or_default();
+                write!(f, "HTTP {} {}: {}", code, reason, body)
+            }
+            AppError::Deserialize { msg, .. } => write!(f, "Parse error: {}", msg),
+            AppError::Config { msg, .. } => write!(f, "Config error: {}", msg),
+            AppError::Startup { msg, .. } => write!(f, "Startup error: {}", msg),
             AppError::Terminal { msg, .. } => write!(f, "Terminal error: {}", msg),
         }
     }
 }
 
 impl Error for AppError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=bsky.app";
+
+    #[test]
+    fn http_status_shows_the_url_and_status() {
+        let body = r#"{"error":"InvalidRequest","message":"Profile not found"}"#;
+        let report = miette::Report::new(AppError::http_status(URL, 400, body.into()));
+
+        assert_eq!(
+            render(&report),
+            format!(
+                "HTTP 400 Bad Request: {}\n\n  {}\n  {}{} server returned 400",
+                body,
+                URL,
+                " ".repeat("https://bsky.social/xrpc/".len()),
+                "^".repeat("app.bsky.actor.getProfile".len())
+            )
+        );
+    }
+
+    #[test]
+    fn label_goes_on_the_focus_when_its_in_the_url() {
+        let report = miette::Report::new(AppError::request_at(
+            URL,
+            Some("bsky.app"),
+            "no such actor".into(),
+        ));
+        let rendered = render(&report);
+        let underline = rendered.lines().last().unwrap();
+        assert_eq!(underline.trim_start(), "^^^^^^^^ request failed");
+        assert_eq!(underline.find('^'), Some(2 + URL.find("bsky.app").unwrap()));
+    }
+
+    #[test]
+    fn urls_that_arent_xrpc_are_labelled_whole() {
+        let url = "https://plc.directory/did:plc:test";
+        let report = miette::Report::new(AppError::network(url, "timed out".into()));
+        let rendered = render(&report);
+        assert!(rendered.starts_with("Network error: timed out\n\n"));
+        assert!(rendered.ends_with(&format!("  {} no response", "^".repeat(url.len()))));
+    }
+
+    #[test]
+    fn reads_the_xrpc_error_name() {
+        let err = AppError::http_status(URL, 401, r#"{"error":"AuthFactorTokenRequired"}"#.into());
+        assert_eq!(err.xrpc_error().as_deref(), Some("AuthFactorTokenRequired"));
+        let err = AppError::http_status(URL, 502, "<html>Bad Gateway</html>".into());
+        assert_eq!(err.xrpc_error(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+
+use crate::{
+    cli::ExecArgs,
+    commands::{self, is_handle, ParamKind},
+    error, output,
+    xrpc::AUTH_FACTOR_REQUIRED,
+    App,
+};
+
+// Exit codes, so scripts can tell failures apart
+const EXIT_REQUEST_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_AUTH_FAILED: i32 = 3;
+const EXIT_OUTPUT_FAILED: i32 = 4;
+
+/// Runs a single command without the TUI and writes the response to stdout
+/// or `--output`. Logs in first if `OXAT_PASSWORD` is set, using
+/// `OXAT_IDENTIFIER` or the selected profile's identifier, and otherwise
+/// uses the profile's saved session if there is one.
+pub async fn run(mut app: App, args: ExecArgs) -> i32 {
+    let Some(cmd) = commands::find(&args.method) else {
+        eprintln!("Unknown command '{}'", args.method);
+        return EXIT_USAGE;
+    };
+
+    if cmd.destructive && !args.yes {
+        eprintln!("{} can't be undone; pass --yes to send it", cmd.method);
+        return EXIT_USAGE;
+    }
+
+    if let Some((name, _)) = args
+        .params
+        .iter()
+        .find(|(name, _)| !cmd.parameters.iter().any(|p| p.name == name))
+    {
+        eprintln!("{} has no parameter '{}'", cmd.method, name);
+        return EXIT_USAGE;
+    }
+
+    let mut params = Vec::new();
+    for param in cmd.parameters {
+        // A list can be given as `name=a,b` or by repeating `name=`
+        let values: Vec<&str> = args
+            .params
+            .iter()
+            .filter(|(name, _)| name == param.name)
+            .map(|(_, value)| value.as_str())
+            .collect();
+        if values.len() > 1 && !param.is_array {
+            eprintln!("{} takes a single value", param.name);
+            return EXIT_USAGE;
+        }
+        let value = (!values.is_empty()).then(|| values.join(","));
+
+        match value {
+            Some(value) => {
+                for item in param.values(&value) {
+                    let accepts_handle = param.kind == ParamKind::Did
+                        && app.state.resolve_handles
+                        && is_handle(item);
+                    if let Err(hint) = param.kind.validate(item) {
+                        if !accepts_handle {
+                            eprintln!("{} {}", param.name, hint);
+                            return EXIT_USAGE;
+                        }
+                    }
+                }
+                params.push(value)
+            }
+            None if param.optional => params.push(param.default.unwrap_or("").to_string()),
+            None => {
+                eprintln!("Missing required parameter '{}'", param.name);
+                return EXIT_USAGE;
+            }
+        }
+    }
+
+    let identifier = std::env::var("OXAT_IDENTIFIER")
+        .ok()
+        .or(app.state.identifier.take());
+    let password = std::env::var("OXAT_PASSWORD")
+        .ok()
+        .filter(|_| app.state.offline.is_none());
+    if let (Some(identifier), Some(password)) = (identifier, password) {
+        let auth_factor_token = std::env::var("OXAT_AUTH_FACTOR_TOKEN").ok();
+        if let Err(e) = app
+            .handle_auth(identifier, password, auth_factor_token)
+            .await
+        {
+            eprintln!("{}", e);
+            if crate::xrpc_error(&e).as_deref() == Some(AUTH_FACTOR_REQUIRED) {
+                eprintln!("Set OXAT_AUTH_FACTOR_TOKEN to the sign-in code from your email");
+            }
+            return EXIT_AUTH_FAILED;
+        }
+    } else if app.state.offline.is_none() {
+        app.resume_session().await;
+    }
+
+    if let Err(e) = app.execute_command(cmd.method, &params).await {
+        eprintln!("{}", error::render(&e));
+        return EXIT_REQUEST_FAILED;
+    }
+
+    // There's no one to press `s`, so images are saved like other downloads
+    if app.state.image.is_some() {
+        if let Err(msg) = app.save_image() {
+            eprintln!("{}", msg);
+            return EXIT_OUTPUT_FAILED;
+        }
+    }
+
+    let Some(value) = &app.state.output else {
+        return EXIT_REQUEST_FAILED;
+    };
+
+    let formatted = match output::format_json(value, args.format) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("Failed to format output as {}: {}", args.format.name(), e);
+            return EXIT_REQUEST_FAILED;
+        }
+    };
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, formatted + "\n") {
+                eprintln!("Failed to write {}: {}", path.display(), e);
+                return EXIT_OUTPUT_FAILED;
+            }
+        }
+        None => println!("{}", formatted),
+    }
+
+    0
+}
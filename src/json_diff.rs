@@ -0,0 +1,321 @@
+use serde_json::Value;
+
+/// Arrays bigger than this (old × new items) are compared item by item
+/// instead of aligned, so the diff stays quick enough to draw every frame.
+const MAX_ALIGN_CELLS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+}
+
+/// One line of a diff, laid out like pretty-printed JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+}
+
+/// The changes from `old` to `new` as pretty-printed JSON lines. Objects
+/// are compared key by key and arrays item by item, with unchanged items
+/// lined up so an insertion doesn't mark everything after it as changed.
+/// Unchanged objects and arrays are shown on one line as `{…}` or
+/// `[…N items]`.
+pub fn diff_lines(old: &Value, new: &Value) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+    diff_node(&mut lines, old, new, 0, "", "");
+    lines
+}
+
+/// Whether the diff adds or removes anything.
+pub fn has_changes(lines: &[DiffLine]) -> bool {
+    lines.iter().any(|line| line.change != Change::Same)
+}
+
+fn diff_node(
+    lines: &mut Vec<DiffLine>,
+    old: &Value,
+    new: &Value,
+    depth: usize,
+    key: &str,
+    comma: &str,
+) {
+    let indent = "  ".repeat(depth);
+    if old == new {
+        let text = match new {
+            Value::Array(items) if items.len() == 1 => "[…1 item]".to_string(),
+            Value::Array(items) if !items.is_empty() => format!("[…{} items]", items.len()),
+            Value::Object(fields) if !fields.is_empty() => "{…}".to_string(),
+            _ => new.to_string(),
+        };
+        push(
+            lines,
+            Change::Same,
+            format!("{}{}{}{}", indent, key, text, comma),
+        );
+        return;
+    }
+
+    match (old, new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => {
+            push(lines, Change::Same, format!("{}{}{{", indent, key));
+            let mut names: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+            names.sort();
+            names.dedup();
+
+            let last = names.len().saturating_sub(1);
+            for (i, name) in names.into_iter().enumerate() {
+                let key = format!("{}: ", Value::String(name.clone()));
+                let comma = if i == last { "" } else { "," };
+                match (old_fields.get(name), new_fields.get(name)) {
+                    (Some(old), Some(new)) => diff_node(lines, old, new, depth + 1, &key, comma),
+                    (Some(old), None) => {
+                        push_value(lines, Change::Removed, old, depth + 1, &key, comma)
+                    }
+                    (None, Some(new)) => {
+                        push_value(lines, Change::Added, new, depth + 1, &key, comma)
+                    }
+                    (None, None) => {}
+                }
+            }
+            push(lines, Change::Same, format!("{}}}{}", indent, comma));
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            push(lines, Change::Same, format!("{}{}[", indent, key));
+            let steps = align(old_items, new_items);
+            let last = steps.len().saturating_sub(1);
+            for (i, step) in steps.into_iter().enumerate() {
+                let comma = if i == last { "" } else { "," };
+                match step {
+                    (Some(old), Some(new)) => diff_node(
+                        lines,
+                        &old_items[old],
+                        &new_items[new],
+                        depth + 1,
+                        "",
+                        comma,
+                    ),
+                    (Some(old), None) => push_value(
+                        lines,
+                        Change::Removed,
+                        &old_items[old],
+                        depth + 1,
+                        "",
+                        comma,
+                    ),
+                    (None, Some(new)) => {
+                        push_value(lines, Change::Added, &new_items[new], depth + 1, "", comma)
+                    }
+                    (None, None) => {}
+                }
+            }
+            push(lines, Change::Same, format!("{}]{}", indent, comma));
+        }
+        _ => {
+            push_value(lines, Change::Removed, old, depth, key, comma);
+            push_value(lines, Change::Added, new, depth, key, comma);
+        }
+    }
+}
+
+/// Pairs up the items of two arrays as (old index, new index). Equal items
+/// are matched by longest common subsequence; between matches, the rest are
+/// paired in order so edited items are diffed field by field, and any left
+/// over are removals or additions.
+fn align(old: &[Value], new: &[Value]) -> Vec<(Option<usize>, Option<usize>)> {
+    let matches = if old.len() * new.len() <= MAX_ALIGN_CELLS {
+        common_items(old, new)
+    } else {
+        Vec::new()
+    };
+
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in matches.into_iter().chain([(old.len(), new.len())]) {
+        while i < next_i || j < next_j {
+            steps.push(((i < next_i).then_some(i), (j < next_j).then_some(j)));
+            i = (i + 1).min(next_i);
+            j = (j + 1).min(next_j);
+        }
+        if next_i < old.len() {
+            steps.push((Some(next_i), Some(next_j)));
+            i = next_i + 1;
+            j = next_j + 1;
+        }
+    }
+    steps
+}
+
+/// Index pairs of equal items in a longest common subsequence of `old` and
+/// `new`.
+fn common_items(old: &[Value], new: &[Value]) -> Vec<(usize, usize)> {
+    // lengths[i][j] is the LCS length of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Adds all of `value`, pretty-printed, as added or removed lines.
+fn push_value(
+    lines: &mut Vec<DiffLine>,
+    change: Change,
+    value: &Value,
+    depth: usize,
+    key: &str,
+    comma: &str,
+) {
+    let indent = "  ".repeat(depth);
+    let pretty = serde_json::to_string_pretty(value).unwrap_or_default();
+    let mut rows = pretty.lines().peekable();
+    let mut first = true;
+    while let Some(row) = rows.next() {
+        let prefix = if first { key } else { "" };
+        let comma = if rows.peek().is_none() { comma } else { "" };
+        push(
+            lines,
+            change,
+            format!("{}{}{}{}", indent, prefix, row, comma),
+        );
+        first = false;
+    }
+}
+
+fn push(lines: &mut Vec<DiffLine>, change: Change, text: String) {
+    lines.push(DiffLine { change, text });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The diff as text, with `+` and `-` marking added and removed lines.
+    fn render(lines: &[DiffLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                let mark = match line.change {
+                    Change::Same => ' ',
+                    Change::Added => '+',
+                    Change::Removed => '-',
+                };
+                format!("{}{}", mark, line.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_changed_leaf_is_shown_inside_its_parents() {
+        let old = json!({ "a": { "b": { "c": 1, "d": 2 } } });
+        let new = json!({ "a": { "b": { "c": 3, "d": 2 } } });
+
+        assert_eq!(
+            render(&diff_lines(&old, &new)),
+            [
+                " {",
+                "   \"a\": {",
+                "     \"b\": {",
+                "-      \"c\": 1,",
+                "+      \"c\": 3,",
+                "       \"d\": 2",
+                "     }",
+                "   }",
+                " }",
+            ]
+        );
+    }
+
+    #[test]
+    fn an_inserted_item_leaves_the_rest_unchanged() {
+        let old = json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }]);
+        let new = json!([{ "id": 1 }, { "id": 9 }, { "id": 2 }, { "id": 3 }]);
+
+        assert_eq!(
+            render(&diff_lines(&old, &new)),
+            [
+                " [",
+                "   {…},",
+                "+  {",
+                "+    \"id\": 9",
+                "+  },",
+                "   {…},",
+                "   {…}",
+                " ]",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_value_that_changes_type_is_removed_and_added_whole() {
+        let old = json!({ "x": { "y": 1 } });
+        let new = json!({ "x": "y" });
+
+        assert_eq!(
+            render(&diff_lines(&old, &new)),
+            [
+                " {",
+                "-  \"x\": {",
+                "-    \"y\": 1",
+                "-  }",
+                "+  \"x\": \"y\"",
+                " }",
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_values_have_no_changes() {
+        let value = json!({ "feed": [1, 2, 3], "cursor": "abc" });
+        let lines = diff_lines(&value, &value);
+
+        assert!(!has_changes(&lines));
+        assert_eq!(render(&lines), [" {…}"]);
+    }
+
+    #[test]
+    fn big_arrays_are_paired_in_order_instead_of_aligned() {
+        let old: Vec<Value> = (0..101).map(|n| json!(n)).collect();
+        let mut new = old.clone();
+        new.insert(0, json!(-1));
+        new.pop();
+        assert!(old.len() * new.len() > MAX_ALIGN_CELLS);
+
+        let pairs: Vec<_> = (0..101).map(|n| (Some(n), Some(n))).collect();
+        assert_eq!(align(&old, &new), pairs);
+
+        // Under the limit the same insertion is lined up
+        assert_eq!(
+            align(&old[..3], &new[..4]),
+            [
+                (None, Some(0)),
+                (Some(0), Some(1)),
+                (Some(1), Some(2)),
+                (Some(2), Some(3))
+            ]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Deserialize)]
+struct Claims {
+    exp: u64,
+}
+
+/// When `token` expires, read from its `exp` claim. The signature isn't
+/// checked, so this is only good for telling the user how long is left.
+pub fn expiry(token: &str) -> Option<SystemTime> {
+    let payload = token.split('.').nth(1)?;
+    let bytes =
+        base64::decode_config(payload.trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()?;
+    let claims: Claims = serde_json::from_slice(&bytes).ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(claims.exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(claims: &str) -> String {
+        let encode = |part: &str| base64::encode_config(part, base64::URL_SAFE_NO_PAD);
+        format!(
+            "{}.{}.signature",
+            encode(r#"{"typ":"at+jwt","alg":"ES256K"}"#),
+            encode(claims)
+        )
+    }
+
+    #[test]
+    fn reads_the_exp_claim() {
+        let token =
+            token(r#"{"scope":"com.atproto.access","sub":"did:plc:test","exp":1760000000}"#);
+        assert_eq!(
+            expiry(&token),
+            Some(UNIX_EPOCH + Duration::from_secs(1_760_000_000))
+        );
+    }
+
+    #[test]
+    fn padded_payloads_still_decode() {
+        let payload = base64::encode_config(r#"{"exp":12}"#, base64::URL_SAFE);
+        assert!(payload.ends_with('='));
+        let token = format!("header.{}.signature", payload);
+        assert_eq!(expiry(&token), Some(UNIX_EPOCH + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn no_exp_claim_is_none() {
+        assert_eq!(expiry(&token(r#"{"sub":"did:plc:test"}"#)), None);
+        assert_eq!(expiry(&token(r#"{"exp":"tomorrow"}"#)), None);
+        assert_eq!(expiry(&token("not json")), None);
+    }
+
+    #[test]
+    fn malformed_base64_is_none() {
+        assert_eq!(expiry("header.!!not*base64!!.signature"), None);
+        // Standard base64, not the URL-safe alphabet JWTs use
+        let payload = base64::encode(r#"{"exp":1760000000,"x":"??>>"}"#);
+        assert!(payload.contains('+') || payload.contains('/'));
+        assert_eq!(expiry(&format!("header.{}.signature", payload)), None);
+    }
+
+    #[test]
+    fn tokens_that_arent_jwts_are_none() {
+        assert_eq!(expiry(""), None);
+        assert_eq!(expiry("opaque-refresh-token"), None);
+    }
+}
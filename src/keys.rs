@@ -0,0 +1,289 @@
+use crate::state::InputMode;
+
+/// The keys for one mode, as (keys, action) pairs. The help line shows the
+/// current mode's group and the `?` overlay shows them all, so new keys only
+/// need adding here.
+pub struct KeyGroup {
+    pub name: &'static str,
+    pub bindings: &'static [(&'static str, &'static str)],
+}
+
+impl KeyGroup {
+    /// The one-line summary at the bottom of the screen.
+    pub fn help_line(&self) -> String {
+        self.bindings
+            .iter()
+            .chain(GLOBAL.bindings.first())
+            .map(|(keys, action)| format!("{} - {}", keys, action))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+/// Keys that work on every screen. The first is added to every help line.
+pub const GLOBAL: KeyGroup = KeyGroup {
+    name: "Anywhere",
+    bindings: &[
+        ("Ctrl+c", "Quit"),
+        ("Esc", "Dismiss Message"),
+        ("Ctrl+d", "Error Details"),
+        ("Ctrl+l", "Look Up Handle/DID"),
+        ("Ctrl+p", "Toggle Preview"),
+        ("Ctrl+r", "Refresh Session"),
+        ("Ctrl+v", "Paste"),
+    ],
+};
+
+pub const LOADING: KeyGroup = KeyGroup {
+    name: "While Loading",
+    bindings: &[("Esc", "Cancel Request")],
+};
+
+const LOGIN: KeyGroup = KeyGroup {
+    name: "Login",
+    bindings: &[("Enter", "Submit")],
+};
+
+const AUTH_FACTOR: KeyGroup = KeyGroup {
+    name: "Sign-in Code",
+    bindings: &[("Enter", "Submit Code"), ("Esc", "Back to Login")],
+};
+
+const COMMAND: KeyGroup = KeyGroup {
+    name: "Command List",
+    bindings: &[
+        ("Tab", "Autocomplete"),
+        ("↑↓", "Scroll Commands"),
+        ("Enter", "Select Command"),
+        ("←→", "Collapse/Expand Group"),
+        ("Ctrl+f", "Favorite"),
+        ("h", "History"),
+        ("F5", "Re-run Last"),
+        (":", "Raw XRPC"),
+        ("F2", "Describe (Lexicon)"),
+        ("Ctrl+s", "Settings"),
+        ("?", "Help"),
+        ("Ctrl+r", "Refresh Session"),
+        ("Ctrl+t", "Headers"),
+        ("Ctrl+y", "Copy Token"),
+        ("Ctrl+l", "Look Up Handle/DID"),
+    ],
+};
+
+const HISTORY: KeyGroup = KeyGroup {
+    name: "History",
+    bindings: &[
+        ("↑↓", "Browse History"),
+        ("Enter", "Use Command"),
+        ("c", "Copy URL"),
+        ("y", "Copy as curl"),
+        ("f", "Only Failures"),
+        ("s", "Only Successes"),
+        ("/", "Filter by Method"),
+        ("D", "Clear History"),
+        ("?", "Help"),
+        ("Esc", "Back"),
+    ],
+};
+
+const HISTORY_FILTER: KeyGroup = KeyGroup {
+    name: "History Filter",
+    bindings: &[("Enter", "Apply"), ("Esc", "Clear")],
+};
+
+const FILTER: KeyGroup = KeyGroup {
+    name: "Filter",
+    bindings: &[("Enter", "Apply (empty clears)"), ("Esc", "Cancel")],
+};
+
+const REPEAT: KeyGroup = KeyGroup {
+    name: "Repeat Count",
+    bindings: &[("Enter", "Start (Esc stops it)"), ("Esc", "Cancel")],
+};
+
+const AUTO_REFRESH: KeyGroup = KeyGroup {
+    name: "Auto-refresh Interval",
+    bindings: &[("Enter", "Start"), ("Esc", "Cancel")],
+};
+
+const RAW_XRPC: KeyGroup = KeyGroup {
+    name: "Raw XRPC",
+    bindings: &[("Enter", "Send"), ("Esc", "Cancel")],
+};
+
+const HEADERS: KeyGroup = KeyGroup {
+    name: "Headers",
+    bindings: &[("Enter", "Set/Remove Header"), ("Esc", "Back")],
+};
+
+const SETTINGS: KeyGroup = KeyGroup {
+    name: "Settings",
+    bindings: &[
+        ("↑↓", "Select"),
+        ("Enter", "Edit"),
+        ("s", "Save to Config"),
+        ("?", "Help"),
+        ("Esc", "Back"),
+    ],
+};
+
+const SETTINGS_EDIT: KeyGroup = KeyGroup {
+    name: "Editing a Setting",
+    bindings: &[("Enter", "Apply"), ("Esc", "Cancel")],
+};
+
+const BUILDER: KeyGroup = KeyGroup {
+    name: "Command Builder",
+    bindings: &[
+        ("Enter", "Next Parameter/Submit"),
+        ("↑↓/Shift+Tab", "Edit Previous/Next"),
+        ("Ctrl+o", "Fill In Your Account"),
+        ("Esc", "Cancel"),
+        ("Ctrl+p", "Toggle Preview"),
+    ],
+};
+
+const RESPONSE: KeyGroup = KeyGroup {
+    name: "Response",
+    bindings: &[
+        ("↑↓", "Move Focus"),
+        ("PgUp/PgDn", "Scroll"),
+        ("Home/End", "Top/Bottom"),
+        ("]", "Next Page"),
+        ("r/F5", "Re-run"),
+        ("R", "Repeat N Times"),
+        ("a", "Auto-refresh On/Off"),
+        ("o", "Open URI"),
+        ("Backspace/b", "Back"),
+        ("/", "Search"),
+        ("f", "Filter"),
+        ("n/N", "Next/Prev Match"),
+        ("i", "Toggle Case"),
+        ("Space", "Fold"),
+        ("l", "Line Numbers"),
+        ("w", "Wrap"),
+        ("v", "Toggle Summary/Post List"),
+        ("P", "Pin for Comparison"),
+        ("d", "Diff Against Pinned"),
+        ("←→", "Scroll Sideways"),
+        ("Enter", "Return to Commands"),
+        ("c/C", "Copy Pretty/Compact"),
+        ("y", "Copy Value"),
+        ("p", "Copy Path"),
+        ("x", "Copy as curl"),
+        ("e", "Export"),
+        ("E", "Export Format"),
+        ("Ctrl+e", "Export Request Details"),
+        ("O/Ctrl+o", "Open Export/Its Folder"),
+        ("s", "Save Image"),
+        ("?", "Help"),
+    ],
+};
+
+const CONFIRM_REQUEST: KeyGroup = KeyGroup {
+    name: "Request Preview",
+    bindings: &[
+        ("Enter", "Send"),
+        ("Esc", "Back to Parameters"),
+        ("Ctrl+p", "Toggle Preview"),
+    ],
+};
+
+const CONFIRM_DESTRUCTIVE: KeyGroup = KeyGroup {
+    name: "Destructive Command",
+    bindings: &[("yes + Enter", "Confirm"), ("Esc", "Back to Parameters")],
+};
+
+const SEARCH: KeyGroup = KeyGroup {
+    name: "Search",
+    bindings: &[("Enter", "Search (empty clears)"), ("Esc", "Cancel")],
+};
+
+const ERROR_DETAIL: KeyGroup = KeyGroup {
+    name: "Error Details",
+    bindings: &[
+        ("↑↓/PgUp/PgDn", "Scroll"),
+        ("Home/End", "Top/Bottom"),
+        ("?", "Help"),
+        ("Esc", "Close"),
+    ],
+};
+
+const LEXICON: KeyGroup = KeyGroup {
+    name: "Lexicon",
+    bindings: &[
+        ("↑↓/PgUp/PgDn", "Scroll"),
+        ("Home/End", "Top/Bottom"),
+        ("?", "Help"),
+        ("Esc", "Close"),
+    ],
+};
+
+const LOOKUP: KeyGroup = KeyGroup {
+    name: "Handle/DID Lookup",
+    bindings: &[
+        ("Enter", "Look Up"),
+        ("Tab", "Insert into Input"),
+        ("Ctrl+y", "Copy"),
+        ("Esc", "Close"),
+    ],
+};
+
+const HELP: KeyGroup = KeyGroup {
+    name: "Help",
+    bindings: &[("↑↓/PgUp/PgDn", "Scroll"), ("Esc/?", "Close")],
+};
+
+/// Every group, in the order the overlay lists them.
+pub const ALL: &[&KeyGroup] = &[
+    &GLOBAL,
+    &LOGIN,
+    &AUTH_FACTOR,
+    &COMMAND,
+    &BUILDER,
+    &CONFIRM_REQUEST,
+    &CONFIRM_DESTRUCTIVE,
+    &RESPONSE,
+    &SEARCH,
+    &FILTER,
+    &REPEAT,
+    &AUTO_REFRESH,
+    &HISTORY,
+    &HISTORY_FILTER,
+    &RAW_XRPC,
+    &HEADERS,
+    &SETTINGS,
+    &SETTINGS_EDIT,
+    &ERROR_DETAIL,
+    &LEXICON,
+    &LOOKUP,
+    &LOADING,
+    &HELP,
+];
+
+/// The keys for `mode`.
+pub fn for_mode(mode: &InputMode) -> &'static KeyGroup {
+    match mode {
+        InputMode::Normal | InputMode::Password => &LOGIN,
+        InputMode::AuthFactor { .. } => &AUTH_FACTOR,
+        InputMode::Command => &COMMAND,
+        InputMode::History => &HISTORY,
+        InputMode::HistoryFilter => &HISTORY_FILTER,
+        InputMode::FilterResponse => &FILTER,
+        InputMode::RepeatCount => &REPEAT,
+        InputMode::AutoRefreshInterval => &AUTO_REFRESH,
+        InputMode::RawXrpc => &RAW_XRPC,
+        InputMode::Headers => &HEADERS,
+        InputMode::Settings { editing: true, .. } => &SETTINGS_EDIT,
+        InputMode::Settings { .. } => &SETTINGS,
+        InputMode::CommandBuilder { .. } => &BUILDER,
+        InputMode::ViewingResponse => &RESPONSE,
+        InputMode::ConfirmRequest { .. } => &CONFIRM_REQUEST,
+        InputMode::ConfirmDestructive { .. } => &CONFIRM_DESTRUCTIVE,
+        InputMode::SearchResponse => &SEARCH,
+        InputMode::ErrorDetail { .. } => &ERROR_DETAIL,
+        InputMode::Lexicon { .. } => &LEXICON,
+        InputMode::Lookup { .. } => &LOOKUP,
+        InputMode::Help { .. } => &HELP,
+    }
+}
@@ -0,0 +1,336 @@
+use serde_json::Value;
+use std::{fs, path::Path};
+
+use crate::commands::{ParamKind, Parameter, XrpcCommand, JSON};
+
+/// Reads every `.json` lexicon in `dir`. Files that aren't JSON queries are
+/// skipped; problems are returned as messages rather than failing startup.
+pub fn load_dir(dir: &Path) -> (Vec<XrpcCommand>, Vec<String>) {
+    let mut commands = Vec::new();
+    let mut warnings = Vec::new();
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        // No lexicon directory is the normal case
+        return (commands, warnings);
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse(&contents));
+        match parsed {
+            Ok(Some(command)) => commands.push(command),
+            Ok(None) => {}
+            Err(e) => warnings.push(format!("Skipped lexicon {}: {}", path.display(), e)),
+        }
+    }
+
+    (commands, warnings)
+}
+
+/// Builds a command from a lexicon document. Returns `None` for lexicons
+/// that aren't queries with a JSON output, since those can't be sent or
+/// displayed.
+pub fn parse(contents: &str) -> Result<Option<XrpcCommand>, String> {
+    let doc: Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+
+    let id = doc["id"].as_str().ok_or("missing \"id\"")?;
+    let main = &doc["defs"]["main"];
+    if main["type"] != "query" {
+        return Ok(None);
+    }
+    if let Some(encoding) = main["output"]["encoding"].as_str() {
+        if encoding != "application/json" {
+            return Ok(None);
+        }
+    }
+
+    let required: Vec<&str> = main["parameters"]["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    let mut parameters: Vec<Parameter> = main["parameters"]["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, schema)| {
+            // A list's items say what each value must look like
+            let is_array = schema["type"] == "array";
+            let item = if is_array { &schema["items"] } else { schema };
+            let kind = match (item["type"].as_str(), item["format"].as_str()) {
+                (Some("integer"), _) => ParamKind::Integer,
+                (Some("boolean"), _) => ParamKind::Boolean,
+                (Some("string"), Some("did")) => ParamKind::Did,
+                (Some("string"), Some("at-uri")) => ParamKind::AtUri,
+                (Some("string"), Some("at-identifier" | "handle")) => ParamKind::AtIdentifier,
+                _ => ParamKind::Text,
+            };
+            let default = match &schema["default"] {
+                Value::Null => None,
+                Value::String(s) => Some(leak(s.clone())),
+                other => Some(leak(other.to_string())),
+            };
+
+            Parameter {
+                name: leak(name.clone()),
+                description: leak(schema["description"].as_str().unwrap_or(name).to_string()),
+                optional: !required.contains(&name.as_str()),
+                default,
+                kind,
+                is_array,
+            }
+        })
+        .collect();
+    // Required params first and the cursor last, matching the built-in commands
+    parameters.sort_by_key(|p| (p.optional, p.name == "cursor"));
+
+    Ok(Some(XrpcCommand {
+        method: leak(id.to_string()),
+        alias: None,
+        description: leak(main["description"].as_str().unwrap_or(id).to_string()),
+        encoding: JSON,
+        destructive: false,
+        parameters: Box::leak(parameters.into_boxed_slice()),
+    }))
+}
+
+// Commands live for the whole program, so loaded strings are leaked to fit
+// the `'static` fields the built-in commands use.
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+/// A command's lexicon, fetched to describe it, and where it came from.
+#[derive(Debug, Clone)]
+pub struct Lexicon {
+    pub doc: Value,
+    pub source: String,
+}
+
+/// A property of an object schema, as listed when describing a lexicon.
+pub struct Field {
+    /// How deeply it's nested inside other objects.
+    pub depth: usize,
+    pub name: String,
+    pub kind: String,
+    pub required: bool,
+    pub constraints: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// The properties of `object`, each followed by the properties of any
+/// object nested in it.
+pub fn fields(object: &Value, depth: usize) -> Vec<Field> {
+    let required: Vec<&str> = object["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    // Properties come back sorted by name, so required ones are put first
+    let mut properties: Vec<_> = object["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .collect();
+    properties.sort_by_key(|(name, _)| !required.contains(&name.as_str()));
+
+    let mut fields = Vec::new();
+    for (name, schema) in properties {
+        fields.push(Field {
+            depth,
+            name: name.clone(),
+            kind: type_name(schema),
+            required: required.contains(&name.as_str()),
+            constraints: constraints(schema),
+            description: schema["description"].as_str().map(str::to_string),
+        });
+        let inner = if schema["type"] == "array" {
+            &schema["items"]
+        } else {
+            schema
+        };
+        if inner["type"] == "object" {
+            fields.extend(self::fields(inner, depth + 1));
+        }
+    }
+    fields
+}
+
+/// A short name for the type `schema` describes, e.g. `string (did)`,
+/// `array of #like` or `union of #a | #b`.
+pub fn type_name(schema: &Value) -> String {
+    let kind = schema["type"].as_str().unwrap_or("unknown");
+    match kind {
+        "array" => format!("array of {}", type_name(&schema["items"])),
+        "ref" => schema["ref"].as_str().unwrap_or("ref").to_string(),
+        "union" => {
+            let refs: Vec<&str> = schema["refs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            format!("union of {}", refs.join(" | "))
+        }
+        _ => match schema["format"].as_str() {
+            Some(format) => format!("{} ({})", kind, format),
+            None => kind.to_string(),
+        },
+    }
+}
+
+/// What `schema` allows beyond its type: ranges, lengths, defaults and
+/// fixed or suggested values.
+pub fn constraints(schema: &Value) -> Vec<String> {
+    let number = |key: &str| schema[key].as_i64();
+    let mut constraints = Vec::new();
+
+    match (number("minimum"), number("maximum")) {
+        (Some(min), Some(max)) => constraints.push(format!("{}–{}", min, max)),
+        (Some(min), None) => constraints.push(format!("at least {}", min)),
+        (None, Some(max)) => constraints.push(format!("at most {}", max)),
+        (None, None) => {}
+    }
+    let unit = if schema["type"] == "array" {
+        "items"
+    } else {
+        "bytes"
+    };
+    for (key, bound, unit) in [
+        ("minLength", "at least", unit),
+        ("maxLength", "at most", unit),
+        ("minGraphemes", "at least", "graphemes"),
+        ("maxGraphemes", "at most", "graphemes"),
+        ("maxSize", "at most", "bytes"),
+    ] {
+        if let Some(value) = number(key) {
+            constraints.push(format!("{} {} {}", bound, value, unit));
+        }
+    }
+    if let Some(accept) = schema["accept"].as_array() {
+        constraints.push(format!("accepts {}", join(accept)));
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        constraints.push(format!("one of {}", join(values)));
+    }
+    if let Some(values) = schema["knownValues"].as_array() {
+        constraints.push(format!("known values {}", join(values)));
+    }
+    if !schema["const"].is_null() {
+        constraints.push(format!("always {}", schema["const"]));
+    }
+    if !schema["default"].is_null() {
+        constraints.push(format!("default {}", schema["default"]));
+    }
+    if schema["type"] == "array" {
+        constraints.extend(
+            self::constraints(&schema["items"])
+                .into_iter()
+                .map(|constraint| format!("each {}", constraint)),
+        );
+    }
+    constraints
+}
+
+/// Lists string values as they are and anything else as JSON.
+fn join(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lexicons")
+    }
+
+    #[test]
+    fn parses_get_profile() {
+        let contents =
+            fs::read_to_string(fixtures().join("app.bsky.actor.getProfile.json")).unwrap();
+        let command = parse(&contents).unwrap().expect("a query");
+
+        assert_eq!(command.method, "app.bsky.actor.getProfile");
+        assert!(command.description.starts_with("Get detailed profile view"));
+        assert_eq!(command.encoding, JSON);
+        assert!(!command.destructive);
+        assert_eq!(command.parameters.len(), 1);
+
+        let actor = &command.parameters[0];
+        assert_eq!(actor.name, "actor");
+        assert_eq!(
+            actor.description,
+            "Handle or DID of account to fetch profile of."
+        );
+        assert!(!actor.optional);
+        assert_eq!(actor.default, None);
+        assert_eq!(actor.kind, ParamKind::AtIdentifier);
+        assert!(!actor.is_array);
+    }
+
+    #[test]
+    fn orders_required_params_first_and_cursor_last() {
+        let contents =
+            fs::read_to_string(fixtures().join("app.bsky.feed.getAuthorFeed.json")).unwrap();
+        let command = parse(&contents).unwrap().expect("a query");
+
+        let names: Vec<_> = command.parameters.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["actor", "includePins", "limit", "cursor"]);
+
+        let limit = &command.parameters[command.param_index("limit").unwrap()];
+        assert_eq!(limit.kind, ParamKind::Integer);
+        assert_eq!(limit.default, Some("50"));
+        // No description falls back to the name
+        assert_eq!(limit.description, "limit");
+        let pins = &command.parameters[command.param_index("includePins").unwrap()];
+        assert_eq!(pins.kind, ParamKind::Boolean);
+        assert_eq!(pins.default, Some("false"));
+    }
+
+    #[test]
+    fn load_dir_keeps_queries_and_reports_broken_files() {
+        let (commands, warnings) = load_dir(&fixtures());
+
+        let methods: Vec<_> = commands.iter().map(|c| c.method).collect();
+        assert_eq!(
+            methods,
+            ["app.bsky.actor.getProfile", "app.bsky.feed.getAuthorFeed"]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("broken.json"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn load_dir_without_a_directory_is_empty() {
+        let (commands, warnings) = load_dir(&fixtures().join("missing"));
+        assert!(commands.is_empty());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn skips_non_json_output_and_needs_an_id() {
+        let blob = r#"{"id": "com.atproto.sync.getBlob", "defs": {"main": {
+            "type": "query", "output": {"encoding": "*/*"}}}}"#;
+        assert!(parse(blob).unwrap().is_none());
+        assert!(parse(r#"{"defs": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use futures::future::BoxFuture;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use surf::{middleware::Next, Client, Request, Response};
+use time::OffsetDateTime;
+
+use crate::post;
+
+/// Once the log grows past this, it's moved aside at the next startup so
+/// it can't grow forever. Only one old log is kept.
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Writes oxat's log records to a file, one line each. Records from
+/// dependencies are left out.
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(mut file) = self.file.lock() {
+            // A full disk shouldn't take the app down with it
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                post::timestamp(OffsetDateTime::now_utc()),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Starts appending log records to `path`, first moving a log that's grown
+/// too big to `<path>.1`.
+pub fn init(path: &Path) -> io::Result<()> {
+    if fs::metadata(path).is_ok_and(|meta| meta.len() > MAX_LOG_SIZE) {
+        fs::rename(path, rotated_path(path))?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    log::set_boxed_logger(Box::new(FileLogger {
+        file: Mutex::new(file),
+    }))
+    .map_err(io::Error::other)?;
+    log::set_max_level(LevelFilter::Info);
+    log::info!("oxat {} started", env!("CARGO_PKG_VERSION"));
+    Ok(())
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = OsString::from(path);
+    rotated.push(".1");
+    rotated.into()
+}
+
+/// Records a request that never finished, e.g. because it timed out or
+/// was cancelled, when it's dropped before `finish`.
+struct InFlight {
+    line: Option<String>,
+    started: Instant,
+}
+
+impl InFlight {
+    fn finish(mut self, level: Level, outcome: String) {
+        if let Some(line) = self.line.take() {
+            log::log!(
+                level,
+                "{} {} latency_ms={}",
+                line,
+                outcome,
+                self.started.elapsed().as_millis()
+            );
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(line) = self.line.take() {
+            log::warn!(
+                "{} outcome=abandoned latency_ms={}",
+                line,
+                self.started.elapsed().as_millis()
+            );
+        }
+    }
+}
+
+/// Surf middleware that logs every request sent, retries included, with
+/// its status or error and how long it took. Bodies and headers are left
+/// out, since they can hold passwords and tokens.
+pub fn log_requests(
+    req: Request,
+    client: Client,
+    next: Next<'_>,
+) -> BoxFuture<'_, surf::Result<Response>> {
+    Box::pin(async move {
+        if !log::log_enabled!(Level::Info) {
+            return next.run(req, client).await;
+        }
+
+        let url = req.url();
+        let nsid = url.path().strip_prefix("/xrpc/").unwrap_or("-").to_string();
+        let in_flight = InFlight {
+            line: Some(format!(
+                "request http_method={} xrpc={} url={}",
+                req.method(),
+                nsid,
+                url
+            )),
+            started: Instant::now(),
+        };
+
+        let result = next.run(req, client).await;
+        match &result {
+            Ok(res) => {
+                let level = if res.status().is_success() {
+                    Level::Info
+                } else {
+                    Level::Warn
+                };
+                in_flight.finish(level, format!("status={}", u16::from(res.status())));
+            }
+            Err(e) => in_flight.finish(Level::Error, format!("error={:?}", e.to_string())),
+        }
+        result
+    })
+}
@@ -1,54 +1,175 @@
+mod cli;
 mod commands;
+mod config;
+mod content;
+mod describe;
+mod diff;
 mod error;
+mod exec;
+mod json_diff;
+mod jwt;
+mod keys;
+mod lexicon;
+mod logging;
+mod oauth;
+mod offline;
+mod opener;
+mod output;
+mod post;
+mod proxy;
+mod refresh;
+mod repeat;
+mod settings;
 mod state;
+#[cfg(test)]
+mod test_support;
+mod theme;
 mod ui;
+mod viewer;
+mod xrpc;
 
 use arboard::Clipboard;
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode, KeyEventKind},
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{
+        self, Event as CEvent, KeyCode, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use futures::FutureExt;
-use miette::{IntoDiagnostic, Result};
+use futures::{AsyncReadExt, AsyncWriteExt, FutureExt};
+use miette::Result;
 use ratatui::prelude::*;
-use smol::channel::{bounded, Receiver};
+use smol::channel::{bounded, Receiver, Sender};
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    fs::{self, File},
     io::Write,
-    time::{Duration, SystemTime},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
 };
-use surf::Client;
+use surf::{Client, StatusCode};
 use time::OffsetDateTime;
 
 use crate::{
-    commands::AVAILABLE_COMMANDS,
+    cli::Args,
+    commands::{
+        is_destructive, is_handle, ParamKind, XrpcCommand, DELETE_RECORD, DIFF_FOLLOWERS,
+        GET_RECORD, GET_UNREAD_COUNT, JSON, POST,
+    },
+    config::Config,
+    content::{Download, Handling, Image},
     error::{AppError, AppResult},
-    state::{AppState, InputMode, RequestHistory},
+    oauth::{OAuthSession, Tokens},
+    output::OutputFormat,
+    proxy::Proxy,
+    refresh::MAX_REFRESH_INTERVAL,
+    repeat::MAX_REPEAT,
+    settings::SETTINGS,
+    state::{
+        AppState, ErrorDetail, HistoryOutcome, InputMode, RequestHistory, RequestPreview,
+        ResponseInfo, SavedSession, SearchState, ServerInfo,
+    },
+    theme::{Theme, THEME_NAMES},
     ui::render,
+    xrpc::{
+        is_header_name, RateLimit, Session, XrpcClient, AUTH_FACTOR_REQUIRED, DEFAULT_PDS_HOST,
+        INVALID_TOKEN,
+    },
 };
 
-const MAX_HISTORY: usize = 100;
+const SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Columns moved per Left/Right press when response lines don't wrap.
+const HORIZONTAL_SCROLL_STEP: u16 = 8;
+/// Posts moved per PgUp/PgDn in a feed's list of posts.
+const FEED_PAGE_STEP: usize = 5;
 
 enum AppEvent {
     Input(CEvent),
     Tick,
+    /// Result of the request with the given id, sent from its task.
+    Response(u64, surf::Result<Fetched>),
+    /// Bytes saved so far by the download with the given id.
+    Progress(u64, u64),
+    /// The request with the given id failed in a way worth retrying and
+    /// will be sent again.
+    Retrying {
+        id: u64,
+        attempt: u32,
+        delay: Duration,
+        reason: String,
+    },
+    ServerInfo(Result<ServerInfo, String>),
+    UnreadCount(u64),
+    /// The browser came back from OAuth sign-in, and the code was traded
+    /// for tokens or failed to be.
+    OAuth(Result<(OAuthSession, Tokens), AppError>),
+}
+
+enum FetchError {
+    /// The user pressed Esc while waiting.
+    Cancelled,
+    Http(surf::Error),
+}
+
+impl FetchError {
+    /// What to show the user when a request couldn't complete.
+    fn message(&self) -> String {
+        match self {
+            FetchError::Cancelled => "Request cancelled".to_string(),
+            FetchError::Http(e) if e.status() == StatusCode::RequestTimeout => e.to_string(),
+            FetchError::Http(e) => format!("Request failed: {}", describe_http_error(e)),
+        }
+    }
+
+    /// The error for a request to `url` that didn't complete.
+    fn into_error(self, url: &str) -> AppError {
+        match self {
+            FetchError::Cancelled => AppError::request_at(url, None, self.message()),
+            FetchError::Http(e) => AppError::network(url, describe_http_error(&e)),
+        }
+    }
+}
+
+/// The error from the HTTP client, pointing at `ca_cert` when the PDS's
+/// certificate wasn't trusted.
+fn describe_http_error(e: &surf::Error) -> String {
+    let msg = e.to_string();
+    if msg.contains("BadServerCertificate") {
+        format!(
+            "{} (the PDS's certificate isn't trusted; set ca_cert in the config for a private CA)",
+            msg
+        )
+    } else {
+        msg
+    }
+}
+
+/// A response read in full by a request task.
+struct Fetched {
+    status: StatusCode,
+    /// Empty when the body was saved to a file or is an image.
+    body: String,
+    /// Where the body was saved and how many bytes were written, if it was.
+    saved: Option<(PathBuf, u64)>,
+    /// The body, if it's an image.
+    image: Option<Vec<u8>>,
+    content_type: Option<String>,
+    /// Time until the response headers arrived.
+    elapsed: Duration,
+    rate_limit: Option<RateLimit>,
 }
 
 struct App {
     state: AppState,
     events: Receiver<AppEvent>,
-    client: Client,
-    clipboard: Clipboard,
-}
-
-#[derive(Debug, serde::Deserialize)]
-struct AuthResponse {
-    #[serde(rename = "accessJwt")]
-    access_jwt: String,
-    #[serde(rename = "refreshJwt")]
-    refresh_jwt: String,
+    event_tx: Sender<AppEvent>,
+    xrpc: XrpcClient,
+    clipboard: Option<Clipboard>,
+    /// Set while the TUI is running, so in-flight requests can redraw.
+    terminal: Option<TerminalHandler>,
+    /// Id of the latest request; responses with any other id are stale.
+    request_id: u64,
 }
 
 struct TerminalHandler {
@@ -71,6 +192,12 @@ impl TerminalHandler {
                 msg: e.to_string(),
             })?
             .execute(EnableMouseCapture)
+            .map_err(|e| AppError::Terminal {
+                src: "terminal setup".into(),
+                err_span: (0, 0),
+                msg: e.to_string(),
+            })?
+            .execute(EnableBracketedPaste)
             .map_err(|e| AppError::Terminal {
                 src: "terminal setup".into(),
                 err_span: (0, 0),
@@ -93,49 +220,259 @@ impl Drop for TerminalHandler {
         crossterm::terminal::disable_raw_mode().ok();
         std::io::stdout().execute(LeaveAlternateScreen).ok();
         std::io::stdout().execute(DisableMouseCapture).ok();
+        std::io::stdout().execute(DisableBracketedPaste).ok();
     }
 }
 
 impl App {
-    fn new() -> Result<Self> {
+    fn new(config: &Config, profile: Option<&str>) -> Result<Self> {
         let (tx, rx) = bounded(100);
 
-        let event_tx = tx.clone();
-        smol::spawn(async move {
-            loop {
-                if event::poll(Duration::from_millis(100)).unwrap() {
-                    if let Ok(event) = event::read() {
-                        let _ = event_tx.send(AppEvent::Input(event)).await;
-                    }
+        let proxy =
+            Proxy::from_config(config.proxy.as_deref()).map_err(|msg| AppError::Startup {
+                src: "proxy".into(),
+                err_span: (0, 5),
+                msg,
+            })?;
+        if let Some(path) = &config.ca_cert {
+            if let Err(e) = fs::metadata(path) {
+                let src = path.display().to_string();
+                return Err(AppError::Startup {
+                    err_span: (0, src.len()),
+                    msg: format!("Can't read the CA certificates in ca_cert: {}", e),
+                    src,
                 }
-                let _ = event_tx.send(AppEvent::Tick).await;
+                .into());
             }
-        })
-        .detach();
+        }
+
+        let client_proxy = proxy.clone();
+        let ca_cert = config.ca_cert.clone();
+        let insecure_tls = config.danger_accept_invalid_certs;
+        let client = setup_with_timeout("HTTP client", move || {
+            let http_client =
+                xrpc::http_client(client_proxy.as_ref(), ca_cert.as_deref(), insecure_tls)?;
+            let client: std::result::Result<Client, _> = surf::Config::new()
+                .set_http_client(http_client)
+                // Timeouts are applied per request so they can vary by method
+                .set_timeout(None)
+                .try_into();
+            client
+                .map(|client| client.with(logging::log_requests))
+                .map_err(|e| e.to_string())
+        })?;
 
-        let client = surf::Config::new()
-            .set_timeout(Some(Duration::from_secs(10)))
-            .try_into()
-            .into_diagnostic()?;
+        let mut state = AppState {
+            resolve_handles: config.resolve_handles,
+            self_params: config.self_params.clone(),
+            curl_include_token: config.curl_include_token,
+            preview_requests: config.preview_requests,
+            error_timeout: Duration::from_secs(config.error_timeout),
+            request_timeout: Duration::from_secs(config.request_timeout),
+            retries: config.retries,
+            method_timeouts: config
+                .timeouts
+                .iter()
+                .map(|(method, secs)| (method.clone(), Duration::from_secs(*secs)))
+                .collect(),
+            headers: custom_headers(&config.headers),
+            method_headers: config
+                .method_headers
+                .iter()
+                .map(|(method, headers)| (method.clone(), custom_headers(headers)))
+                .collect(),
+            export_filename: config.export_filename.clone(),
+            export_envelope: config.export_envelope,
+            proxy,
+            insecure_tls,
+            request_history: state::load_history(config.history_size),
+            favorites: state::load_favorites(),
+            collapsed_groups: state::load_collapsed_groups(),
+            history_size: config.history_size,
+            theme: Theme::by_name(&config.theme).unwrap_or_default(),
+            theme_name: Theme::by_name(&config.theme)
+                .map_or("dark", |_| config.theme.as_str())
+                .to_string(),
+            ..Default::default()
+        };
+        state.select_first_command();
+        let pds_host = config
+            .pds_host
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PDS_HOST.to_string());
+        state.profile = profile.map(str::to_string);
+        if let Some(identifier) = profile
+            .and_then(|name| config.profile.get(name))
+            .and_then(|profile| profile.identifier.clone())
+        {
+            state.identifier = Some(identifier);
+            state.input.mode = InputMode::Password;
+        }
 
+        let xrpc = XrpcClient::new(client, pds_host, state.request_timeout);
         Ok(Self {
-            state: AppState::default(),
+            state,
             events: rx,
-            client,
-            clipboard: Clipboard::new().into_diagnostic()?,
+            event_tx: tx,
+            xrpc,
+            clipboard: None,
+            terminal: None,
+            request_id: 0,
         })
     }
 
+    /// Fails startup if the proxy isn't accepting connections, rather than
+    /// letting every request time out.
+    fn check_proxy(&self) -> AppResult<()> {
+        let Some(proxy) = self.state.proxy.clone() else {
+            return Ok(());
+        };
+        setup_with_timeout("Proxy", move || proxy.check())
+    }
+
+    fn init_clipboard(&mut self, config: &Config) {
+        if !config.clipboard {
+            return;
+        }
+
+        // The clipboard is a convenience, so a broken one shouldn't stop startup
+        match setup_with_timeout("clipboard", || Clipboard::new().map_err(|e| e.to_string())) {
+            Ok(clipboard) => self.clipboard = Some(clipboard),
+            Err(e) => {
+                self.state.error = Some(format!(
+                    "{} (set `clipboard = false` in the config to skip it)",
+                    e
+                ));
+                self.state.error_time = Some(SystemTime::now());
+            }
+        }
+    }
+
     async fn handle_input(&mut self, event: CEvent) -> AppResult<()> {
+        if let CEvent::Mouse(mouse) = event {
+            self.handle_mouse(mouse);
+            return Ok(());
+        }
+
+        if let CEvent::Paste(text) = &event {
+            self.paste(text);
+            return Ok(());
+        }
+
         if let CEvent::Key(key) = event {
             if key.kind != KeyEventKind::Press {
                 return Ok(());
             }
 
+            if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                && key.code == KeyCode::Char('v')
+            {
+                let text = match &mut self.clipboard {
+                    Some(clipboard) => clipboard.get_text().ok(),
+                    None => {
+                        self.state.error = Some("Clipboard is unavailable".into());
+                        self.state.error_time = Some(SystemTime::now());
+                        return Ok(());
+                    }
+                };
+                self.paste(text.as_deref().unwrap_or(""));
+                return Ok(());
+            }
+
             if key.modifiers.contains(event::KeyModifiers::CONTROL)
                 && key.code == KeyCode::Char('c')
             {
-                self.state.quit = true;
+                self.state.quit = self.state.confirm_quit();
+                return Ok(());
+            }
+
+            // Esc dismisses a status message, then still does what it does in
+            // the current mode, like closing a prompt
+            if key.code == KeyCode::Esc {
+                self.state.error = None;
+                self.state.error_time = None;
+            }
+
+            // Text fields take `?` as typed, except for the empty command filter
+            if key.code == KeyCode::Char('?')
+                && !matches!(self.state.input.mode, InputMode::Help { .. })
+                && (!self.state.input.mode.accepts_text()
+                    || (self.state.input.mode == InputMode::Command
+                        && self.state.input.content.is_empty()))
+            {
+                self.state.input.mode = InputMode::Help {
+                    previous: Box::new(self.state.input.mode.clone()),
+                    scroll: 0,
+                };
+                return Ok(());
+            }
+
+            if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                && key.code == KeyCode::Char('d')
+                && self.state.last_error.is_some()
+                && !matches!(self.state.input.mode, InputMode::ErrorDetail { .. })
+            {
+                self.state.input.mode = InputMode::ErrorDetail {
+                    previous: Box::new(self.state.input.mode.clone()),
+                    previous_scroll: self.state.scroll_offset,
+                };
+                self.state.scroll_offset = 0;
+                return Ok(());
+            }
+
+            if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                && key.code == KeyCode::Char('l')
+                && !matches!(
+                    self.state.input.mode,
+                    InputMode::Password | InputMode::Lookup { .. }
+                )
+            {
+                self.state.input.mode = InputMode::Lookup {
+                    previous: Box::new(self.state.input.mode.clone()),
+                    saved_input: std::mem::take(&mut self.state.input.content),
+                    saved_cursor: self.state.input.cursor_position,
+                };
+                self.state.input.cursor_position = 0;
+                self.state.lookup = None;
+                return Ok(());
+            }
+
+            if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                && key.code == KeyCode::Char('r')
+                && self.state.is_authenticated
+            {
+                match self.refresh_session().await {
+                    Ok(()) => {
+                        self.fetch_unread_count();
+                        self.state.error = Some("Session refreshed".into());
+                        self.state.error_time = Some(SystemTime::now());
+                    }
+                    Err(e) => {
+                        self.state.error = Some(format!("Session refresh failed: {}", e));
+                        self.state.error_time = Some(SystemTime::now());
+                        if !self.state.is_authenticated {
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.state.input.mode = InputMode::Normal;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+
+            if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                && key.code == KeyCode::Char('p')
+            {
+                self.state.preview_requests = !self.state.preview_requests;
+                self.state.error = Some(format!(
+                    "Request preview {}",
+                    if self.state.preview_requests {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                ));
+                self.state.error_time = Some(SystemTime::now());
                 return Ok(());
             }
 
@@ -152,19 +489,44 @@ impl App {
                         }
                     }
                     _ => {
-                        self.state.input.handle_key(key.code);
+                        self.state.input.handle_key(key);
                     }
                 },
                 InputMode::Password => match key.code {
+                    KeyCode::Enter if self.state.input.content.is_empty() => {
+                        if let Some(identifier) = self.state.identifier.take() {
+                            match self.login_oauth(&identifier).await {
+                                Ok(()) => self.finish_login(),
+                                Err(e) => {
+                                    self.state.error =
+                                        Some(format!("Authentication failed: {}", e));
+                                    self.state.error_time = Some(SystemTime::now());
+                                    self.state.input.mode = InputMode::Normal;
+                                }
+                            }
+                        }
+                    }
                     KeyCode::Enter => {
                         if let Some(identifier) = self.state.identifier.take() {
                             let password = self.state.input.content.clone();
                             self.state.input.content.clear();
                             self.state.input.cursor_position = 0;
 
-                            match self.handle_auth(identifier.clone(), password).await {
-                                Ok(()) => {
-                                    self.state.input.mode = InputMode::Command;
+                            match self
+                                .handle_auth(identifier.clone(), password.clone(), None)
+                                .await
+                            {
+                                Ok(()) => self.finish_login(),
+                                Err(e)
+                                    if xrpc_error(&e).as_deref() == Some(AUTH_FACTOR_REQUIRED) =>
+                                {
+                                    self.state.error =
+                                        Some("Enter the sign-in code sent to your email".into());
+                                    self.state.error_time = Some(SystemTime::now());
+                                    self.state.input.mode = InputMode::AuthFactor {
+                                        identifier,
+                                        password,
+                                    };
                                 }
                                 Err(e) => {
                                     self.state.error =
@@ -176,20 +538,64 @@ impl App {
                         }
                     }
                     _ => {
-                        self.state.input.handle_key(key.code);
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::AuthFactor {
+                    identifier,
+                    password,
+                } => match key.code {
+                    KeyCode::Enter if !self.state.input.content.trim().is_empty() => {
+                        let code = self.state.input.content.trim().to_string();
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+
+                        match self
+                            .handle_auth(identifier.clone(), password, Some(code))
+                            .await
+                        {
+                            Ok(()) => self.finish_login(),
+                            Err(e)
+                                if matches!(
+                                    xrpc_error(&e).as_deref(),
+                                    Some(AUTH_FACTOR_REQUIRED | INVALID_TOKEN)
+                                ) =>
+                            {
+                                // Stay on the prompt so the code can be typed again
+                                self.state.error =
+                                    Some("That sign-in code didn't work, try again".into());
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                            Err(e) => {
+                                self.state.error = Some(format!("Authentication failed: {}", e));
+                                self.state.error_time = Some(SystemTime::now());
+                                self.state.identifier = Some(identifier);
+                                self.state.input.mode = InputMode::Normal;
+                            }
+                        }
+                    }
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::Normal;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
                     }
                 },
                 InputMode::Command => match key.code {
                     KeyCode::Enter => {
-                        let command = if !self.state.input.content.is_empty() {
-                            self.state.input.content.clone()
-                        } else if let Some(idx) = self.state.selected_command_index {
-                            AVAILABLE_COMMANDS[idx].method.to_string()
-                        } else {
-                            return Ok(());
+                        let cmd = match commands::find(&self.state.input.content) {
+                            Some(cmd) => Some(cmd),
+                            None if self.state.selected_command().is_none() => {
+                                // Enter on a group's header collapses or expands it
+                                self.toggle_command_group();
+                                None
+                            }
+                            None => self.state.selected_command(),
                         };
 
-                        if let Some(cmd) = AVAILABLE_COMMANDS.iter().find(|c| c.method == command) {
+                        if let Some(cmd) = cmd {
                             self.state.input.content.clear();
                             self.state.input.cursor_position = 0;
                             self.state.output = None;
@@ -207,15 +613,17 @@ impl App {
                                 self.state.selected_command_index = Some(idx - 1);
                             }
                         } else {
-                            self.state.selected_command_index = Some(AVAILABLE_COMMANDS.len() - 1);
+                            self.state.selected_command_index =
+                                self.state.command_rows().len().checked_sub(1);
                         }
                     }
                     KeyCode::Down => {
+                        let visible = self.state.command_rows().len();
                         if let Some(idx) = self.state.selected_command_index {
-                            if idx < AVAILABLE_COMMANDS.len() - 1 {
+                            if idx + 1 < visible {
                                 self.state.selected_command_index = Some(idx + 1);
                             }
-                        } else {
+                        } else if visible > 0 {
                             self.state.selected_command_index = Some(0);
                         }
                     }
@@ -223,25 +631,73 @@ impl App {
                         if let Some(idx) = self.state.input.completion_index {
                             if let Some(completion) = self.state.input.completion_matches.get(idx) {
                                 self.state.input.content = completion.clone();
-                                self.state.input.cursor_position = self.state.input.content.len();
+                                self.state.input.move_cursor_to_end();
                                 self.state.input.completion_index =
                                     Some((idx + 1) % self.state.input.completion_matches.len());
                             }
                         } else if !self.state.input.content.is_empty() {
                             self.state.input.update_completions();
                         }
+                        self.state.clamp_command_selection();
+                    }
+                    KeyCode::Left
+                        if self.state.input.content.is_empty()
+                            && !self.state.selected_group_collapsed() =>
+                    {
+                        self.toggle_command_group();
+                    }
+                    KeyCode::Right
+                        if self.state.input.content.is_empty()
+                            && self.state.selected_group_collapsed() =>
+                    {
+                        self.toggle_command_group();
+                    }
+                    KeyCode::F(5) => {
+                        self.rerun_last().await?;
+                    }
+                    KeyCode::Char('f') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        self.state.toggle_favorite();
+                        if let Err(e) = state::save_favorites(&self.state.favorites) {
+                            self.state.error = Some(format!("Failed to save favorites: {}", e));
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                    }
+                    KeyCode::Char(':') if self.state.input.content.is_empty() => {
+                        self.state.input.mode = InputMode::RawXrpc;
+                    }
+                    KeyCode::F(2) => {
+                        if let Some(cmd) = self.state.selected_command() {
+                            self.describe_command(cmd.method).await;
+                        }
+                    }
+                    KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        self.state.input.mode = InputMode::Settings {
+                            selected: 0,
+                            editing: false,
+                        };
+                    }
+                    KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        self.copy_access_token();
                     }
-                    KeyCode::Char('h') | KeyCode::Char('H') => {
+                    KeyCode::Char('t') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::Headers;
+                    }
+                    KeyCode::Char('h') | KeyCode::Char('H')
+                        if self.state.input.content.is_empty() =>
+                    {
                         self.state.input.mode = InputMode::History;
-                        self.state.selected_command_index =
-                            if !self.state.request_history.is_empty() {
-                                Some(0)
-                            } else {
-                                None
-                            };
+                        self.state.selected_command_index = Some(0);
+                        self.state.clamp_history_selection();
                     }
                     _ => {
-                        self.state.input.handle_key(key.code);
+                        let previous = self.state.input.content.clone();
+                        self.state.input.handle_key(key);
+                        if self.state.input.content != previous {
+                            // The filter changed, so restart selection at the first match
+                            self.state.select_first_command();
+                        }
                         if !self.state.input.content.is_empty() {
                             self.state.input.update_completions();
                         }
@@ -249,18 +705,27 @@ impl App {
                 },
                 InputMode::History => match key.code {
                     KeyCode::Enter => {
-                        if let Some(idx) = self.state.selected_command_index {
-                            if let Some(hist) = self.state.request_history.get(idx) {
-                                let method = hist.method.clone();
-                                let params = hist.params.clone();
-                                self.execute_command(&method, &params).await?;
+                        if let Some(idx) = self.state.selected_history_entry() {
+                            if self.replay(idx).await?
+                                && !matches!(
+                                    self.state.input.mode,
+                                    InputMode::ConfirmDestructive { .. }
+                                )
+                            {
                                 self.state.input.mode = InputMode::ViewingResponse;
+
+                                let max_scroll =
+                                    self.get_content_height().saturating_sub(viewport_height());
+                                self.state.scroll_offset =
+                                    self.state.saved_scroll().unwrap_or(0).min(max_scroll);
+                                self.state.cursor_line = 0;
+                                self.cursor_into_view(viewport_width(), viewport_height());
                             }
                         }
                     }
                     KeyCode::Esc => {
                         self.state.input.mode = InputMode::Command;
-                        self.state.selected_command_index = Some(0);
+                        self.state.select_first_command();
                     }
                     KeyCode::Up => {
                         if let Some(idx) = self.state.selected_command_index {
@@ -271,20 +736,66 @@ impl App {
                     }
                     KeyCode::Down => {
                         if let Some(idx) = self.state.selected_command_index {
-                            if idx < self.state.request_history.len() - 1 {
+                            if idx + 1 < self.state.visible_history().len() {
                                 self.state.selected_command_index = Some(idx + 1);
                             }
                         }
                     }
+                    KeyCode::Char(c @ ('c' | 'y')) => {
+                        if let Some(idx) = self.state.selected_history_entry() {
+                            self.copy_history_entry(idx, c == 'y');
+                        }
+                    }
+                    KeyCode::Char(c @ ('f' | 's')) => {
+                        let outcome = if c == 'f' {
+                            HistoryOutcome::Failed
+                        } else {
+                            HistoryOutcome::Succeeded
+                        };
+                        // Pressing it again shows everything
+                        self.state.history_outcome = if self.state.history_outcome == outcome {
+                            HistoryOutcome::All
+                        } else {
+                            outcome
+                        };
+                        self.state.clamp_history_selection();
+                    }
+                    KeyCode::Char('D') => self.clear_history(),
+                    KeyCode::Char('/') => {
+                        self.state.input.content = self.state.history_query.clone();
+                        self.state.input.move_cursor_to_end();
+                        self.state.input.mode = InputMode::HistoryFilter;
+                    }
                     _ => {}
                 },
+                InputMode::HistoryFilter => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            self.state.input.mode = InputMode::History;
+                        }
+                        KeyCode::Esc => {
+                            self.state.input.content.clear();
+                            self.state.input.mode = InputMode::History;
+                        }
+                        _ => {
+                            self.state.input.handle_key(key);
+                        }
+                    }
+                    // The list narrows as the filter is typed
+                    self.state.history_query = self.state.input.content.clone();
+                    if self.state.input.mode == InputMode::History {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                    }
+                    self.state.clamp_history_selection();
+                }
                 InputMode::CommandBuilder {
                     command,
                     current_param,
                     params,
                 } => match key.code {
                     KeyCode::Enter => {
-                        let cmd = AVAILABLE_COMMANDS
+                        let cmd = commands::all()
                             .iter()
                             .find(|c| c.method == command)
                             .ok_or_else(|| AppError::Request {
@@ -293,11 +804,16 @@ impl App {
                                 msg: "Command not found".into(),
                             })?;
 
-                        let param = &cmd.parameters[current_param];
+                        let Some(param) = cmd.parameters.get(current_param) else {
+                            // Nothing left to fill in, so send what we have
+                            return self.submit_command(command, params).await;
+                        };
                         let mut new_params = params.clone();
 
                         let param_value = if self.state.input.content.is_empty() {
-                            if param.optional {
+                            if let Some(did) = self.state.own_default(&command, param) {
+                                did.to_string()
+                            } else if param.optional {
                                 param.default.unwrap_or("").to_string()
                             } else {
                                 return Ok(());
@@ -306,77 +822,634 @@ impl App {
                             self.state.input.content.clone()
                         };
 
-                        if new_params.len() == current_param {
-                            new_params.push(param_value);
-                        } else {
-                            new_params[current_param] = param_value;
+                        // The hint next to the field already says what's wrong
+                        if self
+                            .state
+                            .check_param(&command, param, &param_value)
+                            .is_err()
+                        {
+                            return Ok(());
                         }
 
-                        self.state.input.content.clear();
-                        self.state.input.cursor_position = 0;
+                        if new_params.len() <= current_param {
+                            new_params.resize(current_param + 1, String::new());
+                        }
+                        new_params[current_param] = param_value;
 
                         if current_param + 1 < cmd.parameters.len() {
+                            self.state.input.content = new_params
+                                .get(current_param + 1)
+                                .cloned()
+                                .unwrap_or_default();
+                            self.state.input.move_cursor_to_end();
                             self.state.input.mode = InputMode::CommandBuilder {
                                 command,
                                 current_param: current_param + 1,
                                 params: new_params,
                             };
+                        } else if let Some(missing) = cmd
+                            .parameters
+                            .iter()
+                            .zip(&new_params)
+                            .position(|(p, value)| !p.optional && value.is_empty())
+                        {
+                            // A required field was skipped over while navigating back
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.state.error =
+                                Some(format!("{} is required", cmd.parameters[missing].name));
+                            self.state.error_time = Some(SystemTime::now());
+                            self.state.input.mode = InputMode::CommandBuilder {
+                                command,
+                                current_param: missing,
+                                params: new_params,
+                            };
                         } else {
-                            self.execute_command(&command, &new_params).await?;
-                            self.state.input.mode = InputMode::ViewingResponse;
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.submit_command(command, new_params).await?;
+                        }
+                    }
+                    KeyCode::Up | KeyCode::BackTab | KeyCode::Down | KeyCode::Tab => {
+                        let backwards = matches!(key.code, KeyCode::Up | KeyCode::BackTab);
+                        // Only move forward into fields that have already been visited
+                        let target = if backwards {
+                            current_param.checked_sub(1)
+                        } else {
+                            Some(current_param + 1).filter(|&i| i < params.len())
+                        };
+                        let Some(target) = target else {
+                            return Ok(());
+                        };
+
+                        // Keep whatever was typed into the field being left
+                        let mut new_params = params.clone();
+                        if new_params.len() <= current_param {
+                            new_params.resize(current_param + 1, String::new());
                         }
+                        new_params[current_param] = self.state.input.content.clone();
+
+                        self.state.input.content = new_params[target].clone();
+                        self.state.input.move_cursor_to_end();
+                        self.state.input.mode = InputMode::CommandBuilder {
+                            command,
+                            current_param: target,
+                            params: new_params,
+                        };
                     }
                     KeyCode::Esc => {
                         self.state.input.content.clear();
                         self.state.input.cursor_position = 0;
                         self.state.input.mode = InputMode::Command;
                     }
+                    KeyCode::Char('o') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        let param = commands::find(&command)
+                            .and_then(|cmd| cmd.parameters.get(current_param));
+                        if let Some(Err(msg)) =
+                            param.map(|param| self.state.fill_own_account(param))
+                        {
+                            self.state.error = Some(msg);
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                    }
                     _ => {
-                        self.state.input.handle_key(key.code);
+                        self.state.input.handle_key(key);
                     }
                 },
-                InputMode::ViewingResponse => {
-                    let viewport_height = if let Ok((_, rows)) = crossterm::terminal::size() {
-                        // Subtract 7 for the header (3), status (3), and help (1) areas
-                        rows.saturating_sub(7)
-                    } else {
-                        0
-                    };
+                InputMode::RawXrpc => match key.code {
+                    KeyCode::Enter => {
+                        let input = self.state.input.content.trim().to_string();
+                        let (method, args) = input.split_once(' ').unwrap_or((&input, ""));
+                        if !commands::is_nsid(method) {
+                            self.state.error = Some(format!("'{}' isn't a valid NSID", method));
+                            self.state.error_time = Some(SystemTime::now());
+                            return Ok(());
+                        }
 
-                    match key.code {
-                        KeyCode::Enter => {
-                            self.state.input.mode = InputMode::Command;
+                        self.execute_raw(method, args.trim()).await?;
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.scroll_to_top();
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::Command;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::ConfirmDestructive { command, params } => match key.code {
+                    KeyCode::Enter => {
+                        if self.state.input.content.trim().eq_ignore_ascii_case("yes") {
                             self.state.input.content.clear();
                             self.state.input.cursor_position = 0;
-                            self.state.scroll_offset = 0; // Reset scroll position
-                        }
-                        KeyCode::Up => {
-                            self.update_scroll(-1, viewport_height);
-                        }
-                        KeyCode::Down => {
-                            self.update_scroll(1, viewport_height);
+                            self.send_command(command, params).await?;
+                        } else {
+                            self.state.error =
+                                Some("Type yes to confirm, or Esc to go back".into());
+                            self.state.error_time = Some(SystemTime::now());
                         }
-                        KeyCode::PageUp => {
-                            self.update_scroll(-10, viewport_height);
+                    }
+                    KeyCode::Esc => {
+                        self.back_to_builder(command, params);
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::ConfirmRequest { command, params } => match key.code {
+                    KeyCode::Enter => {
+                        self.state.preview = None;
+                        self.execute_command(&command, &params).await?;
+                        self.state.scroll_to_top();
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    KeyCode::Esc => {
+                        self.state.preview = None;
+                        self.back_to_builder(command, params);
+                    }
+                    _ => {}
+                },
+                InputMode::ErrorDetail {
+                    previous,
+                    previous_scroll,
+                } => {
+                    let viewport_height = viewport_height();
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Enter => {
+                            self.state.input.mode = *previous;
+                            self.state.scroll_offset = previous_scroll;
                         }
-                        KeyCode::PageDown => {
-                            self.update_scroll(10, viewport_height);
+                        KeyCode::Up => self.update_scroll(-1, viewport_height),
+                        KeyCode::Down => self.update_scroll(1, viewport_height),
+                        KeyCode::PageUp => self.update_scroll(-10, viewport_height),
+                        KeyCode::PageDown => self.update_scroll(10, viewport_height),
+                        KeyCode::Home => self.state.scroll_offset = 0,
+                        KeyCode::End => {
+                            self.state.scroll_offset =
+                                self.get_content_height().saturating_sub(viewport_height);
                         }
-                        KeyCode::Home => {
-                            self.state.scroll_offset = 0;
+                        _ => {}
+                    }
+                }
+                InputMode::Lexicon {
+                    previous,
+                    previous_scroll,
+                    ..
+                } => {
+                    let viewport_height = viewport_height();
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Enter => {
+                            self.state.input.mode = *previous;
+                            self.state.scroll_offset = previous_scroll;
                         }
+                        KeyCode::Up => self.update_scroll(-1, viewport_height),
+                        KeyCode::Down => self.update_scroll(1, viewport_height),
+                        KeyCode::PageUp => self.update_scroll(-10, viewport_height),
+                        KeyCode::PageDown => self.update_scroll(10, viewport_height),
+                        KeyCode::Home => self.state.scroll_offset = 0,
                         KeyCode::End => {
-                            let max_scroll =
+                            self.state.scroll_offset =
                                 self.get_content_height().saturating_sub(viewport_height);
-                            self.state.scroll_offset = max_scroll;
                         }
-                        KeyCode::Char('c') => {
-                            if let Some(output) = &self.state.output {
-                                match serde_json::to_string_pretty(output) {
+                        _ => {}
+                    }
+                }
+                InputMode::Settings { selected, editing } => {
+                    let setting = SETTINGS[selected];
+                    if editing {
+                        match key.code {
+                            KeyCode::Enter => {
+                                let input = self.state.input.content.clone();
+                                match self.apply_setting(setting, &input) {
+                                    Ok(()) => {
+                                        self.state.input.content.clear();
+                                        self.state.input.cursor_position = 0;
+                                        self.state.input.mode = InputMode::Settings {
+                                            selected,
+                                            editing: false,
+                                        };
+                                        self.state.param_error = None;
+                                        self.state.error = Some(format!(
+                                            "{} changed for this session (s to save)",
+                                            setting.label()
+                                        ));
+                                        self.state.error_time = Some(SystemTime::now());
+                                    }
+                                    Err(hint) => {
+                                        self.state.param_error =
+                                            Some(format!("{} {}", setting.label(), hint));
+                                    }
+                                }
+                            }
+                            KeyCode::Esc => {
+                                self.state.input.content.clear();
+                                self.state.input.cursor_position = 0;
+                                self.state.param_error = None;
+                                self.state.input.mode = InputMode::Settings {
+                                    selected,
+                                    editing: false,
+                                };
+                            }
+                            _ => {
+                                self.state.input.handle_key(key);
+                            }
+                        }
+                    } else {
+                        match key.code {
+                            KeyCode::Up => {
+                                self.state.input.mode = InputMode::Settings {
+                                    selected: selected.saturating_sub(1),
+                                    editing: false,
+                                };
+                            }
+                            KeyCode::Down => {
+                                self.state.input.mode = InputMode::Settings {
+                                    selected: (selected + 1).min(SETTINGS.len() - 1),
+                                    editing: false,
+                                };
+                            }
+                            KeyCode::Enter => {
+                                self.state.input.content =
+                                    setting.current(&self.state, &self.xrpc.pds_host);
+                                self.state.input.move_cursor_to_end();
+                                self.state.input.mode = InputMode::Settings {
+                                    selected,
+                                    editing: true,
+                                };
+                            }
+                            KeyCode::Char('s') => {
+                                match self.save_settings() {
+                                    Ok(path) => {
+                                        self.state.error = Some(format!("Saved to {}", path));
+                                    }
+                                    Err(e) => {
+                                        self.state.error =
+                                            Some(format!("Failed to save settings: {}", e));
+                                    }
+                                }
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                            KeyCode::Esc => {
+                                self.state.input.mode = InputMode::Command;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                InputMode::Headers => match key.code {
+                    KeyCode::Enter => {
+                        self.edit_header();
+                    }
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::Command;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::Help { previous, scroll } => {
+                    // The overlay fills the screen inside its borders, with a
+                    // heading and a gap between groups
+                    let lines: usize = keys::ALL.iter().map(|g| g.bindings.len() + 2).sum();
+                    let height =
+                        crossterm::terminal::size().map_or(0, |(_, rows)| rows.saturating_sub(2));
+                    let max_scroll = (lines as u16).saturating_sub(height + 1);
+                    let scroll = match key.code {
+                        KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q') => {
+                            self.state.input.mode = *previous;
+                            return Ok(());
+                        }
+                        KeyCode::Up => scroll.saturating_sub(1),
+                        KeyCode::Down => scroll + 1,
+                        KeyCode::PageUp => scroll.saturating_sub(10),
+                        KeyCode::PageDown => scroll + 10,
+                        KeyCode::Home => 0,
+                        KeyCode::End => max_scroll,
+                        _ => scroll,
+                    };
+                    self.state.input.mode = InputMode::Help {
+                        previous,
+                        scroll: scroll.min(max_scroll),
+                    };
+                }
+                InputMode::Lookup { .. } => match key.code {
+                    KeyCode::Enter => {
+                        self.lookup().await;
+                    }
+                    KeyCode::Tab => {
+                        let Some(Ok(value)) = self.state.lookup.clone() else {
+                            return Ok(());
+                        };
+                        self.close_lookup();
+                        if self.state.input.mode.accepts_text() {
+                            self.paste(&value);
+                        } else {
+                            self.state.error = Some("No input to insert into here".into());
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                    }
+                    KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        if let Some(Ok(value)) = self.state.lookup.clone() {
+                            if self.copy_to_clipboard(value.clone()) {
+                                self.state.error = Some(format!("Copied {}", value));
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                        }
+                    }
+                    KeyCode::Esc => {
+                        self.close_lookup();
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::SearchResponse => match key.code {
+                    KeyCode::Enter => {
+                        self.state.search.query = self.state.input.content.clone();
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::ViewingResponse;
+                        self.state.update_search_matches();
+
+                        if !self.state.search.query.is_empty() {
+                            self.jump_to_match(true, viewport_height());
+                        }
+                    }
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::FilterResponse => match key.code {
+                    KeyCode::Enter => {
+                        let path = self.state.input.content.trim();
+                        // An empty path clears the filter and shows everything again
+                        self.state.filter = (!path.is_empty()).then(|| path.to_string());
+                        self.state.collapsed.clear();
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.scroll_to_top();
+                        self.state.input.mode = InputMode::ViewingResponse;
+                        self.state.update_search_matches();
+                    }
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::RepeatCount => match key.code {
+                    KeyCode::Enter => match self.state.input.content.trim().parse::<u32>() {
+                        Ok(count @ 1..=MAX_REPEAT) => {
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.state.input.mode = InputMode::ViewingResponse;
+                            self.repeat_last(count).await?;
+                        }
+                        _ => {
+                            self.state.error =
+                                Some(format!("Enter a number from 1 to {}", MAX_REPEAT));
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                    },
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::AutoRefreshInterval => match key.code {
+                    KeyCode::Enter => match self.state.input.content.trim().parse::<u64>() {
+                        Ok(secs @ 1..=MAX_REFRESH_INTERVAL) => {
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.state.input.mode = InputMode::ViewingResponse;
+                            self.start_auto_refresh(Duration::from_secs(secs))?;
+                        }
+                        _ => {
+                            self.state.error = Some(format!(
+                                "Enter a number of seconds from 1 to {}",
+                                MAX_REFRESH_INTERVAL
+                            ));
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                    },
+                    KeyCode::Esc => {
+                        self.state.input.content.clear();
+                        self.state.input.cursor_position = 0;
+                        self.state.input.mode = InputMode::ViewingResponse;
+                    }
+                    _ => {
+                        self.state.input.handle_key(key);
+                    }
+                },
+                InputMode::ViewingResponse => {
+                    let viewport_height = viewport_height();
+
+                    match key.code {
+                        KeyCode::Enter => {
+                            self.state.input.mode = InputMode::Command;
+                            self.state.input.content.clear();
+                            self.state.input.cursor_position = 0;
+                            self.state.remember_scroll();
+                            self.state.scroll_to_top();
+                            self.state.scroll_x = 0;
+                            self.state.search = SearchState::default();
+                            self.state.filter = None;
+                            self.state.collapsed.clear();
+                            self.state.back.clear();
+                        }
+                        KeyCode::Char(' ') => {
+                            if let Some(line) = self.state.toggle_fold(self.state.cursor_line) {
+                                self.state.cursor_line = line;
+                                // Folding shortens what's below, so keep the offset in range
+                                let max_scroll =
+                                    self.get_content_height().saturating_sub(viewport_height);
+                                self.state.scroll_offset = self.state.scroll_offset.min(max_scroll);
+                                self.scroll_to_cursor(viewport_width(), viewport_height);
+                            }
+                        }
+                        KeyCode::Char('l') => {
+                            self.state.line_numbers = !self.state.line_numbers;
+                            // The numbers take columns, so wrapped lines may gain rows
+                            self.scroll_to_cursor(viewport_width(), viewport_height);
+                        }
+                        KeyCode::Char('w') => {
+                            self.state.wrap_lines = !self.state.wrap_lines;
+                            self.state.scroll_x = 0;
+                            // Row counts change, so keep the offset in range
+                            let max_scroll =
+                                self.get_content_height().saturating_sub(viewport_height);
+                            self.state.scroll_offset = self.state.scroll_offset.min(max_scroll);
+                            self.scroll_to_cursor(viewport_width(), viewport_height);
+                        }
+                        KeyCode::Left if !self.state.wrap_lines => {
+                            self.state.scroll_x =
+                                self.state.scroll_x.saturating_sub(HORIZONTAL_SCROLL_STEP);
+                        }
+                        KeyCode::Right if !self.state.wrap_lines => {
+                            let max_scroll_x = ui::response_width(&self.state)
+                                .saturating_sub(viewport_width() as usize)
+                                as u16;
+                            self.state.scroll_x =
+                                (self.state.scroll_x + HORIZONTAL_SCROLL_STEP).min(max_scroll_x);
+                        }
+                        KeyCode::Char('f') => {
+                            self.state.input.content =
+                                self.state.filter.clone().unwrap_or_default();
+                            self.state.input.move_cursor_to_end();
+                            self.state.input.mode = InputMode::FilterResponse;
+                        }
+                        KeyCode::Char(']') => {
+                            self.next_page().await?;
+                        }
+                        KeyCode::Char('r') | KeyCode::F(5) => {
+                            self.rerun_last().await?;
+                        }
+                        KeyCode::Char('R') => match self.repeat_blocker() {
+                            Some(reason) => {
+                                self.state.error = Some(reason.into());
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                            None => self.state.input.mode = InputMode::RepeatCount,
+                        },
+                        KeyCode::Char('a') if self.state.auto_refresh.is_some() => {
+                            self.stop_auto_refresh("Auto-refresh off");
+                        }
+                        KeyCode::Char('a') => match self.repeat_blocker() {
+                            Some(reason) => {
+                                self.state.error = Some(reason.into());
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                            None => {
+                                self.state.input.content =
+                                    self.state.auto_refresh_interval.as_secs().to_string();
+                                self.state.input.move_cursor_to_end();
+                                self.state.input.mode = InputMode::AutoRefreshInterval;
+                            }
+                        },
+                        KeyCode::Char('o')
+                            if key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                        {
+                            self.open_export(true);
+                        }
+                        KeyCode::Char('o') => {
+                            self.follow_uri().await?;
+                        }
+                        KeyCode::Backspace | KeyCode::Char('b') => {
+                            if self.state.back.is_empty() {
+                                self.state.error = Some("Nothing to go back to".into());
+                                self.state.error_time = Some(SystemTime::now());
+                            } else {
+                                self.stop_auto_refresh("Auto-refresh stopped");
+                                self.state.go_back();
+                                // The terminal may have been resized since
+                                self.cursor_into_view(viewport_width(), viewport_height);
+                            }
+                        }
+                        KeyCode::Char('/') => {
+                            self.state.input.content = self.state.search.query.clone();
+                            self.state.input.move_cursor_to_end();
+                            self.state.input.mode = InputMode::SearchResponse;
+                        }
+                        KeyCode::Char('n') => {
+                            self.jump_to_match(true, viewport_height);
+                        }
+                        KeyCode::Char('N') => {
+                            self.jump_to_match(false, viewport_height);
+                        }
+                        KeyCode::Char('i') => {
+                            self.state.search.case_sensitive = !self.state.search.case_sensitive;
+                            self.state.update_search_matches();
+                            self.state.error = Some(format!(
+                                "Case-sensitive search {}",
+                                if self.state.search.case_sensitive {
+                                    "on"
+                                } else {
+                                    "off"
+                                }
+                            ));
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                        KeyCode::Up
+                        | KeyCode::Down
+                        | KeyCode::PageUp
+                        | KeyCode::PageDown
+                        | KeyCode::Home
+                        | KeyCode::End
+                            if self.state.feed_view().is_some_and(|feed| !feed.is_empty()) =>
+                        {
+                            let last = self.state.feed_view().map_or(0, |feed| feed.len()) - 1;
+                            let current = self.state.selected_feed_item();
+                            let next = match key.code {
+                                KeyCode::Up => current.saturating_sub(1),
+                                KeyCode::Down => current + 1,
+                                KeyCode::PageUp => current.saturating_sub(FEED_PAGE_STEP),
+                                KeyCode::PageDown => current + FEED_PAGE_STEP,
+                                KeyCode::Home => 0,
+                                _ => last,
+                            };
+                            self.state.select_feed_item(next.min(last));
+                        }
+                        KeyCode::Up if self.state.focuses_lines() => {
+                            self.move_cursor(-1, viewport_width(), viewport_height);
+                        }
+                        KeyCode::Down if self.state.focuses_lines() => {
+                            self.move_cursor(1, viewport_width(), viewport_height);
+                        }
+                        KeyCode::Up => {
+                            self.update_scroll(-1, viewport_height);
+                        }
+                        KeyCode::Down => {
+                            self.update_scroll(1, viewport_height);
+                        }
+                        KeyCode::PageUp => {
+                            self.update_scroll(-10, viewport_height);
+                            self.cursor_into_view(viewport_width(), viewport_height);
+                        }
+                        KeyCode::PageDown => {
+                            self.update_scroll(10, viewport_height);
+                            self.cursor_into_view(viewport_width(), viewport_height);
+                        }
+                        KeyCode::Home => {
+                            self.state.scroll_to_top();
+                        }
+                        KeyCode::End => {
+                            let max_scroll =
+                                self.get_content_height().saturating_sub(viewport_height);
+                            self.state.scroll_offset = max_scroll;
+                            self.state.cursor_line =
+                                self.state.view_lines().len().saturating_sub(1);
+                        }
+                        KeyCode::Char(c @ ('c' | 'C')) => {
+                            if let Some(output) = self.state.displayed_output() {
+                                // C copies the response on one line, e.g. for a script
+                                let (json, format) = if c == 'C' {
+                                    (serde_json::to_string(output), "compact")
+                                } else {
+                                    (serde_json::to_string_pretty(output), "pretty-printed")
+                                };
+                                match json {
                                     Ok(json_str) => {
-                                        if let Err(e) = self.clipboard.set_text(json_str) {
+                                        if self.copy_to_clipboard(json_str) {
                                             self.state.error =
-                                                Some(format!("Failed to copy to clipboard: {}", e));
+                                                Some(format!("Copied response as {} JSON", format));
                                             self.state.error_time = Some(SystemTime::now());
                                         }
                                     }
@@ -388,25 +1461,124 @@ impl App {
                                 }
                             }
                         }
+                        KeyCode::Char('p') => {
+                            let Some(path) = self.state.path_at(self.state.cursor_line) else {
+                                return Ok(());
+                            };
+                            if path.is_empty() {
+                                self.state.error =
+                                    Some("The focused line is the whole response".into());
+                                self.state.error_time = Some(SystemTime::now());
+                            } else if self.copy_to_clipboard(path.clone()) {
+                                self.state.error = Some(format!("Copied {}", path));
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                        }
+                        KeyCode::Char('y') => {
+                            let Some((pointer, value)) =
+                                self.state.value_at(self.state.cursor_line)
+                            else {
+                                return Ok(());
+                            };
+                            // Strings are copied bare, so a URI or DID pastes cleanly
+                            let text = match value {
+                                serde_json::Value::String(s) => s.clone(),
+                                other => serde_json::to_string_pretty(other).unwrap_or_default(),
+                            };
+                            let label = if pointer.is_empty() {
+                                "the whole response".to_string()
+                            } else {
+                                pointer
+                            };
+                            if self.copy_to_clipboard(text) {
+                                self.state.error = Some(format!("Copied {}", label));
+                                self.state.error_time = Some(SystemTime::now());
+                            }
+                        }
+                        KeyCode::Char('x') => {
+                            self.copy_history_entry(0, true);
+                        }
+                        KeyCode::Char('O') => {
+                            self.open_export(false);
+                        }
+                        KeyCode::Char('s') => {
+                            self.state.error = Some(match self.save_image() {
+                                Ok(path) => format!("Saved to {} (O opens it)", path.display()),
+                                Err(msg) => msg,
+                            });
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                        KeyCode::Char('e')
+                            if key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                        {
+                            self.state.export_envelope = !self.state.export_envelope;
+                            self.state.error = Some(if self.state.export_envelope {
+                                "Exports include the request details".into()
+                            } else {
+                                "Exports hold just the response body".into()
+                            });
+                            self.state.error_time = Some(SystemTime::now());
+                        }
                         KeyCode::Char('e') => {
-                            if let Some(output) = &self.state.output {
-                                let now = OffsetDateTime::now_utc();
-                                let filename = format!(
-                                    "bsky_response_{:04}_{:02}_{:02}_{:02}_{:02}_{:02}.json",
-                                    now.year(),
-                                    now.month() as u8,
-                                    now.day(),
-                                    now.hour(),
-                                    now.minute(),
-                                    now.second()
+                            if let Some(output) = self.state.displayed_output() {
+                                let method = self
+                                    .state
+                                    .request_history
+                                    .front()
+                                    .map_or("response", |hist| hist.method.as_str());
+                                let handle = self
+                                    .state
+                                    .handle
+                                    .as_deref()
+                                    .or(self.state.identifier.as_deref())
+                                    .unwrap_or("anonymous");
+                                let filename = output::export_filename(
+                                    &self.state.export_filename,
+                                    method,
+                                    handle,
+                                    OffsetDateTime::now_utc(),
                                 );
 
-                                match serde_json::to_string_pretty(output) {
+                                // Only lists can be written as CSV; anything else stays JSON
+                                let mut format = self.state.export_format;
+                                let mut note = "";
+
+                                // CSV is a table of the body's list, so it can't hold the request
+                                let envelope = match self.state.request_history.front() {
+                                    Some(hist)
+                                        if self.state.export_envelope
+                                            && format != OutputFormat::Csv =>
+                                    {
+                                        Some(output::export_envelope(
+                                            hist,
+                                            self.state.response_info.as_ref(),
+                                            output,
+                                        ))
+                                    }
+                                    _ => None,
+                                };
+                                let output = envelope.as_ref().unwrap_or(output);
+                                let mut formatted = output::format_json(output, format);
+                                if formatted.is_err() && format == OutputFormat::Csv {
+                                    format = OutputFormat::Pretty;
+                                    note = " (not a list, so as JSON)";
+                                    formatted = output::format_json(output, format);
+                                }
+                                let filename = Path::new(&filename)
+                                    .with_extension(format.extension())
+                                    .display()
+                                    .to_string();
+
+                                match formatted {
                                     Ok(json_str) => match File::create(&filename) {
                                         Ok(mut file) => match file.write_all(json_str.as_bytes()) {
                                             Ok(_) => {
-                                                self.state.error =
-                                                    Some(format!("Exported to {}", filename));
+                                                self.state.last_export =
+                                                    std::path::absolute(&filename).ok();
+                                                self.state.error = Some(format!(
+                                                    "Exported to {}{} (O opens it)",
+                                                    filename, note
+                                                ));
                                                 self.state.error_time = Some(SystemTime::now());
                                             }
                                             Err(e) => {
@@ -422,13 +1594,52 @@ impl App {
                                         }
                                     },
                                     Err(e) => {
-                                        self.state.error =
-                                            Some(format!("Failed to format JSON: {}", e));
+                                        self.state.error = Some(format!(
+                                            "Failed to format {}: {}",
+                                            format.name(),
+                                            e
+                                        ));
                                         self.state.error_time = Some(SystemTime::now());
                                     }
                                 }
                             }
                         }
+                        KeyCode::Char('v') => {
+                            self.state.show_summary = !self.state.show_summary;
+                            // Leaving a post list shows the JSON at the selected post
+                            self.scroll_to_cursor(viewport_width(), viewport_height);
+                        }
+                        KeyCode::Char('P') => {
+                            self.state.pinned = self.state.output.clone();
+                            self.state.show_diff = false;
+                            self.state.error = Some(
+                                "Pinned this response; run another and press d to compare".into(),
+                            );
+                            self.state.error_time = Some(SystemTime::now());
+                        }
+                        KeyCode::Char('d') => {
+                            if self.state.pinned.is_none() {
+                                self.state.error =
+                                    Some("Pin a response with P first to compare against".into());
+                                self.state.error_time = Some(SystemTime::now());
+                            } else {
+                                self.state.show_diff = !self.state.show_diff;
+                                self.state.scroll_to_top();
+                                self.state.scroll_x = 0;
+                            }
+                        }
+                        KeyCode::Char('E') => {
+                            self.state.export_format = match self.state.export_format {
+                                OutputFormat::Pretty => OutputFormat::Yaml,
+                                OutputFormat::Yaml => OutputFormat::Csv,
+                                _ => OutputFormat::Pretty,
+                            };
+                            self.state.error = Some(format!(
+                                "Export format: {}",
+                                self.state.export_format.name()
+                            ));
+                            self.state.error_time = Some(SystemTime::now());
+                        }
                         _ => {}
                     }
                 }
@@ -437,377 +1648,2785 @@ impl App {
         Ok(())
     }
 
-    async fn handle_auth(&mut self, identifier: String, password: String) -> AppResult<()> {
-        self.state.error = None;
-
-        let json_body = serde_json::json!({
-            "identifier": identifier,
-            "password": password
-        });
-
-        let endpoint = format!(
-            "{}/xrpc/com.atproto.server.createSession",
-            self.state.pds_host.trim_end_matches('/')
-        );
-
-        let mut res = match self
-            .client
-            .post(&endpoint)
-            .header("Content-Type", "application/json")
-            .body_json(&json_body)
-            .map_err(|e| AppError::Auth {
-                src: "building auth request".into(),
-                err_span: (0, 0),
-                msg: format!("Failed to build auth request: {}", e),
-            })?
-            .await
-        {
-            Ok(res) => res,
-            Err(e) => {
-                let error_msg = format!("Auth request failed: {}", e);
-                self.state.error = Some(error_msg.clone());
-                return Err(AppError::Auth {
-                    src: "authentication".into(),
-                    err_span: (0, 0),
-                    msg: error_msg,
-                }
-                .into());
-            }
-        };
-
-        if !res.status().is_success() {
-            let status = res.status();
-            let error_body = match res.body_string().await {
-                Ok(text) => text,
-                Err(e) => format!("Failed to read error response: {}", e),
-            };
-
-            let error_msg = format!("Auth failed ({}): {}", status, error_body);
-            self.state.error = Some(error_msg.clone());
+    /// Inserts text at the cursor in any mode that edits the input field.
+    fn paste(&mut self, text: &str) {
+        if !self.state.input.mode.accepts_text() {
+            return;
+        }
+        if text.trim().is_empty() {
+            self.state.error = Some("Clipboard is empty".into());
             self.state.error_time = Some(SystemTime::now());
+            return;
+        }
 
-            return Err(AppError::Auth {
-                src: "authentication".into(),
-                err_span: (0, 0),
-                msg: error_msg,
-            }
-            .into());
+        // A list pasted one per line becomes one entry per line
+        let mut text = text.to_string();
+        if self.editing_list() {
+            let before_cursor: String = self
+                .state
+                .input
+                .content
+                .chars()
+                .take(self.state.input.cursor_position)
+                .collect();
+            let entries: Vec<&str> = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            let separator = match before_cursor.trim_end() {
+                "" => "",
+                before if before.ends_with(',') => "",
+                _ => ",",
+            };
+            text = format!("{}{}", separator, entries.join(","));
         }
 
-        let auth_response = match res.body_json::<AuthResponse>().await {
-            Ok(resp) => resp,
-            Err(e) => {
-                return Err(AppError::Auth {
-                    src: "parsing response".into(),
-                    err_span: (0, 0),
-                    msg: format!("Failed to parse response as JSON: {}", e),
-                }
-                .into());
-            }
-        };
+        self.state.input.insert_str(&text);
+        self.state.param_error = None;
+        if self.state.input.mode == InputMode::Command {
+            self.state.select_first_command();
+        }
+    }
 
-        self.state.auth_token = Some(auth_response.access_jwt);
-        self.state.refresh_token = Some(auth_response.refresh_jwt);
-        self.state.is_authenticated = true;
-        Ok(())
+    /// Collapses or expands the selected command group, remembering it for
+    /// next time.
+    fn toggle_command_group(&mut self) {
+        self.state.toggle_group();
+        if let Err(e) = state::save_collapsed_groups(&self.state.collapsed_groups) {
+            self.state.error = Some(format!("Failed to save collapsed groups: {}", e));
+            self.state.error_time = Some(SystemTime::now());
+        }
     }
 
-    async fn execute_command(&mut self, method: &str, params: &[String]) -> AppResult<()> {
-        let cmd = AVAILABLE_COMMANDS
+    /// Whether the builder's current field takes a list.
+    fn editing_list(&self) -> bool {
+        let InputMode::CommandBuilder {
+            command,
+            current_param,
+            ..
+        } = &self.state.input.mode
+        else {
+            return false;
+        };
+        commands::all()
             .iter()
-            .find(|c| c.method == method)
-            .ok_or_else(|| AppError::Request {
-                src: "executing command".into(),
-                err_span: (0, 0),
-                msg: "Command not found".into(),
-            })?;
+            .find(|cmd| cmd.method == *command)
+            .and_then(|cmd| cmd.parameters.get(*current_param))
+            .is_some_and(|param| param.is_array)
+    }
 
-        let mut url = format!(
-            "{}/xrpc/{}",
-            self.state.pds_host.trim_end_matches('/'),
-            method
-        );
+    /// Copies `text`, showing why in the status bar if it couldn't be.
+    fn copy_to_clipboard(&mut self, text: String) -> bool {
+        let result = match &mut self.clipboard {
+            Some(clipboard) => clipboard
+                .set_text(text)
+                .map_err(|e| format!("Failed to copy to clipboard: {}", e)),
+            None => Err("Clipboard is unavailable".to_string()),
+        };
 
-        let mut query_params: Vec<(String, String)> = Vec::new();
-        for (i, param) in cmd.parameters.iter().enumerate() {
-            if let Some(value) = params.get(i) {
-                if !value.is_empty() || !param.optional {
-                    query_params.push((param.name.to_string(), value.clone()));
-                }
+        match result {
+            Ok(()) => true,
+            Err(msg) => {
+                self.state.error = Some(msg);
+                self.state.error_time = Some(SystemTime::now());
+                false
             }
         }
+    }
 
-        if !query_params.is_empty() {
-            url.push('?');
-            for (i, (name, value)) in query_params.iter().enumerate() {
-                if i > 0 {
-                    url.push('&');
-                }
-                url.push_str(&format!("{}={}", name, value));
-            }
+    /// Empties the history and deletes its file once it's asked for twice.
+    fn clear_history(&mut self) {
+        if self.state.request_history.is_empty() {
+            self.state.error = Some("History is already empty".into());
+            self.state.error_time = Some(SystemTime::now());
+            return;
         }
 
-        self.add_to_history(method, url.clone(), params.to_vec());
-
-        let mut req = self.client.get(&url);
-        if let Some(token) = &self.state.auth_token {
-            req = req.header("Authorization", format!("Bearer {}", token));
+        let confirmed = self
+            .state
+            .history_clear_requested
+            .take()
+            .and_then(|requested| requested.elapsed().ok())
+            .is_some_and(|elapsed| elapsed < self.state.error_timeout);
+        if !confirmed {
+            self.state.history_clear_requested = Some(SystemTime::now());
+            self.state.error = Some(format!(
+                "Press D again to delete all {} history entries",
+                self.state.request_history.len()
+            ));
+            self.state.error_time = Some(SystemTime::now());
+            return;
         }
 
-        match req.send().await {
-            Ok(mut res) => {
-                if !res.status().is_success() {
-                    let status = res.status();
-                    let error_body = match res.body_string().await {
-                        Ok(text) => text,
-                        Err(e) => format!("Failed to read error response: {}", e),
-                    };
-
-                    let error_msg = format!("Request failed ({}): {}", status, error_body);
-                    self.state.error = Some(error_msg.clone());
-                    self.update_history_success(method, false);
-                    return Err(AppError::Request {
-                        src: "request".into(),
-                        err_span: (0, 0),
-                        msg: error_msg,
-                    }
-                    .into());
-                }
+        self.state.request_history.clear();
+        self.state.selected_command_index = None;
+        // Offline runs never wrote the file, so leave it alone
+        let removed = if self.state.offline.is_none() {
+            state::remove_history()
+        } else {
+            Ok(())
+        };
+        self.state.error = Some(match removed {
+            Ok(()) => "Cleared history".into(),
+            Err(e) => format!("Cleared history, but couldn't delete the saved copy: {}", e),
+        });
+        self.state.error_time = Some(SystemTime::now());
+    }
 
-                match res.body_json::<serde_json::Value>().await {
-                    Ok(json) => {
-                        self.state.output = Some(json);
-                        self.state.error = None;
-                        self.update_history_success(method, true);
-                        Ok(())
-                    }
-                    Err(e) => {
-                        let error_msg = format!("Failed to parse response: {}", e);
-                        self.state.error = Some(error_msg.clone());
-                        self.update_history_success(method, false);
-                        Err(AppError::Request {
-                            src: "parsing response".into(),
-                            err_span: (0, 0),
-                            msg: error_msg,
-                        }
-                        .into())
-                    }
-                }
-            }
-            Err(e) => {
-                let error_msg = format!("Request failed: {}", e);
-                self.state.error = Some(error_msg.clone());
-                self.update_history_success(method, false);
-                Err(AppError::Request {
-                    src: "request".into(),
-                    err_span: (0, 0),
-                    msg: error_msg,
-                }
-                .into())
+    /// Copies the URL of history entry `idx`, or with `curl` a command that
+    /// sends it again with the current session and headers.
+    fn copy_history_entry(&mut self, idx: usize, curl: bool) {
+        let entry = self
+            .state
+            .request_history
+            .get(idx)
+            .filter(|hist| !hist.url.is_empty() && self.state.offline.is_none());
+        let Some(hist) = entry else {
+            // Offline runs and workflows like diffFollowers have no single URL
+            self.state.error = Some("That request has no URL to copy".into());
+            self.state.error_time = Some(SystemTime::now());
+            return;
+        };
+
+        let (text, label) = if curl {
+            if self.xrpc.oauth.is_some() {
+                self.state.error = Some(
+                    "An OAuth session signs each request with a fresh DPoP proof, so it can't be copied as a curl command"
+                        .into(),
+                );
+                self.state.error_time = Some(SystemTime::now());
+                return;
             }
+            (self.curl_for(hist), "curl command")
+        } else {
+            (hist.url.clone(), "URL")
+        };
+        if self.copy_to_clipboard(text) {
+            self.state.error = Some(format!("Copied {}", label));
+            self.state.error_time = Some(SystemTime::now());
         }
     }
 
-    fn add_to_history(&mut self, method: &str, url: String, params: Vec<String>) {
-        self.state.request_history.push_front(RequestHistory {
-            method: method.to_string(),
-            timestamp: OffsetDateTime::now_utc(),
-            success: false,
-            url,
-            params,
-        });
+    /// A `curl` command sending `hist` again with the current session and
+    /// headers.
+    fn curl_for(&self, hist: &RequestHistory) -> String {
+        let token = self
+            .xrpc
+            .access_token
+            .as_deref()
+            .filter(|_| self.state.curl_include_token);
+        output::curl_command(
+            &hist.url,
+            hist.body.as_ref(),
+            self.xrpc.access_token.is_some(),
+            token,
+            &self.state.headers_for(&hist.method),
+        )
+    }
 
-        if self.state.request_history.len() > MAX_HISTORY {
-            self.state.request_history.pop_back();
+    /// Copies the access token for use outside oxat once it's asked for
+    /// twice, warning in between since it's as good as a password for now.
+    /// The token is never shown.
+    fn copy_access_token(&mut self) {
+        let Some(token) = self.xrpc.access_token.clone() else {
+            self.state.error = Some("Not logged in, so there's no token to copy".into());
+            self.state.error_time = Some(SystemTime::now());
+            return;
+        };
+        if self.xrpc.oauth.is_some() {
+            self.state.error = Some(
+                "An OAuth token only works with a DPoP proof signed by oxat, so it isn't copied"
+                    .into(),
+            );
+            self.state.error_time = Some(SystemTime::now());
+            return;
         }
-    }
 
-    fn update_history_success(&mut self, method: &str, success: bool) {
-        if let Some(hist) = self
+        let confirmed = self
             .state
-            .request_history
-            .iter_mut()
-            .find(|h| h.method == method)
-        {
-            hist.success = success;
+            .token_copy_requested
+            .take()
+            .and_then(|requested| requested.elapsed().ok())
+            .is_some_and(|elapsed| elapsed < self.state.error_timeout);
+        if !confirmed {
+            self.state.token_copy_requested = Some(SystemTime::now());
+            self.state.error = Some(
+                "Your access token lets anyone act as you until it expires. Press Ctrl+y again to copy it"
+                    .into(),
+            );
+            self.state.error_time = Some(SystemTime::now());
+            return;
         }
-    }
 
-    fn get_content_height(&self) -> u16 {
-        if let Some(output) = &self.state.output {
-            let formatted = serde_json::to_string_pretty(output).unwrap_or_default();
-            let text = ui::syntax_highlight(&formatted);
-            text.lines.len() as u16
-        } else if self.state.error.is_some() {
-            1
-        } else {
-            0
+        if self.copy_to_clipboard(token) {
+            self.state.error = Some("Copied access token (not shown)".into());
+            self.state.error_time = Some(SystemTime::now());
         }
     }
 
-    fn update_scroll(&mut self, direction: i16, viewport_height: u16) {
-        let content_height = self.get_content_height();
-        let max_scroll = content_height.saturating_sub(viewport_height);
-
-        match direction {
-            1 => {
-                // Scroll down
-                self.state.scroll_offset = (self.state.scroll_offset + 1).min(max_scroll);
-            }
-            -1 => {
-                // Scroll up
-                self.state.scroll_offset = self.state.scroll_offset.saturating_sub(1);
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match (&self.state.input.mode, mouse.kind) {
+            (
+                InputMode::ViewingResponse
+                | InputMode::SearchResponse
+                | InputMode::ErrorDetail { .. }
+                | InputMode::Lexicon { .. },
+                MouseEventKind::ScrollUp,
+            ) => {
+                self.update_scroll(-1, viewport_height());
+                self.wheel_cursor_into_view();
             }
-            10 => {
-                // Page down
-                self.state.scroll_offset =
-                    (self.state.scroll_offset + viewport_height).min(max_scroll);
+            (
+                InputMode::ViewingResponse
+                | InputMode::SearchResponse
+                | InputMode::ErrorDetail { .. }
+                | InputMode::Lexicon { .. },
+                MouseEventKind::ScrollDown,
+            ) => {
+                self.update_scroll(1, viewport_height());
+                self.wheel_cursor_into_view();
             }
-            -10 => {
-                // Page up
-                self.state.scroll_offset = self.state.scroll_offset.saturating_sub(viewport_height);
+            (
+                InputMode::Command | InputMode::History | InputMode::HistoryFilter,
+                MouseEventKind::Down(MouseButton::Left),
+            ) => {
+                if let Some(idx) = ui::list_item_at(&self.state, mouse.row) {
+                    self.state.selected_command_index = Some(idx);
+                }
             }
             _ => {}
         }
     }
 
-    async fn run(&mut self) -> AppResult<()> {
-        let mut terminal_handler = TerminalHandler::new()?;
-
-        while !self.state.quit {
-            terminal_handler
-                .terminal
-                .draw(|f| render(&self.state, f))
-                .map_err(|e| AppError::Terminal {
-                    src: "drawing terminal".into(),
-                    err_span: (0, 0),
-                    msg: e.to_string(),
-                })?;
+    async fn handle_auth(
+        &mut self,
+        identifier: String,
+        password: String,
+        auth_factor_token: Option<String>,
+    ) -> AppResult<()> {
+        self.state.error = None;
 
-            match self.events.try_recv() {
-                Ok(AppEvent::Input(event)) => {
-                    if let Err(e) = self.handle_input(event).await {
-                        self.state.error = Some(e.to_string());
-                        self.state.error_time = Some(SystemTime::now());
+        let login = self
+            .xrpc
+            .login(&identifier, &password, auth_factor_token.as_deref());
+        let session = match login.await {
+            Ok(session) => session,
+            Err(e) => {
+                let (error_msg, url, status, body) = match &e {
+                    AppError::Network { src, msg, .. } => {
+                        (format!("Auth request failed: {}", msg), src, None, None)
                     }
+                    AppError::HttpStatus {
+                        src, code, body, ..
+                    } => (
+                        format!("Auth failed ({}): {}", code, body),
+                        src,
+                        Some(*code),
+                        Some(body.clone()),
+                    ),
+                    _ => return Err(e.into()),
+                };
+                self.state.error = Some(error_msg.clone());
+                self.state.last_error = Some(ErrorDetail {
+                    message: error_msg,
+                    url: Some(url.clone()),
+                    status,
+                    body,
+                });
+                self.state.error_time = Some(SystemTime::now());
+                return Err(e.into());
+            }
+        };
+
+        self.start_session(session);
+        self.state.is_authenticated = true;
+        Ok(())
+    }
+
+    /// Signs in through the browser with OAuth instead of a password. The
+    /// authorization page is opened in the browser, or its URL shown if
+    /// that fails, and the session starts once the browser is redirected
+    /// back. Esc gives up waiting.
+    async fn login_oauth(&mut self, identifier: &str) -> AppResult<()> {
+        self.state.error = Some("Contacting the authorization server…".into());
+        self.state.error_time = None;
+        let _ = self.draw();
+        let authorization = oauth::authorize(&self.xrpc, identifier).await?;
+
+        // Left up, rather than timing out, until the browser comes back
+        self.state.error = Some(match opener::open(&authorization.url) {
+            Ok(()) => "Finish signing in in your browser (Esc cancels)".into(),
+            Err(_) => format!("Open {} to sign in (Esc cancels)", authorization.url),
+        });
+        self.state.error_time = None;
+
+        let xrpc = self.xrpc.clone();
+        let tx = self.event_tx.clone();
+        let task = smol::spawn(async move {
+            let result = authorization.finish(&xrpc).await;
+            let _ = tx.send(AppEvent::OAuth(result)).await;
+        });
+        self.state.loading = Some(Instant::now());
+        let result = loop {
+            let _ = self.draw();
+            match self.events.recv().await {
+                Ok(AppEvent::OAuth(result)) => break result,
+                Ok(AppEvent::Tick) => self.state.update(),
+                Ok(AppEvent::ServerInfo(info)) => self.state.server_info = Some(info),
+                Ok(AppEvent::Input(CEvent::Key(key)))
+                    if key.code == KeyCode::Esc && key.kind == KeyEventKind::Press =>
+                {
+                    break Err(AppError::Auth {
+                        src: "OAuth sign-in".into(),
+                        err_span: (0, 0),
+                        msg: "Sign-in cancelled".into(),
+                    });
                 }
-                Ok(AppEvent::Tick) => {
-                    self.state.update();
-                }
-                Err(smol::channel::TryRecvError::Empty) => {
-                    smol::Timer::after(Duration::from_millis(10)).await;
+                Ok(AppEvent::Input(CEvent::Key(key)))
+                    if key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                {
+                    if self.state.confirm_quit() {
+                        self.state.quit = true;
+                    }
                 }
-                Err(smol::channel::TryRecvError::Closed) => {
-                    break;
+                Ok(_) => {}
+                Err(e) => {
+                    break Err(AppError::Auth {
+                        src: "OAuth sign-in".into(),
+                        err_span: (0, 0),
+                        msg: e.to_string(),
+                    })
                 }
             }
+            if self.state.quit {
+                return Ok(());
+            }
+        };
+        self.state.loading = None;
+        // Stops listening for the redirect if it hasn't come
+        drop(task);
+
+        let (oauth, tokens) = result?;
+        let session = self.xrpc.start_oauth_session(oauth, tokens).await?;
+        self.start_session(session);
+        self.state.is_authenticated = true;
+        self.state.error = None;
+        Ok(())
+    }
+
+    /// Picks up the session saved for this profile last time, if it was with
+    /// the same PDS, refreshing it first if it's expired. Returns whether
+    /// there's a session to use; if not, logging in goes on as usual.
+    async fn resume_session(&mut self) -> bool {
+        let Some(saved) = state::load_session(self.state.profile.as_deref()) else {
+            return false;
+        };
+        // Tokens only ever go back to the PDS that issued them
+        if saved.pds_host != self.xrpc.pds_host {
+            return false;
+        }
+
+        let expired = jwt::expiry(&saved.session.access_jwt)
+            .is_some_and(|expires| expires <= SystemTime::now());
+        self.xrpc.access_token = Some(saved.session.access_jwt.clone());
+        self.xrpc.refresh_token = Some(saved.session.refresh_jwt.clone());
+        self.start_session(saved.session);
+        self.state.is_authenticated = true;
+        if expired && self.refresh_session().await.is_err() {
+            self.xrpc.access_token = None;
+            self.xrpc.refresh_token = None;
+            self.state.is_authenticated = false;
+            self.state.token_expires = None;
+            self.state.did = None;
+            self.state.handle = None;
+            return false;
+        }
+        true
+    }
+
+    /// Moves on to the command list once logged in from the TUI.
+    fn finish_login(&mut self) {
+        self.state.input.mode = InputMode::Command;
+        if !matches!(self.state.server_info, Some(Ok(_))) {
+            self.describe_server();
+        }
+        self.fetch_unread_count();
+    }
+
+    /// Records who a new or refreshed session belongs to and when it ends.
+    /// Password sessions are saved for the profile to resume next launch;
+    /// OAuth ones can't be, since their key only lives in memory.
+    fn start_session(&mut self, session: Session) {
+        self.state.token_expires = jwt::expiry(&session.access_jwt);
+        if self.xrpc.oauth.is_none() && self.state.offline.is_none() {
+            let saved = SavedSession {
+                pds_host: self.xrpc.pds_host.clone(),
+                session: session.clone(),
+            };
+            if let Err(e) = state::save_session(self.state.profile.as_deref(), Some(saved)) {
+                log::error!("saving session error={:?}", e.to_string());
+            }
+        }
+        self.state.did = Some(session.did);
+        self.state.handle = Some(session.handle);
+    }
+
+    async fn execute_command(&mut self, method: &str, params: &[String]) -> AppResult<()> {
+        self.state.image = None;
+        if let Some(dir) = self.state.offline.clone() {
+            return self.load_fixture(&dir, method, params, false);
+        }
+        if method == DIFF_FOLLOWERS {
+            return self.diff_followers(method, params).await;
+        }
+        if method == POST {
+            return self.create_post(method, params).await;
+        }
+        if method == DELETE_RECORD {
+            return self.delete_record(method, params).await;
+        }
+
+        let cmd = commands::all()
+            .iter()
+            .find(|c| c.method == method)
+            .ok_or_else(|| AppError::Request {
+                src: "executing command".into(),
+                err_span: (0, 0),
+                msg: "Command not found".into(),
+            })?;
+
+        let (url, resolved) = self.build_url(cmd, params).await?;
+
+        self.add_to_history(method, url.clone(), params.to_vec());
+
+        let download = Download {
+            path: self.download_path(method),
+            encoding: cmd.encoding,
+        };
+        let req = self.xrpc.get(&url);
+        match self
+            .send_request(method, &url, req, Some(download.clone()))
+            .await
+        {
+            // Sent again once with fresh tokens
+            Err(e) if self.session_expired(&e) => {
+                self.refresh_session().await?;
+                let req = self.xrpc.get(&url);
+                self.send_request(method, &url, req, Some(download)).await?;
+            }
+            sent => sent?,
         }
 
+        if !resolved.is_empty() {
+            self.state.error = Some(format!("Resolved {}", resolved.join(", ")));
+            self.state.error_time = Some(SystemTime::now());
+        }
         Ok(())
     }
 
-    // async fn refresh_session(&mut self) -> AppResult<()> {
-    //     if let Some(refresh_token) = &self.state.refresh_token {
-    //         let endpoint = format!(
-    //             "{}/xrpc/com.atproto.server.refreshSession",
-    //             self.state.pds_host.trim_end_matches('/')
-    //         );
-
-    //         let mut res = match self
-    //             .client
-    //             .post(&endpoint)
-    //             .header("Authorization", format!("Bearer {}", refresh_token))
-    //             .await
-    //         {
-    //             Ok(res) => res,
-    //             Err(e) => {
-    //                 let error_msg = format!("Failed to refresh session: {}", e);
-    //                 self.state.error = Some(error_msg.clone());
-    //                 return Err(AppError::Auth {
-    //                     src: "session refresh".into(),
-    //                     err_span: (0, 0),
-    //                     msg: error_msg,
-    //                 }
-    //                 .into());
-    //             }
-    //         };
-
-    //         if !res.status().is_success() {
-    //             self.state.is_authenticated = false;
-    //             self.state.auth_token = None;
-    //             self.state.refresh_token = None;
-    //             return Err(AppError::Auth {
-    //                 src: "session refresh".into(),
-    //                 err_span: (0, 0),
-    //                 msg: "Session refresh failed".into(),
-    //             }
-    //             .into());
-    //         }
-
-    //         let auth_response = match res.body_json::<AuthResponse>().await {
-    //             Ok(resp) => resp,
-    //             Err(e) => {
-    //                 return Err(AppError::Auth {
-    //                     src: "parsing refresh response".into(),
-    //                     err_span: (0, 0),
-    //                     msg: format!("Failed to parse refresh response: {}", e),
-    //                 }
-    //                 .into());
-    //             }
-    //         };
-
-    //         self.state.auth_token = Some(auth_response.access_jwt);
-    //         self.state.refresh_token = Some(auth_response.refresh_jwt);
-    //         Ok(())
-    //     } else {
-    //         Err(AppError::Auth {
-    //             src: "session refresh".into(),
-    //             err_span: (0, 0),
-    //             msg: "No refresh token available".into(),
-    //         }
-    //         .into())
-    //     }
-    // }
-}
+    /// Opens the last exported or downloaded file, or the folder it's in,
+    /// with the OS's default app. Without an opener the path is shown
+    /// instead, so it can be found by hand.
+    fn open_export(&mut self, folder: bool) {
+        let Some(path) = self.state.last_export.clone() else {
+            self.state.error = Some("Nothing exported yet; e exports the response".into());
+            self.state.error_time = Some(SystemTime::now());
+            return;
+        };
+        let target = match path.parent() {
+            Some(dir) if folder => dir,
+            _ => path.as_path(),
+        };
+        self.state.error = Some(match opener::open(target) {
+            Ok(()) => format!("Opened {}", target.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => format!(
+                "No {} to open files with; it's at {}",
+                opener::program(),
+                target.display()
+            ),
+            Err(e) => format!("Couldn't open {}: {}", target.display(), e),
+        });
+        self.state.error_time = Some(SystemTime::now());
+    }
 
-fn main() -> AppResult<()> {
-    #[cfg(debug_assertions)]
-    std::env::set_var("RUST_BACKTRACE", "1");
+    /// Where a response that isn't JSON is saved: the export filename, whose
+    /// extension is set from the type that comes back.
+    fn download_path(&self, method: &str) -> PathBuf {
+        let handle = self
+            .state
+            .handle
+            .as_deref()
+            .or(self.state.identifier.as_deref())
+            .unwrap_or("anonymous");
+        let filename = output::export_filename(
+            &self.state.export_filename,
+            method,
+            handle,
+            OffsetDateTime::now_utc(),
+        );
+        Path::new(&filename).with_extension("")
+    }
 
-    let result = smol::block_on(async {
-        let app_result = std::panic::AssertUnwindSafe(App::new()?.run())
-            .catch_unwind()
-            .await;
+    /// Composes the request URL for `cmd`, resolving handles given for DID
+    /// parameters. Also returns a `handle → did` note for each resolution.
+    async fn build_url(
+        &mut self,
+        cmd: &XrpcCommand,
+        params: &[String],
+    ) -> AppResult<(String, Vec<String>)> {
+        let mut url = self.xrpc.url(cmd.method);
 
-        match app_result {
-            Ok(res) => res,
-            Err(err) => {
-                let panic_msg = if let Some(s) = err.downcast_ref::<&str>() {
-                    s.to_string()
-                } else if let Some(s) = err.downcast_ref::<String>() {
-                    s.clone()
+        let mut query_params: Vec<(String, String)> = Vec::new();
+        let mut resolved = Vec::new();
+        for (param, input) in cmd.parameters.iter().zip(params) {
+            // Lists repeat the key once per value
+            for value in param.values(input) {
+                let value = if param.kind == ParamKind::Did
+                    && self.state.resolve_handles
+                    && !value.is_empty()
+                    && !value.starts_with("did:")
+                {
+                    let did = self.resolve_handle(value).await?;
+                    resolved.push(format!("{} → {}", value, did));
+                    did
                 } else {
-                    "Unknown panic occurred".to_string()
+                    value.to_string()
                 };
 
-                Err(AppError::Terminal {
-                    src: "panic".into(),
-                    err_span: (0, 0),
-                    msg: panic_msg,
+                if !value.is_empty() || !param.optional {
+                    query_params.push((param.name.to_string(), value));
                 }
-                .into())
             }
         }
-    });
 
-    crossterm::terminal::disable_raw_mode().ok();
-    std::io::stdout().execute(LeaveAlternateScreen).ok();
-    std::io::stdout().execute(DisableMouseCapture).ok();
+        url.push_str(&xrpc::query_string(&query_params));
+        Ok((url, resolved))
+    }
 
-    if let Err(e) = &result {
-        println!("Application error: {}", e);
-        std::process::exit(1);
+    /// Sends a command from the builder, first asking for "yes" to be typed
+    /// if it's destructive.
+    async fn submit_command(&mut self, command: String, params: Vec<String>) -> AppResult<()> {
+        if is_destructive(&command) {
+            self.confirm_destructive(command, params);
+            return Ok(());
+        }
+        self.send_command(command, params).await
     }
 
-    Ok(())
+    /// Reopens the builder on the last field so values can be corrected.
+    fn back_to_builder(&mut self, command: String, params: Vec<String>) {
+        let current_param = params.len().saturating_sub(1);
+        self.state.input.content = params.get(current_param).cloned().unwrap_or_default();
+        self.state.input.move_cursor_to_end();
+        self.state.input.mode = InputMode::CommandBuilder {
+            command,
+            current_param,
+            params,
+        };
+    }
+
+    /// Asks for "yes" to be typed before `command` is sent.
+    fn confirm_destructive(&mut self, command: String, params: Vec<String>) {
+        self.state.input.content.clear();
+        self.state.input.cursor_position = 0;
+        self.state.input.mode = InputMode::ConfirmDestructive { command, params };
+    }
+
+    /// Sends a command, or shows what would be sent and waits for
+    /// confirmation when previews are on.
+    async fn send_command(&mut self, command: String, params: Vec<String>) -> AppResult<()> {
+        let cmd = commands::all().iter().find(|c| c.method == command);
+        // Workflows send several requests, so there's no single one to preview
+        let Some(cmd) = cmd.filter(|_| self.state.preview_requests && command != DIFF_FOLLOWERS)
+        else {
+            self.execute_command(&command, &params).await?;
+            self.state.input.mode = InputMode::ViewingResponse;
+            return Ok(());
+        };
+
+        let mut headers = Vec::new();
+        if self.xrpc.oauth.is_some() {
+            // The proof is made as the request is sent, for its method and URL
+            headers.push(("Authorization".to_string(), "DPoP $TOKEN".to_string()));
+            headers.push((
+                "DPoP".to_string(),
+                "<proof signed for this request>".to_string(),
+            ));
+        } else if self.xrpc.access_token.is_some() {
+            headers.push(("Authorization".to_string(), "Bearer $TOKEN".to_string()));
+        }
+        headers.extend(self.state.headers_for(&command));
+
+        self.state.preview = Some(if command == POST || command == DELETE_RECORD {
+            let (url, body) = if command == POST {
+                self.post_request(&params).await?
+            } else {
+                self.delete_request(&params)?
+            };
+            headers.push(("Content-Type".to_string(), "application/json".to_string()));
+            RequestPreview {
+                http_method: "POST",
+                url,
+                headers,
+                body: serde_json::to_string_pretty(&body).ok(),
+                notes: Vec::new(),
+            }
+        } else {
+            let (url, notes) = self.build_url(cmd, &params).await?;
+            RequestPreview {
+                http_method: "GET",
+                url,
+                headers,
+                body: None,
+                notes,
+            }
+        });
+        self.state.input.mode = InputMode::ConfirmRequest { command, params };
+        Ok(())
+    }
+
+    /// Re-sends a history entry the same way it was first sent, or asks
+    /// for confirmation first if it's destructive. Returns `false` if
+    /// there's no entry at `idx`.
+    async fn replay(&mut self, idx: usize) -> AppResult<bool> {
+        let Some(hist) = self.state.request_history.get(idx) else {
+            return Ok(false);
+        };
+        let method = hist.method.clone();
+        let params = hist.params.clone();
+
+        if hist.raw {
+            let args = params.first().cloned().unwrap_or_default();
+            self.execute_raw(&method, &args).await?;
+        } else if is_destructive(&method) {
+            self.confirm_destructive(method, params);
+        } else {
+            self.execute_command(&method, &params).await?;
+        }
+        Ok(true)
+    }
+
+    /// Re-runs the most recent request and shows its response from the top.
+    /// Posts and other writes aren't, since one key press would send them
+    /// again unasked; they can still be sent again from history.
+    async fn rerun_last(&mut self) -> AppResult<()> {
+        if self
+            .state
+            .request_history
+            .front()
+            .is_some_and(repeat::changes_data)
+        {
+            self.state.error =
+                Some("The last request changed data; send it again from history (h)".into());
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        }
+        if !self.replay(0).await? {
+            self.state.error = Some("No previous command to re-run".into());
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        }
+        if matches!(self.state.input.mode, InputMode::ConfirmDestructive { .. }) {
+            return Ok(());
+        }
+
+        self.state.input.mode = InputMode::ViewingResponse;
+        self.state.scroll_to_top();
+        self.state.update_search_matches();
+        Ok(())
+    }
+
+    /// Fetches the record named by the `at://` URI on the focused line,
+    /// keeping the current response so Backspace can return to it.
+    async fn follow_uri(&mut self) -> AppResult<()> {
+        // A post or other record view carries its URI under `uri`
+        let uri = self
+            .state
+            .value_at(self.state.cursor_line)
+            .and_then(|(_, value)| match value {
+                serde_json::Value::String(s) => Some(s.clone()),
+                other => other["uri"].as_str().map(str::to_string),
+            });
+        let Some(uri) = uri.filter(|uri| uri.starts_with("at://")) else {
+            self.state.error = Some("No at:// URI on this line".into());
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        };
+        let Some((repo, collection, rkey)) = commands::parse_at_uri(&uri) else {
+            self.state.error = Some(format!("{} doesn't name a record", uri));
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        };
+
+        let params = [repo.to_string(), collection.to_string(), rkey.to_string()];
+        let previous = self.state.output.clone();
+        let previous_method = self.state.output_method.clone();
+        let scroll_offset = self.state.scroll_offset;
+        let cursor_line = self.state.cursor_line;
+        self.execute_command(GET_RECORD, &params).await?;
+
+        if let Some(previous) = previous {
+            self.state
+                .push_back(previous, previous_method, scroll_offset, cursor_line);
+        }
+        self.state.scroll_to_top();
+        self.state.scroll_x = 0;
+        self.state.filter = None;
+        self.state.collapsed.clear();
+        self.state.update_search_matches();
+        Ok(())
+    }
+
+    /// Calls `method` directly, bypassing the command list. `args` is either a
+    /// `key=value&...` query string, or a JSON object to POST as a procedure.
+    async fn execute_raw(&mut self, method: &str, args: &str) -> AppResult<()> {
+        if let Some(dir) = self.state.offline.clone() {
+            return self.load_fixture(&dir, method, &[args.to_string()], true);
+        }
+
+        let mut url = self.xrpc.url(method);
+
+        let body: Option<serde_json::Value> = if args.starts_with('{') {
+            let body = serde_json::from_str(args).map_err(|e| AppError::Request {
+                src: args.to_string(),
+                err_span: (0, args.len()),
+                msg: format!("Invalid JSON body: {}", e),
+            })?;
+            Some(body)
+        } else {
+            None
+        };
+        let req = if let Some(body) = &body {
+            self.xrpc
+                .post(&url)
+                .body_json(body)
+                .map_err(|e| AppError::Request {
+                    src: "building request".into(),
+                    err_span: (0, 0),
+                    msg: format!("Failed to build request: {}", e),
+                })?
+        } else {
+            // Split into pairs so values are encoded the way built commands' are
+            let query: Vec<(&str, &str)> = args
+                .trim_start_matches('?')
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+                .collect();
+            url.push_str(&xrpc::query_string(&query));
+            self.xrpc.get(&url)
+        };
+
+        self.add_to_history(method, url.clone(), vec![args.to_string()]);
+        if let Some(hist) = self.state.request_history.front_mut() {
+            hist.raw = true;
+        }
+        if let Some(body) = &body {
+            self.record_body(body);
+        }
+
+        // Raw calls say nothing about their output, so JSON is expected
+        let download = Download {
+            path: self.download_path(method),
+            encoding: JSON,
+        };
+        self.send_request(method, &url, req, Some(download)).await
+    }
+
+    /// Sends a request with the session's auth, storing the JSON response as
+    /// the output and recording the outcome on the latest history entry.
+    async fn send_request(
+        &mut self,
+        method: &str,
+        url: &str,
+        req: surf::RequestBuilder,
+        download: Option<Download>,
+    ) -> AppResult<()> {
+        self.state.response_info = None;
+        self.state.image = None;
+        let timeout = self.state.timeout_for(method);
+        let req = self
+            .state
+            .headers_for(method)
+            .iter()
+            .fold(req, |req, (name, value)| {
+                req.header(name.as_str(), value.as_str())
+            });
+        let image_path = download.as_ref().map(|download| download.path.clone());
+        match self.fetch_into(req, timeout, download).await {
+            Ok(res) => {
+                let status = res.status;
+                if let Some(hist) = self.state.request_history.front_mut() {
+                    hist.status = Some(status.into());
+                }
+                self.state.response_info = Some(ResponseInfo {
+                    status: status.into(),
+                    reason: status.canonical_reason().to_string(),
+                    elapsed: res.elapsed,
+                });
+
+                if !status.is_success() {
+                    let error_body = res.body;
+                    let mut error_msg = format!("Request failed ({}): {}", status, error_body);
+                    if let (StatusCode::TooManyRequests, Some(rate_limit)) =
+                        (status, res.rate_limit)
+                    {
+                        error_msg = format!(
+                            "Rate limited, requests allowed again in {}: {}",
+                            ui::format_remaining(rate_limit.resets_in()),
+                            error_body
+                        );
+                    }
+                    self.state.error = Some(error_msg.clone());
+                    self.state.last_error = Some(ErrorDetail {
+                        message: error_msg,
+                        url: Some(url.to_string()),
+                        status: Some(status.into()),
+                        body: Some(error_body.clone()),
+                    });
+                    self.update_history_success(method, false);
+                    return Err(AppError::http_status(url, status.into(), error_body).into());
+                }
+
+                if let Some((path, bytes)) = res.saved {
+                    // Binary bodies can't be shown, so say where they went instead
+                    self.state.output = Some(serde_json::json!({
+                        "savedTo": path.display().to_string(),
+                        "contentType": res.content_type,
+                        "bytes": bytes,
+                    }));
+                    self.state.output_method = None;
+                    self.state.collapsed.clear();
+                    self.state.last_export = std::path::absolute(&path).ok();
+                    self.state.error = Some(format!("Saved to {}", path.display()));
+                    self.state.error_time = Some(SystemTime::now());
+                    self.update_history_success(method, true);
+                    return Ok(());
+                }
+
+                if let (Some(bytes), Some(path)) = (res.image, image_path) {
+                    let image = Image {
+                        content_type: res.content_type.unwrap_or_default(),
+                        bytes,
+                        path,
+                    };
+                    let info = content::describe_image(&image);
+                    let size = match (info["width"].as_u64(), info["height"].as_u64()) {
+                        (Some(width), Some(height)) => format!(", {}×{}", width, height),
+                        _ => String::new(),
+                    };
+                    self.state.error =
+                        Some(format!("{} image{}; s saves it", image.content_type, size));
+                    self.state.error_time = Some(SystemTime::now());
+                    self.state.output = Some(info);
+                    self.state.output_method = None;
+                    self.state.collapsed.clear();
+                    self.state.image = Some(image);
+                    self.update_history_success(method, true);
+                    return Ok(());
+                }
+
+                match serde_json::from_str::<serde_json::Value>(&res.body) {
+                    Ok(json) => {
+                        if method == GET_UNREAD_COUNT {
+                            self.state.unread_count = json["count"].as_u64();
+                        }
+                        self.state.output = Some(json);
+                        self.state.output_method = Some(method.to_string());
+                        self.state.collapsed.clear();
+                        self.state.error = None;
+                        self.update_history_success(method, true);
+                        Ok(())
+                    }
+                    Err(e) => {
+                        let error_msg = format!("Failed to parse response: {}", e);
+                        self.state.error = Some(error_msg.clone());
+                        self.state.last_error = Some(ErrorDetail {
+                            message: error_msg,
+                            url: Some(url.to_string()),
+                            status: Some(status.into()),
+                            body: Some(res.body),
+                        });
+                        self.update_history_success(method, false);
+                        Err(AppError::deserialize(url, e.to_string()).into())
+                    }
+                }
+            }
+            Err(e) => {
+                let error_msg = e.message();
+                self.state.error = Some(error_msg.clone());
+                if !matches!(e, FetchError::Cancelled) {
+                    self.state.last_error = Some(ErrorDetail {
+                        message: error_msg.clone(),
+                        url: Some(url.to_string()),
+                        ..Default::default()
+                    });
+                }
+                self.update_history_success(method, false);
+                Err(e.into_error(url).into())
+            }
+        }
+    }
+
+    /// Resolves a handle to its DID via `com.atproto.identity.resolveHandle`,
+    /// caching the result for the rest of the session.
+    async fn resolve_handle(&mut self, handle: &str) -> AppResult<String> {
+        let handle = handle.trim_start_matches('@');
+        if let Some(did) = self.state.resolved_handles.get(handle) {
+            return Ok(did.clone());
+        }
+
+        let url = format!(
+            "{}{}",
+            self.xrpc.url("com.atproto.identity.resolveHandle"),
+            xrpc::query_string(&[("handle", handle)])
+        );
+
+        let timeout = self.state.request_timeout;
+        let resolved = match self.fetch(self.xrpc.get(&url), timeout).await {
+            Ok(res) if res.status.is_success() => {
+                serde_json::from_str::<serde_json::Value>(&res.body)
+                    .ok()
+                    .and_then(|json| json["did"].as_str().map(str::to_string))
+            }
+            _ => None,
+        };
+
+        let Some(did) = resolved else {
+            let error_msg = format!("Could not resolve handle '{}' to a DID", handle);
+            self.state.error = Some(error_msg.clone());
+            return Err(AppError::request_at(&url, Some(handle), error_msg).into());
+        };
+
+        self.state
+            .resolved_handles
+            .insert(handle.to_string(), did.clone());
+        Ok(did)
+    }
+
+    /// Resolves the handle or DID typed into the lookup, keeping the answer
+    /// in `state.lookup`.
+    async fn lookup(&mut self) {
+        let query = self.state.input.content.trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+        if self.state.offline.is_some() {
+            self.state.lookup = Some(Err("Lookups need the network".into()));
+            return;
+        }
+
+        let result = if commands::is_did(&query) {
+            self.xrpc
+                .resolve_did(&query)
+                .await
+                .map_err(|e| e.to_string())
+        } else if is_handle(&query) {
+            self.resolve_handle(&query).await.map_err(|e| e.to_string())
+        } else {
+            Err(format!("{} is neither a handle nor a DID", query))
+        };
+        self.state.lookup = Some(result);
+    }
+
+    /// Applies `name: value` from the header editor, or removes the header
+    /// when the value is left empty.
+    fn edit_header(&mut self) {
+        let input = self.state.input.content.trim().to_string();
+        let Some((name, value)) = input.split_once(':') else {
+            self.state.error = Some("Enter a header as name: value".into());
+            self.state.error_time = Some(SystemTime::now());
+            return;
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if !is_header_name(&name) {
+            self.state.error = Some(format!("'{}' can't be used as a header name", name));
+            self.state.error_time = Some(SystemTime::now());
+            return;
+        }
+
+        let message = if value.is_empty() {
+            match self.state.headers.remove(&name) {
+                Some(_) => format!("Removed {}", name),
+                None => format!("{} isn't set", name),
+            }
+        } else {
+            self.state.headers.insert(name.clone(), value.to_string());
+            format!("Sending {} with every command", name)
+        };
+        self.state.error = Some(message);
+        self.state.error_time = Some(SystemTime::now());
+        self.state.input.content.clear();
+        self.state.input.cursor_position = 0;
+    }
+
+    /// Leaves the lookup, giving the screen under it back its input.
+    fn close_lookup(&mut self) {
+        let mode = std::mem::take(&mut self.state.input.mode);
+        if let InputMode::Lookup {
+            previous,
+            saved_input,
+            saved_cursor,
+        } = mode
+        {
+            self.state.input.mode = *previous;
+            self.state.input.content = saved_input;
+            self.state.input.cursor_position = saved_cursor;
+        }
+    }
+
+    fn add_to_history(&mut self, method: &str, url: String, params: Vec<String>) {
+        self.state.request_history.push_front(RequestHistory {
+            method: method.to_string(),
+            timestamp: OffsetDateTime::now_utc(),
+            success: false,
+            url,
+            params,
+            status: None,
+            raw: false,
+            body: None,
+        });
+
+        if self.state.request_history.len() > self.state.history_size {
+            self.state.request_history.pop_back();
+        }
+    }
+
+    /// Notes on the latest history entry that it POSTed `body`, so it can
+    /// be copied as the same request.
+    fn record_body(&mut self, body: &serde_json::Value) {
+        if let Some(hist) = self.state.request_history.front_mut() {
+            hist.body = Some(body.clone());
+        }
+    }
+
+    fn update_history_success(&mut self, method: &str, success: bool) {
+        if let Some(hist) = self
+            .state
+            .request_history
+            .iter_mut()
+            .find(|h| h.method == method)
+        {
+            hist.success = success;
+        }
+
+        // History is a convenience; failing to persist it shouldn't mask the
+        // response. Offline runs are kept out of it.
+        if self.state.offline.is_none() {
+            let _ = state::save_history(&self.state.request_history);
+        }
+    }
+
+    fn get_content_height(&self) -> u16 {
+        // Measured at the viewer's width, since long lines wrap onto more rows
+        let width = viewport_width();
+        if let (InputMode::ErrorDetail { .. }, Some(detail)) =
+            (&self.state.input.mode, &self.state.last_error)
+        {
+            ui::error_detail_height(detail, &self.state.theme, width) as u16
+        } else if let InputMode::Lexicon { method, .. } = &self.state.input.mode {
+            self.state.lexicons.get(method).map_or(0, |lexicon| {
+                ui::lexicon_height(lexicon, &self.state.theme, width) as u16
+            })
+        } else if self.state.displayed_output().is_some() {
+            ui::response_height(&self.state, width) as u16
+        } else if let Some(error) = &self.state.error {
+            ui::message_height(error, width) as u16
+        } else {
+            0
+        }
+    }
+
+    /// Re-runs the most recent command with its `cursor` parameter set from
+    /// the current response, replacing the view with the next page.
+    async fn next_page(&mut self) -> AppResult<()> {
+        let Some(cursor) = self.state.response_cursor().map(str::to_string) else {
+            self.state.error = Some("No more pages".into());
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        };
+
+        let Some(hist) = self.state.request_history.front() else {
+            return Ok(());
+        };
+        let method = hist.method.clone();
+        let mut params = hist.params.clone();
+
+        let Some((cmd, idx)) = commands::all()
+            .iter()
+            .find(|c| c.method == method)
+            .and_then(|cmd| Some((cmd, cmd.param_index("cursor")?)))
+        else {
+            self.state.error = Some(format!("{} doesn't take a cursor", method));
+            self.state.error_time = Some(SystemTime::now());
+            return Ok(());
+        };
+
+        params.resize(cmd.parameters.len(), String::new());
+        params[idx] = cursor;
+
+        self.execute_command(&method, &params).await?;
+        self.state.scroll_to_top();
+        self.state.update_search_matches();
+        Ok(())
+    }
+
+    /// Moves to the next (or previous) search match, wrapping around,
+    /// focuses it and scrolls it to the top of the viewport.
+    fn jump_to_match(&mut self, forward: bool, viewport_height: u16) {
+        let matches = &self.state.search.match_lines;
+        if matches.is_empty() {
+            if !self.state.search.query.is_empty() {
+                self.state.error = Some(format!("No matches for '{}'", self.state.search.query));
+                self.state.error_time = Some(SystemTime::now());
+            }
+            return;
+        }
+
+        let next = match self.state.search.current_match {
+            Some(i) if forward => (i + 1) % matches.len(),
+            Some(i) => (i + matches.len() - 1) % matches.len(),
+            None if forward => 0,
+            None => matches.len() - 1,
+        };
+        let line = matches[next];
+
+        self.state.search.current_match = Some(next);
+        self.state.cursor_line = line;
+        // Wrapped lines above push the match down by more than one row each
+        let row: usize = ui::line_rows(&self.state, viewport_width())[..line]
+            .iter()
+            .sum();
+        let max_scroll = self.get_content_height().saturating_sub(viewport_height);
+        self.state.scroll_offset = (row as u16).min(max_scroll);
+    }
+
+    /// Moves the focused line `delta` lines up or down, scrolling just far
+    /// enough to keep it in a viewer `width` by `height`.
+    fn move_cursor(&mut self, delta: isize, width: u16, height: u16) {
+        let last = self.state.view_lines().len().saturating_sub(1);
+        self.state.cursor_line = self
+            .state
+            .cursor_line
+            .saturating_add_signed(delta)
+            .min(last);
+        self.scroll_to_cursor(width, height);
+    }
+
+    /// Scrolls just far enough to show all of the focused line, or its first
+    /// row if it wraps onto more rows than there are.
+    fn scroll_to_cursor(&mut self, width: u16, height: u16) {
+        let rows = ui::line_rows(&self.state, width);
+        let Some(&cursor_rows) = rows.get(self.state.cursor_line) else {
+            return;
+        };
+        // The summary sits above the lines rather than scrolling with them
+        let visible = (height as usize).saturating_sub(ui::summary_rows(&self.state, width));
+        let start: usize = rows[..self.state.cursor_line].iter().sum();
+        let end = start + cursor_rows;
+        let scroll = (self.state.scroll_offset as usize)
+            .max(end.saturating_sub(visible))
+            .min(start);
+        self.state.scroll_offset = scroll as u16;
+    }
+
+    /// Moves the focused line onto the rows scrolled to, if it's been
+    /// scrolled out of sight: to the first line shown if it's above them, or
+    /// the last if it's below.
+    fn cursor_into_view(&mut self, width: u16, height: u16) {
+        if !self.state.focuses_lines() {
+            return;
+        }
+        let visible = (height as usize).saturating_sub(ui::summary_rows(&self.state, width));
+        let top = self.state.scroll_offset as usize;
+        let bottom = top + visible;
+
+        let mut start = 0;
+        let mut shown = Vec::new();
+        for (i, rows) in ui::line_rows(&self.state, width).into_iter().enumerate() {
+            if start < bottom && start + rows > top {
+                shown.push(i);
+            }
+            start += rows;
+        }
+        let (Some(&first), Some(&last)) = (shown.first(), shown.last()) else {
+            return;
+        };
+        self.state.cursor_line = self.state.cursor_line.clamp(first, last);
+    }
+
+    /// Keeps the focused line in sight after the mouse wheel scrolls the
+    /// response, but not the error details or a lexicon over it.
+    fn wheel_cursor_into_view(&mut self) {
+        if matches!(
+            self.state.input.mode,
+            InputMode::ViewingResponse | InputMode::SearchResponse
+        ) {
+            self.cursor_into_view(viewport_width(), viewport_height());
+        }
+    }
+
+    fn update_scroll(&mut self, direction: i16, viewport_height: u16) {
+        let content_height = self.get_content_height();
+        let max_scroll = content_height.saturating_sub(viewport_height);
+
+        match direction {
+            1 => {
+                // Scroll down
+                self.state.scroll_offset = (self.state.scroll_offset + 1).min(max_scroll);
+            }
+            -1 => {
+                // Scroll up
+                self.state.scroll_offset = self.state.scroll_offset.saturating_sub(1);
+            }
+            10 => {
+                // Page down
+                self.state.scroll_offset =
+                    (self.state.scroll_offset + viewport_height).min(max_scroll);
+            }
+            -10 => {
+                // Page up
+                self.state.scroll_offset = self.state.scroll_offset.saturating_sub(viewport_height);
+            }
+            _ => {}
+        }
+    }
+
+    async fn run(&mut self) -> AppResult<()> {
+        self.terminal = Some(TerminalHandler::new()?);
+
+        let event_tx = self.event_tx.clone();
+        smol::spawn(async move {
+            loop {
+                if event::poll(Duration::from_millis(100)).unwrap() {
+                    if let Ok(event) = event::read() {
+                        let _ = event_tx.send(AppEvent::Input(event)).await;
+                    }
+                }
+                let _ = event_tx.send(AppEvent::Tick).await;
+            }
+        })
+        .detach();
+
+        if self.state.offline.is_none() {
+            self.describe_server();
+        }
+
+        while !self.state.quit {
+            self.draw()?;
+
+            match self.events.try_recv() {
+                Ok(AppEvent::Input(event)) => {
+                    if let Err(e) = self.handle_input(event).await {
+                        log::warn!("error={:?}", e.to_string());
+                        self.state.error = Some(e.to_string());
+                        self.state.error_time = Some(SystemTime::now());
+                    }
+                }
+                Ok(AppEvent::Tick) => {
+                    self.state.update();
+                    self.auto_refresh_if_due().await;
+                }
+                Ok(AppEvent::ServerInfo(info)) => {
+                    self.state.server_info = Some(info);
+                }
+                Ok(AppEvent::UnreadCount(count)) => {
+                    self.state.unread_count = Some(count);
+                }
+                // A response for a request nobody is waiting on any more
+                Ok(
+                    AppEvent::Response(..)
+                    | AppEvent::Progress(..)
+                    | AppEvent::Retrying { .. }
+                    | AppEvent::OAuth(..),
+                ) => {}
+                Err(smol::channel::TryRecvError::Empty) => {
+                    smol::Timer::after(Duration::from_millis(10)).await;
+                }
+                Err(smol::channel::TryRecvError::Closed) => {
+                    break;
+                }
+            }
+        }
+
+        self.shutdown()
+    }
+
+    /// Saves everything kept between sessions on the way out. Called when
+    /// the run loop ends and, as a best effort, after a panic.
+    fn shutdown(&mut self) -> AppResult<()> {
+        if let Err(e) = self.state.save() {
+            log::error!("saving state error={:?}", e.to_string());
+            return Err(AppError::Config {
+                src: "saving history, favorites and collapsed groups".into(),
+                err_span: (0, 0),
+                msg: format!("Failed to save state: {}", e),
+            }
+            .into());
+        }
+        log::info!("oxat exiting");
+        Ok(())
+    }
+
+    /// Asks the PDS to describe itself without blocking input; the result
+    /// arrives as `AppEvent::ServerInfo` and is kept for the session.
+    fn describe_server(&self) {
+        let xrpc = self.xrpc.clone();
+        let tx = self.event_tx.clone();
+
+        smol::spawn(async move {
+            let result = match xrpc.query("com.atproto.server.describeServer", &[]).await {
+                Ok(json) => serde_json::from_value::<ServerInfo>(json).map_err(|e| e.to_string()),
+                Err(AppError::HttpStatus { code, .. }) => Err(format!("server returned {}", code)),
+                Err(e) => Err(e.to_string()),
+            };
+            let _ = tx.send(AppEvent::ServerInfo(result)).await;
+        })
+        .detach();
+    }
+
+    /// Fetches the unread notification count for the status bar in the
+    /// background. Failures are ignored since the count is only a hint.
+    fn fetch_unread_count(&self) {
+        if self.xrpc.access_token.is_none() {
+            return;
+        }
+        let xrpc = self.xrpc.clone();
+        let tx = self.event_tx.clone();
+
+        smol::spawn(async move {
+            let Ok(json) = xrpc.query(GET_UNREAD_COUNT, &[]).await else {
+                return;
+            };
+            if let Some(count) = json["count"].as_u64() {
+                let _ = tx.send(AppEvent::UnreadCount(count)).await;
+            }
+        })
+        .detach();
+    }
+
+    fn draw(&mut self) -> AppResult<()> {
+        let Some(handler) = &mut self.terminal else {
+            return Ok(());
+        };
+
+        handler
+            .terminal
+            .draw(|f| render(&self.state, &self.xrpc.pds_host, f))
+            .map_err(|e| AppError::Terminal {
+                src: "drawing terminal".into(),
+                err_span: (0, 0),
+                msg: e.to_string(),
+            })?;
+        Ok(())
+    }
+
+    /// Sends `req` from a separate task and waits for its response, redrawing
+    /// on each tick so the loading spinner keeps moving. Esc cancels the
+    /// request; other input that arrives in the meantime is dropped.
+    async fn fetch(
+        &mut self,
+        req: surf::RequestBuilder,
+        timeout: Duration,
+    ) -> Result<Fetched, FetchError> {
+        self.fetch_into(req, timeout, None).await
+    }
+
+    /// Like `fetch`, but with a `download` a successful body is handled by
+    /// its type: JSON is read as usual, an image is kept in memory, and
+    /// anything else is streamed to a file. The timeout only covers waiting
+    /// for the response to start, so large downloads aren't cut off. A
+    /// cancelled download's partial file is removed.
+    async fn fetch_into(
+        &mut self,
+        req: surf::RequestBuilder,
+        timeout: Duration,
+        download: Option<Download>,
+    ) -> Result<Fetched, FetchError> {
+        self.request_id += 1;
+        let id = self.request_id;
+        let tx = self.event_tx.clone();
+        // Saved under a temporary name until the whole body has arrived
+        let partial = download
+            .as_ref()
+            .map(|download| download.path.with_extension("part"));
+        let xrpc = self.xrpc.clone();
+        let retries = self.state.retries;
+
+        let task = smol::spawn(async move {
+            let result = async {
+                let (mut res, elapsed) = xrpc
+                    .send_with_retries(req.build(), timeout, retries, |attempt, delay, reason| {
+                        let _ = tx.try_send(AppEvent::Retrying {
+                            id,
+                            attempt,
+                            delay,
+                            reason,
+                        });
+                    })
+                    .await?;
+                let status = res.status();
+                let rate_limit = RateLimit::from_headers(&res);
+                let content_type = res
+                    .header("Content-Type")
+                    .map(|value| value.as_str().to_string());
+                let handling = match &download {
+                    Some(download) if status.is_success() => {
+                        Handling::for_response(download.encoding, content_type.as_deref())
+                    }
+                    _ => Handling::Json,
+                };
+                let mut fetched = Fetched {
+                    status,
+                    body: String::new(),
+                    saved: None,
+                    image: None,
+                    content_type,
+                    elapsed,
+                    rate_limit,
+                };
+                match (handling, download) {
+                    (Handling::Save, Some(download)) => {
+                        let part = download.path.with_extension("part");
+                        let bytes = save_body(&mut res, &part, |bytes| {
+                            let _ = tx.try_send(AppEvent::Progress(id, bytes));
+                        })
+                        .await?;
+                        let content_type = fetched.content_type.as_deref();
+                        let path = download.path.with_extension(content::extension(
+                            content_type.unwrap_or(download.encoding),
+                        ));
+                        smol::fs::rename(&part, &path).await.map_err(|e| {
+                            surf::Error::from_str(
+                                StatusCode::InternalServerError,
+                                format!("Failed to write {}: {}", path.display(), e),
+                            )
+                        })?;
+                        fetched.saved = Some((path, bytes));
+                    }
+                    (Handling::Image, _) => fetched.image = Some(res.body_bytes().await?),
+                    _ => fetched.body = res.body_string().await?,
+                }
+                Ok(fetched)
+            }
+            .await;
+            let _ = tx.send(AppEvent::Response(id, result)).await;
+        });
+
+        self.state.loading = Some(Instant::now());
+        let result = loop {
+            // Drawing problems will surface again from the main loop
+            let _ = self.draw();
+
+            match self.events.recv().await {
+                Ok(AppEvent::Response(response_id, result)) if response_id == id => {
+                    break result.map_err(FetchError::Http)
+                }
+                Ok(AppEvent::Progress(progress_id, bytes)) if progress_id == id => {
+                    self.state.downloaded = Some(bytes);
+                }
+                Ok(AppEvent::Retrying {
+                    id: retry_id,
+                    attempt,
+                    delay,
+                    reason,
+                }) if retry_id == id => {
+                    self.state.error = Some(format!(
+                        "{}; retrying ({}/{}) in {:.1}s",
+                        reason,
+                        attempt,
+                        self.state.retries,
+                        delay.as_secs_f32()
+                    ));
+                    self.state.error_time = Some(SystemTime::now());
+                }
+                Ok(AppEvent::Tick) => self.state.update(),
+                Ok(AppEvent::ServerInfo(info)) => self.state.server_info = Some(info),
+                Ok(AppEvent::UnreadCount(count)) => self.state.unread_count = Some(count),
+                Ok(AppEvent::Input(CEvent::Key(key)))
+                    if key.code == KeyCode::Esc && key.kind == KeyEventKind::Press =>
+                {
+                    break Err(FetchError::Cancelled);
+                }
+                Ok(AppEvent::Input(CEvent::Key(key)))
+                    if key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                {
+                    if self.state.confirm_quit() {
+                        self.state.quit = true;
+                        break Err(FetchError::Cancelled);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    break Err(FetchError::Http(surf::Error::from_str(
+                        StatusCode::InternalServerError,
+                        e,
+                    )))
+                }
+            }
+        };
+        self.state.loading = None;
+        self.state.downloaded = None;
+        if let Some(rate_limit) = result.as_ref().ok().and_then(|res| res.rate_limit) {
+            self.state.rate_limit = Some(rate_limit);
+        }
+
+        // Dropping the task aborts the request if it's still running, and the
+        // id check above ignores anything it already sent
+        drop(task);
+        if let (Err(FetchError::Cancelled), Some(path)) = (&result, partial) {
+            let _ = fs::remove_file(path);
+        }
+        result
+    }
+
+    /// Whether `err` is the PDS turning down an expired access token, which
+    /// refreshing the session can fix.
+    fn session_expired(&self, err: &miette::Report) -> bool {
+        let Some(e @ AppError::HttpStatus { code, .. }) = err.downcast_ref::<AppError>() else {
+            return false;
+        };
+        self.xrpc.refresh_token.is_some()
+            && (*code == 401 || e.xrpc_error().as_deref() == Some("ExpiredToken"))
+    }
+
+    async fn refresh_session(&mut self) -> AppResult<()> {
+        match self.xrpc.refresh().await {
+            Ok(session) => {
+                self.start_session(session);
+                Ok(())
+            }
+            Err(e @ AppError::HttpStatus { .. }) => {
+                // The PDS refused, so the session is over
+                self.state.is_authenticated = false;
+                self.state.token_expires = None;
+                if let Err(e) = state::save_session(self.state.profile.as_deref(), None) {
+                    log::error!("removing session error={:?}", e.to_string());
+                }
+                Err(e.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Streams a response body into a new file at `path`, calling `progress`
+/// with the running total. Returns the number of bytes written.
+async fn save_body(
+    res: &mut surf::Response,
+    path: &Path,
+    mut progress: impl FnMut(u64),
+) -> surf::Result<u64> {
+    let write_error = |e: std::io::Error| {
+        surf::Error::from_str(
+            StatusCode::InternalServerError,
+            format!("Failed to write {}: {}", path.display(), e),
+        )
+    };
+
+    let mut file = smol::fs::File::create(path).await.map_err(write_error)?;
+    let mut buf = vec![0; 64 * 1024];
+    let mut total = 0;
+    loop {
+        let read = res.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read]).await.map_err(write_error)?;
+        total += read as u64;
+        progress(total);
+    }
+    file.flush().await.map_err(write_error)?;
+    Ok(total)
+}
+
+/// Runs a startup step on a separate thread so a hung component (e.g. a
+/// clipboard daemon that never answers) can't block launch forever.
+fn setup_with_timeout<T, F>(component: &'static str, setup: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> std::result::Result<T, String> + Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(setup());
+    });
+
+    let msg = match rx.recv_timeout(SETUP_TIMEOUT) {
+        Ok(Ok(value)) => return Ok(value),
+        Ok(Err(e)) => format!("{} setup failed: {}", component, e),
+        Err(_) => format!(
+            "{} setup timed out after {}s",
+            component,
+            SETUP_TIMEOUT.as_secs()
+        ),
+    };
+
+    Err(AppError::Startup {
+        src: component.into(),
+        err_span: (0, component.len()),
+        msg,
+    }
+    .into())
+}
+
+/// Headers from the config with valid names, lowercased so they can be
+/// looked up and replaced regardless of case.
+fn custom_headers(headers: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| is_header_name(name))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+        .collect()
+}
+
+/// The XRPC error name of a failed request, if the server sent one.
+fn xrpc_error(report: &miette::Report) -> Option<String> {
+    report.downcast_ref::<AppError>()?.xrpc_error()
+}
+
+/// Width of the response viewer, inside its borders.
+fn viewport_width() -> u16 {
+    if let Ok((cols, _)) = crossterm::terminal::size() {
+        cols.saturating_sub(2)
+    } else {
+        0
+    }
+}
+
+/// Height of the response viewer, derived from the terminal size.
+fn viewport_height() -> u16 {
+    if let Ok((_, rows)) = crossterm::terminal::size() {
+        // Subtract 10 for the header (3), status (3), and help (1) areas, plus
+        // the response block's borders (2) and request summary line (1)
+        rows.saturating_sub(10)
+    } else {
+        0
+    }
+}
+
+fn main() -> AppResult<()> {
+    #[cfg(debug_assertions)]
+    std::env::set_var("RUST_BACKTRACE", "1");
+
+    let args = Args::parse()?;
+
+    // A broken config shouldn't stop the app from starting
+    let mut warnings = Vec::new();
+    let config = Config::load().unwrap_or_else(|e| {
+        warnings.push(format!("{} (using defaults)", e));
+        Config::default()
+    });
+    // Everything below sees the profile's settings in place of the top-level ones
+    let config = match &args.profile {
+        Some(name) => config.with_profile(name)?,
+        None => config,
+    };
+    for name in config.headers.keys().chain(
+        config
+            .method_headers
+            .values()
+            .flat_map(|headers| headers.keys()),
+    ) {
+        if !is_header_name(name) {
+            warnings.push(format!("Ignoring header '{}' from the config", name));
+        }
+    }
+    if config.danger_accept_invalid_certs {
+        warnings.push(
+            "TLS certificates aren't being checked (danger_accept_invalid_certs); \
+             anyone on the network can read and change your requests"
+                .to_string(),
+        );
+    }
+    if Theme::by_name(&config.theme).is_none() {
+        warnings.push(format!(
+            "Unknown theme '{}' (expected one of {}), using the default",
+            config.theme,
+            THEME_NAMES.join(", ")
+        ));
+    }
+
+    if let Some(path) = args.log.as_ref().or(config.log_file.as_ref()) {
+        if let Err(e) = logging::init(path) {
+            warnings.push(format!("Can't write the log to {}: {}", path.display(), e));
+        }
+    }
+
+    let (lexicons, lexicon_warnings) = config
+        .lexicon_dir()
+        .map(|dir| lexicon::load_dir(&dir))
+        .unwrap_or_default();
+    commands::init(lexicons);
+    warnings.extend(lexicon_warnings);
+
+    if let Some(exec_args) = args.exec {
+        for warning in &warnings {
+            eprintln!("{}", warning);
+        }
+        let mut app = App::new(&config, args.profile.as_deref())?;
+        app.state.offline = args.offline;
+        if app.state.offline.is_none() {
+            app.check_proxy()?;
+        }
+        let code = smol::block_on(exec::run(app, exec_args));
+        std::process::exit(code);
+    }
+
+    let result = smol::block_on(async {
+        let mut app = App::new(&config, args.profile.as_deref())?;
+        if args.offline.is_none() {
+            app.check_proxy()?;
+        }
+        app.init_clipboard(&config);
+        if let Some(dir) = args.offline {
+            // Nothing to log in to, so go straight to the commands
+            app.state.offline = Some(dir);
+            app.state.input.mode = InputMode::Command;
+        } else if app.resume_session().await {
+            app.finish_login();
+        }
+        if !warnings.is_empty() {
+            app.state.error = Some(warnings.join("; "));
+            app.state.error_time = Some(SystemTime::now());
+        }
+
+        let app_result = std::panic::AssertUnwindSafe(app.run()).catch_unwind().await;
+
+        match app_result {
+            Ok(res) => res,
+            Err(err) => {
+                let panic_msg = if let Some(s) = err.downcast_ref::<&str>() {
+                    s.to_string()
+                } else if let Some(s) = err.downcast_ref::<String>() {
+                    s.clone()
+                } else {
+                    "Unknown panic occurred".to_string()
+                };
+                // The state may be half-updated, so saving it can fail or
+                // panic too; either way the original panic is what's reported
+                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| app.shutdown()));
+
+                Err(AppError::Terminal {
+                    src: "panic".into(),
+                    err_span: (0, 0),
+                    msg: panic_msg,
+                }
+                .into())
+            }
+        }
+    });
+
+    crossterm::terminal::disable_raw_mode().ok();
+    std::io::stdout().execute(LeaveAlternateScreen).ok();
+    std::io::stdout().execute(DisableMouseCapture).ok();
+
+    if let Err(e) = &result {
+        println!("Application error: {}", e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, MockServer, Reply};
+    use crossterm::event::{KeyEvent, KeyModifiers};
+    use serde_json::json;
+    use std::thread;
+
+    fn press(app: &mut App, code: KeyCode) {
+        smol::block_on(app.handle_input(CEvent::Key(KeyEvent::new(code, KeyModifiers::NONE))))
+            .unwrap();
+    }
+
+    fn press_ctrl(app: &mut App, c: char) {
+        let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        smol::block_on(app.handle_input(CEvent::Key(key))).unwrap();
+    }
+
+    fn show_message(app: &mut App) {
+        app.state.error = Some("Copied URL".into());
+        app.state.error_time = Some(SystemTime::now());
+    }
+
+    #[test]
+    fn esc_dismisses_the_message_and_closes_the_builder_at_once() {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.state.input.mode = InputMode::CommandBuilder {
+            command: "app.bsky.actor.getProfile".into(),
+            current_param: 0,
+            params: Vec::new(),
+        };
+        show_message(&mut app);
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.state.error, None);
+        assert_eq!(app.state.input.mode, InputMode::Command);
+    }
+
+    #[test]
+    fn esc_dismisses_the_message_and_closes_prompts_at_once() {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        for mode in [InputMode::RawXrpc, InputMode::Headers] {
+            app.state.input.mode = mode;
+            app.state.input.content = "typed".into();
+            show_message(&mut app);
+
+            press(&mut app, KeyCode::Esc);
+            assert_eq!(app.state.error, None);
+            assert_eq!(app.state.input.mode, InputMode::Command);
+            assert!(app.state.input.content.is_empty());
+        }
+    }
+
+    #[test]
+    fn esc_only_dismisses_the_message_where_it_means_nothing_else() {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.state.input.mode = InputMode::ViewingResponse;
+        show_message(&mut app);
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.state.error, None);
+        assert_eq!(app.state.input.mode, InputMode::ViewingResponse);
+    }
+
+    /// A PDS that takes five seconds to answer anything.
+    fn slow_server() -> MockServer {
+        MockServer::start(|_| {
+            thread::sleep(Duration::from_secs(5));
+            Reply::json(200, json!({ "late": true }))
+        })
+    }
+
+    /// Presses Esc from another thread after `delay`, while the app waits.
+    fn press_esc_after(app: &App, delay: Duration) {
+        let tx = app.event_tx.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+            let _ = smol::block_on(tx.send(AppEvent::Input(CEvent::Key(esc))));
+        });
+    }
+
+    #[test]
+    fn esc_cancels_a_request_in_flight() {
+        let server = slow_server();
+        let mut app = test_support::app(&server.url);
+        press_esc_after(&app, Duration::from_millis(200));
+
+        let started = Instant::now();
+        let req = app.xrpc.get(&app.xrpc.url("app.bsky.actor.getProfile"));
+        let result = smol::block_on(app.fetch(req, Duration::from_secs(30)));
+
+        assert!(matches!(result, Err(FetchError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(app.state.loading.is_none());
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].method, "GET");
+        assert_eq!(received[0].path, "/xrpc/app.bsky.actor.getProfile");
+    }
+
+    #[test]
+    fn cancelling_keeps_the_previous_response() {
+        let server = slow_server();
+        let mut app = test_support::app(&server.url);
+        let earlier = json!({ "did": "did:plc:earlier" });
+        app.state.output = Some(earlier.clone());
+        app.state.output_method = Some("app.bsky.actor.getProfile".into());
+        press_esc_after(&app, Duration::from_millis(200));
+
+        let result = smol::block_on(
+            app.execute_command("app.bsky.actor.getProfile", &["bsky.app".to_string()]),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(app.state.output, Some(earlier));
+        assert_eq!(app.state.error.as_deref(), Some("Request cancelled"));
+        // Not a failure worth showing details for
+        assert!(app.state.last_error.is_none());
+        let hist = app.state.request_history.front().unwrap();
+        assert!(!hist.success);
+    }
+
+    /// A PDS for signing in as `test.bsky.social` with the password
+    /// `hunter2`, answering `getProfile` with `profile` for a valid token.
+    /// Tokens named `expired` are turned down as a PDS does once they run
+    /// out, and `refresh-1` can be swapped for a fresh session once.
+    fn pds() -> MockServer {
+        MockServer::start(|req| {
+            let auth = req.header("Authorization").unwrap_or_default().to_string();
+            match req.xrpc_method() {
+                "com.atproto.server.createSession" if req.json()["password"] == "hunter2" => {
+                    Reply::json(200, test_support::session("expired", "refresh-1"))
+                }
+                "com.atproto.server.createSession" => Reply::json(
+                    401,
+                    json!({ "error": "AuthenticationRequired", "message": "Invalid identifier or password" }),
+                ),
+                "com.atproto.server.refreshSession" if auth == "Bearer refresh-1" => {
+                    Reply::json(200, test_support::session("fresh", "refresh-2"))
+                }
+                "com.atproto.server.refreshSession" => Reply::json(
+                    400,
+                    json!({ "error": "ExpiredToken", "message": "Token has been revoked" }),
+                ),
+                _ if auth == format!("Bearer {}", test_support::jwt("expired")) => Reply::json(
+                    400,
+                    json!({ "error": "ExpiredToken", "message": "Token has expired" }),
+                ),
+                "app.bsky.actor.getProfile" if req.path.ends_with("actor=nobody.test") => {
+                    Reply::json(
+                        400,
+                        json!({ "error": "InvalidRequest", "message": "Profile not found" }),
+                    )
+                }
+                "app.bsky.actor.getProfile" => Reply::json(200, profile()),
+                _ => Reply::json(404, json!({ "error": "MethodNotImplemented" })),
+            }
+        })
+    }
+
+    fn profile() -> serde_json::Value {
+        json!({ "did": "did:plc:z72i7hdynmk6r22z27h6tvur", "handle": "bsky.app" })
+    }
+
+    fn log_in(app: &mut App, password: &str) -> AppResult<()> {
+        smol::block_on(app.handle_auth("test.bsky.social".into(), password.into(), None))
+    }
+
+    fn get_profile(app: &mut App, actor: &str) -> AppResult<()> {
+        smol::block_on(app.execute_command("app.bsky.actor.getProfile", &[actor.to_string()]))
+    }
+
+    #[test]
+    fn logging_in_starts_a_session() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+
+        log_in(&mut app, "hunter2").unwrap();
+
+        assert!(app.state.is_authenticated);
+        assert_eq!(app.state.did.as_deref(), Some("did:plc:test"));
+        assert_eq!(app.state.handle.as_deref(), Some("test.bsky.social"));
+        assert!(app.state.token_expires.is_some());
+        assert_eq!(app.xrpc.refresh_token.as_deref(), Some("refresh-1"));
+        let login = &server.received()[0];
+        assert_eq!(login.method, "POST");
+        assert_eq!(
+            login.json(),
+            json!({ "identifier": "test.bsky.social", "password": "hunter2" })
+        );
+    }
+
+    #[test]
+    fn a_failed_login_shows_the_status_and_body() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+
+        assert!(log_in(&mut app, "wrong").is_err());
+
+        assert!(!app.state.is_authenticated);
+        assert!(app.xrpc.access_token.is_none());
+        let error = app.state.error.as_deref().unwrap();
+        assert!(error.starts_with("Auth failed (401)"), "{}", error);
+        assert!(
+            error.contains("Invalid identifier or password"),
+            "{}",
+            error
+        );
+        let detail = app.state.last_error.as_ref().unwrap();
+        assert_eq!(detail.status, Some(401));
+        assert!(detail
+            .url
+            .as_deref()
+            .unwrap()
+            .ends_with("/xrpc/com.atproto.server.createSession"));
+    }
+
+    #[test]
+    fn a_command_shows_its_json_response() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+
+        get_profile(&mut app, "bsky.app").unwrap();
+
+        assert_eq!(app.state.output, Some(profile()));
+        assert_eq!(
+            app.state.output_method.as_deref(),
+            Some("app.bsky.actor.getProfile")
+        );
+        assert_eq!(app.state.response_info.as_ref().unwrap().status, 200);
+        assert_eq!(app.state.error, None);
+        let sent = &server.received()[0];
+        assert_eq!(sent.path, "/xrpc/app.bsky.actor.getProfile?actor=bsky.app");
+        assert_eq!(sent.header("Authorization"), None);
+    }
+
+    #[test]
+    fn sessions_are_saved_for_their_profile_and_resumed() {
+        let dir = test_support::own_config_dir("sessions");
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+        app.state.profile = Some("work".into());
+        log_in(&mut app, "hunter2").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("sessions.json"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let mut work = test_support::app(&server.url);
+        work.state.profile = Some("work".into());
+        assert!(smol::block_on(work.resume_session()));
+        assert!(work.state.is_authenticated);
+        assert_eq!(work.state.handle.as_deref(), Some("test.bsky.social"));
+        assert_eq!(work.xrpc.refresh_token.as_deref(), Some("refresh-1"));
+        // Refreshed tokens replace the saved ones
+        get_profile(&mut work, "bsky.app").unwrap();
+        let saved = state::load_session(Some("work")).unwrap();
+        assert_eq!(saved.session.refresh_jwt, "refresh-2");
+
+        // Other profiles, and launching without one, have sessions of their own
+        for profile in [None, Some("alt")] {
+            let mut other = test_support::app(&server.url);
+            other.state.profile = profile.map(str::to_string);
+            assert!(!smol::block_on(other.resume_session()));
+            assert!(!other.state.is_authenticated);
+        }
+
+        // The tokens aren't sent to a different PDS
+        let mut elsewhere = test_support::app("http://127.0.0.1:9");
+        elsewhere.state.profile = Some("work".into());
+        assert!(!smol::block_on(elsewhere.resume_session()));
+        assert_eq!(elsewhere.xrpc.access_token, None);
+    }
+
+    #[test]
+    fn an_expired_saved_session_is_refreshed_or_forgotten() {
+        test_support::own_config_dir("expired-sessions");
+        let server = pds();
+        let save = |refresh: &str| {
+            let session = Session {
+                access_jwt: test_support::jwt_expiring("old", 1),
+                refresh_jwt: refresh.into(),
+                did: "did:plc:test".into(),
+                handle: "test.bsky.social".into(),
+            };
+            let saved = SavedSession {
+                pds_host: server.url.clone(),
+                session,
+            };
+            state::save_session(None, Some(saved)).unwrap();
+        };
+
+        save("refresh-1");
+        let mut app = test_support::app(&server.url);
+        assert!(smol::block_on(app.resume_session()));
+        assert_eq!(
+            app.xrpc.access_token.as_deref(),
+            Some(test_support::jwt("fresh").as_str())
+        );
+
+        // A refresh the PDS turns down ends the saved session too
+        save("revoked");
+        let mut app = test_support::app(&server.url);
+        assert!(!smol::block_on(app.resume_session()));
+        assert!(!app.state.is_authenticated);
+        assert_eq!(app.xrpc.access_token, None);
+        assert!(state::load_session(None).is_none());
+    }
+
+    #[test]
+    fn an_error_response_is_kept_for_the_detail_view() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+        app.state.output = Some(profile());
+
+        let err = get_profile(&mut app, "nobody.test").unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("HTTP 400 Bad Request"),
+            "{}",
+            err
+        );
+        assert!(app
+            .state
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("Profile not found"));
+        let detail = app.state.last_error.as_ref().unwrap();
+        assert_eq!(detail.status, Some(400));
+        assert!(detail.body.as_deref().unwrap().contains("InvalidRequest"));
+        // The last good response stays up
+        assert_eq!(app.state.output, Some(profile()));
+    }
+
+    #[test]
+    fn an_expired_session_is_refreshed_and_the_command_sent_again() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+        log_in(&mut app, "hunter2").unwrap();
+
+        get_profile(&mut app, "bsky.app").unwrap();
+
+        assert_eq!(app.state.output, Some(profile()));
+        assert_eq!(app.state.error, None);
+        assert_eq!(app.xrpc.refresh_token.as_deref(), Some("refresh-2"));
+        let received = server.received();
+        let calls: Vec<_> = received.iter().map(|req| req.xrpc_method()).collect();
+        assert_eq!(
+            calls,
+            [
+                "com.atproto.server.createSession",
+                "app.bsky.actor.getProfile",
+                "com.atproto.server.refreshSession",
+                "app.bsky.actor.getProfile",
+            ]
+        );
+        assert_eq!(
+            received[3].header("Authorization"),
+            Some(format!("Bearer {}", test_support::jwt("fresh")).as_str())
+        );
+        let hist = app.state.request_history.front().unwrap();
+        assert!(hist.success);
+        assert_eq!(hist.status, Some(200));
+    }
+
+    #[test]
+    fn a_refused_refresh_ends_the_session() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+        log_in(&mut app, "hunter2").unwrap();
+        app.xrpc.refresh_token = Some("revoked".into());
+
+        assert!(get_profile(&mut app, "bsky.app").is_err());
+
+        assert!(!app.state.is_authenticated);
+        assert!(app.xrpc.access_token.is_none());
+        // Tried once, not over and over
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[test]
+    fn commands_are_recorded_in_history_newest_first() {
+        let server = pds();
+        let mut app = test_support::app(&server.url);
+        app.state.request_history.clear();
+
+        get_profile(&mut app, "bsky.app").unwrap();
+        let _ = get_profile(&mut app, "nobody.test");
+
+        let history: Vec<_> = app.state.request_history.iter().collect();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].params, ["nobody.test"]);
+        assert_eq!(history[0].status, Some(400));
+        assert!(!history[0].success);
+        assert_eq!(history[1].method, "app.bsky.actor.getProfile");
+        assert_eq!(
+            history[1].url,
+            format!(
+                "{}/xrpc/app.bsky.actor.getProfile?actor=bsky.app",
+                server.url
+            )
+        );
+        assert_eq!(history[1].status, Some(200));
+        assert!(history[1].success);
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn destructive_commands_arent_sent_until_yes_is_typed() {
+        let server = MockServer::start(|_| Reply::json(200, json!({})));
+        let mut app = test_support::app(&server.url);
+        let params = vec!["at://did:plc:test/app.bsky.feed.post/3k44".to_string()];
+        let confirming = InputMode::ConfirmDestructive {
+            command: DELETE_RECORD.into(),
+            params: params.clone(),
+        };
+
+        smol::block_on(app.submit_command(DELETE_RECORD.into(), params.clone())).unwrap();
+        assert_eq!(app.state.input.mode, confirming);
+
+        type_text(&mut app, "y");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.state.input.mode, confirming);
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("Type yes to confirm, or Esc to go back")
+        );
+
+        // Backing out returns to the builder with the URI still filled in
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(
+            app.state.input.mode,
+            InputMode::CommandBuilder { .. }
+        ));
+        assert_eq!(app.state.input.content, params[0]);
+        assert!(server.received().is_empty());
+
+        smol::block_on(app.submit_command(DELETE_RECORD.into(), params.clone())).unwrap();
+        type_text(&mut app, "yes");
+        press(&mut app, KeyCode::Enter);
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].xrpc_method(), DELETE_RECORD);
+        assert_eq!(
+            received[0].json(),
+            json!({ "repo": "did:plc:test", "collection": "app.bsky.feed.post", "rkey": "3k44" })
+        );
+        assert_eq!(app.state.input.mode, InputMode::ViewingResponse);
+    }
+
+    #[test]
+    fn posts_arent_re_run() {
+        let server =
+            MockServer::start(|_| Reply::json(200, json!({ "uri": "at://x", "cid": "y" })));
+        let mut app = test_support::app(&server.url);
+        app.state.did = Some("did:plc:test".into());
+        app.state.is_authenticated = true;
+
+        smol::block_on(app.execute_command(POST, &["hello".to_string()])).unwrap();
+        assert_eq!(server.received().len(), 1);
+
+        app.state.input.mode = InputMode::Command;
+        press(&mut app, KeyCode::F(5));
+        app.state.input.mode = InputMode::ViewingResponse;
+        press(&mut app, KeyCode::Char('r'));
+
+        assert_eq!(server.received().len(), 1);
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("The last request changed data; send it again from history (h)")
+        );
+    }
+
+    #[test]
+    fn plain_letters_type_into_the_command_filter() {
+        let server = MockServer::start(|_| Reply::json(200, json!({})));
+        let mut app = test_support::app(&server.url);
+        let params = ["bsky.app".to_string()];
+        smol::block_on(app.execute_command(commands::GET_PROFILE, &params)).unwrap();
+        app.state.input.mode = InputMode::Command;
+
+        let aliases = [
+            "records",
+            "reposts",
+            "feed",
+            "followers",
+            "follows",
+            "delete",
+            "diff",
+            "server",
+        ];
+        for alias in aliases {
+            app.state.input.content.clear();
+            app.state.input.cursor_position = 0;
+            type_text(&mut app, alias);
+            assert_eq!(app.state.input.content, alias);
+            assert_eq!(app.state.input.mode, InputMode::Command);
+        }
+        assert_eq!(server.received().len(), 1);
+        assert!(app.state.favorites.is_empty());
+    }
+
+    #[test]
+    fn ctrl_f_favorites_the_selected_command_while_filtering() {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.state.input.mode = InputMode::Command;
+        type_text(&mut app, "timeline");
+        let selected = app.state.selected_command().unwrap().method;
+
+        press_ctrl(&mut app, 'f');
+        assert!(app.state.favorites.contains(selected));
+        assert_eq!(app.state.input.content, "timeline");
+
+        press_ctrl(&mut app, 'f');
+        assert!(!app.state.favorites.contains(selected));
+    }
+
+    #[test]
+    fn ctrl_s_opens_the_settings() {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.state.input.mode = InputMode::Command;
+        type_text(&mut app, "ser");
+
+        press_ctrl(&mut app, 's');
+        assert_eq!(
+            app.state.input.mode,
+            InputMode::Settings {
+                selected: 0,
+                editing: false
+            }
+        );
+    }
+
+    #[test]
+    fn f2_describes_the_selected_command_while_filtering() {
+        let server = MockServer::start(|req| {
+            Reply::json(
+                200,
+                json!({ "uri": "at://lexicons", "schema": { "lexicon": 1, "id": req.path } }),
+            )
+        });
+        let mut app = test_support::app(&server.url);
+        app.state.input.mode = InputMode::Command;
+        type_text(&mut app, "timeline");
+        let selected = app.state.selected_command().unwrap().method;
+
+        press(&mut app, KeyCode::F(2));
+        assert!(matches!(
+            &app.state.input.mode,
+            InputMode::Lexicon { method, .. } if method == selected
+        ));
+        assert_eq!(
+            server.received()[0].path,
+            format!("/xrpc/com.atproto.lexicon.resolveLexicon?nsid={}", selected)
+        );
+    }
+
+    #[test]
+    fn other_commands_are_sent_straight_away() {
+        let server = MockServer::start(|_| Reply::json(200, json!({ "feed": [] })));
+        let mut app = test_support::app(&server.url);
+
+        smol::block_on(app.submit_command("app.bsky.feed.getTimeline".into(), Vec::new())).unwrap();
+
+        assert_eq!(server.received().len(), 1);
+        assert_eq!(app.state.input.mode, InputMode::ViewingResponse);
+    }
+
+    fn url_for(app: &mut App, method: &str, params: &[&str]) -> String {
+        let cmd = commands::all().iter().find(|c| c.method == method).unwrap();
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        let (url, _) = smol::block_on(app.build_url(cmd, &params)).unwrap();
+        url
+    }
+
+    #[test]
+    fn list_params_repeat_their_name() {
+        let server = MockServer::start(|_| Reply::json(200, json!({ "profiles": [] })));
+        let mut app = test_support::app(&server.url);
+
+        smol::block_on(app.execute_command(
+            "app.bsky.actor.getProfiles",
+            &["bsky.app, did:plc:z72i7hdynmk6r22z27h6tvur".to_string()],
+        ))
+        .unwrap();
+
+        assert_eq!(
+            server.received()[0].path,
+            "/xrpc/app.bsky.actor.getProfiles?actors=bsky.app&actors=did:plc:z72i7hdynmk6r22z27h6tvur"
+        );
+    }
+
+    #[test]
+    fn built_urls_encode_values_and_skip_empty_optional_ones() {
+        let mut app = test_support::app("https://pds.example.com");
+        let url = url_for(
+            &mut app,
+            "app.bsky.feed.getAuthorFeed",
+            &["bsky.app", "", "2026-10-16T00:00:00.000Z+abc=="],
+        );
+        assert_eq!(
+            url,
+            "https://pds.example.com/xrpc/app.bsky.feed.getAuthorFeed?actor=bsky.app&cursor=2026-10-16T00:00:00.000Z%2Babc%3D%3D"
+        );
+    }
+
+    #[test]
+    fn resolved_handles_are_encoded_like_other_values() {
+        let server = MockServer::start(|_| Reply::json(200, json!({ "did": "did:plc:test" })));
+        let mut app = test_support::app(&server.url);
+
+        let did = smol::block_on(app.resolve_handle("@odd handle&x=1")).unwrap();
+        assert_eq!(did, "did:plc:test");
+        assert_eq!(
+            server.received()[0].path,
+            "/xrpc/com.atproto.identity.resolveHandle?handle=odd%20handle%26x%3D1"
+        );
+    }
+
+    /// A PDS for an account with 2FA, whose emailed code is `ABCDE-12345`.
+    fn two_factor_pds() -> MockServer {
+        MockServer::start(|req| {
+            if req.xrpc_method() != "com.atproto.server.createSession" {
+                return Reply::json(404, json!({ "error": "MethodNotImplemented" }));
+            }
+            match req.json()["authFactorToken"].as_str() {
+                None => Reply::json(
+                    401,
+                    json!({ "error": AUTH_FACTOR_REQUIRED, "message": "A sign in code has been sent to your email address" }),
+                ),
+                Some("ABCDE-12345") => Reply::json(200, test_support::session("access", "refresh")),
+                Some(_) => Reply::json(
+                    400,
+                    json!({ "error": INVALID_TOKEN, "message": "Token is invalid" }),
+                ),
+            }
+        })
+    }
+
+    fn logins(server: &MockServer) -> Vec<serde_json::Value> {
+        server
+            .received()
+            .iter()
+            .filter(|req| req.xrpc_method() == "com.atproto.server.createSession")
+            .map(|req| req.json())
+            .collect()
+    }
+
+    #[test]
+    fn two_factor_login_asks_for_the_code_and_retries_a_wrong_one() {
+        let server = two_factor_pds();
+        let mut app = test_support::app(&server.url);
+        app.state.input.mode = InputMode::Normal;
+
+        type_text(&mut app, "test.bsky.social");
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "hunter2");
+        press(&mut app, KeyCode::Enter);
+
+        let asking = InputMode::AuthFactor {
+            identifier: "test.bsky.social".into(),
+            password: "hunter2".into(),
+        };
+        assert_eq!(app.state.input.mode, asking);
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("Enter the sign-in code sent to your email")
+        );
+        assert!(!app.state.is_authenticated);
+
+        type_text(&mut app, "WRONG-00000");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.state.input.mode, asking);
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("That sign-in code didn't work, try again")
+        );
+        assert!(app.state.input.content.is_empty());
+
+        type_text(&mut app, " ABCDE-12345 ");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.state.is_authenticated);
+        assert_eq!(app.state.input.mode, InputMode::Command);
+        assert_eq!(app.state.handle.as_deref(), Some("test.bsky.social"));
+
+        let tokens: Vec<_> = logins(&server)
+            .iter()
+            .map(|body| body["authFactorToken"].as_str().map(str::to_string))
+            .collect();
+        assert_eq!(
+            tokens,
+            [None, Some("WRONG-00000".into()), Some("ABCDE-12345".into())]
+        );
+    }
+
+    #[test]
+    fn esc_gives_up_on_the_code() {
+        let server = two_factor_pds();
+        let mut app = test_support::app(&server.url);
+        app.state.input.mode = InputMode::AuthFactor {
+            identifier: "test.bsky.social".into(),
+            password: "hunter2".into(),
+        };
+        type_text(&mut app, "ABC");
+
+        press(&mut app, KeyCode::Esc);
+
+        assert_eq!(app.state.input.mode, InputMode::Normal);
+        assert!(app.state.input.content.is_empty());
+        assert!(logins(&server).is_empty());
+    }
+
+    #[test]
+    fn shutdown_saves_history_favorites_and_collapsed_groups() {
+        let dir = test_support::own_config_dir("shutdown");
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.add_to_history(
+            "app.bsky.actor.getProfile",
+            "http://127.0.0.1:9/xrpc/app.bsky.actor.getProfile?actor=bsky.app".into(),
+            vec!["bsky.app".into()],
+        );
+        app.state
+            .favorites
+            .insert("app.bsky.feed.getTimeline".into());
+        app.state.collapsed_groups.insert("com.atproto".into());
+
+        app.shutdown().unwrap();
+
+        for file in ["history.json", "favorites.json", "collapsed_groups.json"] {
+            assert!(dir.join(file).exists(), "{} wasn't written", file);
+        }
+        let history = state::load_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].method, "app.bsky.actor.getProfile");
+        assert_eq!(history[0].params, ["bsky.app"]);
+        assert_eq!(state::load_favorites(), app.state.favorites);
+        assert_eq!(state::load_collapsed_groups(), app.state.collapsed_groups);
+    }
+
+    #[test]
+    fn procedures_in_history_are_copied_as_posts() {
+        let server = MockServer::start(|_| Reply::json(200, json!({})));
+        let mut app = test_support::app(&server.url);
+
+        let uri = "at://did:plc:test/app.bsky.feed.post/3k";
+        smol::block_on(app.delete_record(DELETE_RECORD, &[uri.to_string()])).unwrap();
+        let hist = app.state.request_history[0].clone();
+        assert_eq!(
+            app.curl_for(&hist),
+            format!(
+                "curl -X POST '{}/xrpc/com.atproto.repo.deleteRecord' \
+                 -H 'Accept: application/json' -H 'Content-Type: application/json' \
+                 --data '{}'",
+                server.url,
+                server.received()[0].body
+            )
+        );
+
+        smol::block_on(app.execute_raw("com.example.doThing", r#"{"a":1}"#)).unwrap();
+        let hist = app.state.request_history[0].clone();
+        assert_eq!(hist.body, Some(json!({ "a": 1 })));
+        assert!(app.curl_for(&hist).ends_with(r#" --data '{"a":1}'"#));
+
+        smol::block_on(app.execute_raw("com.example.getThing", "a=1")).unwrap();
+        let hist = app.state.request_history[0].clone();
+        assert_eq!(hist.body, None);
+        assert!(app.curl_for(&hist).starts_with("curl -X GET "));
+    }
+
+    #[test]
+    fn raw_queries_are_encoded_pair_by_pair() {
+        let server = MockServer::start(|_| Reply::json(200, json!({})));
+        let mut app = test_support::app(&server.url);
+
+        let args = "?q=c++ #tips&uris=at://did:plc:test/app.bsky.feed.post/3k&uris=x&flag";
+        smol::block_on(app.execute_raw("com.example.getThing", args)).unwrap();
+
+        let path = "/xrpc/com.example.getThing?q=c%2B%2B%20%23tips\
+                    &uris=at://did:plc:test/app.bsky.feed.post/3k&uris=x&flag=";
+        assert_eq!(server.received()[0].path, path);
+        let hist = &app.state.request_history[0];
+        assert_eq!(hist.url, format!("{}{}", server.url, path));
+        assert_eq!(hist.params, [args]);
+    }
+
+    /// An app signed in with OAuth, whose tokens need a DPoP proof.
+    fn oauth_app() -> App {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.xrpc.access_token = Some(test_support::jwt("oauth"));
+        app.xrpc.oauth = Some(OAuthSession {
+            key: oauth::DpopKey::generate(),
+            token_endpoint: "http://127.0.0.1:9/oauth/token".into(),
+            client_id: "http://localhost".into(),
+        });
+        app.state.is_authenticated = true;
+        app
+    }
+
+    #[test]
+    fn oauth_sessions_arent_copied_as_bearer_tokens() {
+        let mut app = oauth_app();
+        app.add_to_history(
+            "app.bsky.actor.getProfile",
+            "http://127.0.0.1:9/xrpc/app.bsky.actor.getProfile?actor=bsky.app".into(),
+            vec!["bsky.app".into()],
+        );
+
+        app.copy_history_entry(0, true);
+        assert!(app.state.error.as_deref().unwrap().contains("DPoP proof"));
+
+        for _ in 0..2 {
+            app.copy_access_token();
+            assert!(app.state.error.as_deref().unwrap().contains("DPoP proof"));
+        }
+        assert_eq!(app.state.token_copy_requested, None);
+    }
+
+    #[test]
+    fn previews_show_dpop_auth_for_oauth_sessions() {
+        let mut app = oauth_app();
+        app.state.preview_requests = true;
+
+        let params = vec!["did:plc:test".to_string()];
+        smol::block_on(app.send_command("app.bsky.actor.getProfile".into(), params)).unwrap();
+
+        let preview = app.state.preview.as_ref().unwrap();
+        let header = |name: &str| {
+            preview
+                .headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("Authorization"), Some("DPoP $TOKEN"));
+        assert!(header("DPoP").is_some());
+    }
+
+    /// A response whose second line wraps onto several rows of a narrow
+    /// viewer, above an object to fold and a list long enough to scroll.
+    fn wrapping_app() -> App {
+        let mut app = test_support::app("http://127.0.0.1:9");
+        app.state.output = Some(json!({
+            "bio": "a bio long enough to wrap onto several rows of a narrow viewer",
+            "nested": { "a": 1 },
+            "tail": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+        }));
+        app.state.wrap_lines = true;
+        app.state.input.mode = InputMode::ViewingResponse;
+        app
+    }
+
+    #[test]
+    fn space_folds_the_focused_line_below_a_wrapped_one() {
+        let (width, height) = (20, 6);
+        let mut app = wrapping_app();
+        let rows = ui::line_rows(&app.state, width);
+        assert!(rows[1] > 1);
+
+        app.move_cursor(1, width, height);
+        app.move_cursor(1, width, height);
+        assert_eq!(app.state.cursor_line, 2);
+        assert!(app.state.view_lines()[2].text.contains("\"nested\""));
+        // The bio's rows put the line further down than its index
+        let start: usize = rows[..2].iter().sum();
+        let scroll = app.state.scroll_offset as usize;
+        assert!(scroll <= start && start + rows[2] <= scroll + height as usize);
+        assert_ne!(scroll, 2);
+
+        press(&mut app, KeyCode::Char(' '));
+        assert!(app.state.collapsed.contains("nested"));
+        assert_eq!(app.state.cursor_line, 2);
+    }
+
+    #[test]
+    fn p_copies_the_path_to_the_focused_line_not_the_top_one() {
+        let mut app = wrapping_app();
+        app.state.scroll_offset = 3;
+        press(&mut app, KeyCode::Char('p'));
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("The focused line is the whole response")
+        );
+
+        app.state.scroll_offset = 0;
+        app.state.cursor_line = 2;
+        press(&mut app, KeyCode::Char('p'));
+        // There's a path to copy, but no clipboard in tests to copy it to
+        assert_eq!(app.state.error.as_deref(), Some("Clipboard is unavailable"));
+    }
+
+    #[test]
+    fn o_follows_the_uri_on_the_focused_line_and_back_returns_to_it() {
+        let server = MockServer::start(|_| {
+            Reply::json(
+                200,
+                json!({ "uri": "at://did:plc:bob/app.bsky.feed.post/2" }),
+            )
+        });
+        let mut app = test_support::app(&server.url);
+        app.state.output = Some(json!({
+            "posts": [
+                { "uri": "at://did:plc:alice/app.bsky.feed.post/1" },
+                { "uri": "at://did:plc:bob/app.bsky.feed.post/2" },
+            ],
+        }));
+        app.state.input.mode = InputMode::ViewingResponse;
+        let line = app
+            .state
+            .view_lines()
+            .iter()
+            .position(|line| line.text.contains("did:plc:bob"))
+            .unwrap();
+        app.state.cursor_line = line;
+
+        press(&mut app, KeyCode::Char('o'));
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].xrpc_method(), GET_RECORD);
+        assert!(received[0].path.contains("bob") && received[0].path.ends_with("rkey=2"));
+        assert_eq!(app.state.cursor_line, 0);
+
+        press(&mut app, KeyCode::Char('b'));
+        assert_eq!(app.state.cursor_line, line);
+    }
+
+    #[test]
+    fn down_reaches_the_last_screenful() {
+        let (width, height) = (20, 6);
+        let mut app = wrapping_app();
+        let last = app.state.view_lines().len() - 1;
+        let max_scroll = ui::response_height(&app.state, width) - height as usize;
+
+        for _ in 0..=last {
+            app.move_cursor(1, width, height);
+        }
+        assert_eq!(app.state.cursor_line, last);
+        assert_eq!(app.state.scroll_offset as usize, max_scroll);
+
+        // Scrolling back up past the focused line brings it along
+        app.state.scroll_offset = 0;
+        app.cursor_into_view(width, height);
+        let rows = ui::line_rows(&app.state, width);
+        let start: usize = rows[..app.state.cursor_line].iter().sum();
+        assert!(start < height as usize);
+        app.move_cursor(-1, width, height);
+        assert_eq!(app.state.scroll_offset, 0);
+    }
+
+    #[test]
+    fn follower_diffs_have_no_url_to_copy_or_repeat() {
+        let server = MockServer::start(|req| {
+            let followers = if req.path.contains("actor=alice.test") {
+                json!([{ "did": "did:plc:carol", "handle": "carol.test" }])
+            } else {
+                json!([])
+            };
+            Reply::json(200, json!({ "followers": followers }))
+        });
+        let mut app = test_support::app(&server.url);
+
+        let params = ["alice.test".to_string(), "bob.test".to_string()];
+        smol::block_on(app.execute_command(DIFF_FOLLOWERS, &params)).unwrap();
+        assert_eq!(app.state.output.as_ref().unwrap()["onlyFollowA"], 1);
+
+        let hist = &app.state.request_history[0];
+        assert_eq!(hist.url, "");
+        assert_eq!(hist.params, params);
+
+        app.state.scroll_offset = 5;
+        app.state.remember_scroll();
+        assert!(app.state.scroll_positions.is_empty());
+        assert_eq!(app.state.saved_scroll(), None);
+
+        app.copy_history_entry(0, false);
+        assert_eq!(
+            app.state.error.as_deref(),
+            Some("That request has no URL to copy")
+        );
+        assert_eq!(
+            app.repeat_blocker(),
+            Some("Only requests sent to the PDS can be repeated")
+        );
+    }
 }
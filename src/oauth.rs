@@ -0,0 +1,694 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use smol::net::{TcpListener, TcpStream};
+use surf::{
+    middleware::{Middleware, Next},
+    Body, Client, Request, RequestBuilder, Response, StatusCode, Url,
+};
+
+use crate::{error::AppError, xrpc::XrpcClient};
+
+/// atproto access, plus the scope that allows everything an app password
+/// does.
+const SCOPE: &str = "atproto transition:generic";
+
+/// Where the browser is sent back to on the loopback listener.
+const CALLBACK_PATH: &str = "/callback";
+
+/// How long to wait for sign-in to finish in the browser.
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Longest redirect request read from the browser.
+const MAX_CALLBACK_REQUEST: usize = 16 * 1024;
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// `len` random bytes, base64url-encoded.
+fn random_string(len: usize) -> String {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    base64url(&bytes)
+}
+
+/// The PKCE `S256` challenge for `verifier`.
+fn pkce_challenge(verifier: &str) -> String {
+    base64url(&Sha256::digest(verifier.as_bytes()))
+}
+
+fn auth_error(src: &str, msg: impl Into<String>) -> AppError {
+    AppError::Auth {
+        src: src.into(),
+        err_span: (0, 0),
+        msg: msg.into(),
+    }
+}
+
+/// The key an OAuth session's tokens are bound to, made fresh at each
+/// login, and the latest DPoP nonce each server has handed out.
+#[derive(Clone)]
+pub struct DpopKey(Arc<DpopInner>);
+
+struct DpopInner {
+    key: SigningKey,
+    /// Keyed by origin, since the PDS and authorization server keep their
+    /// own.
+    nonces: Mutex<HashMap<String, String>>,
+}
+
+impl fmt::Debug for DpopKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the private key
+        f.write_str("DpopKey(..)")
+    }
+}
+
+impl DpopKey {
+    pub fn generate() -> Self {
+        Self(Arc::new(DpopInner {
+            key: SigningKey::random(&mut OsRng),
+            nonces: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// The public half as a JWK, for proof headers.
+    fn jwk(&self) -> Value {
+        let point = self.0.key.verifying_key().to_encoded_point(false);
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": base64url(&point.x().map(|x| x.to_vec()).unwrap_or_default()),
+            "y": base64url(&point.y().map(|y| y.to_vec()).unwrap_or_default()),
+        })
+    }
+
+    /// A DPoP proof for sending `method` to `url`. Requests to the PDS
+    /// carry the access token and bind the proof to it with `ath`.
+    fn proof(&self, method: &str, url: &Url, access_token: Option<&str>) -> String {
+        let mut htu = url.clone();
+        htu.set_query(None);
+        htu.set_fragment(None);
+        let iat = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let header = json!({ "typ": "dpop+jwt", "alg": "ES256", "jwk": self.jwk() });
+        let mut claims = json!({
+            "jti": random_string(16),
+            "htm": method,
+            "htu": htu.as_str(),
+            "iat": iat,
+        });
+        if let Some(nonce) = self.nonce(url) {
+            claims["nonce"] = nonce.into();
+        }
+        if let Some(token) = access_token {
+            claims["ath"] = base64url(&Sha256::digest(token.as_bytes())).into();
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            base64url(header.to_string().as_bytes()),
+            base64url(claims.to_string().as_bytes())
+        );
+        let signature: Signature = self.0.key.sign(signing_input.as_bytes());
+        format!("{}.{}", signing_input, base64url(&signature.to_bytes()))
+    }
+
+    fn nonce(&self, url: &Url) -> Option<String> {
+        let nonces = self.0.nonces.lock().ok()?;
+        nonces.get(&url.origin().ascii_serialization()).cloned()
+    }
+
+    /// Keeps the nonce `res` carries for later proofs to `url`'s server,
+    /// and returns it.
+    fn remember_nonce(&self, url: &Url, res: &Response) -> Option<String> {
+        let nonce = res.header("DPoP-Nonce")?.as_str().to_string();
+        if let Ok(mut nonces) = self.0.nonces.lock() {
+            nonces.insert(url.origin().ascii_serialization(), nonce.clone());
+        }
+        Some(nonce)
+    }
+}
+
+/// Adds a DPoP proof to one request. When the server turns it down with a
+/// nonce it hasn't been given yet, it's sent once more with a proof that
+/// uses it.
+struct DpopProof {
+    key: DpopKey,
+    access_token: Option<String>,
+}
+
+#[surf::utils::async_trait]
+impl Middleware for DpopProof {
+    async fn handle(
+        &self,
+        mut req: Request,
+        client: Client,
+        next: Next<'_>,
+    ) -> surf::Result<Response> {
+        // A retry needs its own copy of the body
+        let body = req.take_body();
+        let mime = body.mime().clone();
+        let has_body = body.is_empty() == Some(false) || body.len().is_none();
+        let body = body.into_bytes().await?;
+
+        let mut retried = false;
+        loop {
+            let mut attempt = req.clone();
+            if has_body {
+                let mut attempt_body = Body::from_bytes(body.clone());
+                attempt_body.set_mime(mime.clone());
+                attempt.set_body(attempt_body);
+            }
+            let sent_nonce = self.key.nonce(req.url());
+            attempt.insert_header(
+                "DPoP",
+                self.key.proof(
+                    req.method().as_ref(),
+                    req.url(),
+                    self.access_token.as_deref(),
+                ),
+            );
+
+            let res = next.run(attempt, client.clone()).await?;
+            let nonce = self.key.remember_nonce(req.url(), &res);
+            let refused = matches!(
+                res.status(),
+                StatusCode::BadRequest | StatusCode::Unauthorized
+            );
+            if refused && !retried && nonce.is_some() && nonce != sent_nonce {
+                retried = true;
+                continue;
+            }
+            return Ok(res);
+        }
+    }
+}
+
+/// Signs `req` with a DPoP proof from `key`, bound to `access_token` when
+/// it's for the PDS.
+pub fn sign(req: RequestBuilder, key: &DpopKey, access_token: Option<&str>) -> RequestBuilder {
+    req.middleware(DpopProof {
+        key: key.clone(),
+        access_token: access_token.map(str::to_string),
+    })
+}
+
+/// What's needed to keep an OAuth session going after login.
+#[derive(Debug, Clone)]
+pub struct OAuthSession {
+    pub key: DpopKey,
+    pub token_endpoint: String,
+    pub client_id: String,
+}
+
+/// What the token endpoint returns.
+#[derive(Debug, Deserialize)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// The account's DID.
+    pub sub: String,
+}
+
+#[derive(Deserialize)]
+struct ProtectedResource {
+    authorization_servers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ServerMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    pushed_authorization_request_endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PushedRequest {
+    request_uri: String,
+}
+
+/// A sign-in waiting on the browser: the URL to open there, and what's
+/// needed to finish once the browser comes back.
+pub struct Authorization {
+    pub url: String,
+    listener: TcpListener,
+    state: String,
+    verifier: String,
+    redirect_uri: String,
+    client_id: String,
+    issuer: String,
+    token_endpoint: String,
+    key: DpopKey,
+}
+
+/// Starts signing `identifier` in to the PDS with OAuth. The PDS's
+/// authorization server is found from its metadata and sent a pushed
+/// authorization request with PKCE, and a loopback listener is opened for
+/// the browser to be redirected back to. oxat has no client metadata of its
+/// own to host, so it uses a loopback client ID.
+pub async fn authorize(xrpc: &XrpcClient, identifier: &str) -> Result<Authorization, AppError> {
+    let pds = xrpc.pds_host.trim_end_matches('/');
+    let url = format!("{}/.well-known/oauth-protected-resource", pds);
+    let resource: ProtectedResource = xrpc.call(&url, xrpc.http().get(&url)).await?;
+    let Some(issuer) = resource.authorization_servers.first() else {
+        return Err(auth_error(&url, "The PDS names no authorization server"));
+    };
+
+    let url = format!(
+        "{}/.well-known/oauth-authorization-server",
+        issuer.trim_end_matches('/')
+    );
+    let metadata: ServerMetadata = xrpc.call(&url, xrpc.http().get(&url)).await?;
+    if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        return Err(auth_error(
+            &url,
+            format!(
+                "Authorization server claims to be {}, not {}",
+                metadata.issuer, issuer
+            ),
+        ));
+    }
+    let Some(par_endpoint) = metadata.pushed_authorization_request_endpoint else {
+        return Err(auth_error(
+            &url,
+            "The authorization server doesn't take pushed authorization requests",
+        ));
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| auth_error("loopback listener", e.to_string()))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| auth_error("loopback listener", e.to_string()))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{}{}", port, CALLBACK_PATH);
+    let mut client_id = Url::parse("http://localhost").expect("valid URL");
+    client_id
+        .query_pairs_mut()
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("scope", SCOPE);
+    let client_id = client_id.to_string();
+
+    let key = DpopKey::generate();
+    let state = random_string(16);
+    let verifier = random_string(32);
+    let challenge = pkce_challenge(&verifier);
+    let form = [
+        ("response_type", "code"),
+        ("client_id", client_id.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("scope", SCOPE),
+        ("state", state.as_str()),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+        ("login_hint", identifier),
+    ];
+    let pushed: PushedRequest = post_form(xrpc, &par_endpoint, &form, &key).await?;
+
+    let mut url = Url::parse(&metadata.authorization_endpoint)
+        .map_err(|e| auth_error(&metadata.authorization_endpoint, e.to_string()))?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("request_uri", &pushed.request_uri);
+
+    Ok(Authorization {
+        url: url.to_string(),
+        listener,
+        state,
+        verifier,
+        redirect_uri,
+        client_id,
+        issuer: metadata.issuer,
+        token_endpoint: metadata.token_endpoint,
+        key,
+    })
+}
+
+impl Authorization {
+    /// Waits for the browser to be redirected back with a code, then trades
+    /// it for tokens bound to this sign-in's DPoP key.
+    pub async fn finish(self, xrpc: &XrpcClient) -> Result<(OAuthSession, Tokens), AppError> {
+        let params = smol::future::or(wait_for_redirect(&self.listener), async {
+            smol::Timer::after(CALLBACK_TIMEOUT).await;
+            Err(auth_error(
+                &self.redirect_uri,
+                "Sign-in wasn't finished in the browser in time",
+            ))
+        })
+        .await?;
+        let param = |name: &str| params.get(name).map(String::as_str);
+
+        if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or(error);
+            return Err(auth_error(&self.redirect_uri, description));
+        }
+        if param("state") != Some(self.state.as_str()) {
+            return Err(auth_error(
+                &self.redirect_uri,
+                "The redirect doesn't belong to this sign-in",
+            ));
+        }
+        if param("iss").is_some_and(|iss| iss != self.issuer) {
+            return Err(auth_error(
+                &self.redirect_uri,
+                "The redirect came from a different authorization server",
+            ));
+        }
+        let Some(code) = param("code") else {
+            return Err(auth_error(&self.redirect_uri, "The redirect has no code"));
+        };
+
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("code_verifier", self.verifier.as_str()),
+        ];
+        let tokens = request_tokens(xrpc, &self.token_endpoint, &form, &self.key).await?;
+        let session = OAuthSession {
+            key: self.key,
+            token_endpoint: self.token_endpoint,
+            client_id: self.client_id,
+        };
+        Ok((session, tokens))
+    }
+}
+
+/// Trades `refresh_token` for new tokens, bound to the same key.
+pub async fn refresh(
+    xrpc: &XrpcClient,
+    session: &OAuthSession,
+    refresh_token: &str,
+) -> Result<Tokens, AppError> {
+    let form = [
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", session.client_id.as_str()),
+    ];
+    request_tokens(xrpc, &session.token_endpoint, &form, &session.key).await
+}
+
+async fn request_tokens(
+    xrpc: &XrpcClient,
+    url: &str,
+    form: &[(&str, &str)],
+    key: &DpopKey,
+) -> Result<Tokens, AppError> {
+    let tokens: Tokens = post_form(xrpc, url, form, key).await?;
+    if !tokens.token_type.eq_ignore_ascii_case("DPoP") {
+        return Err(auth_error(
+            url,
+            format!("Expected DPoP tokens, got {}", tokens.token_type),
+        ));
+    }
+    Ok(tokens)
+}
+
+/// POSTs `form` to the authorization server with a DPoP proof and parses
+/// the JSON response.
+async fn post_form<T: serde::de::DeserializeOwned>(
+    xrpc: &XrpcClient,
+    url: &str,
+    form: &[(&str, &str)],
+    key: &DpopKey,
+) -> Result<T, AppError> {
+    let body = Body::from_form(&form)
+        .map_err(|e| AppError::request_at(url, None, format!("Failed to build request: {}", e)))?;
+    let req = sign(xrpc.http().post(url).body(body), key, None);
+    xrpc.call(url, req).await
+}
+
+/// Answers requests on the loopback listener until the browser is
+/// redirected to the callback, and returns the callback's query
+/// parameters.
+async fn wait_for_redirect(listener: &TcpListener) -> Result<HashMap<String, String>, AppError> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| auth_error("loopback listener", e.to_string()))?;
+
+        let mut request = Vec::new();
+        let mut chunk = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_CALLBACK_REQUEST
+        {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => request.extend_from_slice(&chunk[..n]),
+            }
+        }
+        let request = String::from_utf8_lossy(&request);
+        let target = request.split_whitespace().nth(1).unwrap_or("/");
+        let url = Url::parse(&format!("http://127.0.0.1{}", target));
+        let Some(url) = url.ok().filter(|url| url.path() == CALLBACK_PATH) else {
+            // e.g. the browser asking for a favicon
+            respond(&mut stream, "404 Not Found", "Not found").await;
+            continue;
+        };
+
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        if params.contains_key("code") {
+            respond(
+                &mut stream,
+                "200 OK",
+                "Signed in to oxat. You can close this tab and go back to the terminal.",
+            )
+            .await;
+        } else {
+            respond(
+                &mut stream,
+                "400 Bad Request",
+                "Sign-in didn't finish. The terminal says why.",
+            )
+            .await;
+        }
+        return Ok(params);
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = format!("<!doctype html><title>oxat</title><p>{}</p>", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    // The browser going away doesn't change the outcome
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.flush().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, MockServer, Reply};
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+    use std::{
+        io::{Read, Write},
+        thread,
+    };
+
+    /// The header and claims of a DPoP proof, after checking it's signed by
+    /// `key`.
+    fn verified(proof: &str, key: &DpopKey) -> (Value, Value) {
+        let parts: Vec<&str> = proof.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let signature = base64::decode_config(parts[2], base64::URL_SAFE_NO_PAD).unwrap();
+        let signature = Signature::from_slice(&signature).unwrap();
+        let signing_input = format!("{}.{}", parts[0], parts[1]);
+        VerifyingKey::from(&key.0.key)
+            .verify(signing_input.as_bytes(), &signature)
+            .expect("a valid signature");
+
+        let decode = |part: &str| -> Value {
+            serde_json::from_slice(&base64::decode_config(part, base64::URL_SAFE_NO_PAD).unwrap())
+                .unwrap()
+        };
+        (decode(parts[0]), decode(parts[1]))
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636() {
+        // Appendix B of RFC 7636
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn proofs_name_the_request_and_bind_the_token() {
+        let key = DpopKey::generate();
+        let url =
+            Url::parse("https://pds.example.com/xrpc/app.bsky.actor.getProfile?actor=bsky.app#top")
+                .unwrap();
+
+        let (header, claims) = verified(&key.proof("GET", &url, Some("access")), &key);
+        assert_eq!(header["typ"], "dpop+jwt");
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["jwk"], key.jwk());
+        assert_eq!(claims["htm"], "GET");
+        assert_eq!(
+            claims["htu"],
+            "https://pds.example.com/xrpc/app.bsky.actor.getProfile"
+        );
+        assert_eq!(claims["ath"], base64url(&Sha256::digest(b"access")));
+        assert!(claims.get("nonce").is_none());
+        assert!(claims["iat"].as_u64().is_some());
+
+        // Nonces are kept per server and proofs to the authorization server
+        // carry no token
+        key.0
+            .nonces
+            .lock()
+            .unwrap()
+            .insert("https://pds.example.com".into(), "nonce-1".into());
+        let (_, claims) = verified(&key.proof("POST", &url, None), &key);
+        assert_eq!(claims["htm"], "POST");
+        assert_eq!(claims["nonce"], "nonce-1");
+        assert!(claims.get("ath").is_none());
+        let other = Url::parse("https://auth.example.com/oauth/token").unwrap();
+        let (_, claims) = verified(&key.proof("POST", &other, None), &key);
+        assert!(claims.get("nonce").is_none());
+    }
+
+    #[test]
+    fn a_new_nonce_is_used_for_one_retry() {
+        let key = DpopKey::generate();
+        let proof_key = key.clone();
+        let server = MockServer::start(move |req| {
+            let (_, claims) = verified(req.header("DPoP").unwrap_or_default(), &proof_key);
+            if claims["nonce"] == "nonce-1" {
+                Reply::json(200, json!({ "ok": true }))
+            } else {
+                Reply::json(401, json!({ "error": "use_dpop_nonce" }))
+                    .header("DPoP-Nonce", "nonce-1")
+            }
+        });
+        let xrpc = test_support::client(&server.url);
+
+        let url = format!("{}/oauth/par", server.url);
+        let body = Body::from_json(&json!({ "a": 1 })).unwrap();
+        let req = sign(xrpc.http().post(&url).body(body), &key, None);
+        let res: Value = smol::block_on(xrpc.call(&url, req)).unwrap();
+        assert_eq!(res, json!({ "ok": true }));
+
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        for req in &received {
+            assert_eq!(req.json(), json!({ "a": 1 }));
+        }
+        let (_, first) = verified(received[0].header("DPoP").unwrap(), &key);
+        let (_, second) = verified(received[1].header("DPoP").unwrap(), &key);
+        assert!(first.get("nonce").is_none());
+        assert_eq!(second["nonce"], "nonce-1");
+        assert_ne!(first["jti"], second["jti"]);
+    }
+
+    #[test]
+    fn a_refused_nonce_isnt_retried_again() {
+        let server = MockServer::start(|_| {
+            Reply::json(401, json!({ "error": "use_dpop_nonce" })).header("DPoP-Nonce", "nonce-1")
+        });
+        let xrpc = test_support::client(&server.url);
+        let key = DpopKey::generate();
+
+        let url = format!("{}/oauth/token", server.url);
+        let req = sign(xrpc.http().get(&url), &key, None);
+        assert!(smol::block_on(xrpc.call::<Value>(&url, req)).is_err());
+        assert_eq!(server.received().len(), 2);
+    }
+
+    /// A sign-in waiting on a loopback listener, expecting `state` back
+    /// from `issuer`.
+    fn waiting(state: &str, issuer: &str) -> Authorization {
+        let listener = smol::block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        Authorization {
+            url: String::new(),
+            listener,
+            state: state.into(),
+            verifier: "verifier".into(),
+            redirect_uri: format!("http://127.0.0.1:{}{}", port, CALLBACK_PATH),
+            client_id: "http://localhost".into(),
+            issuer: issuer.into(),
+            token_endpoint: "http://127.0.0.1:9/oauth/token".into(),
+            key: DpopKey::generate(),
+        }
+    }
+
+    /// Sends the browser's redirect to `authorization` with `query`, once
+    /// it's listening.
+    fn redirect(authorization: &Authorization, query: &str) {
+        let addr = authorization.listener.local_addr().unwrap();
+        let request = format!(
+            "GET {}?{} HTTP/1.1\r\nHost: {}\r\n\r\n",
+            CALLBACK_PATH, query, addr
+        );
+        thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let _ = stream.read_to_end(&mut Vec::new());
+        });
+    }
+
+    fn refusal(result: Result<(OAuthSession, Tokens), AppError>) -> String {
+        match result {
+            Err(AppError::Auth { msg, .. }) => msg,
+            Err(e) => panic!("expected an auth error, got {:?}", e),
+            Ok(_) => panic!("expected the redirect to be refused"),
+        }
+    }
+
+    #[test]
+    fn redirects_for_another_sign_in_are_refused() {
+        let xrpc = test_support::client("http://127.0.0.1:9");
+        let authorization = waiting("state-1", "https://auth.example.com");
+        redirect(
+            &authorization,
+            "state=state-2&code=c&iss=https%3A%2F%2Fauth.example.com",
+        );
+
+        let result = smol::block_on(authorization.finish(&xrpc));
+        assert_eq!(
+            refusal(result),
+            "The redirect doesn't belong to this sign-in"
+        );
+    }
+
+    #[test]
+    fn redirects_from_another_issuer_are_refused() {
+        let xrpc = test_support::client("http://127.0.0.1:9");
+        let authorization = waiting("state-1", "https://auth.example.com");
+        redirect(
+            &authorization,
+            "state=state-1&code=c&iss=https%3A%2F%2Fevil.example.com",
+        );
+
+        let result = smol::block_on(authorization.finish(&xrpc));
+        assert_eq!(
+            refusal(result),
+            "The redirect came from a different authorization server"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{AppError, AppResult},
+    App,
+};
+
+/// Where offline mode looks for `method`'s canned response.
+pub fn fixture_path(dir: &Path, method: &str) -> PathBuf {
+    dir.join(format!("{}.json", method))
+}
+
+impl App {
+    /// Shows the canned response for `method` from `dir` instead of sending
+    /// a request, so the UI can be driven without a network or an account.
+    pub(crate) fn load_fixture(
+        &mut self,
+        dir: &Path,
+        method: &str,
+        params: &[String],
+        raw: bool,
+    ) -> AppResult<()> {
+        let path = fixture_path(dir, method);
+        let src = path.display().to_string();
+
+        self.state.response_info = None;
+        self.add_to_history(method, src.clone(), params.to_vec());
+        if let Some(hist) = self.state.request_history.front_mut() {
+            hist.raw = raw;
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                let msg = if e.kind() == ErrorKind::NotFound {
+                    format!("No offline response for {} (expected {})", method, src)
+                } else {
+                    format!("Failed to read {}: {}", src, e)
+                };
+                self.update_history_success(method, false);
+                return Err(AppError::Request {
+                    err_span: (0, src.len()),
+                    src,
+                    msg,
+                }
+                .into());
+            }
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(json) => {
+                self.state.output = Some(json);
+                self.state.output_method = Some(method.to_string());
+                self.state.collapsed.clear();
+                self.state.error = None;
+                self.update_history_success(method, true);
+                Ok(())
+            }
+            Err(e) => {
+                self.update_history_success(method, false);
+                Err(AppError::Deserialize {
+                    err_span: (0, src.len()),
+                    src,
+                    msg: e.to_string(),
+                }
+                .into())
+            }
+        }
+    }
+}
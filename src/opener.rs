@@ -0,0 +1,37 @@
+use std::{
+    ffi::OsStr,
+    io,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// The program that opens a file or URL with its default app on this OS.
+fn opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        // Unlike `cmd /C start`, nothing in a URL gets read as shell syntax
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// The name of the program `open` uses, for messages.
+pub fn program() -> &'static str {
+    opener()
+}
+
+/// Opens `target`, a path or URL, with the OS's default app. Returns once
+/// the opener has started, so a slow app can't freeze the TUI; it's reaped
+/// in the background.
+pub fn open(target: impl AsRef<OsStr>) -> io::Result<()> {
+    let mut child = Command::new(opener())
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,547 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::state::{RequestHistory, ResponseInfo};
+
+pub const DEFAULT_EXPORT_FILENAME: &str = "bsky_response_{timestamp}.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Pretty,
+    Compact,
+    /// One compact JSON value per line. Arrays are split into their items;
+    /// for XRPC-style objects the first array field (e.g. `feed`) is split.
+    Ndjson,
+    Yaml,
+    /// One row per record of the same list `Ndjson` splits, with nested
+    /// fields flattened into dotted column names.
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pretty" => Some(Self::Pretty),
+            "compact" => Some(Self::Compact),
+            "ndjson" => Some(Self::Ndjson),
+            "yaml" => Some(Self::Yaml),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pretty => "JSON",
+            Self::Compact => "compact JSON",
+            Self::Ndjson => "NDJSON",
+            Self::Yaml => "YAML",
+            Self::Csv => "CSV",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Pretty | Self::Compact => "json",
+            Self::Ndjson => "ndjson",
+            Self::Yaml => "yaml",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// The records in a list-shaped response: the value itself if it's an
+/// array, or the first array field of an XRPC-style object (e.g. `feed`).
+fn records(value: &Value) -> Option<&Vec<Value>> {
+    match value {
+        Value::Array(items) => Some(items),
+        Value::Object(fields) => fields.values().find_map(Value::as_array),
+        _ => None,
+    }
+}
+
+/// Serializes a response the same way for TUI exports and `exec` output.
+pub fn format_json(value: &Value, format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Pretty => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        OutputFormat::Compact => serde_json::to_string(value).map_err(|e| e.to_string()),
+        OutputFormat::Ndjson => match records(value) {
+            Some(items) => items
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<serde_json::Result<Vec<_>>>()
+                .map(|lines| lines.join("\n"))
+                .map_err(|e| e.to_string()),
+            None => serde_json::to_string(value).map_err(|e| e.to_string()),
+        },
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        OutputFormat::Csv => {
+            let items = records(value).ok_or("the response isn't a list")?;
+            Ok(to_csv(items))
+        }
+    }
+}
+
+fn to_csv(items: &[Value]) -> String {
+    let rows: Vec<Vec<(String, String)>> = items
+        .iter()
+        .map(|item| {
+            let mut row = Vec::new();
+            flatten("", item, &mut row);
+            row
+        })
+        .collect();
+
+    // Columns in the order they're first seen across all rows
+    let mut columns: Vec<&str> = Vec::new();
+    for (key, _) in rows.iter().flatten() {
+        if !columns.contains(&key.as_str()) {
+            columns.push(key);
+        }
+    }
+
+    let mut csv = columns
+        .iter()
+        .map(|column| csv_field(column))
+        .collect::<Vec<_>>()
+        .join(",");
+    for row in &rows {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                row.iter()
+                    .find(|(key, _)| key == column)
+                    .map(|(_, value)| csv_field(value))
+                    .unwrap_or_default()
+            })
+            .collect();
+        csv.push('\n');
+        csv.push_str(&fields.join(","));
+    }
+    csv
+}
+
+/// Flattens nested objects into `parent.child` keys. Arrays are kept as
+/// JSON since they don't map onto a fixed set of columns.
+fn flatten(prefix: &str, value: &Value, row: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, row);
+            }
+        }
+        Value::String(s) => row.push((prefix.to_string(), s.clone())),
+        Value::Null => row.push((prefix.to_string(), String::new())),
+        other => row.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Builds a `curl` invocation reproducing a request, custom `headers`
+/// included: a GET, or with a `body` a JSON POST to a procedure. Without a
+/// literal token, authenticated requests reference `$TOKEN` so the JWT
+/// isn't leaked.
+pub fn curl_command(
+    url: &str,
+    body: Option<&Value>,
+    authenticated: bool,
+    token: Option<&str>,
+    headers: &BTreeMap<String, String>,
+) -> String {
+    let mut cmd = format!(
+        "curl -X {} {} -H {}",
+        if body.is_some() { "POST" } else { "GET" },
+        shell_quote(url),
+        shell_quote("Accept: application/json")
+    );
+    if body.is_some() {
+        cmd.push_str(&format!(
+            " -H {}",
+            shell_quote("Content-Type: application/json")
+        ));
+    }
+    match token {
+        Some(token) => cmd.push_str(&format!(
+            " -H {}",
+            shell_quote(&format!("Authorization: Bearer {}", token))
+        )),
+        // Double quotes so the shell expands the placeholder
+        None if authenticated => cmd.push_str(" -H \"Authorization: Bearer $TOKEN\""),
+        None => {}
+    }
+    for (name, value) in headers {
+        cmd.push_str(&format!(
+            " -H {}",
+            shell_quote(&format!("{}: {}", name, value))
+        ));
+    }
+    if let Some(body) = body {
+        cmd.push_str(&format!(" --data {}", shell_quote(&body.to_string())));
+    }
+    cmd
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `body` with the request that produced it, so an exported file says what
+/// it is. `info` is the response's status and latency when they're known.
+pub fn export_envelope(hist: &RequestHistory, info: Option<&ResponseInfo>, body: &Value) -> Value {
+    let timestamp = hist
+        .timestamp
+        .to_offset(time::UtcOffset::UTC)
+        .format(&Rfc3339)
+        .ok();
+    json!({
+        "method": hist.method,
+        "url": hist.url,
+        "params": hist.params,
+        "success": hist.success,
+        "status": info.map(|info| info.status).or(hist.status),
+        "timestamp": timestamp,
+        "latencyMs": info.map(|info| info.elapsed.as_millis() as u64),
+        "body": body,
+    })
+}
+
+/// Fills in an export filename template. Token values have anything that
+/// isn't alphanumeric, `-` or `_` replaced, so NSIDs and DIDs like
+/// `did:plc:abc` become `did_plc_abc`.
+pub fn export_filename(template: &str, method: &str, handle: &str, now: OffsetDateTime) -> String {
+    let timestamp = format!(
+        "{:04}_{:02}_{:02}_{:02}_{:02}_{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+
+    template
+        .replace("{method}", &sanitize(method))
+        .replace("{timestamp}", &timestamp)
+        .replace("{handle}", &sanitize(handle))
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Writes the node at JSON Pointer `pointer` as a path like
+/// `feed[3].post.author.handle`, which `select_path` reads back. Keys that
+/// would be misread, like `a.b`, are quoted as `["a.b"]`. The root is an
+/// empty path.
+pub fn path_from_pointer(value: &Value, pointer: &str) -> Option<String> {
+    let mut path = String::new();
+    let mut current = value;
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        current = match current {
+            Value::Array(items) => {
+                let idx = segment.parse::<usize>().ok()?;
+                path.push_str(&format!("[{}]", idx));
+                items.get(idx)?
+            }
+            Value::Object(fields) => {
+                let plain = !segment.is_empty()
+                    && !segment
+                        .chars()
+                        .any(|c| matches!(c, '.' | '[' | ']' | '"') || c.is_whitespace());
+                if !plain {
+                    path.push_str(&format!("[{}]", Value::String(segment.clone())));
+                } else if path.is_empty() {
+                    path.push_str(&segment);
+                } else {
+                    path.push_str(&format!(".{}", segment));
+                }
+                fields.get(&segment)?
+            }
+            _ => return None,
+        };
+    }
+    Some(path)
+}
+
+/// The JSON Pointer for a path as `select_path` reads it, or `None` if a
+/// quoted key isn't closed.
+pub fn path_to_pointer(path: &str) -> Option<String> {
+    let segments = path_segments(path)?;
+    Some(
+        segments
+            .iter()
+            .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+            .collect(),
+    )
+}
+
+/// Looks up a path like `feed.0.post.record.text`, `.feed[0]` or
+/// `labels["a.b"]`. Numeric segments index arrays; an empty path selects
+/// the whole value.
+pub fn select_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path_segments(path)?
+        .iter()
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            Value::Object(fields) => fields.get(segment),
+            _ => None,
+        })
+}
+
+/// Splits a path into its keys and indices. Dots and brackets both
+/// separate segments, and a bracketed JSON string is a key taken as it is,
+/// dots and all. `None` if a quoted key isn't closed.
+fn path_segments(path: &str) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if !matches!(c, '.' | '[' | ']') {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            segments.push(std::mem::take(&mut current));
+        }
+        if c == '[' && chars.as_str().starts_with('"') {
+            let rest = chars.as_str();
+            let mut quoted = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+            segments.push(quoted.next()?.ok()?);
+            chars = rest[quoted.byte_offset()..].strip_prefix(']')?.chars();
+        }
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> Value {
+        json!({
+            "cursor": "abc",
+            "feed": [
+                {"post": {"author": {"handle": "alice.test"}, "likeCount": 3}},
+                {"post": {"author": {"handle": "bob.test"}, "likeCount": 0}},
+            ],
+        })
+    }
+
+    #[test]
+    fn envelopes_describe_the_request() {
+        let hist = RequestHistory {
+            method: "app.bsky.actor.getProfile".into(),
+            timestamp: OffsetDateTime::from_unix_timestamp(1_760_000_000)
+                .unwrap()
+                .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap()),
+            success: true,
+            url: "https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=bsky.app".into(),
+            params: vec!["bsky.app".into()],
+            status: Some(200),
+            raw: false,
+            body: None,
+        };
+        let info = ResponseInfo {
+            status: 200,
+            reason: "OK".into(),
+            elapsed: std::time::Duration::from_millis(123),
+        };
+        let body = json!({ "handle": "bsky.app" });
+
+        assert_eq!(
+            export_envelope(&hist, Some(&info), &body),
+            json!({
+                "method": "app.bsky.actor.getProfile",
+                "url": "https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=bsky.app",
+                "params": ["bsky.app"],
+                "success": true,
+                "status": 200,
+                "timestamp": "2025-10-09T08:53:20Z",
+                "latencyMs": 123,
+                "body": body,
+            })
+        );
+
+        // Without the response's details, e.g. after a failure
+        let failed = RequestHistory {
+            success: false,
+            status: None,
+            ..hist
+        };
+        let envelope = export_envelope(&failed, None, &body);
+        assert_eq!(envelope["success"], false);
+        assert_eq!(envelope["status"], Value::Null);
+        assert_eq!(envelope["latencyMs"], Value::Null);
+    }
+
+    #[test]
+    fn curl_for_a_query_is_a_get() {
+        let headers = BTreeMap::from([("atproto-proxy".to_string(), "did:web:x#y".to_string())]);
+        assert_eq!(
+            curl_command(
+                "https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=it's.me",
+                None,
+                true,
+                None,
+                &headers
+            ),
+            "curl -X GET 'https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=it'\\''s.me' \
+             -H 'Accept: application/json' -H \"Authorization: Bearer $TOKEN\" \
+             -H 'atproto-proxy: did:web:x#y'"
+        );
+    }
+
+    #[test]
+    fn curl_for_a_procedure_posts_its_body() {
+        let body = json!({ "repo": "did:plc:test", "rkey": "3k" });
+        assert_eq!(
+            curl_command(
+                "https://bsky.social/xrpc/com.atproto.repo.deleteRecord",
+                Some(&body),
+                true,
+                Some("jwt"),
+                &BTreeMap::new()
+            ),
+            "curl -X POST 'https://bsky.social/xrpc/com.atproto.repo.deleteRecord' \
+             -H 'Accept: application/json' -H 'Content-Type: application/json' \
+             -H 'Authorization: Bearer jwt' --data '{\"repo\":\"did:plc:test\",\"rkey\":\"3k\"}'"
+        );
+    }
+
+    #[test]
+    fn select_path_follows_object_keys() {
+        let value = feed();
+        assert_eq!(select_path(&value, "cursor"), Some(&json!("abc")));
+        assert_eq!(
+            select_path(&value, "feed.0.post.author"),
+            Some(&json!({"handle": "alice.test"}))
+        );
+    }
+
+    #[test]
+    fn select_path_indexes_arrays_either_way() {
+        let value = feed();
+        let bob = Some(&json!("bob.test"));
+        assert_eq!(select_path(&value, "feed.1.post.author.handle"), bob);
+        assert_eq!(select_path(&value, "feed[1].post.author.handle"), bob);
+        assert_eq!(select_path(&value, ".feed[1].post.author.handle"), bob);
+        assert_eq!(
+            select_path(&value, "feed[1].post.likeCount"),
+            Some(&json!(0))
+        );
+    }
+
+    #[test]
+    fn select_path_empty_is_the_whole_value() {
+        let value = feed();
+        assert_eq!(select_path(&value, ""), Some(&value));
+        assert_eq!(select_path(&value, "."), Some(&value));
+    }
+
+    #[test]
+    fn select_path_missing_is_none() {
+        let value = feed();
+        assert_eq!(select_path(&value, "nope"), None);
+        assert_eq!(select_path(&value, "feed[2]"), None);
+        assert_eq!(select_path(&value, "feed.first"), None);
+        assert_eq!(select_path(&value, "feed[-1]"), None);
+        // Can't go further into a string or number
+        assert_eq!(select_path(&value, "cursor.length"), None);
+        assert_eq!(select_path(&value, "feed[0].post.likeCount.0"), None);
+    }
+
+    #[test]
+    fn paths_are_written_for_nested_objects_and_indices() {
+        let value = json!({
+            "feed": [{}, {}, {}, { "post": { "author": { "handle": "carol.test" } } }],
+        });
+        let path = path_from_pointer(&value, "/feed/3/post/author/handle").unwrap();
+        assert_eq!(path, "feed[3].post.author.handle");
+        assert_eq!(select_path(&value, &path), Some(&json!("carol.test")));
+        assert_eq!(
+            path_to_pointer(&path).unwrap(),
+            "/feed/3/post/author/handle"
+        );
+
+        assert_eq!(path_from_pointer(&value, "").unwrap(), "");
+        assert_eq!(path_from_pointer(&value, "/feed/3").unwrap(), "feed[3]");
+        assert_eq!(path_from_pointer(&value, "/feed/4"), None);
+    }
+
+    #[test]
+    fn paths_start_with_an_index_for_a_top_level_array() {
+        let value = json!([{ "uri": "at://a" }]);
+        let path = path_from_pointer(&value, "/0/uri").unwrap();
+        assert_eq!(path, "[0].uri");
+        assert_eq!(select_path(&value, &path), Some(&json!("at://a")));
+    }
+
+    #[test]
+    fn awkward_keys_are_quoted_and_read_back() {
+        let value = json!({
+            "records": {
+                "app.bsky.feed.post": { "count": 2 },
+                "with space": [true],
+                "a/b~c": "slash",
+                "say \"hi\"": "quotes",
+                "": "empty",
+            },
+        });
+        for (pointer, expected_path) in [
+            (
+                "/records/app.bsky.feed.post/count",
+                r#"records["app.bsky.feed.post"].count"#,
+            ),
+            ("/records/with space/0", r#"records["with space"][0]"#),
+            ("/records/a~1b~0c", "records.a/b~c"),
+            ("/records/say \"hi\"", r#"records["say \"hi\""]"#),
+            ("/records/", r#"records[""]"#),
+        ] {
+            let path = path_from_pointer(&value, pointer).unwrap();
+            assert_eq!(path, expected_path);
+            assert_eq!(
+                select_path(&value, &path),
+                value.pointer(pointer),
+                "{}",
+                path
+            );
+            assert_eq!(path_to_pointer(&path).as_deref(), Some(pointer), "{}", path);
+        }
+    }
+
+    #[test]
+    fn unclosed_quoted_keys_select_nothing() {
+        let value = json!({ "a.b": 1 });
+        assert_eq!(select_path(&value, r#"["a.b"#), None);
+        assert_eq!(select_path(&value, r#"["a.b""#), None);
+        assert_eq!(path_to_pointer(r#"["a.b"#), None);
+    }
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    commands::{parse_at_uri, DELETE_RECORD},
+    error::{AppError, AppResult},
+    xrpc, App,
+};
+
+/// Longest post Bluesky accepts, in graphemes.
+pub const POST_TEXT_LIMIT: usize = 300;
+
+const CREATE_RECORD: &str = "com.atproto.repo.createRecord";
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+const GET_POSTS: &str = "app.bsky.feed.getPosts";
+
+/// Length of post text as Bluesky counts it, so emoji and accented letters
+/// count once.
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+impl App {
+    /// Publishes `params[0]` as a post, replying to the post at `params[1]`
+    /// when one is given.
+    pub(crate) async fn create_post(&mut self, method: &str, params: &[String]) -> AppResult<()> {
+        let (url, body) = self.post_request(params).await?;
+
+        let req = self
+            .xrpc
+            .post(&url)
+            .body_json(&body)
+            .map_err(|e| post_error(format!("Failed to build request: {}", e)))?;
+
+        self.add_to_history(method, url.clone(), params.to_vec());
+        self.record_body(&body);
+        self.send_request(method, &url, req, None).await
+    }
+
+    /// The `createRecord` URL and body for a post, shared by sending and
+    /// previewing so the two can't drift apart.
+    pub(crate) async fn post_request(&mut self, params: &[String]) -> AppResult<(String, Value)> {
+        let text = params.first().map(String::as_str).unwrap_or_default();
+        if text.trim().is_empty() {
+            return Err(post_error("A post needs some text".into()));
+        }
+        let length = grapheme_count(text);
+        if length > POST_TEXT_LIMIT {
+            return Err(post_error(format!(
+                "Post is {} characters, the limit is {}",
+                length, POST_TEXT_LIMIT
+            )));
+        }
+
+        let Some(repo) = self
+            .state
+            .did
+            .clone()
+            .filter(|_| self.state.is_authenticated)
+        else {
+            return Err(post_error("Log in to post".into()));
+        };
+
+        let mut record = json!({
+            "$type": POST_COLLECTION,
+            "text": text,
+            "createdAt": timestamp(OffsetDateTime::now_utc()),
+        });
+        if let Some(uri) = params.get(1).filter(|uri| !uri.is_empty()) {
+            record["reply"] = self.reply_refs(uri).await?;
+        }
+
+        let url = self.xrpc.url(CREATE_RECORD);
+        let body = json!({
+            "repo": repo,
+            "collection": POST_COLLECTION,
+            "record": record,
+        });
+        Ok((url, body))
+    }
+
+    /// Deletes the record at the `at://` URI in `params[0]`.
+    pub(crate) async fn delete_record(&mut self, method: &str, params: &[String]) -> AppResult<()> {
+        let (url, body) = self.delete_request(params)?;
+
+        let req = self
+            .xrpc
+            .post(&url)
+            .body_json(&body)
+            .map_err(|e| post_error(format!("Failed to build request: {}", e)))?;
+
+        self.add_to_history(method, url.clone(), params.to_vec());
+        self.record_body(&body);
+        self.send_request(method, &url, req, None).await
+    }
+
+    /// The `deleteRecord` URL and body for the record at `params[0]`.
+    pub(crate) fn delete_request(&self, params: &[String]) -> AppResult<(String, Value)> {
+        let uri = params.first().map(String::as_str).unwrap_or_default();
+        let Some((repo, collection, rkey)) = parse_at_uri(uri) else {
+            return Err(post_error(format!("{} doesn't name a record", uri)));
+        };
+
+        let url = self.xrpc.url(DELETE_RECORD);
+        let body = json!({
+            "repo": repo,
+            "collection": collection,
+            "rkey": rkey,
+        });
+        Ok((url, body))
+    }
+
+    /// Looks up the post at `uri` to build a reply's `root` and `parent`
+    /// references, which need each post's CID as well as its URI.
+    async fn reply_refs(&mut self, uri: &str) -> AppResult<Value> {
+        let url = format!(
+            "{}{}",
+            self.xrpc.url(GET_POSTS),
+            xrpc::query_string(&[("uris", uri)])
+        );
+        let req = self.xrpc.get(&url);
+
+        let timeout = self.state.timeout_for(GET_POSTS);
+        let post = match self.fetch(req, timeout).await {
+            Ok(res) if res.status.is_success() => serde_json::from_str::<Value>(&res.body)
+                .ok()
+                .and_then(|json| json["posts"].get(0).cloned()),
+            Ok(_) => None,
+            Err(e) => return Err(e.into_error(&url).into()),
+        };
+
+        let Some(cid) = post.as_ref().and_then(|post| post["cid"].as_str()) else {
+            return Err(post_error(format!("Couldn't find the post at {}", uri)));
+        };
+        let parent = json!({ "uri": uri, "cid": cid });
+        // Replying to a reply keeps the thread's original root
+        let root = post
+            .as_ref()
+            .map(|post| &post["record"]["reply"]["root"])
+            .filter(|root| root.is_object())
+            .cloned()
+            .unwrap_or_else(|| parent.clone());
+
+        Ok(json!({ "root": root, "parent": parent }))
+    }
+}
+
+/// RFC 3339 in UTC with milliseconds, as `createdAt` expects.
+pub fn timestamp(now: OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second(),
+        now.millisecond()
+    )
+}
+
+fn post_error(msg: String) -> miette::Report {
+    AppError::Request {
+        src: "posting".into(),
+        err_span: (0, 0),
+        msg,
+    }
+    .into()
+}
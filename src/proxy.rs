@@ -0,0 +1,108 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use isahc::http::Uri;
+
+/// Proxy variables checked when the config doesn't set `proxy`, in order.
+/// Requests go to an `https://` PDS, so its variable comes first.
+const PROXY_VARS: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+];
+
+/// How long to wait for the proxy to accept a connection at startup.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The outbound proxy requests are sent through.
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    pub uri: Uri,
+}
+
+impl Proxy {
+    /// The proxy from the config, or else from the environment. An empty
+    /// `proxy` in the config turns off the environment's.
+    pub fn from_config(configured: Option<&str>) -> Result<Option<Self>, String> {
+        let url = match configured {
+            Some(url) => url.trim().to_string(),
+            None => PROXY_VARS
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|url| !url.is_empty()))
+                .unwrap_or_default(),
+        };
+        if url.is_empty() {
+            return Ok(None);
+        }
+
+        // Like curl, a bare host:port is an HTTP proxy
+        let url = if url.contains("://") {
+            url
+        } else {
+            format!("http://{}", url)
+        };
+        let uri: Uri = url
+            .parse()
+            .map_err(|e| format!("'{}' isn't a valid proxy URL: {}", url, e))?;
+        match uri.scheme_str() {
+            Some("http" | "https" | "socks4" | "socks4a" | "socks5" | "socks5h") => {}
+            _ => {
+                return Err(format!(
+                    "'{}' isn't a supported proxy; use http, https, socks4 or socks5",
+                    url
+                ))
+            }
+        }
+        if uri.host().is_none() {
+            return Err(format!("'{}' has no proxy host", url));
+        }
+        Ok(Some(Self { uri }))
+    }
+
+    fn host(&self) -> &str {
+        self.uri.host().unwrap_or_default()
+    }
+
+    /// The port curl will connect to, using its defaults when none is given.
+    fn port(&self) -> u16 {
+        self.uri.port_u16().unwrap_or(match self.uri.scheme_str() {
+            Some("https") => 443,
+            _ => 1080,
+        })
+    }
+
+    /// The proxy for display, without any credentials in it.
+    pub fn display(&self) -> String {
+        format!(
+            "{}://{}:{}",
+            self.uri.scheme_str().unwrap_or("http"),
+            self.host(),
+            self.port()
+        )
+    }
+
+    /// Fails with a readable reason when nothing is accepting connections
+    /// at the proxy, which would otherwise look like every request timing
+    /// out.
+    pub fn check(&self) -> Result<(), String> {
+        let unreachable =
+            |reason: String| format!("{} isn't reachable: {}", self.display(), reason);
+        let addrs = (self.host(), self.port())
+            .to_socket_addrs()
+            .map_err(|e| unreachable(e.to_string()))?;
+
+        let mut last_error = "its host has no addresses".to_string();
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(_) => return Ok(()),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(unreachable(last_error))
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{
+    error::{AppError, AppResult},
+    state::{AutoRefresh, InputMode},
+    App,
+};
+
+/// Refreshes that can fail in a row before auto-refresh gives up.
+const MAX_REFRESH_FAILURES: u32 = 3;
+
+/// Longest wait between auto-refreshes, in seconds.
+pub const MAX_REFRESH_INTERVAL: u64 = 3600;
+
+impl App {
+    /// Starts re-sending the last request every `interval`. The same
+    /// requests as `R` are allowed, so nothing gets posted or deleted over
+    /// and over.
+    pub(crate) fn start_auto_refresh(&mut self, interval: Duration) -> AppResult<()> {
+        if let Some(reason) = self.repeat_blocker() {
+            return Err(AppError::Request {
+                src: "auto-refreshing the last request".into(),
+                err_span: (0, 0),
+                msg: reason.into(),
+            }
+            .into());
+        }
+        let Some(hist) = self.state.request_history.front() else {
+            return Ok(());
+        };
+        self.state.auto_refresh = Some(AutoRefresh {
+            interval,
+            next: Instant::now() + interval,
+            failures: 0,
+            url: hist.url.clone(),
+        });
+        self.state.auto_refresh_interval = interval;
+        self.state.error = Some(format!(
+            "Refreshing every {}s (a to stop)",
+            interval.as_secs()
+        ));
+        self.state.error_time = Some(SystemTime::now());
+        Ok(())
+    }
+
+    pub(crate) fn stop_auto_refresh(&mut self, reason: &str) {
+        if self.state.auto_refresh.take().is_some() {
+            self.state.error = Some(reason.into());
+            self.state.error_time = Some(SystemTime::now());
+        }
+    }
+
+    /// Re-sends the last request if an auto-refresh is due, keeping the
+    /// scroll position and folds. Called on every tick. Refreshing waits
+    /// while the response is being searched or filtered, and stops once
+    /// it's left or another request has been sent.
+    pub(crate) async fn auto_refresh_if_due(&mut self) {
+        let Some(refresh) = &self.state.auto_refresh else {
+            return;
+        };
+        if !shows_response(&self.state.input.mode) {
+            self.stop_auto_refresh("Auto-refresh stopped");
+            return;
+        }
+        if self
+            .state
+            .request_history
+            .front()
+            .is_none_or(|hist| hist.url != refresh.url)
+        {
+            self.stop_auto_refresh("Auto-refresh stopped: another request was sent");
+            return;
+        }
+        if self.state.input.mode != InputMode::ViewingResponse || Instant::now() < refresh.next {
+            return;
+        }
+
+        let last_sent = self.state.request_history.front().map(|h| h.timestamp);
+        let scroll_offset = self.state.scroll_offset;
+        let scroll_x = self.state.scroll_x;
+        let collapsed = self.state.collapsed.clone();
+
+        let result = self.replay(0).await;
+
+        // One history entry for the refreshed request, not one per refresh
+        if self.state.request_history.front().map(|h| h.timestamp) != last_sent
+            && self.state.request_history.len() > 1
+        {
+            self.state.request_history.remove(1);
+        }
+        self.state.collapsed = collapsed;
+        self.state.scroll_x = scroll_x;
+        let max_scroll = self
+            .get_content_height()
+            .saturating_sub(crate::viewport_height());
+        self.state.scroll_offset = scroll_offset.min(max_scroll);
+        // The new response may be shorter, leaving the focused line behind
+        self.cursor_into_view(crate::viewport_width(), crate::viewport_height());
+        self.state.update_search_matches();
+
+        let succeeded = result.is_ok()
+            && self
+                .state
+                .request_history
+                .front()
+                .is_some_and(|hist| hist.success);
+        let Some(refresh) = self.state.auto_refresh.as_mut() else {
+            return;
+        };
+        if succeeded {
+            refresh.failures = 0;
+        } else {
+            refresh.failures += 1;
+            let reason = match result {
+                Err(e) => e.to_string(),
+                Ok(_) => self.state.error.clone().unwrap_or_default(),
+            };
+            if refresh.failures >= MAX_REFRESH_FAILURES {
+                self.state.auto_refresh = None;
+                self.state.error = Some(format!(
+                    "Auto-refresh stopped after {} failures in a row: {}",
+                    MAX_REFRESH_FAILURES, reason
+                ));
+            } else {
+                self.state.error = Some(format!(
+                    "Auto-refresh failed ({} of {}): {}",
+                    refresh.failures, MAX_REFRESH_FAILURES, reason
+                ));
+            }
+            self.state.error_time = Some(SystemTime::now());
+        }
+        if let Some(refresh) = self.state.auto_refresh.as_mut() {
+            refresh.next = Instant::now() + refresh.interval;
+        }
+    }
+}
+
+/// Whether `mode` is the response view or something opened over it.
+fn shows_response(mode: &InputMode) -> bool {
+    match mode {
+        InputMode::ViewingResponse
+        | InputMode::SearchResponse
+        | InputMode::FilterResponse
+        | InputMode::RepeatCount
+        | InputMode::AutoRefreshInterval => true,
+        InputMode::Help { previous, .. }
+        | InputMode::ErrorDetail { previous, .. }
+        | InputMode::Lookup { previous, .. } => shows_response(previous),
+        _ => false,
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    commands::{self, DELETE_RECORD, POST},
+    error::{AppError, AppResult},
+    state::{InputMode, RequestHistory},
+    App, FetchError,
+};
+
+/// Most times a request can be repeated in one go.
+pub const MAX_REPEAT: u32 = 100;
+
+/// Whether sending `hist` again would change data: a post, a delete or a
+/// raw call with a JSON body.
+pub(crate) fn changes_data(hist: &RequestHistory) -> bool {
+    let body = hist.raw
+        && hist
+            .params
+            .first()
+            .is_some_and(|args| args.starts_with('{'));
+    hist.method == POST || hist.method == DELETE_RECORD || body
+}
+
+impl App {
+    /// Why the last request can't be repeated, if it can't. Only plain GETs
+    /// are, so a load test can't post, delete or download anything.
+    pub(crate) fn repeat_blocker(&self) -> Option<&'static str> {
+        let Some(hist) = self.state.request_history.front() else {
+            return Some("No previous request to repeat");
+        };
+        let binary = commands::find(&hist.method).is_some_and(|cmd| cmd.is_binary());
+        if self.state.offline.is_some() || hist.url.is_empty() {
+            Some("Only requests sent to the PDS can be repeated")
+        } else if changes_data(hist) {
+            Some("Only queries can be repeated, since each send would change data")
+        } else if binary {
+            Some("Downloads can't be repeated")
+        } else {
+            None
+        }
+    }
+
+    /// Sends the last request `count` times in a row and shows the status
+    /// and latency of each with a summary. Retries are off so rate limiting
+    /// shows up as it happens, and Esc stops early. The run is kept as one
+    /// history entry.
+    pub(crate) async fn repeat_last(&mut self, count: u32) -> AppResult<()> {
+        if let Some(reason) = self.repeat_blocker() {
+            return Err(AppError::Request {
+                src: "repeating the last request".into(),
+                err_span: (0, 0),
+                msg: reason.into(),
+            }
+            .into());
+        }
+        let Some(hist) = self.state.request_history.front() else {
+            return Ok(());
+        };
+        let method = hist.method.clone();
+        let url = hist.url.clone();
+        let params = hist.params.clone();
+        let raw = hist.raw;
+
+        // The summary would be replaced at the next refresh
+        self.state.auto_refresh = None;
+        self.add_to_history(&method, url.clone(), params);
+        if let Some(hist) = self.state.request_history.front_mut() {
+            hist.raw = raw;
+        }
+
+        let timeout = self.state.timeout_for(&method);
+        let retries = std::mem::take(&mut self.state.retries);
+        let mut results = Vec::new();
+        let mut aborted = false;
+        for i in 0..count {
+            self.state.error = Some(format!("Sending {} of {}…", i + 1, count));
+            self.state.error_time = Some(SystemTime::now());
+
+            let req = self
+                .state
+                .headers_for(&method)
+                .iter()
+                .fold(self.xrpc.get(&url), |req, (name, value)| {
+                    req.header(name.as_str(), value.as_str())
+                });
+            match self.fetch(req, timeout).await {
+                Ok(res) => results.push(Outcome::Status(res.status.into(), res.elapsed)),
+                Err(FetchError::Cancelled) => {
+                    aborted = true;
+                    break;
+                }
+                Err(FetchError::Http(e)) => results.push(Outcome::Error(e.to_string())),
+            }
+        }
+        self.state.retries = retries;
+
+        let summary = repeat_summary(&method, &url, count, &results, aborted);
+        let succeeded = summary["succeeded"].as_u64().unwrap_or_default();
+        let sent = results.len();
+        self.state.error = Some(match summary["latencyMs"]["avg"].as_u64() {
+            Some(avg) => format!(
+                "Sent {} of {}: {} succeeded, {} ms on average",
+                sent, count, succeeded, avg
+            ),
+            None => format!("Sent {} of {}: {} succeeded", sent, count, succeeded),
+        });
+        self.state.error_time = Some(SystemTime::now());
+        if let Some(hist) = self.state.request_history.front_mut() {
+            hist.status = results.iter().rev().find_map(|outcome| match outcome {
+                Outcome::Status(status, _) => Some(*status),
+                Outcome::Error(_) => None,
+            });
+        }
+        self.update_history_success(&method, sent > 0 && succeeded == sent as u64);
+
+        self.state.response_info = None;
+        self.state.image = None;
+        self.state.output = Some(summary);
+        self.state.output_method = None;
+        self.state.collapsed.clear();
+        self.state.filter = None;
+        self.state.scroll_to_top();
+        self.state.scroll_x = 0;
+        self.state.input.mode = InputMode::ViewingResponse;
+        self.state.update_search_matches();
+        Ok(())
+    }
+}
+
+/// What a single repeated send came back with: its status and latency, or
+/// the error when it got no response.
+enum Outcome {
+    Status(u16, Duration),
+    Error(String),
+}
+
+/// The results of a repeat run: counts by status, latency figures for the
+/// requests that got a response, and each request in order.
+fn repeat_summary(
+    method: &str,
+    url: &str,
+    count: u32,
+    results: &[Outcome],
+    aborted: bool,
+) -> Value {
+    let mut statuses = Map::new();
+    let mut latencies = Vec::new();
+    let mut requests = Vec::new();
+    let mut succeeded = 0;
+    for outcome in results {
+        let key = match outcome {
+            Outcome::Status(status, _) => status.to_string(),
+            Outcome::Error(_) => "error".to_string(),
+        };
+        let seen = statuses.get(&key).and_then(Value::as_u64).unwrap_or(0);
+        statuses.insert(key, json!(seen + 1));
+
+        match outcome {
+            Outcome::Status(status, elapsed) => {
+                if (200..300).contains(status) {
+                    succeeded += 1;
+                }
+                latencies.push(elapsed.as_millis() as u64);
+                requests.push(json!({ "status": status, "latencyMs": elapsed.as_millis() as u64 }));
+            }
+            Outcome::Error(e) => requests.push(json!({ "error": e })),
+        }
+    }
+
+    let latency = if latencies.is_empty() {
+        Value::Null
+    } else {
+        json!({
+            "min": latencies.iter().min(),
+            "max": latencies.iter().max(),
+            "avg": latencies.iter().sum::<u64>() / latencies.len() as u64,
+        })
+    };
+    let sent = results.len();
+    json!({
+        "method": method,
+        "url": url,
+        "requested": count,
+        "sent": sent,
+        "aborted": aborted,
+        "succeeded": succeeded,
+        "successRate": if sent > 0 { succeeded as f64 / sent as f64 } else { 0.0 },
+        "statuses": statuses,
+        "latencyMs": latency,
+        "requests": requests,
+    })
+}
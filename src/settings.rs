@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use crate::{
+    config::Config,
+    error::AppResult,
+    state::AppState,
+    theme::{Theme, THEME_NAMES},
+    App,
+};
+
+/// A config value that can be changed from the settings screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    PdsHost,
+    RequestTimeout,
+    ErrorTimeout,
+    Theme,
+    HistorySize,
+}
+
+/// The settings screen's fields, in display order.
+pub const SETTINGS: &[Setting] = &[
+    Setting::PdsHost,
+    Setting::RequestTimeout,
+    Setting::ErrorTimeout,
+    Setting::Theme,
+    Setting::HistorySize,
+];
+
+impl Setting {
+    /// The setting's key in `config.toml`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Setting::PdsHost => "pds_host",
+            Setting::RequestTimeout => "request_timeout",
+            Setting::ErrorTimeout => "error_timeout",
+            Setting::Theme => "theme",
+            Setting::HistorySize => "history_size",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::PdsHost => "PDS host",
+            Setting::RequestTimeout => "Request timeout (seconds)",
+            Setting::ErrorTimeout => "Message timeout (seconds)",
+            Setting::Theme => "Theme",
+            Setting::HistorySize => "History size",
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            Setting::PdsHost => "Where requests go, unless the profile names a PDS".into(),
+            Setting::RequestTimeout => "How long to wait for a response".into(),
+            Setting::ErrorTimeout => "How long status bar messages stay up".into(),
+            Setting::Theme => format!("One of {}", THEME_NAMES.join(", ")),
+            Setting::HistorySize => "Most history entries to keep".into(),
+        }
+    }
+
+    /// The value in effect, as it's shown and edited.
+    pub fn current(self, state: &AppState, pds_host: &str) -> String {
+        match self {
+            Setting::PdsHost => pds_host.to_string(),
+            Setting::RequestTimeout => state.request_timeout.as_secs().to_string(),
+            Setting::ErrorTimeout => state.error_timeout.as_secs().to_string(),
+            Setting::Theme => state.theme_name.clone(),
+            Setting::HistorySize => state.history_size.to_string(),
+        }
+    }
+
+    /// The value as it's written to the config.
+    fn to_toml(self, state: &AppState, pds_host: &str) -> toml::Value {
+        match self {
+            Setting::PdsHost | Setting::Theme => self.current(state, pds_host).into(),
+            Setting::RequestTimeout => (state.request_timeout.as_secs() as i64).into(),
+            Setting::ErrorTimeout => (state.error_timeout.as_secs() as i64).into(),
+            Setting::HistorySize => (state.history_size as i64).into(),
+        }
+    }
+}
+
+/// A whole number above zero, for sizes and timeouts.
+fn positive(input: &str) -> Result<u64, String> {
+    match input.parse::<u64>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err("must be a whole number above zero".into()),
+    }
+}
+
+impl App {
+    /// Checks `input` for `setting` and applies it for this session,
+    /// returning a hint if it doesn't fit.
+    pub(crate) fn apply_setting(&mut self, setting: Setting, input: &str) -> Result<(), String> {
+        let input = input.trim();
+        match setting {
+            Setting::PdsHost => {
+                if !input.starts_with("https://") && !input.starts_with("http://") {
+                    return Err("must start with https:// or http://".into());
+                }
+                self.xrpc.pds_host = input.trim_end_matches('/').to_string();
+                self.state.server_info = None;
+                if self.state.offline.is_none() {
+                    self.describe_server();
+                }
+            }
+            Setting::RequestTimeout => {
+                let timeout = Duration::from_secs(positive(input)?);
+                self.state.request_timeout = timeout;
+                self.xrpc.timeout = timeout;
+            }
+            Setting::ErrorTimeout => {
+                self.state.error_timeout = Duration::from_secs(positive(input)?);
+            }
+            Setting::Theme => {
+                let theme = Theme::by_name(input)
+                    .ok_or_else(|| format!("must be one of {}", THEME_NAMES.join(", ")))?;
+                self.state.theme = theme;
+                self.state.theme_name = input.to_string();
+            }
+            Setting::HistorySize => {
+                let size = positive(input)? as usize;
+                self.state.history_size = size;
+                self.state.request_history.truncate(size);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every setting's current value to the config file.
+    pub(crate) fn save_settings(&self) -> AppResult<String> {
+        let values: Vec<_> = SETTINGS
+            .iter()
+            .map(|setting| {
+                (
+                    setting.key(),
+                    setting.to_toml(&self.state, &self.xrpc.pds_host),
+                )
+            })
+            .collect();
+        let path = Config::save_values(&values)?;
+        Ok(path.display().to_string())
+    }
+}
@@ -1,22 +1,217 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::VecDeque,
-    time::{Duration, SystemTime},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fs,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
 };
 use time::OffsetDateTime;
+use unicode_width::UnicodeWidthStr;
 
-use crate::commands::AVAILABLE_COMMANDS;
+use crate::{
+    commands::{self, ParamKind, Parameter, XrpcCommand},
+    config::config_dir,
+    content::Image,
+    json_diff::{self, DiffLine},
+    lexicon::Lexicon,
+    output::{self, OutputFormat, DEFAULT_EXPORT_FILENAME},
+    post,
+    proxy::Proxy,
+    theme::Theme,
+    viewer::{self, ViewLine},
+    xrpc::{RateLimit, Session},
+};
 
-const MAX_HISTORY: usize = 100;
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+pub const DEFAULT_RETRIES: u32 = 2;
+/// Parameters that mean your own account when left empty.
+pub const DEFAULT_SELF_PARAMS: &[&str] = &["repo", "actor", "did"];
+const MAX_SCROLL_POSITIONS: usize = 50;
+/// Most responses kept to go back to; the oldest are dropped first.
+const MAX_BACK_DEPTH: usize = 20;
+/// How long a second Ctrl+c has to confirm quitting with work in progress.
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestHistory {
     pub method: String,
     pub timestamp: OffsetDateTime,
     pub success: bool,
+    /// Empty for workflows like diffFollowers that send many requests.
     pub url: String,
     pub params: Vec<String>,
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Sent from the raw XRPC prompt; `params` holds the typed arguments.
+    #[serde(default)]
+    pub raw: bool,
+    /// The JSON body POSTed to a procedure; queries are GETs and have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<serde_json::Value>,
+}
+
+/// A line of the command list.
+#[derive(Debug, Clone, Copy)]
+pub enum CommandRow {
+    /// A group's header. `count` is how many of its commands match the
+    /// filter, shown when it's collapsed.
+    Group {
+        name: &'static str,
+        count: usize,
+        collapsed: bool,
+    },
+    Command(&'static XrpcCommand),
+}
+
+/// Which outcomes the history list shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HistoryOutcome {
+    #[default]
+    All,
+    Failed,
+    Succeeded,
+}
+
+const HISTORY_FILE: &str = "history.json";
+const FAVORITES_FILE: &str = "favorites.json";
+const COLLAPSED_GROUPS_FILE: &str = "collapsed_groups.json";
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// The command list's group for favorites, which comes first.
+pub const FAVORITES_GROUP: &str = "Favorites";
+
+/// Loads persisted history, starting empty if the file is missing or corrupt.
+pub fn load_history(limit: usize) -> VecDeque<RequestHistory> {
+    let mut history: VecDeque<RequestHistory> = config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(HISTORY_FILE)).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    history.truncate(limit);
+    history
+}
+
+pub fn save_history(history: &VecDeque<RequestHistory>) -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)?;
+
+    let json = serde_json::to_string(history).map_err(std::io::Error::other)?;
+    fs::write(dir.join(HISTORY_FILE), json)
+}
+
+/// Removes the persisted history, if there is any.
+pub fn remove_history() -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    match fs::remove_file(dir.join(HISTORY_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Loads the favorited method names, starting empty if the file is missing
+/// or corrupt.
+pub fn load_favorites() -> BTreeSet<String> {
+    load_names(FAVORITES_FILE)
+}
+
+pub fn save_favorites(favorites: &BTreeSet<String>) -> std::io::Result<()> {
+    save_names(FAVORITES_FILE, favorites)
+}
+
+/// Loads the command list groups that were left collapsed, starting with
+/// none if the file is missing or corrupt.
+pub fn load_collapsed_groups() -> BTreeSet<String> {
+    load_names(COLLAPSED_GROUPS_FILE)
+}
+
+pub fn save_collapsed_groups(groups: &BTreeSet<String>) -> std::io::Result<()> {
+    save_names(COLLAPSED_GROUPS_FILE, groups)
+}
+
+fn load_names(file: &str) -> BTreeSet<String> {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(file)).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_names(file: &str, names: &BTreeSet<String>) -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)?;
+
+    let json = serde_json::to_string(names).map_err(std::io::Error::other)?;
+    fs::write(dir.join(file), json)
+}
+
+/// A password session kept for the next launch, with the PDS it's for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSession {
+    pub pds_host: String,
+    #[serde(flatten)]
+    pub session: Session,
+}
+
+/// The saved sessions: one from launching without a profile, and one for
+/// each profile that's logged in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedSessions {
+    #[serde(default)]
+    default: Option<SavedSession>,
+    #[serde(default)]
+    profiles: BTreeMap<String, SavedSession>,
+}
+
+fn load_sessions() -> SavedSessions {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(SESSIONS_FILE)).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// The session saved for `profile`, or for launching without one, if any.
+pub fn load_session(profile: Option<&str>) -> Option<SavedSession> {
+    let mut sessions = load_sessions();
+    match profile {
+        Some(name) => sessions.profiles.remove(name),
+        None => sessions.default,
+    }
+}
+
+/// Saves `session` for `profile`, or removes the one saved for it if that's
+/// `None`. Other profiles' sessions are kept. The file holds tokens, so on
+/// Unix only its owner can read it.
+pub fn save_session(profile: Option<&str>, session: Option<SavedSession>) -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    let mut sessions = load_sessions();
+    match (profile, session) {
+        (Some(name), Some(session)) => {
+            sessions.profiles.insert(name.to_string(), session);
+        }
+        (Some(name), None) => {
+            sessions.profiles.remove(name);
+        }
+        (None, session) => sessions.default = session,
+    }
+    fs::create_dir_all(&dir)?;
+
+    let json = serde_json::to_string(&sessions).map_err(std::io::Error::other)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(dir.join(SESSIONS_FILE))?
+        .write_all(json.as_bytes())
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -24,23 +219,229 @@ pub enum InputMode {
     #[default]
     Normal,
     Password,
+    /// Typing the sign-in code emailed to an account with 2FA, holding on to
+    /// the credentials to send with it.
+    AuthFactor {
+        identifier: String,
+        password: String,
+    },
     Command,
     History,
+    /// Typing a method name to narrow the history list down to.
+    HistoryFilter,
     CommandBuilder {
         command: String,
         current_param: usize,
         params: Vec<String>,
     },
     ViewingResponse,
+    SearchResponse,
+    /// Typing a path to narrow the response down to.
+    FilterResponse,
+    /// Typing how many times to send the last request again.
+    RepeatCount,
+    /// Typing how many seconds to wait between auto-refreshes.
+    AutoRefreshInterval,
+    /// Typing an arbitrary NSID and its query string or JSON body.
+    RawXrpc,
+    /// Typing `name: value` to set a custom header, or `name:` to remove it.
+    Headers,
+    /// Changing config values at runtime. `editing` is set while the
+    /// selected one's new value is being typed.
+    Settings {
+        selected: usize,
+        editing: bool,
+    },
+    /// Waiting for "yes" to be typed before a destructive command is sent.
+    ConfirmDestructive {
+        command: String,
+        params: Vec<String>,
+    },
+    /// Showing `AppState::preview` and waiting for the go-ahead to send it.
+    ConfirmRequest {
+        command: String,
+        params: Vec<String>,
+    },
+    ErrorDetail {
+        previous: Box<InputMode>,
+        previous_scroll: u16,
+    },
+    /// Listing every keybinding over `previous`, scrolled down `scroll` lines.
+    Help {
+        previous: Box<InputMode>,
+        scroll: u16,
+    },
+    /// Showing the lexicon of `method` over `previous`.
+    Lexicon {
+        method: String,
+        previous: Box<InputMode>,
+        previous_scroll: u16,
+    },
+    /// Looking up a handle's DID or a DID's handle over `previous`, whose
+    /// input is set aside until the lookup closes.
+    Lookup {
+        previous: Box<InputMode>,
+        saved_input: String,
+        saved_cursor: usize,
+    },
+}
+
+impl InputMode {
+    /// A short name for the mode, as shown in the breadcrumb and help.
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputMode::Normal => "Login",
+            InputMode::Password => "Password",
+            InputMode::AuthFactor { .. } => "Sign-in Code",
+            InputMode::Command => "Commands",
+            InputMode::History => "History",
+            InputMode::HistoryFilter => "Filter",
+            InputMode::CommandBuilder { .. } => "Builder",
+            InputMode::ViewingResponse => "Response",
+            InputMode::SearchResponse => "Search",
+            InputMode::FilterResponse => "Filter",
+            InputMode::RepeatCount => "Repeat",
+            InputMode::AutoRefreshInterval => "Auto-refresh",
+            InputMode::RawXrpc => "Raw XRPC",
+            InputMode::Headers => "Headers",
+            InputMode::Settings { .. } => "Settings",
+            InputMode::ConfirmDestructive { .. } => "Confirm",
+            InputMode::ConfirmRequest { .. } => "Preview",
+            InputMode::ErrorDetail { .. } => "Error",
+            InputMode::Help { .. } => "Help",
+            InputMode::Lexicon { .. } => "Lexicon",
+            InputMode::Lookup { .. } => "Lookup",
+        }
+    }
+
+    /// The path to this mode, outermost first, e.g. `Response › Search`.
+    /// Overlays follow the mode they were opened over, and the command
+    /// builder shows its command and which parameter is being entered.
+    pub fn breadcrumb(&self) -> Vec<String> {
+        match self {
+            InputMode::Help { previous, .. }
+            | InputMode::ErrorDetail { previous, .. }
+            | InputMode::Lexicon { previous, .. }
+            | InputMode::Lookup { previous, .. } => {
+                let mut crumbs = previous.breadcrumb();
+                crumbs.push(self.name().to_string());
+                crumbs
+            }
+            InputMode::Password | InputMode::AuthFactor { .. } => {
+                vec!["Login".to_string(), self.name().to_string()]
+            }
+            InputMode::HistoryFilter => vec!["History".to_string(), self.name().to_string()],
+            InputMode::SearchResponse
+            | InputMode::FilterResponse
+            | InputMode::RepeatCount
+            | InputMode::AutoRefreshInterval => {
+                vec!["Response".to_string(), self.name().to_string()]
+            }
+            InputMode::Settings { editing: true, .. } => {
+                vec![self.name().to_string(), "Editing".to_string()]
+            }
+            InputMode::CommandBuilder {
+                command,
+                current_param,
+                ..
+            } => {
+                let total = commands::find(command).map_or(0, |cmd| cmd.parameters.len());
+                let step = if total > 0 {
+                    format!("{} ({}/{})", command, (current_param + 1).min(total), total)
+                } else {
+                    command.clone()
+                };
+                vec!["Commands".to_string(), step]
+            }
+            InputMode::ConfirmDestructive { command, .. }
+            | InputMode::ConfirmRequest { command, .. } => vec![
+                "Commands".to_string(),
+                command.clone(),
+                self.name().to_string(),
+            ],
+            _ => vec![self.name().to_string()],
+        }
+    }
+
+    /// Whether the input field is being edited in this mode.
+    pub fn accepts_text(&self) -> bool {
+        matches!(
+            self,
+            InputMode::Normal
+                | InputMode::Password
+                | InputMode::AuthFactor { .. }
+                | InputMode::Command
+                | InputMode::HistoryFilter
+                | InputMode::CommandBuilder { .. }
+                | InputMode::SearchResponse
+                | InputMode::FilterResponse
+                | InputMode::RepeatCount
+                | InputMode::AutoRefreshInterval
+                | InputMode::RawXrpc
+                | InputMode::Headers
+                | InputMode::Settings { editing: true, .. }
+                | InputMode::Lookup { .. }
+                | InputMode::ConfirmDestructive { .. }
+        )
+    }
+}
+
+/// A built request, shown before it's sent.
+#[derive(Debug, Clone)]
+pub struct RequestPreview {
+    pub http_method: &'static str,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Pretty-printed JSON body, for POSTs.
+    pub body: Option<String>,
+    /// Handles resolved while building the URL.
+    pub notes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct InputState {
     pub content: String,
+    /// Cursor position in chars, not bytes.
     pub cursor_position: usize,
     pub mode: InputMode,
     pub completion_index: Option<usize>,
     pub completion_matches: Vec<String>,
+    /// Earlier versions of the content, most recent last, for Ctrl+Z.
+    undo: VecDeque<UndoStep>,
+}
+
+/// Most edits Ctrl+Z can step back through.
+const UNDO_LIMIT: usize = 100;
+
+/// The input as it was before an edit.
+#[derive(Debug, Clone)]
+struct UndoStep {
+    content: String,
+    cursor_position: usize,
+    /// The content the edit left behind. If it's changed since, the input
+    /// was replaced by something other than typing, and this step is stale.
+    after: String,
+}
+
+/// How well `method` matches typed input, lower is better: an exact prefix,
+/// then a case-insensitive substring, then the input's characters appearing
+/// in order. `None` if it doesn't match at all.
+fn completion_rank(method: &str, input: &str) -> Option<u8> {
+    if method.starts_with(input) {
+        return Some(0);
+    }
+
+    let method = method.to_lowercase();
+    let input = input.to_lowercase();
+    if method.contains(&input) {
+        return Some(1);
+    }
+
+    let mut remaining = method.chars();
+    input
+        .chars()
+        .all(|c| remaining.any(|m| m == c))
+        .then_some(2)
 }
 
 impl InputState {
@@ -52,11 +453,24 @@ impl InputState {
                 return;
             }
 
-            self.completion_matches = AVAILABLE_COMMANDS
+            let mut ranked: Vec<(u8, &str)> = commands::all()
                 .iter()
-                .map(|cmd| cmd.method)
-                .filter(|method| method.starts_with(&self.content))
-                .map(|s| s.to_string())
+                .filter_map(|cmd| {
+                    // An alias completes to its method, ranked by whichever matches better
+                    let alias_rank = cmd
+                        .alias
+                        .and_then(|alias| completion_rank(alias, &self.content));
+                    let rank = completion_rank(cmd.method, &self.content)
+                        .into_iter()
+                        .chain(alias_rank)
+                        .min()?;
+                    Some((rank, cmd.method))
+                })
+                .collect();
+            ranked.sort_by_key(|(rank, _)| *rank);
+            self.completion_matches = ranked
+                .into_iter()
+                .map(|(_, method)| method.to_string())
                 .collect();
 
             self.completion_index = if self.completion_matches.is_empty() {
@@ -73,18 +487,72 @@ impl InputState {
         }
     }
 
-    pub fn handle_key(&mut self, key: KeyCode) -> bool {
-        match key {
+    /// Byte offset of the char at `char_idx`, or the end of the content.
+    fn byte_index(&self, char_idx: usize) -> usize {
+        self.content
+            .char_indices()
+            .nth(char_idx)
+            .map_or(self.content.len(), |(idx, _)| idx)
+    }
+
+    /// Inserts pasted text at the cursor. Newlines and other control
+    /// characters are dropped since every field is a single line, and a
+    /// stray escape or tab would end up hidden in a masked password.
+    pub fn insert_str(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        let before = self.snapshot();
+        let idx = self.byte_index(self.cursor_position);
+        self.content.insert_str(idx, &text);
+        self.cursor_position += text.chars().count();
+        self.record_undo(before);
+        self.update_completions();
+    }
+
+    /// Replaces the whole input with `text`, with the cursor at the end.
+    /// Ctrl+Z brings back what was there.
+    pub fn replace(&mut self, text: &str) {
+        let before = self.snapshot();
+        self.content = text.to_string();
+        self.move_cursor_to_end();
+        self.record_undo(before);
+        self.update_completions();
+    }
+
+    pub fn move_cursor_to_end(&mut self) {
+        self.cursor_position = self.content.chars().count();
+    }
+
+    /// Terminal column of the cursor, accounting for wide characters.
+    pub fn cursor_column(&self) -> usize {
+        if self.mode == InputMode::Password {
+            // Masked with one bullet per char
+            return self.cursor_position;
+        }
+        self.content[..self.byte_index(self.cursor_position)].width()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return self.edit_line(key.code);
+        }
+
+        match key.code {
             KeyCode::Char(c) => {
-                self.content.insert(self.cursor_position, c);
+                let before = self.snapshot();
+                let idx = self.byte_index(self.cursor_position);
+                self.content.insert(idx, c);
                 self.cursor_position += 1;
+                self.record_undo(before);
                 self.update_completions();
                 true
             }
             KeyCode::Backspace => {
                 if self.cursor_position > 0 {
-                    self.content.remove(self.cursor_position - 1);
+                    let before = self.snapshot();
+                    let idx = self.byte_index(self.cursor_position - 1);
+                    self.content.remove(idx);
                     self.cursor_position -= 1;
+                    self.record_undo(before);
                     self.update_completions();
                 }
                 true
@@ -96,60 +564,1517 @@ impl InputState {
                 true
             }
             KeyCode::Right => {
-                if self.cursor_position < self.content.len() {
+                if self.cursor_position < self.content.chars().count() {
                     self.cursor_position += 1;
                 }
                 true
             }
+            KeyCode::Home => {
+                self.cursor_position = 0;
+                true
+            }
+            KeyCode::End => {
+                self.move_cursor_to_end();
+                true
+            }
             _ => false,
         }
     }
+
+    /// Readline-style editing for Ctrl+A/E (jump to start/end), Ctrl+U/K
+    /// (delete to start/end), Ctrl+W (delete the previous word) and
+    /// Ctrl+Left/Right (jump a word), plus Ctrl+Z to undo.
+    fn edit_line(&mut self, key: KeyCode) -> bool {
+        let end = self.content.chars().count();
+        match key {
+            KeyCode::Char('a') => self.cursor_position = 0,
+            KeyCode::Char('e') => self.cursor_position = end,
+            KeyCode::Left => self.cursor_position = self.word_start(),
+            KeyCode::Right => self.cursor_position = self.word_end(),
+            KeyCode::Char('u') => self.delete_chars(0, self.cursor_position),
+            KeyCode::Char('k') => self.delete_chars(self.cursor_position, end),
+            KeyCode::Char('w') => self.delete_chars(self.word_start(), self.cursor_position),
+            KeyCode::Char('z') => self.undo(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Start of the word before the cursor. Words are runs of letters and
+    /// digits, so Ctrl+W takes a URI or NSID apart one segment at a time;
+    /// any punctuation or spaces right before the cursor go with the word.
+    fn word_start(&self) -> usize {
+        let before: Vec<char> = self.content.chars().take(self.cursor_position).collect();
+        let mut start = before.len();
+        while start > 0 && !before[start - 1].is_alphanumeric() {
+            start -= 1;
+        }
+        while start > 0 && before[start - 1].is_alphanumeric() {
+            start -= 1;
+        }
+        start
+    }
+
+    /// End of the word after the cursor, the counterpart of `word_start`.
+    fn word_end(&self) -> usize {
+        let after: Vec<char> = self.content.chars().skip(self.cursor_position).collect();
+        let mut end = 0;
+        while end < after.len() && !after[end].is_alphanumeric() {
+            end += 1;
+        }
+        while end < after.len() && after[end].is_alphanumeric() {
+            end += 1;
+        }
+        self.cursor_position + end
+    }
+
+    /// Removes the chars from `start` up to `end` and leaves the cursor
+    /// where they were.
+    fn delete_chars(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let before = self.snapshot();
+        let range = self.byte_index(start)..self.byte_index(end);
+        self.content.replace_range(range, "");
+        self.cursor_position = start;
+        self.record_undo(before);
+        self.update_completions();
+    }
+
+    fn snapshot(&self) -> (String, usize) {
+        (self.content.clone(), self.cursor_position)
+    }
+
+    /// Remembers the input from `before` an edit so Ctrl+Z can restore it.
+    fn record_undo(&mut self, (content, cursor_position): (String, usize)) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(UndoStep {
+            content,
+            cursor_position,
+            after: self.content.clone(),
+        });
+    }
+
+    /// Restores the input from before the last edit. Steps left over from a
+    /// different field (the content was cleared or replaced since) are
+    /// dropped instead.
+    fn undo(&mut self) {
+        match self.undo.pop_back() {
+            Some(step) if step.after == self.content => {
+                self.content = step.content;
+                self.cursor_position = step.cursor_position;
+                self.update_completions();
+            }
+            _ => self.undo.clear(),
+        }
+    }
+}
+
+/// Everything known about the most recent failure, for the detail view.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorDetail {
+    pub message: String,
+    pub url: Option<String>,
+    pub status: Option<u16>,
+    pub body: Option<String>,
+}
+
+/// What `com.atproto.server.describeServer` says about the PDS.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub did: String,
+    #[serde(default)]
+    pub available_user_domains: Vec<String>,
+    #[serde(default)]
+    pub invite_code_required: bool,
+    #[serde(default)]
+    pub links: ServerLinks,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerLinks {
+    pub privacy_policy: Option<String>,
+    pub terms_of_service: Option<String>,
+}
+
+/// Status line details for the response being viewed.
+#[derive(Debug, Clone)]
+pub struct ResponseInfo {
+    pub status: u16,
+    pub reason: String,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchState {
+    pub query: String,
+    pub case_sensitive: bool,
+    pub match_lines: Vec<usize>,
+    pub current_match: Option<usize>,
+}
+
+impl SearchState {
+    /// Byte ranges of every match of the query in `haystack`. Case folding is
+    /// ASCII-only so the ranges stay valid for the original string.
+    pub fn find_all(&self, haystack: &str) -> Vec<(usize, usize)> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+
+        let (haystack, query) = if self.case_sensitive {
+            (haystack.to_string(), self.query.clone())
+        } else {
+            (
+                haystack.to_ascii_lowercase(),
+                self.query.to_ascii_lowercase(),
+            )
+        };
+
+        haystack
+            .match_indices(&query)
+            .map(|(start, m)| (start, start + m.len()))
+            .collect()
+    }
+}
+
+/// A running auto-refresh of the response.
+#[derive(Debug, Clone)]
+pub struct AutoRefresh {
+    pub interval: Duration,
+    /// When the request is next sent.
+    pub next: Instant,
+    /// Refreshes that have failed in a row.
+    pub failures: u32,
+    /// The request being refreshed, so sending another one stops it.
+    pub url: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub input: InputState,
-    pub auth_token: Option<String>,
-    pub refresh_token: Option<String>,
+    /// When the access token expires, from its `exp` claim.
+    pub token_expires: Option<SystemTime>,
     pub output: Option<serde_json::Value>,
+    /// The command that produced `output`, which decides whether it gets a
+    /// summary view.
+    pub output_method: Option<String>,
+    /// Show summaries (e.g. a profile card) above responses that have one,
+    /// toggled with `v`.
+    pub show_summary: bool,
+    /// A response kept with `P` to compare later ones against.
+    pub pinned: Option<serde_json::Value>,
+    /// Show the changes from the pinned response instead of the response,
+    /// toggled with `d`.
+    pub show_diff: bool,
+    /// Re-sending the last request on a timer, toggled with `a`.
+    pub auto_refresh: Option<AutoRefresh>,
+    /// The interval last used for auto-refresh, offered next time.
+    pub auto_refresh_interval: Duration,
     pub error: Option<String>,
     pub error_time: Option<SystemTime>,
-    pub pds_host: String,
+    /// Base time before a status message is cleared.
+    pub error_timeout: Duration,
+    pub request_timeout: Duration,
+    /// Per-method overrides of `request_timeout`.
+    pub method_timeouts: HashMap<String, Duration>,
+    /// Headers sent with every command, by lowercase name. Editable while
+    /// running with `Ctrl+t`.
+    pub headers: BTreeMap<String, String>,
+    /// Extra headers for particular methods, on top of `headers`.
+    pub method_headers: HashMap<String, BTreeMap<String, String>>,
+    pub last_error: Option<ErrorDetail>,
+    pub response_info: Option<ResponseInfo>,
+    /// When the in-flight request started, if there is one.
+    pub loading: Option<Instant>,
+    /// Bytes saved so far when the in-flight request is a download.
+    pub downloaded: Option<u64>,
+    /// Fetched once in the background, or why that failed.
+    pub server_info: Option<Result<ServerInfo, String>>,
     pub is_authenticated: bool,
+    /// Extra attempts for requests that fail in a way that might not last.
+    pub retries: u32,
+    /// The quota reported with the most recent response, if the PDS sends
+    /// one.
+    pub rate_limit: Option<RateLimit>,
+    /// Unread notifications, shown in the status bar once logged in.
+    pub unread_count: Option<u64>,
     pub request_history: VecDeque<RequestHistory>,
+    /// Most history entries to keep, in memory and on disk.
+    pub history_size: usize,
+    pub theme: Theme,
+    /// Name of `theme`, as the config spells it.
+    pub theme_name: String,
     pub quit: bool,
+    /// The `--profile` launched with, whose session is saved and resumed.
+    pub profile: Option<String>,
     pub identifier: Option<String>,
+    /// The session's handle, as returned by the PDS.
+    pub handle: Option<String>,
+    /// The session's DID, used as the default for your own `repo` or
+    /// `actor`.
+    pub did: Option<String>,
+    /// Parameters that default to `did` when left empty: a name like
+    /// `actor`, or `method.param` for one command's.
+    pub self_params: Vec<String>,
+    /// Directory of canned responses to show instead of sending requests.
+    pub offline: Option<PathBuf>,
     pub selected_command_index: Option<usize>,
+    /// Rows scrolled down, counting each row of a wrapped line.
     pub scroll_offset: u16,
+    /// The line of the response view that Space, `y`, `p` and `o` act on.
+    /// Up and Down move it, and the view scrolls to keep it in sight.
+    pub cursor_line: usize,
+    pub search: SearchState,
+    /// Path applied to the response before it's shown, e.g. `feed.0.post`.
+    pub filter: Option<String>,
+    /// Paths of objects and arrays folded in the response view.
+    pub collapsed: HashSet<String>,
+    pub line_numbers: bool,
+    /// Whether long response lines wrap; otherwise they scroll sideways.
+    pub wrap_lines: bool,
+    /// Columns scrolled right when lines don't wrap.
+    pub scroll_x: u16,
+    /// Validation hint for the setting being edited.
+    pub param_error: Option<String>,
+    pub resolve_handles: bool,
+    pub curl_include_token: bool,
+    /// Confirm built commands before sending them, toggled with `Ctrl+p`.
+    pub preview_requests: bool,
+    pub preview: Option<RequestPreview>,
+    pub export_filename: String,
+    /// Format used by `e`, cycled with `E`.
+    pub export_format: OutputFormat,
+    /// Whether `e` wraps the response in the request's details, toggled
+    /// with `Ctrl+e`.
+    pub export_envelope: bool,
+    /// Where the last export or download was written, for `O` to open.
+    pub last_export: Option<PathBuf>,
+    /// The last response, if it was an image, for `s` to save.
+    pub image: Option<Image>,
+    /// The outbound proxy requests go through, if any.
+    pub proxy: Option<Proxy>,
+    /// Whether TLS certificates go unchecked, flagged in the status bar.
+    pub insecure_tls: bool,
+    pub resolved_handles: HashMap<String, String>,
+    /// Lexicons fetched by `d`, by method.
+    pub lexicons: HashMap<String, Lexicon>,
+    /// Outcomes shown in the history list, toggled with `f` and `s`.
+    pub history_outcome: HistoryOutcome,
+    /// Text the history list's methods are filtered by.
+    pub history_query: String,
+    /// Methods pinned to the top of the command list.
+    pub favorites: BTreeSet<String>,
+    /// Command list groups showing only their header.
+    pub collapsed_groups: BTreeSet<String>,
+    /// Last scroll offset per request URL, most recent first.
+    pub scroll_positions: VecDeque<(String, u16)>,
+    /// The last handle ↔ DID lookup's answer, or why it failed.
+    pub lookup: Option<Result<String, String>>,
+    /// When copying the access token was first asked for; a second press
+    /// before the warning clears copies it.
+    pub token_copy_requested: Option<SystemTime>,
+    /// When clearing the history was first asked for.
+    pub history_clear_requested: Option<SystemTime>,
+    /// When Ctrl+c was first pressed with something that quitting would lose.
+    pub quit_requested: Option<SystemTime>,
+    /// Responses left by following an `at://` URI, with the command that
+    /// produced them, where they were scrolled to and their focused line,
+    /// most recent last.
+    pub back: VecDeque<(serde_json::Value, Option<String>, u16, usize)>,
 }
 
 impl AppState {
     pub fn update(&mut self) {
-        if let Some(error_time) = self.error_time {
-            if error_time.elapsed().unwrap_or_default() >= Duration::from_secs(5) {
+        if let (Some(error), Some(error_time)) = (&self.error, self.error_time) {
+            if error_time.elapsed().unwrap_or_default() >= self.error_display_time(error) {
                 self.error = None;
                 self.error_time = None;
             }
         }
     }
+
+    /// Writes everything kept between sessions: the history, unless offline,
+    /// the favorites and the collapsed command groups.
+    pub fn save(&self) -> std::io::Result<()> {
+        if self.offline.is_none() {
+            save_history(&self.request_history)?;
+        }
+        save_favorites(&self.favorites)?;
+        save_collapsed_groups(&self.collapsed_groups)
+    }
+
+    /// How long a status message stays up. Long messages (like a server's
+    /// JSON error body) get extra time to be read, up to four times the base.
+    fn error_display_time(&self, message: &str) -> Duration {
+        let extra = message.chars().count().saturating_sub(80) as u32;
+        (self.error_timeout + Duration::from_millis(50) * extra).min(self.error_timeout * 4)
+    }
+
+    /// Commands shown in the command list, filtered by a case-insensitive
+    /// substring match of the input against the method and description.
+    /// Favorites come first, otherwise the built-in order is kept.
+    pub fn visible_commands(&self) -> Vec<&'static XrpcCommand> {
+        let query = self.input.content.to_lowercase();
+        let mut visible: Vec<_> = commands::all()
+            .iter()
+            .filter(|cmd| {
+                query.is_empty()
+                    || cmd.method.to_lowercase().contains(&query)
+                    || cmd.alias.is_some_and(|alias| alias.contains(&query))
+                    || cmd.description.to_lowercase().contains(&query)
+            })
+            .collect();
+        visible.sort_by_key(|cmd| !self.is_favorite(cmd.method));
+        visible
+    }
+
+    pub fn is_favorite(&self, method: &str) -> bool {
+        self.favorites.contains(method)
+    }
+
+    /// The group `cmd` is listed under: favorites, or its namespace.
+    pub fn group_of(&self, cmd: &'static XrpcCommand) -> &'static str {
+        if self.is_favorite(cmd.method) {
+            FAVORITES_GROUP
+        } else {
+            commands::namespace(cmd.method)
+        }
+    }
+
+    /// The command list as shown: `visible_commands` under a header for
+    /// each group, in the order the groups first appear. Collapsed groups
+    /// leave their commands out.
+    pub fn command_rows(&self) -> Vec<CommandRow> {
+        let mut groups: Vec<(&'static str, Vec<&'static XrpcCommand>)> = Vec::new();
+        for cmd in self.visible_commands() {
+            let name = self.group_of(cmd);
+            match groups.iter_mut().find(|(group, _)| *group == name) {
+                Some((_, members)) => members.push(cmd),
+                None => groups.push((name, vec![cmd])),
+            }
+        }
+
+        let mut rows = Vec::new();
+        for (name, members) in groups {
+            let collapsed = self.collapsed_groups.contains(name);
+            rows.push(CommandRow::Group {
+                name,
+                count: members.len(),
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(members.into_iter().map(CommandRow::Command));
+            }
+        }
+        rows
+    }
+
+    /// Favorites or unfavorites the selected command, keeping it selected
+    /// wherever it moves to in the list.
+    pub fn toggle_favorite(&mut self) {
+        let Some(cmd) = self.selected_command() else {
+            return;
+        };
+
+        if !self.favorites.remove(cmd.method) {
+            self.favorites.insert(cmd.method.to_string());
+        }
+        self.select_command(cmd);
+    }
+
+    /// Selects `cmd`, or its group's header if the group is collapsed.
+    fn select_command(&mut self, cmd: &'static XrpcCommand) {
+        let group = self.group_of(cmd);
+        self.selected_command_index = self.command_rows().iter().position(|row| match row {
+            CommandRow::Command(listed) => listed.method == cmd.method,
+            CommandRow::Group {
+                name, collapsed, ..
+            } => *collapsed && *name == group,
+        });
+    }
+
+    /// Collapses or expands the selected group, or the group of the
+    /// selected command, and selects its header.
+    pub fn toggle_group(&mut self) {
+        let Some(group) = self.selected_group() else {
+            return;
+        };
+        if !self.collapsed_groups.remove(group) {
+            self.collapsed_groups.insert(group.to_string());
+        }
+        self.selected_command_index = self
+            .command_rows()
+            .iter()
+            .position(|row| matches!(row, CommandRow::Group { name, .. } if *name == group));
+    }
+
+    /// The group whose header or command is selected.
+    pub fn selected_group(&self) -> Option<&'static str> {
+        match self.selected_row()? {
+            CommandRow::Group { name, .. } => Some(name),
+            CommandRow::Command(cmd) => Some(self.group_of(cmd)),
+        }
+    }
+
+    /// Whether the selected group is collapsed.
+    pub fn selected_group_collapsed(&self) -> bool {
+        matches!(
+            self.selected_row(),
+            Some(CommandRow::Group {
+                collapsed: true,
+                ..
+            })
+        )
+    }
+
+    /// Selects the first command in the list, or the first header if every
+    /// group is collapsed.
+    pub fn select_first_command(&mut self) {
+        let rows = self.command_rows();
+        self.selected_command_index = rows
+            .iter()
+            .position(|row| matches!(row, CommandRow::Command(_)))
+            .or((!rows.is_empty()).then_some(0));
+    }
+
+    /// The logged-in DID, for parameters of `method` that name an account
+    /// and are listed in `self_params`.
+    pub fn own_default(&self, method: &str, param: &Parameter) -> Option<&str> {
+        let listed = self.self_params.iter().any(|name| {
+            name == param.name
+                || name
+                    .strip_suffix(param.name)
+                    .and_then(|prefix| prefix.strip_suffix('.'))
+                    == Some(method)
+        });
+        self.did
+            .as_deref()
+            .filter(|_| listed && param.takes_account())
+    }
+
+    /// Fills the builder field for `param` with your DID, or swaps your DID
+    /// for your handle if it's already there. A list gets your DID added as
+    /// another entry instead.
+    pub fn fill_own_account(&mut self, param: &Parameter) -> Result<(), String> {
+        if !param.takes_account() {
+            return Err(format!("{} doesn't take an account", param.name));
+        }
+        let Some(did) = self.did.clone() else {
+            return Err("Log in to fill in your own account".into());
+        };
+
+        if param.is_array {
+            let typed = self.input.content.trim_end();
+            let separator = if typed.is_empty() || typed.ends_with(',') {
+                ""
+            } else {
+                ","
+            };
+            self.input.move_cursor_to_end();
+            self.input.insert_str(&format!("{}{}", separator, did));
+            return Ok(());
+        }
+
+        // A handle only works in a DID field if it'll be resolved
+        let handle = self
+            .handle
+            .clone()
+            .filter(|_| param.kind == ParamKind::AtIdentifier || self.resolve_handles);
+        match handle {
+            Some(handle) if self.input.content == did => self.input.replace(&handle),
+            _ => self.input.replace(&did),
+        }
+        Ok(())
+    }
+
+    /// Checks what's typed into a builder field the way Enter does,
+    /// returning a short hint when it wouldn't be accepted. Empty entries
+    /// pass, since Enter fills in the default.
+    pub fn check_param(&self, command: &str, param: &Parameter, input: &str) -> Result<(), String> {
+        if command == commands::POST && param.name == "text" {
+            let length = post::grapheme_count(input);
+            if length > post::POST_TEXT_LIMIT {
+                return Err(format!(
+                    "is {} characters, the limit is {}",
+                    length,
+                    post::POST_TEXT_LIMIT
+                ));
+            }
+        }
+
+        for value in param.values(input) {
+            // Handles typed into DID fields are resolved before sending
+            let resolvable =
+                param.kind == ParamKind::Did && self.resolve_handles && commands::is_handle(value);
+            if value.is_empty() || resolvable {
+                continue;
+            }
+            if let Err(hint) = param.kind.validate(value) {
+                return Err(if param.is_array {
+                    format!("{} {}", value, hint)
+                } else {
+                    hint.to_string()
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The custom headers sent with `method`.
+    pub fn headers_for(&self, method: &str) -> BTreeMap<String, String> {
+        let mut headers = self.headers.clone();
+        if let Some(extra) = self.method_headers.get(method) {
+            headers.extend(extra.clone());
+        }
+        headers
+    }
+
+    pub fn timeout_for(&self, method: &str) -> Duration {
+        self.method_timeouts
+            .get(method)
+            .copied()
+            .unwrap_or(self.request_timeout)
+    }
+
+    /// The part of the response being shown: the whole output, or whatever
+    /// the filter path selects from it.
+    pub fn displayed_output(&self) -> Option<&serde_json::Value> {
+        let output = self.output.as_ref()?;
+        match &self.filter {
+            Some(path) => output::select_path(output, path),
+            None => Some(output),
+        }
+    }
+
+    /// Path from the top of the whole response to the value starting on
+    /// `line` of the view, like `feed[3].post.author.handle`, so it can be
+    /// pasted back into the filter. Empty for the whole response.
+    pub fn path_at(&self, line: usize) -> Option<String> {
+        let (pointer, _) = self.value_at(line)?;
+        // Under a filter, the view starts at the filtered node
+        let filter = self.filter.as_deref().unwrap_or_default();
+        let pointer = format!("{}{}", output::path_to_pointer(filter)?, pointer);
+        output::path_from_pointer(self.output.as_ref()?, &pointer)
+    }
+
+    /// The changes from the pinned response to the current one, when the
+    /// diff is being shown.
+    pub fn diff_view(&self) -> Option<Vec<DiffLine>> {
+        if !self.show_diff {
+            return None;
+        }
+        Some(json_diff::diff_lines(
+            self.pinned.as_ref()?,
+            self.output.as_ref()?,
+        ))
+    }
+
+    /// The displayed output laid out line by line, with folded nodes
+    /// collapsed.
+    pub fn view_lines(&self) -> Vec<ViewLine> {
+        self.displayed_output()
+            .map(|output| viewer::view_lines(output, &self.collapsed))
+            .unwrap_or_default()
+    }
+
+    /// Folds or unfolds the node at `line` of the view, returning the line
+    /// it now starts on.
+    pub fn toggle_fold(&mut self, line: usize) -> Option<usize> {
+        let path = self.view_lines().get(line)?.fold_path().to_string();
+        if !self.collapsed.remove(&path) {
+            self.collapsed.insert(path.clone());
+        }
+        self.update_search_matches();
+        self.view_lines()
+            .iter()
+            .position(|line| line.opens.as_deref() == Some(path.as_str()))
+    }
+
+    /// The value starting on `line` of the view and its JSON Pointer.
+    pub fn value_at(&self, line: usize) -> Option<(String, &serde_json::Value)> {
+        let pointer = self.view_lines().get(line)?.pointer.clone();
+        let value = self.displayed_output()?.pointer(&pointer)?;
+        Some((pointer, value))
+    }
+
+    /// The items of a feed response while they're shown as a list of posts.
+    pub fn feed_view(&self) -> Option<&Vec<serde_json::Value>> {
+        let method = self.output_method.as_deref()?;
+        if !self.show_summary
+            || self.show_diff
+            || self.filter.is_some()
+            || !commands::FEED_METHODS.contains(&method)
+        {
+            return None;
+        }
+        self.output.as_ref()?.get("feed")?.as_array()
+    }
+
+    /// The feed item selected in the list of posts: the one the JSON view's
+    /// focused line is in, so `y`, `o` and `p` act on it.
+    pub fn selected_feed_item(&self) -> usize {
+        let lines = self.view_lines();
+        let Some(line) = lines.get(self.cursor_line) else {
+            return 0;
+        };
+        line.pointer
+            .strip_prefix("/feed/")
+            .and_then(|rest| rest.split('/').next()?.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Selects feed item `index` by focusing its post in the JSON view.
+    pub fn select_feed_item(&mut self, index: usize) {
+        let item = format!("/feed/{}", index);
+        let post = format!("{}/post", item);
+        let lines = self.view_lines();
+        // The item's own line is all that's left of it when it's folded
+        let line = lines
+            .iter()
+            .position(|line| line.pointer == post)
+            .or_else(|| lines.iter().position(|line| line.pointer == item));
+        if let Some(line) = line {
+            self.cursor_line = line;
+        }
+    }
+
+    /// Whether the response view is of JSON lines that can be focused,
+    /// rather than a diff or an error.
+    pub fn focuses_lines(&self) -> bool {
+        self.diff_view().is_none() && self.displayed_output().is_some()
+    }
+
+    /// Shows the response from its first line, e.g. once a new one comes in.
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_offset = 0;
+        self.cursor_line = 0;
+    }
+
+    /// The pagination cursor returned with the current response, if any.
+    pub fn response_cursor(&self) -> Option<&str> {
+        self.output.as_ref()?.get("cursor")?.as_str()
+    }
+
+    /// Records the current scroll offset against the most recent request so
+    /// re-opening it from history returns to the same place.
+    pub fn remember_scroll(&mut self) {
+        let Some(url) = self
+            .request_history
+            .front()
+            .map(|hist| hist.url.clone())
+            .filter(|url| !url.is_empty())
+        else {
+            return;
+        };
+
+        self.scroll_positions.retain(|(key, _)| *key != url);
+        self.scroll_positions.push_front((url, self.scroll_offset));
+        self.scroll_positions.truncate(MAX_SCROLL_POSITIONS);
+    }
+
+    /// Keeps `output` from `method`, shown at `scroll_offset` with
+    /// `cursor_line` focused, to go back to once something else replaces it.
+    pub fn push_back(
+        &mut self,
+        output: serde_json::Value,
+        method: Option<String>,
+        scroll_offset: u16,
+        cursor_line: usize,
+    ) {
+        if self.back.len() == MAX_BACK_DEPTH {
+            self.back.pop_front();
+        }
+        self.back
+            .push_back((output, method, scroll_offset, cursor_line));
+    }
+
+    /// What quitting now would lose: a request in flight, or a command or
+    /// input that's been typed but not sent.
+    pub fn work_in_progress(&self) -> Option<&'static str> {
+        if self.loading.is_some() {
+            return Some("a request is still loading");
+        }
+        let typed = !self.input.content.is_empty();
+        match &self.input.mode {
+            InputMode::CommandBuilder { params, .. } if typed || !params.is_empty() => {
+                Some("the command being built will be lost")
+            }
+            InputMode::ConfirmRequest { .. } | InputMode::ConfirmDestructive { .. } => {
+                Some("the command hasn't been sent")
+            }
+            InputMode::RawXrpc | InputMode::Headers | InputMode::Settings { .. } if typed => {
+                Some("what you've typed will be lost")
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether Ctrl+c should quit: straight away when there's nothing to
+    /// lose, otherwise only when pressed again within a few seconds.
+    pub fn confirm_quit(&mut self) -> bool {
+        let Some(loss) = self.work_in_progress() else {
+            self.quit_requested = None;
+            return true;
+        };
+
+        let confirmed = self
+            .quit_requested
+            .take()
+            .and_then(|requested| requested.elapsed().ok())
+            .is_some_and(|elapsed| elapsed < QUIT_CONFIRM_WINDOW);
+        if !confirmed {
+            self.quit_requested = Some(SystemTime::now());
+            self.error = Some(format!("Press Ctrl+c again to quit; {}", loss));
+            self.error_time = Some(SystemTime::now());
+        }
+        confirmed
+    }
+
+    /// Restores the response that was showing before the last drill-down,
+    /// its scroll offset and its focused line.
+    pub fn go_back(&mut self) {
+        let Some((output, method, scroll_offset, cursor_line)) = self.back.pop_back() else {
+            return;
+        };
+        self.output = Some(output);
+        self.output_method = method;
+        self.scroll_offset = scroll_offset;
+        self.cursor_line = cursor_line;
+        self.scroll_x = 0;
+        self.filter = None;
+        self.collapsed.clear();
+        self.update_search_matches();
+    }
+
+    /// The remembered scroll offset for the most recent request, if any.
+    pub fn saved_scroll(&self) -> Option<u16> {
+        let url = &self.request_history.front()?.url;
+        if url.is_empty() {
+            return None;
+        }
+        self.scroll_positions
+            .iter()
+            .find(|(key, _)| key == url)
+            .map(|(_, offset)| *offset)
+    }
+
+    /// Recomputes which lines of the view match the search. Matches inside
+    /// folded nodes aren't counted until they're unfolded.
+    pub fn update_search_matches(&mut self) {
+        self.search.match_lines = self
+            .view_lines()
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !self.search.find_all(&line.text).is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        self.search.current_match = None;
+    }
+
+    /// The command under the cursor in the visible (filtered) list, if any.
+    pub fn selected_command(&self) -> Option<&'static XrpcCommand> {
+        match self.selected_row()? {
+            CommandRow::Command(cmd) => Some(cmd),
+            CommandRow::Group { .. } => None,
+        }
+    }
+
+    /// The command list line under the cursor, if any.
+    pub fn selected_row(&self) -> Option<CommandRow> {
+        self.selected_command_index
+            .and_then(|idx| self.command_rows().get(idx).copied())
+    }
+
+    /// Indices into `request_history` of the entries the history list shows.
+    pub fn visible_history(&self) -> Vec<usize> {
+        let query = self.history_query.to_lowercase();
+        self.request_history
+            .iter()
+            .enumerate()
+            .filter(|(_, hist)| match self.history_outcome {
+                HistoryOutcome::All => true,
+                HistoryOutcome::Failed => !hist.success,
+                HistoryOutcome::Succeeded => hist.success,
+            })
+            .filter(|(_, hist)| hist.method.to_lowercase().contains(&query))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The `request_history` index of the selected history list entry.
+    pub fn selected_history_entry(&self) -> Option<usize> {
+        self.visible_history()
+            .get(self.selected_command_index?)
+            .copied()
+    }
+
+    /// Keeps the history list's selection on one of the entries it shows.
+    pub fn clamp_history_selection(&mut self) {
+        let visible = self.visible_history().len();
+        self.selected_command_index = match self.selected_command_index {
+            _ if visible == 0 => None,
+            Some(idx) => Some(idx.min(visible - 1)),
+            None => Some(0),
+        };
+    }
+
+    /// Keeps `selected_command_index` inside the visible command list, which
+    /// can shrink whenever the filter changes.
+    pub fn clamp_command_selection(&mut self) {
+        let visible = self.command_rows().len();
+        self.selected_command_index = match self.selected_command_index {
+            _ if visible == 0 => None,
+            Some(idx) => Some(idx.min(visible - 1)),
+            None => Some(0),
+        };
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
             input: InputState::default(),
-            auth_token: None,
-            refresh_token: None,
+            token_expires: None,
             output: None,
+            output_method: None,
+            show_summary: true,
+            pinned: None,
+            show_diff: false,
+            auto_refresh: None,
+            auto_refresh_interval: Duration::from_secs(30),
             error: None,
             error_time: None,
-            pds_host: "https://bsky.social".to_string(),
+            error_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+            method_timeouts: HashMap::new(),
+            headers: BTreeMap::new(),
+            method_headers: HashMap::new(),
+            last_error: None,
+            response_info: None,
+            loading: None,
+            downloaded: None,
+            server_info: None,
             is_authenticated: false,
-            request_history: VecDeque::with_capacity(MAX_HISTORY),
+            retries: DEFAULT_RETRIES,
+            unread_count: None,
+            request_history: VecDeque::with_capacity(DEFAULT_HISTORY_SIZE),
+            history_size: DEFAULT_HISTORY_SIZE,
+            theme: Theme::default(),
+            theme_name: "dark".into(),
             quit: false,
+            profile: None,
             identifier: None,
+            handle: None,
+            did: None,
+            self_params: DEFAULT_SELF_PARAMS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            offline: None,
             selected_command_index: Some(0),
             scroll_offset: 0,
+            cursor_line: 0,
+            search: SearchState::default(),
+            filter: None,
+            collapsed: HashSet::new(),
+            line_numbers: false,
+            wrap_lines: true,
+            scroll_x: 0,
+            param_error: None,
+            resolve_handles: true,
+            curl_include_token: false,
+            preview_requests: false,
+            preview: None,
+            export_filename: DEFAULT_EXPORT_FILENAME.to_string(),
+            last_export: None,
+            image: None,
+            export_format: OutputFormat::Pretty,
+            export_envelope: false,
+            proxy: None,
+            insecure_tls: false,
+            resolved_handles: HashMap::new(),
+            lexicons: HashMap::new(),
+            history_outcome: HistoryOutcome::All,
+            history_query: String::new(),
+            favorites: BTreeSet::new(),
+            collapsed_groups: BTreeSet::new(),
+            scroll_positions: VecDeque::new(),
+            back: VecDeque::new(),
+            lookup: None,
+            token_copy_requested: None,
+            history_clear_requested: None,
+            quit_requested: None,
+            rate_limit: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filtered(query: &str) -> AppState {
+        let mut state = AppState {
+            input: InputState {
+                mode: InputMode::Command,
+                content: query.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        state.input.move_cursor_to_end();
+        state
+    }
+
+    #[test]
+    fn selection_past_the_filtered_list_is_clamped() {
+        let mut state = filtered("timeline");
+        state.selected_command_index = Some(999);
+        assert!(state.selected_row().is_none());
+        assert!(state.selected_command().is_none());
+
+        state.clamp_command_selection();
+        let rows = state.command_rows().len();
+        assert_eq!(state.selected_command_index, Some(rows - 1));
+        assert!(state.selected_row().is_some());
+    }
+
+    #[test]
+    fn selecting_after_filtering_picks_a_match() {
+        let mut state = filtered("getTimeline");
+        state.select_first_command();
+
+        let cmd = state.selected_command().expect("a command is selected");
+        assert_eq!(cmd.method, "app.bsky.feed.getTimeline");
+    }
+
+    #[test]
+    fn filtering_out_everything_selects_nothing() {
+        let mut state = filtered("no command is called this");
+        state.selected_command_index = Some(3);
+
+        state.clamp_command_selection();
+        assert_eq!(state.selected_command_index, None);
+        state.select_first_command();
+        assert_eq!(state.selected_command_index, None);
+        assert!(state.selected_command().is_none());
+        state.toggle_favorite();
+        state.toggle_group();
+    }
+
+    #[test]
+    fn history_timestamps_round_trip() {
+        let timestamp = OffsetDateTime::from_unix_timestamp_nanos(1_760_000_000_123_456_789)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap());
+        let history = VecDeque::from([RequestHistory {
+            method: "app.bsky.feed.getTimeline".into(),
+            timestamp,
+            success: true,
+            url: "https://bsky.social/xrpc/app.bsky.feed.getTimeline?limit=5".into(),
+            params: vec!["5".into()],
+            status: Some(200),
+            raw: false,
+            body: None,
+        }]);
+
+        let json = serde_json::to_string(&history).unwrap();
+        let loaded: VecDeque<RequestHistory> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded[0].timestamp, timestamp);
+        assert_eq!(loaded[0].timestamp.offset(), timestamp.offset());
+        assert_eq!(loaded[0].status, Some(200));
+    }
+
+    #[test]
+    fn history_saved_before_status_was_kept_still_loads() {
+        let json = serde_json::to_string(&serde_json::json!([{
+            "method": "app.bsky.actor.getProfile",
+            "timestamp": OffsetDateTime::UNIX_EPOCH,
+            "success": false,
+            "url": "https://bsky.social/xrpc/app.bsky.actor.getProfile?actor=bsky.app",
+            "params": ["bsky.app"],
+        }]))
+        .unwrap();
+
+        let loaded: VecDeque<RequestHistory> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded[0].timestamp, OffsetDateTime::UNIX_EPOCH);
+        assert_eq!(loaded[0].status, None);
+        assert!(!loaded[0].raw);
+    }
+
+    #[test]
+    fn ctrl_c_quits_at_once_when_idle() {
+        let mut state = AppState::default();
+        assert!(state.confirm_quit());
+        assert_eq!(state.error, None);
+    }
+
+    #[test]
+    fn ctrl_c_while_loading_needs_a_second_press() {
+        let mut state = AppState {
+            loading: Some(Instant::now()),
+            ..Default::default()
+        };
+        assert!(!state.confirm_quit());
+        assert_eq!(
+            state.error.as_deref(),
+            Some("Press Ctrl+c again to quit; a request is still loading")
+        );
+        assert!(state.confirm_quit());
+    }
+
+    #[test]
+    fn a_second_press_after_the_window_starts_over() {
+        let mut state = AppState {
+            loading: Some(Instant::now()),
+            quit_requested: Some(SystemTime::now() - QUIT_CONFIRM_WINDOW - Duration::from_secs(1)),
+            ..Default::default()
+        };
+        assert!(!state.confirm_quit());
+        assert!(state.quit_requested.unwrap().elapsed().unwrap() < QUIT_CONFIRM_WINDOW);
+        assert!(state.confirm_quit());
+    }
+
+    #[test]
+    fn a_command_being_built_is_work_in_progress() {
+        let mut state = AppState {
+            input: InputState {
+                mode: InputMode::CommandBuilder {
+                    command: "app.bsky.actor.getProfile".into(),
+                    current_param: 0,
+                    params: Vec::new(),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(state.work_in_progress(), None);
+
+        state.input.content = "bsky.app".into();
+        assert_eq!(
+            state.work_in_progress(),
+            Some("the command being built will be lost")
+        );
+        assert!(!state.confirm_quit());
+        assert!(state.confirm_quit());
+    }
+
+    fn key(input: &mut InputState, code: KeyCode) {
+        input.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn typed(text: &str) -> InputState {
+        let mut input = InputState::default();
+        for c in text.chars() {
+            key(&mut input, KeyCode::Char(c));
+        }
+        input
+    }
+
+    #[test]
+    fn typing_between_multibyte_chars() {
+        let mut input = typed("héllo🦋");
+        assert_eq!(input.cursor_position, 6);
+
+        key(&mut input, KeyCode::Left);
+        key(&mut input, KeyCode::Left);
+        key(&mut input, KeyCode::Char('ü'));
+        assert_eq!(input.content, "héllüo🦋");
+        assert_eq!(input.cursor_position, 5);
+
+        input.insert_str("日本");
+        assert_eq!(input.content, "héllü日本o🦋");
+        assert_eq!(input.cursor_position, 7);
+    }
+
+    #[test]
+    fn backspace_removes_whole_multibyte_chars() {
+        let mut input = typed("a🦋é");
+        key(&mut input, KeyCode::Backspace);
+        assert_eq!(input.content, "a🦋");
+        key(&mut input, KeyCode::Backspace);
+        assert_eq!(input.content, "a");
+        assert_eq!(input.cursor_position, 1);
+
+        let mut input = typed("日本語");
+        key(&mut input, KeyCode::Home);
+        key(&mut input, KeyCode::Right);
+        key(&mut input, KeyCode::Backspace);
+        assert_eq!(input.content, "本語");
+        assert_eq!(input.cursor_position, 0);
+        key(&mut input, KeyCode::Backspace);
+        assert_eq!(input.content, "本語");
+    }
+
+    #[test]
+    fn cursor_column_counts_wide_chars_twice() {
+        let mut input = typed("日本x");
+        assert_eq!(input.cursor_column(), 5);
+        input.mode = InputMode::Password;
+        assert_eq!(input.cursor_column(), 3);
+    }
+
+    fn completions(text: &str) -> Vec<String> {
+        let mut input = InputState {
+            mode: InputMode::Command,
+            content: text.to_string(),
+            ..Default::default()
+        };
+        input.update_completions();
+        input.completion_matches
+    }
+
+    #[test]
+    fn alias_method_and_namespace_all_find_the_timeline() {
+        for text in ["timeline", "getTimeline", "bsky.feed"] {
+            let matches = completions(text);
+            assert!(
+                matches.iter().any(|m| m == "app.bsky.feed.getTimeline"),
+                "{:?} didn't match the timeline: {:?}",
+                text,
+                matches
+            );
+        }
+        assert_eq!(completions("timeline")[0], "app.bsky.feed.getTimeline");
+        assert_eq!(completions("getTimeline")[0], "app.bsky.feed.getTimeline");
+    }
+
+    #[test]
+    fn completion_ranks_prefix_then_substring_then_in_order() {
+        let method = "app.bsky.feed.getTimeline";
+        assert_eq!(completion_rank(method, "app.bsky"), Some(0));
+        assert_eq!(completion_rank(method, "feed.get"), Some(1));
+        assert_eq!(completion_rank(method, "GETTIMELINE"), Some(1));
+        assert_eq!(completion_rank(method, "abfgt"), Some(2));
+        assert_eq!(completion_rank(method, "tga"), None);
+        // Prefixes are case-sensitive, but still match as substrings
+        assert_eq!(completion_rank(method, "App"), Some(1));
+    }
+
+    #[test]
+    fn alias_prefix_comes_before_substring_matches() {
+        // `feed` is getFeed's alias, and a substring of every other feed method
+        let matches = completions("feed");
+        assert_eq!(matches[0], "app.bsky.feed.getFeed");
+        let first_other = matches
+            .iter()
+            .position(|m| !m.contains("feed"))
+            .unwrap_or(matches.len());
+        assert!(matches[first_other..].iter().all(|m| !m.contains("feed")));
+        assert!(matches.iter().any(|m| m == "app.bsky.feed.getAuthorFeed"));
+    }
+
+    #[test]
+    fn prefix_matches_come_before_fuzzy_ones() {
+        let matches = completions("com.atproto.sync.get");
+        assert_eq!(
+            &matches[..2],
+            ["com.atproto.sync.getRepo", "com.atproto.sync.getBlob"]
+        );
+        assert!(completions("").is_empty());
+        assert!(completions("zzzz").is_empty());
+    }
+
+    #[test]
+    fn toggling_a_fold_collapses_then_expands() {
+        let mut state = AppState {
+            output: Some(serde_json::json!({ "a": { "b": 1, "c": 2 }, "d": [1, 2] })),
+            ..Default::default()
+        };
+        let expanded = state.view_lines().len();
+
+        // From a line inside `a`, the fold lands on the line that opens it
+        assert_eq!(state.toggle_fold(2), Some(1));
+        assert!(state.collapsed.contains("a"));
+        assert_eq!(state.view_lines().len(), expanded - 3);
+
+        assert_eq!(state.toggle_fold(1), Some(1));
+        assert!(state.collapsed.is_empty());
+        assert_eq!(state.view_lines().len(), expanded);
+
+        assert_eq!(state.toggle_fold(0), Some(0));
+        assert_eq!(state.view_lines().len(), 1);
+        assert_eq!(state.toggle_fold(99), None);
+    }
+
+    #[test]
+    fn own_account_defaults_to_the_session_did() {
+        let actor = commands::all()
+            .iter()
+            .find(|cmd| cmd.method == "app.bsky.actor.getProfile")
+            .map(|cmd| &cmd.parameters[0])
+            .unwrap();
+        let mut state = AppState::default();
+        assert_eq!(state.own_default("app.bsky.actor.getProfile", actor), None);
+
+        state.did = Some("did:plc:test".into());
+        assert_eq!(
+            state.own_default("app.bsky.actor.getProfile", actor),
+            Some("did:plc:test")
+        );
+        state.self_params = vec!["app.bsky.feed.getAuthorFeed.actor".into()];
+        assert_eq!(state.own_default("app.bsky.actor.getProfile", actor), None);
+        assert_eq!(
+            state.own_default("app.bsky.feed.getAuthorFeed", actor),
+            Some("did:plc:test")
+        );
+    }
+
+    fn ctrl(input: &mut InputState, c: char) {
+        input.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+    }
+
+    /// `text` typed in, with the cursor moved back to char `cursor`.
+    fn typed_at(text: &str, cursor: usize) -> InputState {
+        let mut input = typed(text);
+        input.cursor_position = cursor;
+        input
+    }
+
+    #[test]
+    fn ctrl_a_and_e_jump_to_the_ends() {
+        let mut input = typed_at("app.bsky.feed.getLikes", 8);
+        ctrl(&mut input, 'a');
+        assert_eq!(input.cursor_position, 0);
+        ctrl(&mut input, 'e');
+        assert_eq!(input.cursor_position, 22);
+        assert_eq!(input.content, "app.bsky.feed.getLikes");
+    }
+
+    #[test]
+    fn ctrl_u_deletes_to_the_start() {
+        let mut input = typed_at("did:plc:abc", 8);
+        ctrl(&mut input, 'u');
+        assert_eq!(input.content, "abc");
+        assert_eq!(input.cursor_position, 0);
+        ctrl(&mut input, 'u');
+        assert_eq!(input.content, "abc");
+    }
+
+    #[test]
+    fn ctrl_k_deletes_to_the_end() {
+        let mut input = typed_at("did:plc:abc", 8);
+        ctrl(&mut input, 'k');
+        assert_eq!(input.content, "did:plc:");
+        assert_eq!(input.cursor_position, 8);
+        ctrl(&mut input, 'k');
+        assert_eq!(input.content, "did:plc:");
+    }
+
+    #[test]
+    fn ctrl_w_deletes_a_word_with_the_punctuation_before_the_cursor() {
+        let mut input = typed("app.bsky.feed.");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "app.bsky.");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "app.");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "");
+
+        let mut input = typed("at://did:plc:abc/app.bsky.feed.post/3k2");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "at://did:plc:abc/app.bsky.feed.post/");
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "at://did:plc:abc/app.bsky.feed.");
+    }
+
+    #[test]
+    fn ctrl_w_in_the_middle_keeps_what_follows() {
+        let mut input = typed_at("hello, wörld again", 12);
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "hello,  again");
+        assert_eq!(input.cursor_position, 7);
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, " again");
+        assert_eq!(input.cursor_position, 0);
+    }
+
+    /// Where the cursor lands after each of `presses` Ctrl+`code` presses,
+    /// starting from char `cursor` of `text`.
+    fn word_jumps(text: &str, cursor: usize, code: KeyCode, presses: usize) -> Vec<usize> {
+        let mut input = typed_at(text, cursor);
+        (0..presses)
+            .map(|_| {
+                input.handle_key(KeyEvent::new(code, KeyModifiers::CONTROL));
+                assert_eq!(input.content, text);
+                input.cursor_position
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ctrl_arrows_jump_between_nsid_segments() {
+        let nsid = "app.bsky.feed";
+        assert_eq!(word_jumps(nsid, 13, KeyCode::Left, 4), [9, 4, 0, 0]);
+        assert_eq!(word_jumps(nsid, 0, KeyCode::Right, 4), [3, 8, 13, 13]);
+    }
+
+    #[test]
+    fn ctrl_arrows_jump_between_uri_parts() {
+        let uri = "at://did:plc:x/app.bsky.feed.post/3k";
+        assert_eq!(word_jumps(uri, 36, KeyCode::Left, 4), [34, 29, 24, 19]);
+        assert_eq!(word_jumps(uri, 0, KeyCode::Right, 5), [2, 8, 12, 14, 18]);
+    }
+
+    #[test]
+    fn ctrl_arrows_skip_runs_of_spaces() {
+        let text = "get   the  feed";
+        assert_eq!(word_jumps(text, 15, KeyCode::Left, 3), [11, 6, 0]);
+        assert_eq!(word_jumps(text, 0, KeyCode::Right, 3), [3, 9, 15]);
+        assert_eq!(word_jumps(text, 4, KeyCode::Left, 1), [0]);
+        assert_eq!(word_jumps(text, 4, KeyCode::Right, 1), [9]);
+    }
+
+    #[test]
+    fn ctrl_arrows_count_multibyte_words_in_chars() {
+        let text = "héllo wörld 🦋";
+        assert_eq!(word_jumps(text, 13, KeyCode::Left, 3), [6, 0, 0]);
+        assert_eq!(word_jumps(text, 0, KeyCode::Right, 3), [5, 11, 13]);
+
+        let mut input = typed(text);
+        input.handle_key(KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL));
+        key(&mut input, KeyCode::Char('X'));
+        assert_eq!(input.content, "héllo Xwörld 🦋");
+    }
+
+    #[test]
+    fn ctrl_arrows_stay_put_at_the_ends() {
+        assert_eq!(word_jumps("did:plc:abc", 0, KeyCode::Left, 1), [0]);
+        assert_eq!(word_jumps("did:plc:abc", 11, KeyCode::Right, 1), [11]);
+        assert_eq!(word_jumps("", 0, KeyCode::Left, 1), [0]);
+        assert_eq!(word_jumps("", 0, KeyCode::Right, 1), [0]);
+    }
+
+    #[test]
+    fn unbound_ctrl_keys_are_left_to_the_app() {
+        let mut input = typed("abc");
+        assert!(!input.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+        assert_eq!(input.content, "abc");
+    }
+
+    #[test]
+    fn undo_steps_back_through_typing_one_char_at_a_time() {
+        let mut input = typed("ab");
+        ctrl(&mut input, 'z');
+        assert_eq!((input.content.as_str(), input.cursor_position), ("a", 1));
+        ctrl(&mut input, 'z');
+        assert_eq!((input.content.as_str(), input.cursor_position), ("", 0));
+        // Nothing left to undo
+        ctrl(&mut input, 'z');
+        assert_eq!(input.content, "");
+    }
+
+    #[test]
+    fn undo_brings_back_deleted_text_and_the_cursor() {
+        let mut input = typed_at("app.bsky.feed", 8);
+        key(&mut input, KeyCode::Backspace);
+        assert_eq!(input.content, "app.bsk.feed");
+        ctrl(&mut input, 'z');
+        assert_eq!(
+            (input.content.as_str(), input.cursor_position),
+            ("app.bsky.feed", 8)
+        );
+
+        ctrl(&mut input, 'k');
+        ctrl(&mut input, 'w');
+        assert_eq!(input.content, "app.");
+        ctrl(&mut input, 'z');
+        assert_eq!(
+            (input.content.as_str(), input.cursor_position),
+            ("app.bsky", 8)
+        );
+        ctrl(&mut input, 'z');
+        assert_eq!(
+            (input.content.as_str(), input.cursor_position),
+            ("app.bsky.feed", 8)
+        );
+    }
+
+    #[test]
+    fn undo_takes_back_a_paste_at_once() {
+        let mut input = typed("at://");
+        input.insert_str("did:plc:abc/app.bsky.feed.post/3k2");
+        ctrl(&mut input, 'z');
+        assert_eq!(
+            (input.content.as_str(), input.cursor_position),
+            ("at://", 5)
+        );
+
+        input.replace("app.bsky.feed.getTimeline");
+        ctrl(&mut input, 'z');
+        assert_eq!(input.content, "at://");
+    }
+
+    #[test]
+    fn undo_history_is_capped() {
+        let mut input = InputState::default();
+        for _ in 0..UNDO_LIMIT + 20 {
+            key(&mut input, KeyCode::Char('x'));
+        }
+        assert_eq!(input.undo.len(), UNDO_LIMIT);
+
+        for _ in 0..UNDO_LIMIT + 20 {
+            ctrl(&mut input, 'z');
+        }
+        // The oldest steps were dropped, so the first 20 chars stay
+        assert_eq!(input.content, "x".repeat(20));
+    }
+
+    #[test]
+    fn undo_ignores_edits_from_a_field_that_was_cleared() {
+        let mut input = typed("secret");
+        // As when moving on to the next prompt
+        input.content.clear();
+        input.cursor_position = 0;
+
+        ctrl(&mut input, 'z');
+        assert_eq!(input.content, "");
+        assert!(input.undo.is_empty());
+    }
+
+    #[test]
+    fn paths_from_the_view_lead_back_to_the_value() {
+        let mut state = AppState {
+            output: Some(serde_json::json!({
+                "cursor": "abc",
+                "feed": [
+                    { "post": { "author": { "handle": "alice.test" } } },
+                    { "post": { "author": { "handle": "bob.test" } } },
+                ],
+            })),
+            ..Default::default()
+        };
+        let line_of = |state: &AppState, text: &str| {
+            state
+                .view_lines()
+                .iter()
+                .position(|line| line.text.contains(text))
+                .unwrap()
+        };
+
+        assert_eq!(state.path_at(0).as_deref(), Some(""));
+        let bob = line_of(&state, "bob.test");
+        assert_eq!(
+            state.path_at(bob).as_deref(),
+            Some("feed[1].post.author.handle")
+        );
+
+        // Filtered down, paths still start from the top of the response
+        state.filter = Some("feed[1].post".into());
+        let bob = line_of(&state, "bob.test");
+        let path = state.path_at(bob).unwrap();
+        assert_eq!(path, "feed[1].post.author.handle");
+        assert_eq!(
+            output::select_path(state.output.as_ref().unwrap(), &path),
+            Some(&serde_json::json!("bob.test"))
+        );
+        assert_eq!(state.path_at(0).as_deref(), Some("feed[1].post"));
+    }
+}
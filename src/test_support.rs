@@ -0,0 +1,221 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex, Once},
+    thread,
+};
+
+use serde_json::Value;
+
+use crate::{config::Config, xrpc::XrpcClient, App};
+
+/// A request the mock server received.
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub method: String,
+    /// The path and query string, e.g. `/xrpc/app.bsky.actor.getProfile?actor=bsky.app`.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The XRPC method called, e.g. `app.bsky.actor.getProfile`.
+    pub fn xrpc_method(&self) -> &str {
+        let path = self.path.split('?').next().unwrap_or_default();
+        path.strip_prefix("/xrpc/").unwrap_or(path)
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+/// What the mock server answers a request with.
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// An HTTP server on a free local port standing in for a PDS. Each request
+/// is answered by `handler` and recorded. It runs until the tests finish.
+pub struct MockServer {
+    pub url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockServer {
+    pub fn start(handler: impl Fn(&Received) -> Reply + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("binding the mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = Arc::new(handler);
+        let log = received.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let log = log.clone();
+                // Answered on their own threads so a slow reply doesn't hold
+                // up the next request
+                thread::spawn(move || {
+                    let Some(request) = read_request(&stream) else {
+                        return;
+                    };
+                    log.lock().unwrap().push(request.clone());
+                    write_reply(stream, handler(&request));
+                });
+            }
+        });
+
+        Self { url, received }
+    }
+
+    /// Every request so far, oldest first.
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<Received> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Received {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn write_reply(mut stream: TcpStream, reply: Reply) {
+    let mut head = format!("HTTP/1.1 {} Mock\r\n", reply.status);
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.body.len()
+    ));
+    // The client may have given up already, e.g. after cancelling
+    let _ = stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(&reply.body));
+}
+
+/// Points the config directory at a scratch one for this test run, so
+/// tests never read or overwrite the real history and favorites.
+pub fn isolate_config() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("oxat-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("XDG_CONFIG_HOME", dir);
+    });
+}
+
+/// Points the config directory at a new, empty one for the calling test's
+/// thread only, and returns it.
+pub fn own_config_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("oxat-tests-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    crate::config::TEST_CONFIG_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir.clone()));
+    dir
+}
+
+/// An `App` talking to `pds_host` directly, without retries, a clipboard or
+/// a terminal.
+pub fn app(pds_host: &str) -> App {
+    isolate_config();
+    let config = Config {
+        pds_host: Some(pds_host.to_string()),
+        clipboard: false,
+        retries: 0,
+        proxy: Some(String::new()),
+        ..Default::default()
+    };
+    App::new(&config, None).expect("creating the app")
+}
+
+/// The `XrpcClient` an app from `app` would use.
+pub fn client(pds_host: &str) -> XrpcClient {
+    app(pds_host).xrpc
+}
+
+/// A `createSession` answer for `did:plc:test` / `test.bsky.social`, with an
+/// access token that expires in 2100.
+pub fn session(access: &str, refresh: &str) -> Value {
+    serde_json::json!({
+        "accessJwt": jwt(access),
+        "refreshJwt": refresh,
+        "did": "did:plc:test",
+        "handle": "test.bsky.social",
+    })
+}
+
+/// An unsigned JWT whose payload names `subject` and expires in 2100, since
+/// oxat only ever reads the claims.
+pub fn jwt(subject: &str) -> String {
+    jwt_expiring(subject, 4_102_444_800)
+}
+
+/// Like `jwt`, but expiring `exp` seconds after the Unix epoch.
+pub fn jwt_expiring(subject: &str, exp: u64) -> String {
+    let encode = |value: Value| base64::encode_config(value.to_string(), base64::URL_SAFE_NO_PAD);
+    format!(
+        "{}.{}.signature",
+        encode(serde_json::json!({ "alg": "HS256", "typ": "JWT" })),
+        encode(serde_json::json!({ "sub": subject, "exp": exp }))
+    )
+}
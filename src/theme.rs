@@ -0,0 +1,126 @@
+use ratatui::style::Color;
+
+/// Semantic colors used across the UI, so render code never names a
+/// literal color.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Headings, selected items and method names.
+    pub accent: Color,
+    pub success: Color,
+    pub error: Color,
+    /// Non-fatal states, like a 4xx status.
+    pub warning: Color,
+    /// Labels, parameter names and URLs.
+    pub info: Color,
+    /// Hints, help text and descriptions that should stay out of the way.
+    pub muted: Color,
+    /// Secondary details like timestamps.
+    pub secondary: Color,
+    /// Input text while viewing a response.
+    pub response: Color,
+    /// Input text while searching or filtering.
+    pub search: Color,
+    pub match_fg: Color,
+    pub match_bg: Color,
+    pub current_match_bg: Color,
+    pub json_key: Color,
+    pub json_string: Color,
+    pub json_bracket: Color,
+    pub json_colon: Color,
+    pub json_number: Color,
+    pub json_bool: Color,
+    pub json_null: Color,
+}
+
+/// Names accepted by `theme` in the config.
+pub const THEME_NAMES: &[&str] = &["dark", "light", "high-contrast"];
+
+impl Theme {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            accent: Color::Yellow,
+            success: Color::Green,
+            error: Color::Red,
+            warning: Color::Yellow,
+            info: Color::Cyan,
+            muted: Color::DarkGray,
+            secondary: Color::Gray,
+            response: Color::Blue,
+            search: Color::Magenta,
+            match_fg: Color::Black,
+            match_bg: Color::Yellow,
+            current_match_bg: Color::LightMagenta,
+            json_key: Color::Blue,
+            json_string: Color::Green,
+            json_bracket: Color::Yellow,
+            json_colon: Color::Cyan,
+            json_number: Color::Magenta,
+            json_bool: Color::Red,
+            json_null: Color::DarkGray,
+        }
+    }
+
+    /// Avoids yellows and light grays, which wash out on a white background.
+    pub fn light() -> Self {
+        Self {
+            accent: Color::Magenta,
+            success: Color::Green,
+            error: Color::Red,
+            warning: Color::Rgb(176, 112, 0),
+            info: Color::Blue,
+            muted: Color::Gray,
+            secondary: Color::DarkGray,
+            response: Color::Blue,
+            search: Color::Magenta,
+            match_fg: Color::White,
+            match_bg: Color::Rgb(176, 112, 0),
+            current_match_bg: Color::Magenta,
+            json_key: Color::Blue,
+            json_string: Color::Green,
+            json_bracket: Color::DarkGray,
+            json_colon: Color::DarkGray,
+            json_number: Color::Magenta,
+            json_bool: Color::Red,
+            json_null: Color::Gray,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            accent: Color::LightYellow,
+            success: Color::LightGreen,
+            error: Color::LightRed,
+            warning: Color::LightYellow,
+            info: Color::LightCyan,
+            muted: Color::White,
+            secondary: Color::White,
+            response: Color::LightBlue,
+            search: Color::LightMagenta,
+            match_fg: Color::Black,
+            match_bg: Color::LightYellow,
+            current_match_bg: Color::LightMagenta,
+            json_key: Color::LightCyan,
+            json_string: Color::LightGreen,
+            json_bracket: Color::White,
+            json_colon: Color::White,
+            json_number: Color::LightMagenta,
+            json_bool: Color::LightRed,
+            json_null: Color::White,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
@@ -3,16 +3,39 @@ use ratatui::{
     prelude::Position,
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState, Wrap,
+    },
     Frame,
 };
+use serde_json::Value;
+use std::time::{Duration, Instant, SystemTime};
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
-    commands::AVAILABLE_COMMANDS,
-    state::{AppState, InputMode}
+    commands,
+    json_diff::{self, Change, DiffLine},
+    keys,
+    lexicon::{self, Lexicon},
+    post,
+    settings::SETTINGS,
+    state::{AppState, CommandRow, ErrorDetail, HistoryOutcome, InputMode, FAVORITES_GROUP},
+    theme::Theme,
+    viewer::{self, ViewLine},
+    xrpc::PROXY_HEADER,
 };
 
-pub fn render(app: &AppState, f: &mut Frame) {
+pub fn render(app: &AppState, pds_host: &str, f: &mut Frame) {
+    if let InputMode::Help { previous, scroll } = &app.input.mode {
+        // Draw the screen help was opened from underneath it
+        let mut under = app.clone();
+        under.input.mode = (**previous).clone();
+        render(&under, pds_host, f);
+        render_help_overlay(app, *scroll, f);
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -24,59 +47,231 @@ pub fn render(app: &AppState, f: &mut Frame) {
         .split(f.area());
 
     render_input(app, f, chunks[0]);
-    render_status(app, f, chunks[1]);
+    render_status(app, pds_host, f, chunks[1]);
+
+    if let Some(started) = app.loading {
+        render_loading(app, started, f, chunks[2]);
+        render_help(app, f, chunks[3]);
+        return;
+    }
 
+    render_main(app, pds_host, f, chunks[2]);
+    render_help(app, f, chunks[3]);
+}
+
+/// The main content area for the current mode.
+fn render_main(app: &AppState, pds_host: &str, f: &mut Frame, area: Rect) {
     match &app.input.mode {
+        InputMode::Normal | InputMode::Password | InputMode::AuthFactor { .. } => {
+            render_server_info(app, pds_host, f, area);
+        }
         InputMode::Command => {
-            render_commands(app, f, chunks[2]);
+            render_commands(app, f, area);
         }
-        InputMode::History => {
-            render_history(app, f, chunks[2]);
+        InputMode::History | InputMode::HistoryFilter => {
+            render_history(app, f, area);
         }
         InputMode::CommandBuilder { .. } => {
-            render_command_builder(app, f, chunks[2]);
+            render_command_builder(app, f, area);
+        }
+        InputMode::ConfirmRequest { .. } => {
+            render_request_preview(app, f, area);
+        }
+        InputMode::Headers => {
+            render_headers(app, f, area);
+        }
+        InputMode::Settings { selected, .. } => {
+            render_settings(app, pds_host, *selected, f, area);
+        }
+        InputMode::ConfirmDestructive { command, params } => {
+            render_destructive_confirm(app, command, params, f, area);
         }
         InputMode::ViewingResponse => {
-            render_output(app, f, chunks[2]);
+            render_output(app, f, area);
+        }
+        InputMode::ErrorDetail { .. } => {
+            render_error_detail(app, f, area);
+        }
+        InputMode::Lexicon { method, .. } => {
+            render_lexicon(app, method, f, area);
+        }
+        InputMode::Lookup {
+            previous,
+            saved_input,
+            saved_cursor,
+        } => {
+            // Draw the screen the lookup was opened from underneath it
+            let mut under = app.clone();
+            under.input.mode = (**previous).clone();
+            under.input.content = saved_input.clone();
+            under.input.cursor_position = *saved_cursor;
+            render_main(&under, pds_host, f, area);
+            render_lookup(app, f, area);
         }
         _ => {
-            render_output(app, f, chunks[2]);
+            render_output(app, f, area);
         }
     }
+}
 
-    render_help(app, f, chunks[3]);
+/// The handle ↔ DID lookup, as a box centred over `area`.
+fn render_lookup(app: &AppState, f: &mut Frame, area: Rect) {
+    let width = area.width.min(64);
+    let height = area.height.min(3);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let line = match &app.lookup {
+        None => Line::styled(
+            "Type a handle or DID and press Enter",
+            Style::default().fg(app.theme.muted),
+        ),
+        Some(Ok(value)) => Line::from(vec![
+            Span::styled("→ ", Style::default().fg(app.theme.muted)),
+            Span::styled(value.as_str(), Style::default().fg(app.theme.success)),
+        ]),
+        Some(Err(e)) => Line::styled(e.as_str(), Style::default().fg(app.theme.error)),
+    };
+
+    let block = Block::default()
+        .title("Look Up Handle or DID")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.accent));
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(line).block(block).wrap(Wrap { trim: true }),
+        popup,
+    );
 }
 
-fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
-    let input_style = match app.input.mode {
+fn render_loading(app: &AppState, started: Instant, f: &mut Frame, area: Rect) {
+    const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+    let elapsed = started.elapsed();
+    let frame = FRAMES[(elapsed.as_millis() / 100) as usize % FRAMES.len()];
+    let status = match app.downloaded {
+        Some(bytes) => format!(" Downloading… {}", format_bytes(bytes)),
+        None => " Loading…".to_string(),
+    };
+    let line = Line::from(vec![
+        Span::styled(frame, Style::default().fg(app.theme.accent)),
+        Span::raw(format!("{} {:.1}s", status, elapsed.as_secs_f32())),
+    ]);
+
+    let block = Block::default().title("Response").borders(Borders::ALL);
+    f.render_widget(Paragraph::new(line).block(block), area);
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// How close to expiry the session countdown turns yellow, then red.
+const TOKEN_EXPIRY_WARNING: Duration = Duration::from_secs(10 * 60);
+const TOKEN_EXPIRY_URGENT: Duration = Duration::from_secs(2 * 60);
+
+/// A countdown like `45s`, `12m` or `1h 5m`.
+pub fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// How each mode's input is drawn, also used for its breadcrumb.
+fn mode_style(mode: &InputMode, theme: &Theme) -> Style {
+    match mode {
         InputMode::Password => Style::default().fg(Color::default()),
-        InputMode::Command => Style::default().fg(Color::Yellow),
-        InputMode::CommandBuilder { .. } => Style::default().fg(Color::Green),
-        InputMode::ViewingResponse => Style::default().fg(Color::Blue),
-        InputMode::History => Style::default().fg(Color::Yellow),
+        InputMode::AuthFactor { .. } => Style::default().fg(theme.accent),
+        InputMode::Command => Style::default().fg(theme.accent),
+        InputMode::CommandBuilder { .. } => Style::default().fg(theme.success),
+        InputMode::ConfirmRequest { .. } => Style::default().fg(theme.success),
+        InputMode::ConfirmDestructive { .. } => Style::default().fg(theme.error),
+        InputMode::ViewingResponse => Style::default().fg(theme.response),
+        InputMode::SearchResponse => Style::default().fg(theme.search),
+        InputMode::FilterResponse => Style::default().fg(theme.search),
+        InputMode::RepeatCount => Style::default().fg(theme.search),
+        InputMode::AutoRefreshInterval => Style::default().fg(theme.search),
+        InputMode::ErrorDetail { .. } => Style::default().fg(theme.error),
+        InputMode::Lexicon { .. } => Style::default().fg(theme.info),
+        InputMode::History => Style::default().fg(theme.accent),
+        InputMode::HistoryFilter => Style::default().fg(theme.search),
+        InputMode::RawXrpc => Style::default().fg(theme.info),
+        InputMode::Headers => Style::default().fg(theme.info),
+        InputMode::Settings { .. } => Style::default().fg(theme.success),
+        InputMode::Lookup { .. } => Style::default().fg(theme.accent),
+        InputMode::Help { .. } => Style::default().fg(theme.accent),
         InputMode::Normal => Style::default(),
-    };
+    }
+}
+
+fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
+    let input_style = mode_style(&app.input.mode, &app.theme);
 
     let title = match &app.input.mode {
-        InputMode::Password => "Enter your password",
+        InputMode::Password => "Enter your password, or leave it empty to sign in in your browser",
+        InputMode::AuthFactor { .. } => "Enter the sign-in code from your email",
         InputMode::Normal => "Enter your identifier",
         InputMode::Command => "Enter or select a command (Tab to autocomplete)",
         InputMode::History => "Command History",
+        InputMode::HistoryFilter => "Filter history by method",
+        InputMode::FilterResponse => "Filter response by path (e.g. feed.0.post), empty clears",
+        InputMode::RepeatCount => "Send the last request how many times? (1-100)",
+        InputMode::AutoRefreshInterval => "Refresh the response every how many seconds?",
+        InputMode::RawXrpc => "Enter an NSID, then key=value&... or a JSON body",
+        InputMode::Headers => "Set a header as name: value, or name: to remove it",
+        InputMode::Settings {
+            selected,
+            editing: true,
+        } => &format!("Enter {}", SETTINGS[*selected].label()),
+        InputMode::Settings { .. } => "Settings",
         InputMode::CommandBuilder {
             command,
             current_param,
             ..
         } => {
-            &if let Some(cmd) = AVAILABLE_COMMANDS.iter().find(|c| c.method == *command) {
+            &if let Some(cmd) = commands::all().iter().find(|c| c.method == *command) {
                 if let Some(param) = cmd.parameters.get(*current_param) {
-                    if param.optional {
+                    let name = if param.is_array {
+                        format!("{} (comma-separated)", param.name)
+                    } else {
+                        param.name.to_string()
+                    };
+                    // Any account field can be filled in with Ctrl+o once logged in
+                    let fillable = app.did.is_some() && param.takes_account();
+                    if app.own_default(command, param).is_some() {
+                        format!(
+                            "Enter {} (empty for your account, Ctrl+o fills it in)",
+                            name
+                        )
+                    } else if param.optional {
                         format!(
-                            "Enter {} (optional, default: {})",
-                            param.name,
-                            param.default.unwrap_or("none")
+                            "Enter {} (optional, default: {}{})",
+                            name,
+                            param.default.unwrap_or("none"),
+                            if fillable { ", Ctrl+o for yours" } else { "" }
                         )
+                    } else if fillable {
+                        format!("Enter {} (Ctrl+o for your account)", name)
                     } else {
-                        format!("Enter {}", param.name)
+                        format!("Enter {}", name)
                     }
                 } else {
                     "Enter parameter".to_string()
@@ -86,10 +281,23 @@ fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
             }
         }
         InputMode::ViewingResponse => "Press Enter to return to command list",
+        InputMode::ConfirmRequest { .. } => "Press Enter to send this request",
+        InputMode::ConfirmDestructive { .. } => "Type yes and press Enter to confirm",
+        InputMode::ErrorDetail { .. } => "Press Esc to close the error details",
+        InputMode::Lexicon { .. } => "Press Esc to close the lexicon",
+        InputMode::Lookup { .. } => "Enter a handle or DID to look up",
+        InputMode::Help { .. } => "Help",
+        InputMode::SearchResponse => {
+            if app.search.case_sensitive {
+                "Search response (case-sensitive)"
+            } else {
+                "Search response"
+            }
+        }
     };
 
     let input_content = if app.input.mode == InputMode::Password {
-        "•".repeat(app.input.content.len())
+        "•".repeat(app.input.content.chars().count())
     } else {
         app.input.content.clone()
     };
@@ -100,7 +308,7 @@ fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
     let input_block = Block::default()
         .borders(Borders::ALL)
         .title(title)
-        .title_style(Style::default().fg(Color::Cyan));
+        .title_style(Style::default().fg(app.theme.info));
 
     f.render_widget(input_block.clone(), area);
     let inner_area = input_block.inner(area);
@@ -117,17 +325,20 @@ fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
         if !app.input.content.is_empty() {
             if let Some(idx) = app.input.completion_index {
                 if let Some(completion) = app.input.completion_matches.get(idx) {
-                    if let Some(suggestion) = completion.strip_prefix(&app.input.content) {
-                        spans.push(Span::styled(
-                            suggestion,
-                            Style::default().fg(Color::DarkGray),
-                        ));
+                    // Prefix matches complete inline; others show the full method
+                    let suggestion = match completion.strip_prefix(&app.input.content) {
+                        Some(rest) => rest.to_string(),
+                        None => format!(" → {}", completion),
+                    };
+                    spans.push(Span::styled(
+                        suggestion,
+                        Style::default().fg(app.theme.muted),
+                    ));
 
-                        spans.push(Span::styled(
-                            format!(" ({}/{})", idx + 1, app.input.completion_matches.len()),
-                            Style::default().fg(Color::DarkGray),
-                        ));
-                    }
+                    spans.push(Span::styled(
+                        format!(" ({}/{})", idx + 1, app.input.completion_matches.len()),
+                        Style::default().fg(app.theme.muted),
+                    ));
                 }
             }
         }
@@ -137,7 +348,7 @@ fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
         f.render_widget(input, inner_area);
     } else {
         let text = Text::from(if app.input.mode == InputMode::Password {
-            "•".repeat(app.input.content.len())
+            "•".repeat(app.input.content.chars().count())
         } else {
             app.input.content.clone()
         })
@@ -148,108 +359,494 @@ fn render_input(app: &AppState, f: &mut Frame, area: Rect) {
     }
 
     f.set_cursor_position(Position {
-        x: area.x + 1 + app.input.cursor_position as u16,
+        x: area.x + 1 + app.input.cursor_column() as u16,
         y: area.y + 1,
     });
 }
 
-fn render_status(app: &AppState, f: &mut Frame, area: Rect) {
+fn render_status(app: &AppState, pds_host: &str, f: &mut Frame, area: Rect) {
     let status = if app.is_authenticated {
+        let account = app
+            .handle
+            .as_deref()
+            .or(app.identifier.as_deref())
+            .unwrap_or("Authenticated");
+        vec![
+            Span::styled(account, Style::default().fg(app.theme.accent)),
+            Span::raw(" | "),
+            Span::styled("PDS: ", Style::default().fg(app.theme.secondary)),
+            Span::styled(pds_host, Style::default().fg(app.theme.success)),
+        ]
+    } else if let Some(dir) = &app.offline {
         vec![
-            Span::raw("Authenticated | "),
-            Span::styled("PDS: ", Style::default().fg(Color::Gray)),
-            Span::styled(&app.pds_host, Style::default().fg(Color::Green)),
+            Span::styled("Offline", Style::default().fg(app.theme.warning)),
+            Span::raw(" | "),
+            Span::styled("Responses from: ", Style::default().fg(app.theme.secondary)),
+            Span::raw(dir.display().to_string()),
         ]
     } else {
         vec![Span::styled(
             "Not authenticated",
-            Style::default().fg(Color::Red),
+            Style::default().fg(app.theme.error),
         )]
     };
 
+    let mut status = status;
+    if app.insecure_tls {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            "TLS NOT VERIFIED",
+            Style::default()
+                .fg(app.theme.error)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(refresh) = &app.auto_refresh {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            format!("auto-refresh: {}s", refresh.interval.as_secs()),
+            Style::default().fg(app.theme.info),
+        ));
+    }
+    if let Some(count) = app.unread_count.filter(|_| app.is_authenticated) {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            format!("{} unread", count),
+            Style::default().fg(if count > 0 {
+                app.theme.accent
+            } else {
+                app.theme.muted
+            }),
+        ));
+    }
+    if let Some(expires) = app.token_expires.filter(|_| app.is_authenticated) {
+        let remaining = expires
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        let color = if remaining < TOKEN_EXPIRY_URGENT {
+            app.theme.error
+        } else if remaining < TOKEN_EXPIRY_WARNING {
+            app.theme.warning
+        } else {
+            app.theme.muted
+        };
+        let text = if remaining.is_zero() {
+            "session expired".to_string()
+        } else {
+            format!("expires in {}", format_remaining(remaining))
+        };
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(text, Style::default().fg(color)));
+    }
+    if let Some(proxy) = app.headers.get(PROXY_HEADER) {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            "via ",
+            Style::default().fg(app.theme.secondary),
+        ));
+        status.push(Span::styled(
+            proxy.as_str(),
+            Style::default().fg(app.theme.warning),
+        ));
+    }
+    if let Some(proxy) = &app.proxy {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            "proxy ",
+            Style::default().fg(app.theme.secondary),
+        ));
+        status.push(Span::styled(
+            proxy.display(),
+            Style::default().fg(app.theme.muted),
+        ));
+    }
+    // Once the window has reset the numbers are stale, so they're hidden
+    if let Some(rate_limit) = app.rate_limit.filter(|rl| !rl.resets_in().is_zero()) {
+        let (text, color) = if rate_limit.remaining == 0 {
+            (
+                format!(
+                    "rate limited for {}",
+                    format_remaining(rate_limit.resets_in())
+                ),
+                app.theme.error,
+            )
+        } else {
+            let color = if rate_limit.is_low() {
+                app.theme.warning
+            } else {
+                app.theme.muted
+            };
+            (
+                format!(
+                    "{}/{} requests left",
+                    rate_limit.remaining, rate_limit.limit
+                ),
+                color,
+            )
+        };
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(text, Style::default().fg(color)));
+    }
+    if let Some(error) = &app.error {
+        status.push(Span::raw(" | "));
+        status.push(Span::styled(
+            error.lines().next().unwrap_or_default(),
+            Style::default().fg(app.theme.error),
+        ));
+        if app.last_error.is_some() {
+            status.push(Span::styled(
+                " (Ctrl+d for details)",
+                Style::default().fg(app.theme.muted),
+            ));
+        }
+    }
+
     let status = Paragraph::new(Line::from(status))
-        .block(Block::default().borders(Borders::ALL))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(breadcrumb(app)),
+        )
         .wrap(Wrap { trim: true });
 
     f.render_widget(status, area);
 }
 
-fn render_commands(app: &AppState, f: &mut Frame, area: Rect) {
+/// The current mode and the ones it was reached through, for the status
+/// bar's title. The current one is drawn in its mode's color.
+fn breadcrumb(app: &AppState) -> Line<'static> {
+    let crumbs = app.input.mode.breadcrumb();
+    let last = crumbs.len().saturating_sub(1);
+    let mut spans = vec![Span::raw(" ")];
+    for (i, crumb) in crumbs.into_iter().enumerate() {
+        if i == last {
+            spans.push(Span::styled(
+                crumb,
+                mode_style(&app.input.mode, &app.theme).add_modifier(Modifier::BOLD),
+            ));
+        } else {
+            spans.push(Span::styled(crumb, Style::default().fg(app.theme.muted)));
+            spans.push(Span::styled(" › ", Style::default().fg(app.theme.muted)));
+        }
+    }
+    spans.push(Span::raw(" "));
+    Line::from(spans)
+}
+
+/// What the PDS reported about itself, shown while logging in so it's clear
+/// which server the credentials are going to.
+fn render_server_info(app: &AppState, pds_host: &str, f: &mut Frame, area: Rect) {
     let block = Block::default()
-        .title("Available Commands")
+        .title(format!("Server — {}", pds_host))
         .borders(Borders::ALL);
-    let inner = block.inner(area);
-    f.render_widget(block, area);
+    let label = Style::default().fg(app.theme.info);
+    let muted = Style::default().fg(app.theme.muted);
 
-    let items: Vec<ListItem> = AVAILABLE_COMMANDS
-        .iter()
+    let lines = match &app.server_info {
+        None => vec![Line::styled("Contacting server…", muted)],
+        Some(Err(e)) => vec![Line::styled(
+            format!("Couldn't describe server: {}", e),
+            Style::default().fg(app.theme.error),
+        )],
+        Some(Ok(info)) => {
+            let domains = if info.available_user_domains.is_empty() {
+                "none".to_string()
+            } else {
+                info.available_user_domains.join(", ")
+            };
+            let mut lines = vec![
+                Line::from(vec![Span::styled("DID: ", label), Span::raw(&info.did)]),
+                Line::from(vec![
+                    Span::styled("Handle domains: ", label),
+                    Span::raw(domains),
+                ]),
+                Line::from(vec![
+                    Span::styled("Invite code required: ", label),
+                    Span::raw(if info.invite_code_required {
+                        "yes"
+                    } else {
+                        "no"
+                    }),
+                ]),
+            ];
+            for (name, link) in [
+                ("Privacy policy: ", &info.links.privacy_policy),
+                ("Terms of service: ", &info.links.terms_of_service),
+            ] {
+                if let Some(link) = link {
+                    lines.push(Line::from(vec![
+                        Span::styled(name, label),
+                        Span::raw(link.as_str()),
+                    ]));
+                }
+            }
+            lines
+        }
+    };
+
+    let paragraph = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn render_commands(app: &AppState, f: &mut Frame, area: Rect) {
+    let commands = app.visible_commands();
+    let title = if app.input.content.is_empty() {
+        "Available Commands".to_string()
+    } else {
+        format!(
+            "Available Commands ({}/{})",
+            commands.len(),
+            commands::all().len()
+        )
+    };
+
+    let [list_area, detail_area] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(area);
+
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(list_area);
+    f.render_widget(block, list_area);
+
+    let query = app.input.content.to_lowercase();
+
+    let items: Vec<ListItem> = app
+        .command_rows()
+        .into_iter()
         .enumerate()
-        .map(|(i, cmd)| {
-            let style = if Some(i) == app.selected_command_index {
+        .map(|(i, row)| {
+            let selected = Some(i) == app.selected_command_index;
+            let cmd = match row {
+                CommandRow::Group {
+                    name,
+                    count,
+                    collapsed,
+                } => {
+                    let mut style = Style::default()
+                        .fg(app.theme.info)
+                        .add_modifier(Modifier::BOLD);
+                    if selected {
+                        style = style.add_modifier(Modifier::REVERSED);
+                    }
+                    let arrow = if collapsed { "▸" } else { "▾" };
+                    let name = if name == FAVORITES_GROUP {
+                        format!("★ {}", name)
+                    } else {
+                        name.to_string()
+                    };
+                    let mut spans = vec![Span::styled(format!("{} {}", arrow, name), style)];
+                    if collapsed {
+                        spans.push(Span::styled(
+                            format!(" ({})", count),
+                            Style::default().fg(app.theme.muted),
+                        ));
+                    }
+                    return ListItem::new(Line::from(spans));
+                }
+                CommandRow::Command(cmd) => cmd,
+            };
+
+            let style = if selected {
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(if cmd.destructive {
+                        app.theme.warning
+                    } else {
+                        app.theme.accent
+                    })
                     .add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else if cmd.destructive {
+                Style::default().fg(app.theme.warning)
             } else {
                 Style::default()
             };
 
-            let header_line = Line::from(vec![Span::styled(cmd.method, style)]);
+            // Under its namespace's header the method is shown without the
+            // namespace; favorites keep the full method
+            let name = match cmd.method.strip_prefix(app.group_of(cmd)) {
+                Some(name) => name.trim_start_matches('.'),
+                None => cmd.method,
+            };
+            let mut spans = vec![Span::raw("    ")];
+            spans.extend(highlight_match(name, &query, style, &app.theme));
+            if let Some(alias) = cmd.alias {
+                spans.push(Span::styled(
+                    format!(" ({})", alias),
+                    Style::default().fg(app.theme.muted),
+                ));
+            }
 
-            let desc_line = Line::from(vec![
-                Span::raw("  "),
-                Span::styled(cmd.description, Style::default().fg(Color::Gray)),
-            ]);
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
 
-            let mut lines = vec![header_line, desc_line];
+    f.render_widget(List::new(items), inner);
 
-            for param in cmd.parameters {
-                let param_desc = if param.optional {
-                    format!(
-                        "{} (optional, default: {})",
-                        param.description,
-                        param.default.unwrap_or("none")
-                    )
-                } else {
-                    param.description.to_string()
-                };
+    render_command_detail(app, f, detail_area);
+}
 
-                lines.push(Line::from(vec![
-                    Span::raw("    "),
-                    Span::styled(param.name, Style::default().fg(Color::Cyan)),
-                    Span::raw(": "),
-                    Span::styled(param_desc, Style::default().fg(Color::DarkGray)),
-                ]));
-            }
-            lines.push(Line::from(""));
+/// Full description and parameters of the selected command.
+fn render_command_detail(app: &AppState, f: &mut Frame, area: Rect) {
+    let block = Block::default().title("Details").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
 
-            ListItem::new(lines)
-        })
-        .collect();
+    let cmd = match app.selected_row() {
+        Some(CommandRow::Command(cmd)) => cmd,
+        Some(CommandRow::Group {
+            name,
+            count,
+            collapsed,
+        }) => {
+            let noun = if count == 1 { "command" } else { "commands" };
+            let action = if collapsed { "expand" } else { "collapse" };
+            let lines = vec![
+                Line::styled(
+                    name,
+                    Style::default()
+                        .fg(app.theme.accent)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Line::styled(
+                    format!("{} {}", count, noun),
+                    Style::default().fg(app.theme.muted),
+                ),
+                Line::from(""),
+                Line::from(format!("Enter to {} the group", action)),
+            ];
+            f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+            return;
+        }
+        None => return,
+    };
 
-    let list = List::new(items).block(Block::default()).highlight_style(
-        Style::default()
-            .fg(Color::Yellow)
-            .add_modifier(Modifier::BOLD),
-    );
+    let kind = if cmd.method == commands::DIFF_FOLLOWERS {
+        "workflow (runs several queries)".to_string()
+    } else if cmd.method == commands::POST {
+        "procedure (POST) · com.atproto.repo.createRecord".to_string()
+    } else if cmd.destructive {
+        "procedure (POST) · destructive, asks for confirmation".to_string()
+    } else if cmd.is_binary() {
+        format!(
+            "query (GET) · {} (JSON is shown, images described, anything else saved to a file)",
+            cmd.encoding
+        )
+    } else {
+        format!("query (GET) · {}", cmd.encoding)
+    };
 
-    f.render_widget(list, inner);
+    let mut lines = vec![
+        Line::styled(
+            cmd.method,
+            Style::default()
+                .fg(app.theme.accent)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Line::styled(kind, Style::default().fg(app.theme.muted)),
+        Line::from(""),
+        Line::from(cmd.description),
+        Line::from(""),
+    ];
+
+    if cmd.parameters.is_empty() {
+        lines.push(Line::styled(
+            "No parameters",
+            Style::default().fg(app.theme.muted),
+        ));
+    }
+
+    for param in cmd.parameters {
+        let requirement = match (param.optional, param.default) {
+            (false, _) => "required".to_string(),
+            (true, Some(default)) => format!("optional, default {}", default),
+            (true, None) => "optional".to_string(),
+        };
+
+        lines.push(Line::from(vec![
+            Span::styled(param.name, Style::default().fg(app.theme.info)),
+            Span::styled(
+                format!(" ({}, {})", param.kind.label(), requirement),
+                Style::default().fg(app.theme.muted),
+            ),
+        ]));
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::raw(param.description),
+        ]));
+    }
+
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+// First row of the command/history lists: input (3) + status (3) + border (1)
+const LIST_TOP: u16 = 7;
+
+/// Maps a clicked terminal row to the command or history list item drawn
+/// there, mirroring the item heights used by the renderers.
+pub fn list_item_at(app: &AppState, row: u16) -> Option<usize> {
+    let heights: Vec<u16> = match app.input.mode {
+        // One line per method; details are in the side panel
+        InputMode::Command => vec![1; app.command_rows().len()],
+        // Header and URL
+        InputMode::History | InputMode::HistoryFilter => vec![2; app.visible_history().len()],
+        _ => return None,
+    };
+
+    let mut top = LIST_TOP;
+    for (i, height) in heights.into_iter().enumerate() {
+        if (top..top + height).contains(&row) {
+            return Some(i);
+        }
+        top += height;
+    }
+    None
+}
+
+/// Splits `text` into spans, underlining the first case-insensitive match of
+/// `query`. Method names are ASCII, so lowercasing preserves byte offsets.
+fn highlight_match(
+    text: &'static str,
+    query: &str,
+    style: Style,
+    theme: &Theme,
+) -> Vec<Span<'static>> {
+    match text.to_lowercase().find(query) {
+        Some(start) if !query.is_empty() => {
+            let end = start + query.len();
+            vec![
+                Span::styled(&text[..start], style),
+                Span::styled(
+                    &text[start..end],
+                    style.fg(theme.info).add_modifier(Modifier::UNDERLINED),
+                ),
+                Span::styled(&text[end..], style),
+            ]
+        }
+        _ => vec![Span::styled(text, style)],
+    }
 }
 
 fn render_history(app: &AppState, f: &mut Frame, area: Rect) {
-    let block = Block::default()
-        .title("Command History")
-        .borders(Borders::ALL);
+    let mut title = "Command History".to_string();
+    match app.history_outcome {
+        HistoryOutcome::All => {}
+        HistoryOutcome::Failed => title.push_str(" — failures"),
+        HistoryOutcome::Succeeded => title.push_str(" — successes"),
+    }
+    if !app.history_query.is_empty() {
+        title.push_str(&format!(" — /{}", app.history_query));
+    }
+    let block = Block::default().title(title).borders(Borders::ALL);
     let inner = block.inner(area);
     f.render_widget(block, area);
 
     let items: Vec<ListItem> = app
-        .request_history
-        .iter()
+        .visible_history()
+        .into_iter()
+        .map(|idx| &app.request_history[idx])
         .enumerate()
         .map(|(i, hist)| {
             let style = if Some(i) == app.selected_command_index {
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(app.theme.accent)
                     .add_modifier(Modifier::BOLD | Modifier::REVERSED)
             } else {
                 Style::default()
@@ -263,22 +860,28 @@ fn render_history(app: &AppState, f: &mut Frame, area: Rect) {
             );
 
             let status_style = if hist.success {
-                Style::default().fg(Color::Green)
+                Style::default().fg(app.theme.success)
             } else {
-                Style::default().fg(Color::Red)
+                Style::default().fg(app.theme.error)
             };
 
             let header_line = Line::from(vec![
-                Span::styled(time_str, Style::default().fg(Color::Gray)),
+                Span::styled(time_str, Style::default().fg(app.theme.secondary)),
                 Span::raw(" "),
                 Span::styled(if hist.success { "✓" } else { "✗" }, status_style),
                 Span::raw(" "),
                 Span::styled(&hist.method, style),
             ]);
 
+            // Workflows have no URL, so what they were run on is shown instead
+            let target = if hist.url.is_empty() {
+                hist.params.join(" vs ")
+            } else {
+                hist.url.clone()
+            };
             let url_line = Line::from(vec![
                 Span::raw("  "),
-                Span::styled(&hist.url, Style::default().fg(Color::DarkGray)),
+                Span::styled(target, Style::default().fg(app.theme.muted)),
             ]);
 
             ListItem::new(vec![header_line, url_line])
@@ -287,7 +890,7 @@ fn render_history(app: &AppState, f: &mut Frame, area: Rect) {
 
     let list = List::new(items).block(Block::default()).highlight_style(
         Style::default()
-            .fg(Color::Yellow)
+            .fg(app.theme.accent)
             .add_modifier(Modifier::BOLD),
     );
 
@@ -306,14 +909,14 @@ fn render_command_builder(app: &AppState, f: &mut Frame, area: Rect) {
         params,
     } = &app.input.mode
     {
-        if let Some(cmd) = AVAILABLE_COMMANDS.iter().find(|c| c.method == *command) {
+        if let Some(cmd) = commands::all().iter().find(|c| c.method == *command) {
             let mut text = vec![
                 Line::from(vec![
                     Span::raw("Building command: "),
                     Span::styled(
                         cmd.method,
                         Style::default()
-                            .fg(Color::Yellow)
+                            .fg(app.theme.accent)
                             .add_modifier(Modifier::BOLD),
                     ),
                 ]),
@@ -321,13 +924,19 @@ fn render_command_builder(app: &AppState, f: &mut Frame, area: Rect) {
             ];
 
             for (i, param) in cmd.parameters.iter().enumerate() {
-                let value = params.get(i).map(|s| s.as_str()).unwrap_or("");
+                let active = i == *current_param;
+                // The active field shows the live input rather than the saved value
+                let value = if active {
+                    app.input.content.as_str()
+                } else {
+                    params.get(i).map(|s| s.as_str()).unwrap_or("")
+                };
                 let style = match i.cmp(current_param) {
                     std::cmp::Ordering::Equal => Style::default()
-                        .fg(Color::Green)
+                        .fg(app.theme.success)
                         .add_modifier(Modifier::BOLD),
-                    std::cmp::Ordering::Less => Style::default().fg(Color::Gray),
-                    std::cmp::Ordering::Greater => Style::default().fg(Color::DarkGray),
+                    std::cmp::Ordering::Less => Style::default().fg(app.theme.secondary),
+                    std::cmp::Ordering::Greater => Style::default().fg(app.theme.muted),
                 };
 
                 let param_text = if param.optional {
@@ -336,12 +945,48 @@ fn render_command_builder(app: &AppState, f: &mut Frame, area: Rect) {
                     format!("{}: ", param.name)
                 };
 
-                text.push(Line::from(vec![
+                let mut line = Line::from(vec![
+                    Span::styled(if active { "▶ " } else { "  " }, style),
                     Span::styled(param_text, style),
                     Span::styled(value, style),
-                ]));
+                ]);
+                if cmd.method == commands::POST && param.name == "text" {
+                    let length = post::grapheme_count(value);
+                    let color = if length > post::POST_TEXT_LIMIT {
+                        app.theme.error
+                    } else {
+                        app.theme.muted
+                    };
+                    line.push_span(Span::styled(
+                        format!("  {}/{}", length, post::POST_TEXT_LIMIT),
+                        Style::default().fg(color),
+                    ));
+                }
+                if param.is_array && !value.is_empty() {
+                    let entries = param.values(value).len();
+                    line.push_span(Span::styled(
+                        format!(
+                            "  {} {}",
+                            entries,
+                            if entries == 1 { "entry" } else { "entries" }
+                        ),
+                        Style::default().fg(app.theme.muted),
+                    ));
+                }
+                if active && !value.is_empty() {
+                    line.push_span(match app.check_param(command, param, value) {
+                        Ok(()) => Span::styled("  ✓", Style::default().fg(app.theme.success)),
+                        Err(hint) => Span::styled(
+                            format!("  ✗ {}", hint),
+                            Style::default().fg(app.theme.error),
+                        ),
+                    });
+                }
+                text.push(line);
 
-                let desc = if param.optional {
+                let desc = if app.own_default(cmd.method, param).is_some() {
+                    format!("{} (default: your account)", param.description)
+                } else if param.optional {
                     format!(
                         "{} (default: {})",
                         param.description,
@@ -352,8 +997,8 @@ fn render_command_builder(app: &AppState, f: &mut Frame, area: Rect) {
                 };
 
                 text.push(Line::from(vec![
-                    Span::raw("  "),
-                    Span::styled(desc, Style::default().fg(Color::DarkGray)),
+                    Span::raw("    "),
+                    Span::styled(desc, Style::default().fg(app.theme.muted)),
                 ]));
             }
 
@@ -363,162 +1008,1479 @@ fn render_command_builder(app: &AppState, f: &mut Frame, area: Rect) {
     }
 }
 
-fn render_output(app: &AppState, f: &mut Frame, area: Rect) {
-    let block = Block::default().title("Response").borders(Borders::ALL);
-    let inner = block.inner(area);
-    f.render_widget(block, area);
+/// The request a built command would send, shown for confirmation.
+fn render_request_preview(app: &AppState, f: &mut Frame, area: Rect) {
+    let block = Block::default()
+        .title("Request Preview")
+        .borders(Borders::ALL);
 
-    let text = match (&app.output, &app.error) {
-        (Some(output), _) => {
-            let formatted = serde_json::to_string_pretty(output).unwrap_or_default();
-            syntax_highlight(&formatted)
-        }
-        (_, Some(error)) => Text::styled(error, Style::default().fg(Color::Red)),
-        _ => Text::raw(""),
+    let Some(preview) = &app.preview else {
+        f.render_widget(block, area);
+        return;
     };
 
-    let paragraph = Paragraph::new(text)
-        .wrap(Wrap { trim: true })
-        .scroll((app.scroll_offset, 0));
+    let label = Style::default().fg(app.theme.info);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                preview.http_method,
+                Style::default()
+                    .fg(app.theme.accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" "),
+            Span::raw(preview.url.as_str()),
+        ]),
+        Line::from(""),
+    ];
 
-    f.render_widget(paragraph, inner);
-}
+    if preview.headers.is_empty() {
+        lines.push(Line::styled(
+            "No headers",
+            Style::default().fg(app.theme.muted),
+        ));
+    }
+    for (name, value) in &preview.headers {
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}: ", name), label),
+            Span::raw(value.as_str()),
+        ]));
+    }
 
-fn render_help(app: &AppState, f: &mut Frame, area: Rect) {
-    let help_text = match &app.input.mode {
-        InputMode::Normal | InputMode::Password => {
-            "Enter - Submit | Ctrl+c - Quit"
-        }
-        InputMode::Command => {
-            "Tab - Autocomplete | ↑↓ - Scroll Commands | Enter - Select Command | h - History | Ctrl+c - Quit"
-        }
-        InputMode::History => {
-            "↑↓ - Browse History | Enter - Use Command | Esc - Back | Ctrl+c - Quit"
-        }
-        InputMode::CommandBuilder { .. } => {
-            "Enter - Next Parameter/Submit | Esc - Cancel | Ctrl+c - Quit"
-        }
-        InputMode::ViewingResponse => {
-            "↑↓/PgUp/PgDn - Scroll | Home/End - Top/Bottom | Enter - Return to Commands | c - Copy | e - Export | Ctrl+c - Quit"
+    if let Some(body) = &preview.body {
+        lines.push(Line::from(""));
+        lines.extend(syntax_highlight(body, &app.theme).lines);
+    }
+
+    if !preview.notes.is_empty() {
+        lines.push(Line::from(""));
+        for note in &preview.notes {
+            lines.push(Line::styled(
+                format!("Resolved {}", note),
+                Style::default().fg(app.theme.muted),
+            ));
         }
-    };
+    }
 
-    let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
-    f.render_widget(help, area);
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
 }
 
-pub fn syntax_highlight(json: &str) -> Text<'static> {
-    let mut lines: Vec<Line<'static>> = Vec::new();
-    let mut current_line: Vec<Span<'static>> = Vec::new();
-    let mut indent_level = 0;
-    let mut in_string = false;
-    let mut current = String::new();
+/// The settings that can be changed at runtime, with the selected one's
+/// live input while it's being edited.
+fn render_settings(app: &AppState, pds_host: &str, selected: usize, f: &mut Frame, area: Rect) {
+    let block = Block::default().title("Settings").borders(Borders::ALL);
+    let editing = matches!(app.input.mode, InputMode::Settings { editing: true, .. });
 
-    const SPACE: &str = "\u{00A0}";
-    const INDENT_SIZE: usize = 4;
-
-    let create_indent = |level: usize| -> String { SPACE.repeat(level * INDENT_SIZE) };
-
-    for c in json.chars() {
-        match c {
-            '"' => {
-                if !current.is_empty() {
-                    current_line.push(Span::raw(current.clone()));
-                    current.clear();
-                }
-                in_string = !in_string;
-                current_line.push(Span::styled("\"", Style::default().fg(Color::Green)));
-            }
-            '{' | '[' if !in_string => {
-                if !current.is_empty() {
-                    current_line.push(Span::raw(current.clone()));
-                    current.clear();
-                }
-                current_line.push(Span::styled(
-                    c.to_string(),
-                    Style::default().fg(Color::Yellow),
-                ));
-                lines.push(Line::from(current_line));
-                indent_level += 1;
-                current_line = vec![Span::raw(create_indent(indent_level))];
-            }
-            '}' | ']' if !in_string => {
-                if !current.is_empty() {
-                    current_line.push(Span::raw(current.clone()));
-                    current.clear();
-                }
-                if current_line.len() > 1 {
-                    lines.push(Line::from(current_line));
-                }
-                indent_level = indent_level.saturating_sub(1);
-                current_line = vec![
-                    Span::raw(create_indent(indent_level)),
-                    Span::styled(c.to_string(), Style::default().fg(Color::Yellow)),
-                ];
-            }
-            ':' if !in_string => {
-                if !current.is_empty() {
-                    current_line.push(Span::styled(
-                        current.clone(),
-                        Style::default().fg(Color::Blue),
-                    ));
-                    current.clear();
-                }
-                current_line.push(Span::styled(": ", Style::default().fg(Color::Cyan)));
-            }
-            ',' if !in_string => {
-                if !current.is_empty() {
-                    if current.parse::<f64>().is_ok() {
-                        current_line.push(Span::styled(
-                            current.clone(),
-                            Style::default().fg(Color::Magenta),
-                        ));
-                    } else if current == "true" || current == "false" || current == "null" {
-                        current_line.push(Span::styled(
-                            current.clone(),
-                            Style::default().fg(Color::Red),
-                        ));
-                    } else {
-                        current_line.push(Span::raw(current.clone()));
-                    }
-                    current.clear();
-                }
-                current_line.push(Span::raw(","));
-                lines.push(Line::from(current_line));
-                current_line = vec![Span::raw(create_indent(indent_level))];
-            }
-            '\n' | ' ' if !in_string => {
-                continue;
-            }
-            _ => {
-                if in_string {
-                    current_line.push(Span::styled(
-                        c.to_string(),
-                        Style::default().fg(Color::Green),
-                    ));
-                } else {
-                    current.push(c);
-                }
-            }
-        }
-    }
+    let mut lines = Vec::new();
+    for (i, setting) in SETTINGS.iter().enumerate() {
+        let active = i == selected;
+        let style = if active {
+            Style::default()
+                .fg(app.theme.accent)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let value = if active && editing {
+            app.input.content.clone()
+        } else {
+            setting.current(app, pds_host)
+        };
 
-    if !current.is_empty() {
-        if current.parse::<f64>().is_ok() {
-            current_line.push(Span::styled(
-                current.clone(),
-                Style::default().fg(Color::Magenta),
+        lines.push(Line::from(vec![
+            Span::styled(if active { "▶ " } else { "  " }, style),
+            Span::styled(format!("{}: ", setting.label()), style),
+            Span::styled(value, Style::default().fg(app.theme.success)),
+        ]));
+        lines.push(Line::styled(
+            format!("    {}", setting.description()),
+            Style::default().fg(app.theme.muted),
+        ));
+        if let Some(hint) = app.param_error.as_ref().filter(|_| active && editing) {
+            lines.push(Line::styled(
+                format!("    {}", hint),
+                Style::default().fg(app.theme.error),
             ));
-        } else if current == "true" || current == "false" || current == "null" {
-            current_line.push(Span::styled(current, Style::default().fg(Color::Red)));
-        } else {
-            current_line.push(Span::raw(current));
         }
     }
 
-    if !current_line.is_empty() {
-        lines.push(Line::from(current_line));
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+/// Custom headers: the editable ones sent with every command, then those
+/// the config adds for particular methods.
+fn render_headers(app: &AppState, f: &mut Frame, area: Rect) {
+    let block = Block::default().title("Headers").borders(Borders::ALL);
+    let name = Style::default().fg(app.theme.info);
+    let muted = Style::default().fg(app.theme.muted);
+
+    let mut lines = vec![Line::styled("Every command", muted)];
+    if app.headers.is_empty() {
+        lines.push(Line::styled("  none", muted));
+    }
+    for (header, value) in &app.headers {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {}: ", header), name),
+            Span::raw(value.as_str()),
+        ]));
+    }
+
+    let mut methods: Vec<_> = app.method_headers.iter().collect();
+    methods.sort_by_key(|(method, _)| method.as_str());
+    for (method, headers) in methods {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(format!("{} (from the config)", method), muted));
+        for (header, value) in headers {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {}: ", header), name),
+                Span::raw(value.as_str()),
+            ]));
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+/// What a destructive command is about to do, shown until "yes" is typed.
+fn render_destructive_confirm(
+    app: &AppState,
+    command: &str,
+    params: &[String],
+    f: &mut Frame,
+    area: Rect,
+) {
+    let block = Block::default()
+        .title("Confirm")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.error));
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                command.to_string(),
+                Style::default()
+                    .fg(app.theme.warning)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" can't be undone."),
+        ]),
+        Line::from(""),
+    ];
+    let names = commands::all()
+        .iter()
+        .find(|cmd| cmd.method == command)
+        .map(|cmd| cmd.parameters)
+        .unwrap_or_default();
+    for (param, value) in names.iter().zip(params) {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{}: ", param.name),
+                Style::default().fg(app.theme.info),
+            ),
+            Span::raw(value.as_str()),
+        ]));
     }
+    lines.push(Line::from(""));
+    lines.push(Line::styled(
+        "Type yes and press Enter to send it, or Esc to go back.",
+        Style::default().fg(app.theme.muted),
+    ));
 
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+/// A failed request's message. When it ends in an XRPC error body like
+/// `{"error":"InvalidRequest","message":"..."}`, the code is shown in bold
+/// with the message below it instead of the raw JSON.
+fn error_text<'a>(error: &'a str, theme: &Theme) -> Text<'a> {
+    let style = Style::default().fg(theme.error);
+    let parsed = error.find('{').and_then(|start| {
+        let body = serde_json::from_str::<Value>(&error[start..]).ok()?;
+        let field = |name| body.get(name).and_then(Value::as_str).map(str::to_string);
+        let (code, message) = (field("error"), field("message"));
+        (code.is_some() || message.is_some()).then(|| (&error[..start], code, message))
+    });
+    let Some((prefix, code, message)) = parsed else {
+        return Text::styled(error, style);
+    };
+
+    let mut lines = Vec::new();
+    let prefix = prefix.trim_end().trim_end_matches(':');
+    if !prefix.is_empty() {
+        lines.push(Line::styled(prefix.to_string(), style));
+        lines.push(Line::raw(""));
+    }
+    if let Some(code) = code {
+        lines.push(Line::styled(code, style.add_modifier(Modifier::BOLD)));
+    }
+    if let Some(message) = message {
+        lines.push(Line::styled(message, style));
+    }
     Text::from(lines)
 }
+
+fn render_output(app: &AppState, f: &mut Frame, area: Rect) {
+    let mut title = if app.search.query.is_empty() {
+        "Response".to_string()
+    } else {
+        let position = app
+            .search
+            .current_match
+            .map(|i| (i + 1).to_string())
+            .unwrap_or_else(|| "-".into());
+        format!(
+            "Response — /{} ({}/{})",
+            app.search.query,
+            position,
+            app.search.match_lines.len()
+        )
+    };
+
+    if let Some(filter) = &app.filter {
+        title.push_str(&format!(" — .{}", filter.trim_start_matches('.')));
+    }
+    if app.show_diff && app.pinned.is_some() {
+        title.push_str(" — changes since pinned");
+    }
+
+    let mut title = Line::from(title);
+    if let Some(info) = &app.response_info {
+        let color = match info.status {
+            200..=299 => app.theme.success,
+            300..=499 => app.theme.warning,
+            _ => app.theme.error,
+        };
+        title.push_span(Span::raw(" — "));
+        title.push_span(Span::styled(
+            format!("{} {}", info.status, info.reason),
+            Style::default().fg(color),
+        ));
+        title.push_span(Span::raw(format!(" · {}ms", info.elapsed.as_millis())));
+    }
+
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let inner = if matches!(
+        app.input.mode,
+        InputMode::ViewingResponse
+            | InputMode::SearchResponse
+            | InputMode::FilterResponse
+            | InputMode::RepeatCount
+            | InputMode::AutoRefreshInterval
+    ) {
+        let [summary_area, inner] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        f.render_widget(Paragraph::new(request_summary(app)), summary_area);
+        inner
+    } else {
+        inner
+    };
+
+    let inner = match response_summary(app) {
+        Some(lines) => {
+            let height = summary_height(&lines, inner.width) as u16;
+            let [summary_area, inner] =
+                Layout::vertical([Constraint::Length(height), Constraint::Min(0)]).areas(inner);
+            let block = Block::default()
+                .borders(Borders::BOTTOM)
+                .border_style(Style::default().fg(app.theme.muted));
+            f.render_widget(
+                Paragraph::new(lines).block(block).wrap(Wrap { trim: true }),
+                summary_area,
+            );
+            inner
+        }
+        None => inner,
+    };
+
+    if let Some(diff) = app.diff_view() {
+        render_diff(app, &diff, f, inner);
+        return;
+    }
+    if let Some(feed) = app.feed_view() {
+        render_feed(app, feed, f, inner);
+        return;
+    }
+
+    let mut gutter = None;
+    let mut text = match (app.displayed_output(), &app.error) {
+        (None, _) if app.output.is_some() => Text::styled(
+            format!("Nothing at {}", app.filter.as_deref().unwrap_or_default()),
+            Style::default().fg(app.theme.error),
+        ),
+        (Some(_), _) => {
+            let lines = app.view_lines();
+            let mut text = syntax_highlight(&viewer::join(&lines), &app.theme);
+
+            if !app.search.query.is_empty() {
+                let current_line = app
+                    .search
+                    .current_match
+                    .and_then(|i| app.search.match_lines.get(i).copied());
+
+                for &line_no in &app.search.match_lines {
+                    let style = if Some(line_no) == current_line {
+                        Style::default()
+                            .fg(app.theme.match_fg)
+                            .bg(app.theme.current_match_bg)
+                    } else {
+                        Style::default()
+                            .fg(app.theme.match_fg)
+                            .bg(app.theme.match_bg)
+                    };
+
+                    if let Some(line) = text.lines.get_mut(line_no) {
+                        let content: String = line
+                            .spans
+                            .iter()
+                            .map(|span| span.content.as_ref())
+                            .collect();
+                        let ranges = app.search.find_all(&content);
+                        *line = highlight_ranges(std::mem::take(line), &ranges, style);
+                    }
+                }
+            }
+
+            if app.line_numbers {
+                gutter = Some(line_numbers(&lines, app.cursor_line, &app.theme));
+            } else if let Some(line) = text.lines.get_mut(app.cursor_line) {
+                // No gutter to mark, so underline the focused line instead
+                line.style = line.style.add_modifier(Modifier::UNDERLINED);
+            }
+
+            text
+        }
+        (_, Some(error)) => error_text(error, &app.theme),
+        _ => Text::raw(""),
+    };
+
+    let content_height = if app.wrap_lines {
+        // Numbers go inline so they stay beside the first row of a wrapped line
+        for (line, number) in text.lines.iter_mut().zip(gutter.into_iter().flatten()) {
+            line.spans.insert(0, number);
+        }
+        let paragraph = Paragraph::new(text)
+            .wrap(Wrap { trim: true })
+            .scroll((app.scroll_offset, 0));
+        let height = paragraph.line_count(inner.width);
+        f.render_widget(paragraph, inner);
+        height
+    } else {
+        // Numbers get their own column so they don't scroll sideways
+        let content_area = match gutter {
+            Some(gutter) => {
+                let width = gutter.first().map_or(0, |number| number.width()) as u16;
+                let [gutter_area, content_area] =
+                    Layout::horizontal([Constraint::Length(width), Constraint::Min(0)])
+                        .areas(inner);
+                let gutter: Vec<Line> = gutter.into_iter().map(Line::from).collect();
+                f.render_widget(
+                    Paragraph::new(gutter).scroll((app.scroll_offset, 0)),
+                    gutter_area,
+                );
+                content_area
+            }
+            None => inner,
+        };
+        let height = text.lines.len();
+        f.render_widget(
+            Paragraph::new(text).scroll((app.scroll_offset, app.scroll_x)),
+            content_area,
+        );
+        height
+    };
+
+    let viewport = inner.height as usize;
+    if content_height > viewport {
+        // One position per scrollable offset, so the thumb reaches the bottom at End
+        let mut scrollbar_state = ScrollbarState::new(content_height - viewport + 1)
+            .viewport_content_length(viewport)
+            .position(app.scroll_offset as usize);
+
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            Rect {
+                x: area.x,
+                width: area.width,
+                ..inner
+            },
+            &mut scrollbar_state,
+        );
+    }
+}
+
+/// The changes from the pinned response: added lines in green and removed
+/// ones in red, each with a `+`/`-` marker, and the rest highlighted as usual.
+fn render_diff(app: &AppState, diff: &[DiffLine], f: &mut Frame, area: Rect) {
+    if !json_diff::has_changes(diff) {
+        f.render_widget(
+            Paragraph::new("No changes from the pinned response")
+                .style(Style::default().fg(app.theme.muted)),
+            area,
+        );
+        return;
+    }
+
+    let lines: Vec<Line> = diff
+        .iter()
+        .map(|line| match line.change {
+            Change::Same => {
+                let mut spans = vec![Span::raw("  ")];
+                spans.extend(highlight_line(&line.text, &app.theme));
+                Line::from(spans)
+            }
+            Change::Added => Line::styled(
+                format!("+ {}", line.text),
+                Style::default().fg(app.theme.success),
+            ),
+            Change::Removed => Line::styled(
+                format!("- {}", line.text),
+                Style::default().fg(app.theme.error),
+            ),
+        })
+        .collect();
+    f.render_widget(
+        Paragraph::new(lines).scroll((app.scroll_offset, app.scroll_x)),
+        area,
+    );
+}
+
+fn render_error_detail(app: &AppState, f: &mut Frame, area: Rect) {
+    let block = Block::default()
+        .title("Error Details")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.error));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let Some(detail) = &app.last_error else {
+        return;
+    };
+
+    // Same header-plus-body layout as the response viewer so scrolling lines up
+    let [header_area, inner] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+
+    let mut header = Vec::new();
+    if let Some(status) = detail.status {
+        header.push(Span::styled(
+            format!("Status {}", status),
+            Style::default()
+                .fg(app.theme.error)
+                .add_modifier(Modifier::BOLD),
+        ));
+        header.push(Span::raw("  "));
+    }
+    if let Some(url) = &detail.url {
+        header.push(Span::styled(
+            url.clone(),
+            Style::default().fg(app.theme.info),
+        ));
+    }
+    f.render_widget(Paragraph::new(Line::from(header)), header_area);
+
+    let paragraph = Paragraph::new(error_detail_text(detail, &app.theme))
+        .wrap(Wrap { trim: false })
+        .scroll((app.scroll_offset, 0));
+    f.render_widget(paragraph, inner);
+}
+
+/// Rows the error detail body takes up in a viewer `width` columns wide,
+/// counting each row of a wrapped line.
+pub fn error_detail_height(detail: &ErrorDetail, theme: &Theme, width: u16) -> usize {
+    Paragraph::new(error_detail_text(detail, theme))
+        .wrap(Wrap { trim: false })
+        .line_count(width)
+}
+
+/// Rows a message shown in place of the response takes up.
+pub fn message_height(message: &str, width: u16) -> usize {
+    Paragraph::new(message)
+        .wrap(Wrap { trim: true })
+        .line_count(width)
+}
+
+/// Body of the error detail view: the full message, then the raw response
+/// body (pretty-printed when it's JSON).
+fn error_detail_text(detail: &ErrorDetail, theme: &Theme) -> Text<'static> {
+    let mut lines = vec![
+        Line::styled("Message", Style::default().add_modifier(Modifier::BOLD)),
+        Line::styled(detail.message.clone(), Style::default().fg(theme.error)),
+    ];
+
+    if let Some(body) = &detail.body {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            "Response body",
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(json) => lines.extend(
+                syntax_highlight(
+                    &serde_json::to_string_pretty(&json).unwrap_or_default(),
+                    theme,
+                )
+                .lines,
+            ),
+            Err(_) => lines.extend(body.lines().map(|line| Line::raw(line.to_string()))),
+        }
+    }
+
+    Text::from(lines)
+}
+
+fn render_lexicon(app: &AppState, method: &str, f: &mut Frame, area: Rect) {
+    let block = Block::default()
+        .title(format!("Lexicon — {}", method))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.info));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let Some(lexicon) = app.lexicons.get(method) else {
+        return;
+    };
+
+    let [header_area, inner] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+    f.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("From ", Style::default().fg(app.theme.muted)),
+            Span::styled(lexicon.source.clone(), Style::default().fg(app.theme.info)),
+        ])),
+        header_area,
+    );
+
+    let paragraph = Paragraph::new(lexicon_text(lexicon, &app.theme))
+        .wrap(Wrap { trim: false })
+        .scroll((app.scroll_offset, 0));
+    f.render_widget(paragraph, inner);
+}
+
+/// Rows the lexicon view's body takes up in a viewer `width` columns wide.
+pub fn lexicon_height(lexicon: &Lexicon, theme: &Theme, width: u16) -> usize {
+    Paragraph::new(lexicon_text(lexicon, theme))
+        .wrap(Wrap { trim: false })
+        .line_count(width)
+}
+
+/// Body of the lexicon view: a summary of the main definition's
+/// parameters, input, output and errors, then the other definitions, then
+/// the whole schema.
+fn lexicon_text(lexicon: &Lexicon, theme: &Theme) -> Text<'static> {
+    let heading = |text: String| Line::styled(text, Style::default().add_modifier(Modifier::BOLD));
+    let mut lines = Vec::new();
+    let main = &lexicon.doc["defs"]["main"];
+
+    if main.is_object() {
+        let kind = match main["type"].as_str() {
+            Some("query") => "query (GET)".to_string(),
+            Some("procedure") => "procedure (POST)".to_string(),
+            Some(other) => other.to_string(),
+            None => "unknown".to_string(),
+        };
+        lines.push(Line::styled(kind, Style::default().fg(theme.secondary)));
+        if let Some(description) = main["description"].as_str() {
+            lines.push(Line::raw(description.to_string()));
+        }
+
+        if main["parameters"]["properties"].is_object() {
+            lines.push(Line::raw(""));
+            lines.push(heading("Parameters".to_string()));
+            lines.extend(field_lines(&lexicon::fields(&main["parameters"], 0), theme));
+        }
+        for (key, label) in [
+            ("input", "Input"),
+            ("output", "Output"),
+            ("message", "Messages"),
+        ] {
+            let body = &main[key];
+            if body.is_null() {
+                continue;
+            }
+            lines.push(Line::raw(""));
+            lines.push(heading(match body["encoding"].as_str() {
+                Some(encoding) => format!("{} · {}", label, encoding),
+                None => label.to_string(),
+            }));
+            let schema = &body["schema"];
+            if schema["type"] == "object" {
+                lines.extend(field_lines(&lexicon::fields(schema, 0), theme));
+            } else if !schema.is_null() {
+                lines.push(Line::styled(
+                    format!("  {}", lexicon::type_name(schema)),
+                    Style::default().fg(theme.info),
+                ));
+            }
+        }
+        if let Some(errors) = main["errors"].as_array() {
+            lines.push(Line::raw(""));
+            lines.push(heading("Errors".to_string()));
+            for error in errors {
+                let mut spans = vec![Span::styled(
+                    format!("  {}", error["name"].as_str().unwrap_or("?")),
+                    Style::default().fg(theme.error),
+                )];
+                if let Some(description) = error["description"].as_str() {
+                    spans.push(Span::styled(
+                        format!("  {}", description),
+                        Style::default().fg(theme.muted),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+        }
+    }
+
+    // Definitions the main one refers to as `#name`
+    for (name, def) in lexicon.doc["defs"].as_object().into_iter().flatten() {
+        if name == "main" {
+            continue;
+        }
+        lines.push(Line::raw(""));
+        let mut spans = vec![
+            Span::styled(
+                format!("#{}", name),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                format!("  {}", lexicon::type_name(def)),
+                Style::default().fg(theme.info),
+            ),
+        ];
+        let constraints = lexicon::constraints(def);
+        if !constraints.is_empty() {
+            spans.push(Span::styled(
+                format!("  {}", constraints.join(", ")),
+                Style::default().fg(theme.secondary),
+            ));
+        }
+        lines.push(Line::from(spans));
+        if let Some(description) = def["description"].as_str() {
+            lines.push(Line::raw(description.to_string()));
+        }
+        let inner = if def["type"] == "record" {
+            &def["record"]
+        } else {
+            def
+        };
+        lines.extend(field_lines(&lexicon::fields(inner, 0), theme));
+    }
+
+    lines.push(Line::raw(""));
+    lines.push(heading("Schema".to_string()));
+    lines.extend(
+        syntax_highlight(
+            &serde_json::to_string_pretty(&lexicon.doc).unwrap_or_default(),
+            theme,
+        )
+        .lines,
+    );
+
+    Text::from(lines)
+}
+
+/// One line per field, e.g. `  limit  integer  1–100, default 50`, with its
+/// description under it.
+fn field_lines(fields: &[lexicon::Field], theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for field in fields {
+        let indent = "  ".repeat(field.depth + 1);
+        let mut spans = vec![
+            Span::styled(
+                format!("{}{}", indent, field.name),
+                Style::default().fg(theme.accent),
+            ),
+            Span::styled(format!("  {}", field.kind), Style::default().fg(theme.info)),
+        ];
+        if field.required {
+            spans.push(Span::styled(
+                "  required",
+                Style::default().fg(theme.warning),
+            ));
+        }
+        if !field.constraints.is_empty() {
+            spans.push(Span::styled(
+                format!("  {}", field.constraints.join(", ")),
+                Style::default().fg(theme.secondary),
+            ));
+        }
+        lines.push(Line::from(spans));
+        if let Some(description) = &field.description {
+            lines.push(Line::styled(
+                format!("{}  {}", indent, description),
+                Style::default().fg(theme.muted),
+            ));
+        }
+    }
+    lines
+}
+
+/// One-line description of the request behind the current response, e.g.
+/// `12:01:33 app.bsky.actor.getProfile actor=bsky.app → 200`.
+fn request_summary(app: &AppState) -> Line<'static> {
+    let Some(hist) = app.request_history.front() else {
+        return Line::from("");
+    };
+
+    let time_str = format!(
+        "{:02}:{:02}:{:02}",
+        hist.timestamp.hour(),
+        hist.timestamp.minute(),
+        hist.timestamp.second()
+    );
+
+    let mut spans = vec![
+        Span::styled(time_str, Style::default().fg(app.theme.secondary)),
+        Span::raw(" "),
+        Span::styled(
+            hist.method.clone(),
+            Style::default()
+                .fg(app.theme.accent)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ];
+
+    // Only show the params that distinguish this request from the defaults
+    if let Some(cmd) = commands::all().iter().find(|c| c.method == hist.method) {
+        for (param, value) in cmd.parameters.iter().zip(&hist.params) {
+            if value.is_empty() || param.name == "cursor" || Some(value.as_str()) == param.default {
+                continue;
+            }
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                format!("{}={}", param.name, value),
+                Style::default().fg(app.theme.info),
+            ));
+        }
+    }
+
+    if let Some(status) = hist.status {
+        let status_style = if hist.success {
+            Style::default().fg(app.theme.success)
+        } else {
+            Style::default().fg(app.theme.error)
+        };
+        spans.push(Span::raw(" → "));
+        spans.push(Span::styled(status.to_string(), status_style));
+    }
+
+    Line::from(spans)
+}
+
+/// Restyles the given byte ranges of a line, splitting spans where a range
+/// starts or ends part-way through one.
+fn highlight_ranges(line: Line<'static>, ranges: &[(usize, usize)], style: Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+
+    for span in line.spans {
+        let content = span.content.to_string();
+        let len = content.len();
+        let mut cursor = 0;
+
+        for &(start, end) in ranges {
+            let start = start.clamp(offset, offset + len) - offset;
+            let end = end.clamp(offset, offset + len) - offset;
+            if start >= end {
+                continue;
+            }
+
+            if start > cursor {
+                spans.push(Span::styled(content[cursor..start].to_string(), span.style));
+            }
+            spans.push(Span::styled(
+                content[start..end].to_string(),
+                span.style.patch(style),
+            ));
+            cursor = end;
+        }
+
+        if cursor < len {
+            spans.push(Span::styled(content[cursor..].to_string(), span.style));
+        }
+        offset += len;
+    }
+
+    Line::from(spans)
+}
+
+/// Prefixes each line with its number in the expanded output. The focused
+/// line, which `Space` folds, is marked.
+fn line_numbers(lines: &[ViewLine], focused: usize, theme: &Theme) -> Vec<Span<'static>> {
+    let width = lines.last().map_or(1, |line| line.number.to_string().len());
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let (marker, style) = if i == focused {
+                ("▸", Style::default().fg(theme.accent))
+            } else {
+                (" ", Style::default().fg(theme.muted))
+            };
+            Span::styled(
+                format!("{:>width$}{} ", line.number, marker, width = width),
+                style,
+            )
+        })
+        .collect()
+}
+
+/// A readable summary of responses with a known shape, shown above their
+/// JSON: a profile card for `getProfile`.
+fn response_summary(app: &AppState) -> Option<Vec<Line<'static>>> {
+    if !app.show_summary
+        || app.show_diff
+        || app.filter.is_some()
+        || app.output_method.as_deref() != Some(commands::GET_PROFILE)
+    {
+        return None;
+    }
+    let profile = app.output.as_ref()?;
+    let handle = profile["handle"].as_str()?;
+    let count = |field: &str| profile[field].as_u64().unwrap_or_default();
+
+    let mut name = vec![Span::styled(
+        format!("@{}", handle),
+        Style::default().fg(app.theme.accent),
+    )];
+    if let Some(display_name) = profile["displayName"].as_str().filter(|n| !n.is_empty()) {
+        name.insert(
+            0,
+            Span::styled(
+                format!("{} ", display_name),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    let mut lines = vec![
+        Line::from(name),
+        Line::styled(
+            profile["did"].as_str().unwrap_or_default().to_string(),
+            Style::default().fg(app.theme.muted),
+        ),
+        Line::from(format!(
+            "{} followers · {} following · {} posts",
+            count("followersCount"),
+            count("followsCount"),
+            count("postsCount")
+        )),
+    ];
+    if let Some(bio) = profile["description"].as_str() {
+        lines.extend(
+            bio.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| Line::from(line.to_string())),
+        );
+    }
+    Some(lines)
+}
+
+/// A feed response as one block per post, scrolled to keep the selected one
+/// in view.
+fn render_feed(app: &AppState, feed: &[Value], f: &mut Frame, area: Rect) {
+    if feed.is_empty() {
+        let empty = Line::styled("No posts", Style::default().fg(app.theme.muted));
+        f.render_widget(Paragraph::new(empty), area);
+        return;
+    }
+
+    let selected = app.selected_feed_item();
+    let mut lines = Vec::new();
+    let mut selected_rows = 0..0;
+    let mut row = 0;
+    for (i, item) in feed.iter().enumerate() {
+        let marker = if i == selected {
+            Span::styled("▌ ", Style::default().fg(app.theme.accent))
+        } else {
+            Span::raw("  ")
+        };
+        let block: Vec<Line> = feed_item_lines(item, &app.theme)
+            .into_iter()
+            .map(|mut line| {
+                line.spans.insert(0, marker.clone());
+                line
+            })
+            .collect();
+
+        let rows = Paragraph::new(block.clone())
+            .wrap(Wrap { trim: false })
+            .line_count(area.width);
+        if i == selected {
+            selected_rows = row..row + rows;
+        }
+        row += rows + 1;
+        lines.extend(block);
+        lines.push(Line::raw(""));
+    }
+
+    // Scroll just far enough to show all of the selected post
+    let height = area.height as usize;
+    let scroll = selected_rows
+        .end
+        .saturating_sub(height)
+        .min(selected_rows.start);
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll as u16, 0));
+    f.render_widget(paragraph, area);
+}
+
+/// A feed item: who reposted it or what it replies to, the author, the text,
+/// and its counts with a marker for any embed.
+fn feed_item_lines(item: &Value, theme: &Theme) -> Vec<Line<'static>> {
+    let post = &item["post"];
+    let muted = Style::default().fg(theme.muted);
+    let mut lines = Vec::new();
+
+    match item["reason"]["$type"].as_str() {
+        Some("app.bsky.feed.defs#reasonRepost") => lines.push(Line::styled(
+            format!(
+                "↻ reposted by @{}",
+                item["reason"]["by"]["handle"].as_str().unwrap_or("?")
+            ),
+            muted,
+        )),
+        Some("app.bsky.feed.defs#reasonPin") => lines.push(Line::styled("pinned", muted)),
+        _ => {}
+    }
+    if let Some(parent) = item["reply"]["parent"]["author"]["handle"].as_str() {
+        lines.push(Line::styled(format!("↳ replying to @{}", parent), muted));
+    } else if !post["record"]["reply"].is_null() {
+        lines.push(Line::styled("↳ reply", muted));
+    }
+
+    let mut author = vec![Span::styled(
+        format!("@{}", post["author"]["handle"].as_str().unwrap_or("?")),
+        Style::default().fg(theme.accent),
+    )];
+    if let Some(name) = post["author"]["displayName"]
+        .as_str()
+        .filter(|name| !name.is_empty())
+    {
+        author.insert(
+            0,
+            Span::styled(
+                format!("{} ", name),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+    lines.push(Line::from(author));
+
+    lines.extend(
+        post["record"]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    );
+
+    let count = |field: &str| post[field].as_u64().unwrap_or_default();
+    let mut stats = format!(
+        "♥ {}  ↻ {}  💬 {}",
+        count("likeCount"),
+        count("repostCount"),
+        count("replyCount")
+    );
+    if let Some(embed) = embed_marker(&post["embed"]) {
+        stats.push_str(&format!("  [{}]", embed));
+    }
+    lines.push(Line::styled(stats, muted));
+    lines
+}
+
+/// A short description of a post's embed, e.g. `2 images` or `quote`.
+fn embed_marker(embed: &Value) -> Option<String> {
+    let marker = match embed["$type"].as_str()? {
+        "app.bsky.embed.images#view" => match embed["images"].as_array().map_or(0, Vec::len) {
+            1 => "image".to_string(),
+            n => format!("{} images", n),
+        },
+        "app.bsky.embed.external#view" => {
+            format!(
+                "link: {}",
+                embed["external"]["title"].as_str().unwrap_or("?")
+            )
+        }
+        "app.bsky.embed.record#view" => "quote".to_string(),
+        "app.bsky.embed.recordWithMedia#view" => "quote with media".to_string(),
+        "app.bsky.embed.video#view" => "video".to_string(),
+        _ => "embed".to_string(),
+    };
+    Some(marker)
+}
+
+/// Rows `summary` takes at `width`, with the rule under it.
+fn summary_height(summary: &[Line], width: u16) -> usize {
+    Paragraph::new(summary.to_vec())
+        .wrap(Wrap { trim: true })
+        .line_count(width)
+        + 1
+}
+
+/// Rows the summary above the response takes up in a viewer `width`
+/// columns wide, or 0 if there isn't one.
+pub fn summary_rows(app: &AppState, width: u16) -> usize {
+    response_summary(app).map_or(0, |lines| summary_height(&lines, width))
+}
+
+/// Rows each line of the response takes up in a viewer `width` columns
+/// wide: one, or more for a line that wraps.
+pub fn line_rows(app: &AppState, width: u16) -> Vec<usize> {
+    let lines = app.view_lines();
+    if !app.wrap_lines {
+        return vec![1; lines.len()];
+    }
+
+    // Wrapping depends only on the text, so skip the highlighting
+    let gutter = if app.line_numbers {
+        line_numbers(&lines, 0, &app.theme)
+    } else {
+        Vec::new()
+    };
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut spans = vec![Span::raw(line.text.as_str())];
+            if let Some(number) = gutter.get(i) {
+                spans.insert(0, number.clone());
+            }
+            Paragraph::new(Line::from(spans))
+                .wrap(Wrap { trim: true })
+                .line_count(width)
+        })
+        .collect()
+}
+
+/// Rows the response takes up in a viewer `width` columns wide, counting
+/// each row of a wrapped line.
+pub fn response_height(app: &AppState, width: u16) -> usize {
+    if let Some(diff) = app.diff_view() {
+        return diff.len();
+    }
+    // The summary takes rows from the viewer, so count them as content
+    line_rows(app, width).iter().sum::<usize>() + summary_rows(app, width)
+}
+
+/// Width of the response's longest line, for bounding sideways scrolling.
+pub fn response_width(app: &AppState) -> usize {
+    app.view_lines()
+        .iter()
+        .map(|line| line.text.width())
+        .max()
+        .unwrap_or_default()
+}
+
+fn render_help(app: &AppState, f: &mut Frame, area: Rect) {
+    let keys = if app.loading.is_some() {
+        &keys::LOADING
+    } else {
+        keys::for_mode(&app.input.mode)
+    };
+
+    let help = Paragraph::new(keys.help_line()).style(Style::default().fg(app.theme.muted));
+    f.render_widget(help, area);
+}
+
+/// Every mode's keybindings, over the whole screen.
+fn render_help_overlay(app: &AppState, scroll: u16, f: &mut Frame) {
+    let area = f.area();
+    let InputMode::Help { previous, .. } = &app.input.mode else {
+        return;
+    };
+    let block = Block::default()
+        .title(format!(
+            "Keybindings — {} (↑↓ to scroll, Esc to close)",
+            previous.breadcrumb().join(" › ")
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let key_width = keys::ALL
+        .iter()
+        .flat_map(|group| group.bindings)
+        .map(|(keys, _)| keys.width())
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = Vec::new();
+    for group in keys::ALL {
+        if !lines.is_empty() {
+            lines.push(Line::raw(""));
+        }
+        let mut heading = vec![Span::styled(
+            group.name,
+            Style::default()
+                .fg(app.theme.accent)
+                .add_modifier(Modifier::BOLD),
+        )];
+        // Point out the keys for the screen help was opened from
+        if group.name == keys::for_mode(previous).name {
+            heading.push(Span::styled(
+                format!(" ◀ {}", previous.name()),
+                Style::default().fg(app.theme.info),
+            ));
+        }
+        lines.push(Line::from(heading));
+        for (keys, action) in group.bindings {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {:width$}  ", keys, width = key_width),
+                    Style::default().fg(app.theme.success),
+                ),
+                Span::raw(*action),
+            ]));
+        }
+    }
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
+
+pub fn syntax_highlight(json: &str, theme: &Theme) -> Text<'static> {
+    // The input is already pretty-printed, so we keep its line structure and
+    // only tokenize within each line.
+    const SPACE: &str = "\u{00A0}";
+
+    let lines: Vec<Line<'static>> = json
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+
+            let mut spans = Vec::new();
+            if indent > 0 {
+                spans.push(Span::raw(SPACE.repeat(indent)));
+            }
+            spans.extend(highlight_line(trimmed, theme));
+            Line::from(spans)
+        })
+        .collect();
+
+    Text::from(lines)
+}
+
+fn highlight_line(line: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' => {
+                let mut end = line.len();
+                let mut escaped = false;
+                for (i, c) in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        end = i + 1;
+                        break;
+                    }
+                }
+
+                let is_key = line[end..].trim_start().starts_with(':');
+                let style = if is_key {
+                    Style::default().fg(theme.json_key)
+                } else {
+                    Style::default().fg(theme.json_string)
+                };
+                spans.push(Span::styled(line[start..end].to_string(), style));
+            }
+            '{' | '}' | '[' | ']' => {
+                spans.push(Span::styled(
+                    c.to_string(),
+                    Style::default().fg(theme.json_bracket),
+                ));
+            }
+            ':' => {
+                spans.push(Span::styled(":", Style::default().fg(theme.json_colon)));
+            }
+            ',' | ' ' => {
+                spans.push(Span::raw(c.to_string()));
+            }
+            _ => {
+                let mut end = line.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if matches!(c, ',' | ' ' | '}' | ']' | ':') {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+
+                let literal = &line[start..end];
+                let style = match literal {
+                    "true" | "false" => Style::default().fg(theme.json_bool),
+                    "null" => Style::default().fg(theme.json_null),
+                    _ if literal.parse::<f64>().is_ok() => Style::default().fg(theme.json_number),
+                    _ => Style::default(),
+                };
+                spans.push(Span::styled(literal.to_string(), style));
+            }
+        }
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::InputState;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    /// The tokens of each line, leaving out indentation, spaces and commas.
+    fn tokens(text: &Text) -> Vec<Vec<(String, Option<Color>)>> {
+        text.lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .filter(|span| !matches!(span.content.trim(), "" | ","))
+                    .map(|span| (span.content.to_string(), span.style.fg))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keys_and_string_values_are_colored_apart() {
+        let theme = Theme::default();
+        let text = syntax_highlight("{\n  \"handle\": \"bsky.app\"\n}", &theme);
+
+        assert_eq!(
+            tokens(&text)[1],
+            vec![
+                ("\"handle\"".to_string(), Some(theme.json_key)),
+                (":".to_string(), Some(theme.json_colon)),
+                ("\"bsky.app\"".to_string(), Some(theme.json_string)),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_stay_inside_the_string() {
+        let theme = Theme::default();
+        let json = serde_json::to_string_pretty(&serde_json::json!({
+            "text": "she said \"hi\", then left",
+            "after": 1,
+        }))
+        .unwrap();
+        let text = syntax_highlight(&json, &theme);
+
+        let lines = tokens(&text);
+        assert_eq!(
+            lines[1],
+            vec![
+                ("\"after\"".to_string(), Some(theme.json_key)),
+                (":".to_string(), Some(theme.json_colon)),
+                ("1".to_string(), Some(theme.json_number)),
+            ]
+        );
+        assert_eq!(
+            lines[2][2],
+            (
+                r#""she said \"hi\", then left""#.to_string(),
+                Some(theme.json_string)
+            )
+        );
+        assert_eq!(lines[2].len(), 3);
+    }
+
+    #[test]
+    fn numbers_bools_and_null_get_their_own_colors() {
+        let theme = Theme::default();
+        let text = syntax_highlight("[\n  -1.5e3,\n  true,\n  false,\n  null\n]", &theme);
+
+        let values: Vec<_> = tokens(&text)[1..5]
+            .iter()
+            .map(|line| line[0].clone())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("-1.5e3".to_string(), Some(theme.json_number)),
+                ("true".to_string(), Some(theme.json_bool)),
+                ("false".to_string(), Some(theme.json_bool)),
+                ("null".to_string(), Some(theme.json_null)),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_objects_keep_their_lines_and_indentation() {
+        let theme = Theme::default();
+        let json = "{\n  \"post\": {\n    \"likes\": [\n      1\n    ]\n  }\n}";
+        let text = syntax_highlight(json, &theme);
+
+        assert_eq!(text.lines.len(), json.lines().count());
+        assert_eq!(text.lines[3].spans[0].content, "\u{00A0}".repeat(6));
+        assert_eq!(
+            tokens(&text)[1],
+            vec![
+                ("\"post\"".to_string(), Some(theme.json_key)),
+                (":".to_string(), Some(theme.json_colon)),
+                ("{".to_string(), Some(theme.json_bracket)),
+            ]
+        );
+    }
+
+    #[test]
+    fn multibyte_text_is_kept_whole() {
+        let theme = Theme::default();
+        let text = syntax_highlight("{\n  \"名前\": \"héllo 👋\",\n  \"n\": 2\n}", &theme);
+
+        let lines = tokens(&text);
+        assert_eq!(lines[1][0], ("\"名前\"".to_string(), Some(theme.json_key)));
+        assert_eq!(
+            lines[1][2],
+            ("\"héllo 👋\"".to_string(), Some(theme.json_string))
+        );
+        assert_eq!(lines[2][2], ("2".to_string(), Some(theme.json_number)));
+    }
+
+    /// Draws just the input box for `input`, returning its text row and
+    /// where the cursor ended up.
+    fn draw_input(input: InputState) -> (String, Position) {
+        let state = AppState {
+            input,
+            ..Default::default()
+        };
+        let mut terminal = Terminal::new(TestBackend::new(30, 3)).unwrap();
+        terminal
+            .draw(|f| render_input(&state, f, f.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let row = (1..29).map(|x| buffer[(x, 1)].symbol()).collect::<String>();
+        (
+            row.trim_end().to_string(),
+            terminal.get_cursor_position().unwrap(),
+        )
+    }
+
+    #[test]
+    fn pasted_password_is_masked_one_bullet_per_char() {
+        let mut input = InputState::default();
+        input.mode = InputMode::Password;
+        input.insert_str("pä🦋\t日\x1b");
+
+        assert_eq!(input.content, "pä🦋日");
+        let (row, cursor) = draw_input(input);
+        assert_eq!(row, "••••");
+        assert_eq!(cursor, Position { x: 5, y: 1 });
+    }
+
+    #[test]
+    fn cursor_sits_after_wide_chars() {
+        let mut input = InputState::default();
+        input.insert_str("日本x");
+        let (row, cursor) = draw_input(input.clone());
+        // A wide char's second cell is left blank in the buffer
+        assert_eq!(row, "日 本 x");
+        assert_eq!(cursor, Position { x: 6, y: 1 });
+
+        input.handle_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        let (_, cursor) = draw_input(input);
+        assert_eq!(cursor, Position { x: 5, y: 1 });
+    }
+
+    #[test]
+    fn the_marker_follows_the_focused_line_below_a_wrapped_one() {
+        let (width, height) = (30, 12);
+        let mut state = AppState {
+            output: Some(serde_json::json!({
+                "description": "a bio long enough to wrap onto several rows of a narrow viewer",
+                "handle": "bsky.app",
+            })),
+            wrap_lines: true,
+            line_numbers: true,
+            cursor_line: 2,
+            scroll_offset: 1,
+            ..Default::default()
+        };
+        state.input.mode = InputMode::ViewingResponse;
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| render_output(&state, f, f.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let marked: Vec<String> = (0..height)
+            .map(|y| (0..width).map(|x| buffer[(x, y)].symbol()).collect())
+            .filter(|row: &String| row.contains('▸'))
+            .collect();
+        assert_eq!(marked.len(), 1);
+        assert!(marked[0].contains("\"handle\""));
+    }
+
+    /// The rows of the response viewer drawn `width` by `height`, inside
+    /// its borders and below the request summary.
+    fn response_rows(state: &AppState, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| render_output(state, f, f.area()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (2..height - 1)
+            .map(|y| {
+                (1..width - 1)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    // The marker beside the focused line moves with it
+                    .replace('▸', " ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn end_shows_exactly_the_last_wrapped_row() {
+        let (width, height) = (30, 12);
+        // Less the borders and the request summary
+        let viewport = height - 3;
+        for line_numbers in [false, true] {
+            let mut state = AppState {
+                output: Some(serde_json::json!({
+                    "description": "a bio long enough to wrap onto several rows of a narrow viewer",
+                    "handle": "bsky.app",
+                    "posts": ["the last line is this one, and it wraps as well"],
+                })),
+                wrap_lines: true,
+                line_numbers,
+                ..Default::default()
+            };
+            state.input.mode = InputMode::ViewingResponse;
+
+            let content = response_height(&state, width - 2) as u16;
+            assert!(content > state.view_lines().len() as u16);
+            // Everything at once, in a viewer tall enough for all of it
+            let all = response_rows(&state, width, content + 3);
+            assert_eq!(all.len(), content as usize);
+
+            state.scroll_offset = content - viewport;
+            let end = response_rows(&state, width, height);
+            assert_eq!(end, all[all.len() - viewport as usize..]);
+            assert!(end.last().unwrap().trim_end().ends_with('}'));
+        }
+    }
+}
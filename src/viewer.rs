@@ -0,0 +1,286 @@
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// One line of the response as shown in the viewer.
+#[derive(Debug, Clone)]
+pub struct ViewLine {
+    /// 1-based line number in the fully expanded output, so numbers stay put
+    /// when other nodes are collapsed.
+    pub number: usize,
+    pub text: String,
+    /// Path of the object or array that opens on this line, if any.
+    pub opens: Option<String>,
+    /// Path of the object or array this line belongs to.
+    pub parent: String,
+    /// JSON Pointer to the value that starts on this line, or to the
+    /// object or array a closing bracket ends.
+    pub pointer: String,
+}
+
+impl ViewLine {
+    /// The node folded by toggling on this line: the one it opens, or else
+    /// the one it sits in.
+    pub fn fold_path(&self) -> &str {
+        self.opens.as_deref().unwrap_or(&self.parent)
+    }
+}
+
+/// Lays `value` out the same way as `serde_json::to_string_pretty`, except
+/// that nodes whose path is in `collapsed` are shown on one line as `{…}` or
+/// `[…N items]`. Paths are dot-separated keys and indices, with `""` for the
+/// root.
+pub fn view_lines(value: &Value, collapsed: &HashSet<String>) -> Vec<ViewLine> {
+    let mut builder = Builder {
+        collapsed,
+        lines: Vec::new(),
+        number: 0,
+    };
+    builder.node(value, &Location::default(), "", 0, "", "");
+    builder.lines
+}
+
+/// The text of `lines`, one per line, for highlighting and searching.
+pub fn join(lines: &[ViewLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Where a node sits: its dotted path, used for folding, and its JSON
+/// Pointer, used to look the value up again.
+#[derive(Default)]
+struct Location {
+    path: String,
+    pointer: String,
+}
+
+impl Location {
+    fn child(&self, segment: &str) -> Self {
+        let path = if self.path.is_empty() {
+            segment.to_string()
+        } else {
+            format!("{}.{}", self.path, segment)
+        };
+        let escaped = segment.replace('~', "~0").replace('/', "~1");
+        Self {
+            path,
+            pointer: format!("{}/{}", self.pointer, escaped),
+        }
+    }
+}
+
+struct Builder<'a> {
+    collapsed: &'a HashSet<String>,
+    lines: Vec<ViewLine>,
+    /// Lines emitted so far in the expanded layout.
+    number: usize,
+}
+
+impl Builder<'_> {
+    fn push(&mut self, text: String, opens: Option<String>, parent: &str, pointer: &str) {
+        self.number += 1;
+        self.lines.push(ViewLine {
+            number: self.number,
+            text,
+            opens,
+            parent: parent.to_string(),
+            pointer: pointer.to_string(),
+        });
+    }
+
+    fn node(
+        &mut self,
+        value: &Value,
+        location: &Location,
+        parent: &str,
+        depth: usize,
+        key: &str,
+        comma: &str,
+    ) {
+        let indent = "  ".repeat(depth);
+        let (path, pointer) = (location.path.as_str(), location.pointer.as_str());
+
+        let (open, close, len) = match value {
+            Value::Object(fields) if !fields.is_empty() => ('{', '}', fields.len()),
+            Value::Array(items) if !items.is_empty() => ('[', ']', items.len()),
+            _ => {
+                let text = format!("{}{}{}{}", indent, key, value, comma);
+                self.push(text, None, parent, pointer);
+                return;
+            }
+        };
+
+        if self.collapsed.contains(path) {
+            let summary = match value {
+                Value::Array(_) if len == 1 => "[…1 item]".to_string(),
+                Value::Array(_) => format!("[…{} items]", len),
+                _ => "{…}".to_string(),
+            };
+            let text = format!("{}{}{}{}", indent, key, summary, comma);
+            self.push(text, Some(path.to_string()), parent, pointer);
+            // Keep numbering in step with the expanded layout
+            self.number += expanded_len(value) - 1;
+            return;
+        }
+
+        self.push(
+            format!("{}{}{}", indent, key, open),
+            Some(path.to_string()),
+            parent,
+            pointer,
+        );
+
+        let children: Vec<(Location, String, &Value)> = match value {
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(name, child)| {
+                    let key = format!("{}: ", Value::String(name.clone()));
+                    (location.child(name), key, child)
+                })
+                .collect(),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, child)| (location.child(&i.to_string()), String::new(), child))
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        let last = children.len() - 1;
+        for (i, (child_location, key, child)) in children.into_iter().enumerate() {
+            let comma = if i == last { "" } else { "," };
+            self.node(child, &child_location, path, depth + 1, &key, comma);
+        }
+
+        self.push(format!("{}{}{}", indent, close, comma), None, path, pointer);
+    }
+}
+
+/// Number of lines `value` takes up when fully expanded.
+fn expanded_len(value: &Value) -> usize {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            fields.values().map(expanded_len).sum::<usize>() + 2
+        }
+        Value::Array(items) if !items.is_empty() => {
+            items.iter().map(expanded_len).sum::<usize>() + 2
+        }
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "feed": [
+                { "post": { "likeCount": 3, "uri": "at://did:plc:a/app.bsky.feed.post/1" } },
+                { "post": { "likeCount": 0, "uri": "at://did:plc:a/app.bsky.feed.post/2" } },
+            ],
+            "tags": ["one"],
+            "cursor": "abc",
+        })
+    }
+
+    fn collapsed(paths: &[&str]) -> HashSet<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    fn texts(lines: &[ViewLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.trim()).collect()
+    }
+
+    #[test]
+    fn expanded_matches_pretty_printing() {
+        let value = sample();
+        let lines = view_lines(&value, &HashSet::new());
+        assert_eq!(join(&lines), serde_json::to_string_pretty(&value).unwrap());
+        let numbers: Vec<_> = lines.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, (1..=lines.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn collapsing_an_object_folds_it_onto_one_line() {
+        let value = sample();
+        let expanded = view_lines(&value, &HashSet::new());
+        let lines = view_lines(&value, &collapsed(&["feed.0"]));
+
+        assert_eq!(lines.len(), expanded.len() - 5);
+        let folded = &lines[3];
+        assert_eq!(folded.text, "    {…},");
+        assert_eq!(folded.opens.as_deref(), Some("feed.0"));
+        assert_eq!(folded.pointer, "/feed/0");
+        // The next line keeps the number it has when everything's expanded
+        assert_eq!(lines[4].number, 10);
+        assert_eq!(lines[4].text, expanded[9].text);
+    }
+
+    #[test]
+    fn collapsed_arrays_count_their_items() {
+        let value = sample();
+        let lines = view_lines(&value, &collapsed(&["feed", "tags"]));
+        assert_eq!(
+            texts(&lines),
+            [
+                "{",
+                "\"cursor\": \"abc\",",
+                "\"feed\": […2 items],",
+                "\"tags\": […1 item]",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsing_the_root_leaves_one_line() {
+        let lines = view_lines(&sample(), &collapsed(&[""]));
+        assert_eq!(texts(&lines), ["{…}"]);
+        assert_eq!(lines[0].fold_path(), "");
+    }
+
+    #[test]
+    fn collapsed_children_stay_folded_inside_a_collapsed_parent() {
+        let value = sample();
+        let both = collapsed(&["feed", "feed.1"]);
+        assert_eq!(view_lines(&value, &both).len(), 7);
+
+        // Unfolding the parent shows the child still folded
+        let lines = view_lines(&value, &collapsed(&["feed.1"]));
+        let child = lines
+            .iter()
+            .find(|line| line.opens.as_deref() == Some("feed.1"))
+            .unwrap();
+        assert_eq!(child.text.trim(), "{…}");
+    }
+
+    #[test]
+    fn lines_fold_the_node_they_open_or_sit_in() {
+        let lines = view_lines(&sample(), &HashSet::new());
+        let like = lines
+            .iter()
+            .find(|line| line.text.contains("likeCount\": 3"))
+            .unwrap();
+        assert_eq!(like.fold_path(), "feed.0.post");
+        assert_eq!(like.pointer, "/feed/0/post/likeCount");
+        let close = lines
+            .iter()
+            .position(|line| line.opens.as_deref() == Some("feed.0.post"))
+            .map(|open| &lines[open + 3])
+            .unwrap();
+        assert_eq!(close.text.trim(), "}");
+        assert_eq!(close.fold_path(), "feed.0.post");
+    }
+
+    #[test]
+    fn empty_containers_dont_fold() {
+        let lines = view_lines(&json!({ "a": {}, "b": [] }), &HashSet::new());
+        assert!(lines[1].opens.is_none());
+        assert!(lines[2].opens.is_none());
+        assert_eq!(lines[1].fold_path(), "");
+    }
+}
@@ -0,0 +1,897 @@
+use http_client::isahc::IsahcClient;
+use isahc::{
+    config::{CaCertificate, Configurable, SslOption},
+    HttpClient,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use surf::{Client, RequestBuilder, StatusCode};
+
+use crate::{
+    error::AppError,
+    oauth::{self, OAuthSession, Tokens},
+    proxy::Proxy,
+};
+
+/// Where requests go when neither the config nor the profile names a PDS.
+pub const DEFAULT_PDS_HOST: &str = "https://bsky.social";
+
+/// Where `did:plc` documents are published.
+const PLC_DIRECTORY: &str = "https://plc.directory";
+
+/// Routes a request through the PDS to another service, e.g.
+/// `did:web:api.bsky.chat#bsky_chat` for chat.
+pub const PROXY_HEADER: &str = "atproto-proxy";
+
+const CREATE_SESSION: &str = "com.atproto.server.createSession";
+const REFRESH_SESSION: &str = "com.atproto.server.refreshSession";
+const GET_SESSION: &str = "com.atproto.server.getSession";
+
+/// `createSession` wants the sign-in code emailed to an account with 2FA.
+pub const AUTH_FACTOR_REQUIRED: &str = "AuthFactorTokenRequired";
+/// `createSession` didn't accept the sign-in code.
+pub const INVALID_TOKEN: &str = "InvalidToken";
+
+/// First wait before retrying; it doubles with each attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest `Retry-After` or quota reset that's waited for, so a server can't stall a request
+/// indefinitely.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// What `createSession` and `refreshSession` return.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub access_jwt: String,
+    pub refresh_jwt: String,
+    pub did: String,
+    pub handle: String,
+}
+
+/// The request quota a PDS reports in its `ratelimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// When the window ends and `remaining` goes back up to `limit`.
+    pub reset: SystemTime,
+}
+
+impl RateLimit {
+    /// Reads `ratelimit-limit`, `ratelimit-remaining` and `ratelimit-reset`.
+    /// The PDS sends the reset as a Unix timestamp, but small values are
+    /// taken as seconds from now, as the IETF draft has it. `None` unless all
+    /// three are present and numeric.
+    pub fn from_headers(res: &surf::Response) -> Option<Self> {
+        let header = |name: &str| res.header(name)?.as_str().trim().parse::<u64>().ok();
+        let reset = header("ratelimit-reset")?;
+        Some(Self {
+            limit: header("ratelimit-limit")?,
+            remaining: header("ratelimit-remaining")?,
+            reset: if reset < 1_000_000_000 {
+                SystemTime::now() + Duration::from_secs(reset)
+            } else {
+                UNIX_EPOCH + Duration::from_secs(reset)
+            },
+        })
+    }
+
+    /// How long until the window resets, zero once it has.
+    pub fn resets_in(&self) -> Duration {
+        self.reset
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+    }
+
+    /// Whether less than a tenth of the quota is left.
+    pub fn is_low(&self) -> bool {
+        self.remaining * 10 < self.limit
+    }
+}
+
+/// Talks XRPC to a PDS: builds authenticated requests and keeps the
+/// session's tokens. It knows nothing about the terminal or `AppState`, so
+/// the TUI, `exec` and background tasks all go through the same client.
+#[derive(Debug, Clone)]
+pub struct XrpcClient {
+    client: Client,
+    pub pds_host: String,
+    pub access_token: Option<String>,
+    pub refresh_token: Option<String>,
+    /// Set when logged in with OAuth, whose tokens are DPoP-bound.
+    pub oauth: Option<OAuthSession>,
+    /// How long `query`, `procedure` and the session calls wait for a
+    /// response.
+    pub timeout: Duration,
+}
+
+/// The HTTP client under surf. It sends through `proxy`, or directly when
+/// that's `None`, ignoring the environment's proxy variables. `ca_cert`
+/// replaces the system's trusted CAs, and `accept_invalid_certs` turns off
+/// certificate and hostname checks altogether.
+pub fn http_client(
+    proxy: Option<&Proxy>,
+    ca_cert: Option<&Path>,
+    accept_invalid_certs: bool,
+) -> Result<IsahcClient, String> {
+    let mut builder = HttpClient::builder().proxy(proxy.map(|proxy| proxy.uri.clone()));
+    if let Some(path) = ca_cert {
+        builder = builder.ssl_ca_certificate(CaCertificate::file(path));
+    }
+    if accept_invalid_certs {
+        builder = builder.ssl_options(
+            SslOption::DANGER_ACCEPT_INVALID_CERTS | SslOption::DANGER_ACCEPT_INVALID_HOSTS,
+        );
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+    Ok(IsahcClient::from_client(client))
+}
+
+impl XrpcClient {
+    pub fn new(client: Client, pds_host: String, timeout: Duration) -> Self {
+        Self {
+            client,
+            pds_host,
+            access_token: None,
+            refresh_token: None,
+            oauth: None,
+            timeout,
+        }
+    }
+
+    /// The endpoint for `method` on this PDS.
+    pub fn url(&self, method: &str) -> String {
+        format!("{}/xrpc/{}", self.pds_host.trim_end_matches('/'), method)
+    }
+
+    /// A GET for `url`, carrying the access token once logged in.
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.authorize(self.client.get(url))
+    }
+
+    /// A POST for `url`, carrying the access token once logged in.
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.authorize(self.client.post(url))
+    }
+
+    /// The HTTP client, for requests that aren't XRPC calls.
+    pub fn http(&self) -> &Client {
+        &self.client
+    }
+
+    fn authorize(&self, req: RequestBuilder) -> RequestBuilder {
+        match (&self.access_token, &self.oauth) {
+            (Some(token), Some(oauth)) => oauth::sign(
+                req.header("Authorization", format!("DPoP {}", token)),
+                &oauth.key,
+                Some(token),
+            ),
+            (Some(token), None) => req.header("Authorization", format!("Bearer {}", token)),
+            (None, _) => req,
+        }
+    }
+
+    /// Logs in with `com.atproto.server.createSession` and keeps the
+    /// session's tokens for later requests. `auth_factor_token` is the
+    /// emailed sign-in code for accounts with 2FA.
+    pub async fn login(
+        &mut self,
+        identifier: &str,
+        password: &str,
+        auth_factor_token: Option<&str>,
+    ) -> Result<Session, AppError> {
+        let mut body = json!({
+            "identifier": identifier,
+            "password": password
+        });
+        if let Some(token) = auth_factor_token {
+            body["authFactorToken"] = token.into();
+        }
+        self.access_token = None;
+        self.oauth = None;
+        let value = self.procedure(CREATE_SESSION, &body).await?;
+        let session: Session = serde_json::from_value(value)
+            .map_err(|e| AppError::deserialize(&self.url(CREATE_SESSION), e.to_string()))?;
+        self.set_session(&session);
+        Ok(session)
+    }
+
+    /// Starts using the tokens from an OAuth login or refresh. They don't
+    /// name the account's handle, so it's looked up with
+    /// `com.atproto.server.getSession`, which also checks they work.
+    pub async fn start_oauth_session(
+        &mut self,
+        oauth: OAuthSession,
+        tokens: Tokens,
+    ) -> Result<Session, AppError> {
+        self.access_token = Some(tokens.access_token.clone());
+        self.refresh_token = Some(tokens.refresh_token.clone());
+        self.oauth = Some(oauth);
+
+        #[derive(Deserialize)]
+        struct Account {
+            did: String,
+            handle: String,
+        }
+        let url = self.url(GET_SESSION);
+        let account: Account = self.call(&url, self.get(&url)).await?;
+        if account.did != tokens.sub {
+            self.access_token = None;
+            self.refresh_token = None;
+            self.oauth = None;
+            return Err(AppError::Auth {
+                src: url,
+                err_span: (0, 0),
+                msg: format!(
+                    "Signed in as {}, but the PDS says the session is {}'s",
+                    tokens.sub, account.did
+                ),
+            });
+        }
+        Ok(Session {
+            access_jwt: tokens.access_token,
+            refresh_jwt: tokens.refresh_token,
+            did: account.did,
+            handle: account.handle,
+        })
+    }
+
+    /// Swaps the refresh token for new tokens with
+    /// `com.atproto.server.refreshSession`. A refused refresh ends the
+    /// session, since the old tokens won't work either.
+    pub async fn refresh(&mut self) -> Result<Session, AppError> {
+        let Some(refresh_token) = &self.refresh_token else {
+            return Err(AppError::Auth {
+                src: "session refresh".into(),
+                err_span: (0, 0),
+                msg: "No refresh token available".into(),
+            });
+        };
+
+        if let Some(session) = self.oauth.clone() {
+            return match oauth::refresh(self, &session, refresh_token).await {
+                Ok(tokens) => self.start_oauth_session(session, tokens).await,
+                Err(e) => {
+                    if matches!(e, AppError::HttpStatus { .. }) {
+                        self.access_token = None;
+                        self.refresh_token = None;
+                        self.oauth = None;
+                    }
+                    Err(e)
+                }
+            };
+        }
+
+        let url = self.url(REFRESH_SESSION);
+        let req = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", refresh_token));
+
+        match self.call(&url, req).await {
+            Ok(session) => {
+                self.set_session(&session);
+                Ok(session)
+            }
+            Err(e @ AppError::HttpStatus { .. }) => {
+                self.access_token = None;
+                self.refresh_token = None;
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn set_session(&mut self, session: &Session) {
+        self.access_token = Some(session.access_jwt.clone());
+        self.refresh_token = Some(session.refresh_jwt.clone());
+    }
+
+    /// Calls a query (GET) method with `params` as its query string.
+    pub async fn query(&self, method: &str, params: &[(&str, &str)]) -> Result<Value, AppError> {
+        let url = format!("{}{}", self.url(method), query_string(params));
+        self.call(&url, self.get(&url)).await
+    }
+
+    /// Calls a procedure (POST) method with `body` as its JSON input.
+    pub async fn procedure(&self, method: &str, body: &Value) -> Result<Value, AppError> {
+        let url = self.url(method);
+        let req = self.post(&url).body_json(body).map_err(|e| {
+            AppError::request_at(&url, None, format!("Failed to build request: {}", e))
+        })?;
+        self.call(&url, req).await
+    }
+
+    /// Looks up the handle a DID claims from its DID document, which comes
+    /// from the PLC directory for `did:plc` or the domain for `did:web`.
+    /// These aren't XRPC calls, so the access token isn't sent.
+    pub async fn resolve_did(&self, did: &str) -> Result<String, AppError> {
+        let url = match did.strip_prefix("did:web:") {
+            Some(domain) => format!("https://{}/.well-known/did.json", domain),
+            None if did.starts_with("did:plc:") => format!("{}/{}", PLC_DIRECTORY, did),
+            None => {
+                return Err(AppError::request_at(
+                    did,
+                    None,
+                    "Only did:plc and did:web can be resolved".into(),
+                ))
+            }
+        };
+
+        let document: Value = self.call(&url, self.client.get(&url)).await?;
+        document["alsoKnownAs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .find_map(|aka| aka.strip_prefix("at://"))
+            .map(str::to_string)
+            .ok_or_else(|| AppError::deserialize(&url, "The DID document names no handle".into()))
+    }
+
+    /// Sends `req` to `url` and parses the JSON response.
+    pub(crate) async fn call<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        req: RequestBuilder,
+    ) -> Result<T, AppError> {
+        let mut res = with_timeout(req, self.timeout)
+            .await
+            .map_err(|e| AppError::network(url, e.to_string()))?;
+
+        if !res.status().is_success() {
+            let body = res
+                .body_string()
+                .await
+                .unwrap_or_else(|e| format!("Failed to read error response: {}", e));
+            return Err(AppError::http_status(url, res.status().into(), body));
+        }
+
+        res.body_json::<T>()
+            .await
+            .map_err(|e| AppError::deserialize(url, e.to_string()))
+    }
+
+    /// Sends `request`, retrying up to `retries` times on transient
+    /// failures: transport errors, timeouts, 429 and 502–504. A POST might
+    /// have gone through before failing, so it's only retried on 429. Other
+    /// statuses are returned as they are. Returns the response and how long
+    /// its final attempt took to start responding.
+    pub async fn send_with_retries(
+        &self,
+        mut request: surf::Request,
+        timeout: Duration,
+        retries: u32,
+        mut on_retry: impl FnMut(u32, Duration, String),
+    ) -> surf::Result<(surf::Response, Duration)> {
+        // Each attempt needs its own copy of the body
+        let body = request.take_body();
+        let mime = body.mime().clone();
+        let has_body = body.is_empty() == Some(false) || body.len().is_none();
+        let body = body.into_bytes().await?;
+        let idempotent = request.method() == surf::http::Method::Get;
+
+        let mut attempt = 0;
+        loop {
+            let mut attempt_request = request.clone();
+            if has_body {
+                let mut attempt_body = surf::Body::from_bytes(body.clone());
+                attempt_body.set_mime(mime.clone());
+                attempt_request.set_body(attempt_body);
+            }
+
+            let started = Instant::now();
+            let result = with_timeout(self.client.send(attempt_request), timeout).await;
+            let elapsed = started.elapsed();
+
+            let retry = match &result {
+                Ok(res) => match res.status() {
+                    StatusCode::TooManyRequests => {
+                        // Without a `Retry-After`, wait for the quota to reset
+                        let retry_after = res
+                            .header("Retry-After")
+                            .and_then(|value| value.as_str().trim().parse::<u64>().ok())
+                            .map(Duration::from_secs)
+                            .or_else(|| RateLimit::from_headers(res).map(|rl| rl.resets_in()))
+                            .map(|delay| delay.min(MAX_RETRY_AFTER));
+                        Some((retry_after, "Rate limited".to_string()))
+                    }
+                    StatusCode::BadGateway
+                    | StatusCode::ServiceUnavailable
+                    | StatusCode::GatewayTimeout
+                        if idempotent =>
+                    {
+                        Some((None, format!("Server returned {}", res.status())))
+                    }
+                    _ => None,
+                },
+                Err(e) if idempotent => Some((None, e.to_string())),
+                Err(_) => None,
+            };
+
+            match retry {
+                Some((retry_after, reason)) if attempt < retries => {
+                    let delay = retry_after.unwrap_or(RETRY_BACKOFF * 2u32.pow(attempt));
+                    attempt += 1;
+                    on_retry(attempt, delay, reason);
+                    smol::Timer::after(delay).await;
+                }
+                _ => return result.map(|res| (res, elapsed)),
+            }
+        }
+    }
+}
+
+/// `params` as a query string, `?` included, or empty without any. Values
+/// are percent-encoded so `&`, `#`, `+` and spaces arrive as typed; a list
+/// is given as the same name repeated.
+pub fn query_string<N: AsRef<str>, V: AsRef<str>>(params: &[(N, V)]) -> String {
+    params
+        .iter()
+        .enumerate()
+        .map(|(i, (name, value))| {
+            let separator = if i == 0 { '?' } else { '&' };
+            format!(
+                "{}{}={}",
+                separator,
+                name.as_ref(),
+                percent_encode(value.as_ref())
+            )
+        })
+        .collect()
+}
+
+/// Escapes every byte of `value` except unreserved characters and the `:`,
+/// `/` and `@` that DIDs and AT URIs are full of, which are allowed in a
+/// query as they are.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b':'
+            | b'/'
+            | b'@' => char::from(b).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Whether `name` can be sent as a header: letters, digits and dashes, so
+/// nothing that would need quoting. `Authorization` belongs to the session.
+pub fn is_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !name.eq_ignore_ascii_case("authorization")
+}
+
+/// Fails with a 408 if `request` doesn't get a response in time, so
+/// timeouts can be told apart from other transport errors.
+async fn with_timeout<T>(
+    request: impl Future<Output = surf::Result<T>>,
+    timeout: Duration,
+) -> surf::Result<T> {
+    smol::future::or(request, async {
+        smol::Timer::after(timeout).await;
+        Err(surf::Error::from_str(
+            StatusCode::RequestTimeout,
+            format!("Request timed out after {}s", timeout.as_secs()),
+        ))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, MockServer, Reply};
+    use serde_json::json;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn parses_a_create_session_response() {
+        // As a PDS answers, with fields oxat doesn't use
+        let body = r#"{
+            "did": "did:plc:ewvi7nxzyoun6zhxrhs64oiz",
+            "didDoc": {
+                "@context": ["https://www.w3.org/ns/did/v1"],
+                "id": "did:plc:ewvi7nxzyoun6zhxrhs64oiz",
+                "alsoKnownAs": ["at://atproto.com"]
+            },
+            "handle": "atproto.com",
+            "email": "team@atproto.com",
+            "emailConfirmed": true,
+            "emailAuthFactor": false,
+            "accessJwt": "eyJ0eXAiOiJhdCtqd3QiLCJhbGciOiJFUzI1NksifQ.eyJzY29wZSI6ImNvbS5hdHByb3RvLmFjY2VzcyJ9.sig",
+            "refreshJwt": "eyJ0eXAiOiJyZWZyZXNoK2p3dCIsImFsZyI6IkVTMjU2SyJ9.eyJzY29wZSI6ImNvbS5hdHByb3RvLnJlZnJlc2gifQ.sig",
+            "active": true
+        }"#;
+
+        let session: Session = serde_json::from_str(body).unwrap();
+        assert_eq!(session.did, "did:plc:ewvi7nxzyoun6zhxrhs64oiz");
+        assert_eq!(session.handle, "atproto.com");
+        assert!(session.access_jwt.starts_with("eyJ0eXAiOiJhdCtqd3Qi"));
+        assert!(session
+            .refresh_jwt
+            .starts_with("eyJ0eXAiOiJyZWZyZXNoK2p3dCIs"));
+    }
+
+    #[test]
+    fn a_session_without_a_did_is_rejected() {
+        let body = r#"{"handle": "atproto.com", "accessJwt": "a", "refreshJwt": "r"}"#;
+        let err = serde_json::from_str::<Session>(body).unwrap_err();
+        assert!(err.to_string().contains("did"), "{}", err);
+    }
+
+    /// A PDS that answers the first `failures` requests with `failure`, then
+    /// succeeds.
+    fn flaky_server(
+        failures: usize,
+        failure: impl Fn() -> Reply + Send + Sync + 'static,
+    ) -> MockServer {
+        let count = Arc::new(AtomicUsize::new(0));
+        MockServer::start(move |_| {
+            if count.fetch_add(1, Ordering::SeqCst) < failures {
+                failure()
+            } else {
+                Reply::json(200, json!({ "ok": true }))
+            }
+        })
+    }
+
+    /// Sends `request` with `retries`, returning the final status and the
+    /// delay before each retry.
+    fn send(
+        client: &XrpcClient,
+        request: surf::Request,
+        retries: u32,
+    ) -> (StatusCode, Vec<Duration>) {
+        let mut delays = Vec::new();
+        let (res, _) = smol::block_on(client.send_with_retries(
+            request,
+            Duration::from_secs(10),
+            retries,
+            |_, delay, _| delays.push(delay),
+        ))
+        .unwrap();
+        (res.status(), delays)
+    }
+
+    #[test]
+    fn retries_server_errors_with_backoff() {
+        let server = flaky_server(2, || Reply::json(503, json!({ "error": "Unavailable" })));
+        let client = test_support::client(&server.url);
+
+        let request = client.get(&client.url("app.bsky.feed.getTimeline")).build();
+        let (status, delays) = send(&client, request, 3);
+
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(delays, [RETRY_BACKOFF, RETRY_BACKOFF * 2]);
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[test]
+    fn rate_limits_wait_as_long_as_retry_after_says() {
+        let server = flaky_server(2, || {
+            Reply::json(429, json!({ "error": "RateLimitExceeded" })).header("Retry-After", "0")
+        });
+        let client = test_support::client(&server.url);
+
+        let request = client.get(&client.url("app.bsky.feed.getTimeline")).build();
+        let (status, delays) = send(&client, request, 3);
+
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(delays, [Duration::ZERO, Duration::ZERO]);
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_retry() {
+        let server = flaky_server(usize::MAX, || {
+            Reply::json(429, json!({ "error": "RateLimitExceeded" })).header("Retry-After", "0")
+        });
+        let client = test_support::client(&server.url);
+
+        let request = client.get(&client.url("app.bsky.feed.getTimeline")).build();
+        let (status, delays) = send(&client, request, 2);
+
+        assert_eq!(status, StatusCode::TooManyRequests);
+        assert_eq!(delays.len(), 2);
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[test]
+    fn posts_arent_retried_on_server_errors() {
+        let server = flaky_server(1, || Reply::json(503, json!({ "error": "Unavailable" })));
+        let client = test_support::client(&server.url);
+
+        let request = client
+            .post(&client.url("com.atproto.repo.createRecord"))
+            .body_json(&json!({ "repo": "did:plc:test" }))
+            .unwrap()
+            .build();
+        let (status, delays) = send(&client, request, 3);
+
+        assert_eq!(status, StatusCode::ServiceUnavailable);
+        assert!(delays.is_empty());
+        assert_eq!(server.received().len(), 1);
+    }
+
+    /// A PDS that echoes what it was sent, and hands out sessions for the
+    /// password `hunter2` and the refresh token `refresh-1`.
+    fn echo_server() -> MockServer {
+        MockServer::start(|req| match req.xrpc_method() {
+            CREATE_SESSION if req.json()["password"] == "hunter2" => {
+                Reply::json(200, test_support::session("access-1", "refresh-1"))
+            }
+            CREATE_SESSION => Reply::json(
+                401,
+                json!({ "error": "AuthenticationRequired", "message": "Invalid identifier or password" }),
+            ),
+            REFRESH_SESSION if req.header("Authorization") == Some("Bearer refresh-1") => {
+                Reply::json(200, test_support::session("access-2", "refresh-2"))
+            }
+            REFRESH_SESSION => Reply::json(400, json!({ "error": "ExpiredToken" })),
+            _ => Reply::json(
+                200,
+                json!({
+                    "method": req.method,
+                    "path": req.path,
+                    "authorization": req.header("Authorization"),
+                    "contentType": req.header("Content-Type"),
+                    "body": req.json(),
+                }),
+            ),
+        })
+    }
+
+    #[test]
+    fn login_keeps_the_session_tokens() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+
+        let session = smol::block_on(client.login("test.bsky.social", "hunter2", None)).unwrap();
+
+        assert_eq!(session.did, "did:plc:test");
+        assert_eq!(client.access_token, Some(test_support::jwt("access-1")));
+        assert_eq!(client.refresh_token.as_deref(), Some("refresh-1"));
+        let sent = server.received()[0].json();
+        assert_eq!(sent["identifier"], "test.bsky.social");
+        assert!(sent.get("authFactorToken").is_none());
+    }
+
+    #[test]
+    fn login_sends_the_sign_in_code() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+
+        smol::block_on(client.login("test.bsky.social", "hunter2", Some("ABCDE-12345"))).unwrap();
+        assert_eq!(
+            server.received()[0].json()["authFactorToken"],
+            "ABCDE-12345"
+        );
+    }
+
+    #[test]
+    fn a_refused_login_is_an_http_status_error() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+        client.access_token = Some("from an earlier session".into());
+
+        let err = smol::block_on(client.login("test.bsky.social", "wrong", None)).unwrap_err();
+
+        assert!(matches!(err, AppError::HttpStatus { code: 401, .. }));
+        assert_eq!(err.xrpc_error().as_deref(), Some("AuthenticationRequired"));
+        assert!(client.access_token.is_none());
+    }
+
+    #[test]
+    fn refresh_swaps_in_new_tokens() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+        client.refresh_token = Some("refresh-1".into());
+
+        let session = smol::block_on(client.refresh()).unwrap();
+
+        assert_eq!(session.refresh_jwt, "refresh-2");
+        assert_eq!(client.access_token, Some(test_support::jwt("access-2")));
+        assert_eq!(client.refresh_token.as_deref(), Some("refresh-2"));
+        assert_eq!(server.received()[0].method, "POST");
+    }
+
+    #[test]
+    fn a_refused_refresh_drops_the_tokens() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+        client.access_token = Some("access-1".into());
+        client.refresh_token = Some("revoked".into());
+
+        let err = smol::block_on(client.refresh()).unwrap_err();
+
+        assert!(matches!(err, AppError::HttpStatus { code: 400, .. }));
+        assert!(client.access_token.is_none());
+        assert!(client.refresh_token.is_none());
+    }
+
+    #[test]
+    fn refresh_without_a_session_sends_nothing() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+
+        let err = smol::block_on(client.refresh()).unwrap_err();
+        assert!(matches!(err, AppError::Auth { .. }));
+        assert!(server.received().is_empty());
+    }
+
+    #[test]
+    fn query_is_a_get_with_the_params_and_token() {
+        let server = echo_server();
+        let mut client = test_support::client(&server.url);
+        client.access_token = Some("access-1".into());
+
+        let echo = smol::block_on(client.query(
+            "app.bsky.feed.getAuthorFeed",
+            &[("actor", "bsky.app"), ("limit", "5")],
+        ))
+        .unwrap();
+
+        assert_eq!(echo["method"], "GET");
+        assert_eq!(
+            echo["path"],
+            "/xrpc/app.bsky.feed.getAuthorFeed?actor=bsky.app&limit=5"
+        );
+        assert_eq!(echo["authorization"], "Bearer access-1");
+    }
+
+    #[test]
+    fn procedure_posts_a_json_body() {
+        let server = echo_server();
+        let client = test_support::client(&server.url);
+        let body = json!({ "repo": "did:plc:test", "collection": "app.bsky.feed.like" });
+
+        let echo = smol::block_on(client.procedure("com.atproto.repo.listRecords", &body)).unwrap();
+
+        assert_eq!(echo["method"], "POST");
+        assert_eq!(echo["path"], "/xrpc/com.atproto.repo.listRecords");
+        assert_eq!(echo["contentType"], "application/json");
+        assert_eq!(echo["body"], body);
+        assert_eq!(echo["authorization"], Value::Null);
+    }
+
+    #[test]
+    fn unreachable_servers_are_network_errors() {
+        // Nothing listens on the discard port
+        let client = test_support::client("http://127.0.0.1:9");
+        let err =
+            smol::block_on(client.query("com.atproto.server.describeServer", &[])).unwrap_err();
+        assert!(matches!(err, AppError::Network { .. }));
+    }
+
+    fn response(headers: &[(&str, &str)]) -> surf::Response {
+        let mut res = surf::http::Response::new(StatusCode::Ok);
+        for (name, value) in headers {
+            res.insert_header(*name, *value);
+        }
+        res.into()
+    }
+
+    #[test]
+    fn rate_limit_reads_all_three_headers() {
+        let res = response(&[
+            ("RateLimit-Limit", "3000"),
+            ("RateLimit-Remaining", "120"),
+            ("RateLimit-Reset", "1760000000"),
+        ]);
+        let rate_limit = RateLimit::from_headers(&res).unwrap();
+        assert_eq!(rate_limit.limit, 3000);
+        assert_eq!(rate_limit.remaining, 120);
+        assert_eq!(
+            rate_limit.reset,
+            UNIX_EPOCH + Duration::from_secs(1_760_000_000)
+        );
+        assert!(rate_limit.is_low());
+        assert_eq!(rate_limit.resets_in(), Duration::ZERO);
+    }
+
+    #[test]
+    fn small_resets_are_seconds_from_now() {
+        let res = response(&[
+            ("ratelimit-limit", "100"),
+            ("ratelimit-remaining", " 50 "),
+            ("ratelimit-reset", "60"),
+        ]);
+        let rate_limit = RateLimit::from_headers(&res).unwrap();
+        assert!(!rate_limit.is_low());
+        let left = rate_limit.resets_in();
+        assert!(left > Duration::from_secs(58) && left <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn rate_limit_needs_every_header() {
+        assert_eq!(RateLimit::from_headers(&response(&[])), None);
+        let partial = response(&[("ratelimit-limit", "100"), ("ratelimit-remaining", "50")]);
+        assert_eq!(RateLimit::from_headers(&partial), None);
+        let partial = response(&[("ratelimit-remaining", "50"), ("ratelimit-reset", "60")]);
+        assert_eq!(RateLimit::from_headers(&partial), None);
+    }
+
+    #[test]
+    fn rate_limit_ignores_garbage() {
+        for (limit, remaining, reset) in [
+            ("lots", "50", "60"),
+            ("100", "-1", "60"),
+            ("100", "50", "Thu, 16 Oct 2026 00:00:00 GMT"),
+        ] {
+            let res = response(&[
+                ("ratelimit-limit", limit),
+                ("ratelimit-remaining", remaining),
+                ("ratelimit-reset", reset),
+            ]);
+            assert_eq!(
+                RateLimit::from_headers(&res),
+                None,
+                "{} {} {}",
+                limit,
+                remaining,
+                reset
+            );
+        }
+    }
+
+    #[test]
+    fn query_string_repeats_list_names() {
+        assert_eq!(
+            query_string(&[
+                ("actors", "bsky.app"),
+                ("actors", "did:plc:z72i7hdynmk6r22z27h6tvur")
+            ]),
+            "?actors=bsky.app&actors=did:plc:z72i7hdynmk6r22z27h6tvur"
+        );
+        assert_eq!(query_string::<&str, &str>(&[]), "");
+    }
+
+    #[test]
+    fn query_string_encodes_what_would_be_misread() {
+        assert_eq!(
+            query_string(&[("q", "rust & go #lang"), ("cursor", "a+b/c=")]),
+            "?q=rust%20%26%20go%20%23lang&cursor=a%2Bb/c%3D"
+        );
+        assert_eq!(query_string(&[("q", "日本")]), "?q=%E6%97%A5%E6%9C%AC");
+        // AT URIs stay readable
+        assert_eq!(
+            query_string(&[("uri", "at://did:plc:abc/app.bsky.feed.post/3k44")]),
+            "?uri=at://did:plc:abc/app.bsky.feed.post/3k44"
+        );
+    }
+
+    #[test]
+    fn query_sends_encoded_params() {
+        let server = echo_server();
+        let client = test_support::client(&server.url);
+
+        let echo = smol::block_on(client.query(
+            "app.bsky.feed.searchPosts",
+            &[("q", "a&b #c"), ("sort", "top")],
+        ))
+        .unwrap();
+
+        assert_eq!(
+            echo["path"],
+            "/xrpc/app.bsky.feed.searchPosts?q=a%26b%20%23c&sort=top"
+        );
+    }
+}